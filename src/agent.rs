@@ -0,0 +1,283 @@
+use anyhow::{anyhow, Result};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+    thread,
+};
+use crate::config::Config;
+use crate::glob;
+use crate::AppEvent;
+
+// Set for every agent so it, and the shell hooks it runs, can tell it's
+// running under aiui
+pub const MARKER: (&str, &str) = ("AI_TUI", "1");
+
+// What a masked value is shown as
+const MASK: &str = "********";
+
+// How the agent command is started; shared by the PTY and --headless
+#[derive(Clone, Debug, Default)]
+pub struct Launch {
+    // Run it as `$SHELL -lc "<command>"`, so PATH and version-manager shims
+    // (nvm, pyenv, asdf) set up in the user's profile resolve like they do
+    // in their own terminal. Off by default: exec'ing directly is faster.
+    pub login_shell: bool,
+    // Set on top of the inherited environment
+    pub env: BTreeMap<String, String>,
+    // Taken out of the inherited environment first (e.g. SSH_AUTH_SOCK)
+    pub unset: Vec<String>,
+    // Where it starts, relative to the primary watch root; None is the root
+    pub cwd: Option<PathBuf>,
+    // Program and arguments from the command line, used instead of splitting
+    // the command string when not going through the login shell
+    pub argv: Vec<String>,
+}
+
+impl Launch {
+    pub fn from_config(config: &Config) -> Self {
+        let mut env = config.agent_env.clone();
+        env.entry(MARKER.0.to_string()).or_insert_with(|| MARKER.1.to_string());
+        Self {
+            login_shell: config.agent_login_shell,
+            env,
+            unset: config.agent_env_unset.clone(),
+            cwd: config.agent_cwd.clone(),
+            argv: config.agent_argv.clone(),
+        }
+    }
+
+    // The directory the agent starts in
+    pub fn dir(&self, root: &Path) -> PathBuf {
+        self.cwd.as_ref().map_or_else(|| root.to_path_buf(), |cwd| root.join(cwd))
+    }
+
+    // Program and arguments for `command`. Directly, it's `argv` when given and
+    // otherwise split on whitespace with no quoting; through the login shell,
+    // the shell parses it.
+    pub fn argv(&self, command: &str) -> Result<Vec<String>> {
+        if !self.login_shell && !self.argv.is_empty() {
+            return Ok(self.argv.clone());
+        }
+        if command.trim().is_empty() {
+            return Err(anyhow!("agent command is empty"));
+        }
+        if self.login_shell {
+            if cfg!(windows) {
+                return Ok(vec!["cmd".to_string(), "/C".to_string(), command.to_string()]);
+            }
+            let shell = std::env::var("SHELL").ok().filter(|s| !s.is_empty()).unwrap_or_else(|| "/bin/sh".to_string());
+            return Ok(vec![shell, "-lc".to_string(), command.to_string()]);
+        }
+        Ok(command.split_whitespace().map(str::to_string).collect())
+    }
+}
+
+// `argv` as one sh command line, quoting the words that need it
+pub fn shell_join(argv: &[String]) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+    argv.iter()
+        .map(|word| match word {
+            _ if !word.is_empty() && word.chars().all(plain) => word.clone(),
+            _ => format!("'{}'", word.replace('\'', "'\\''")),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// `env` for showing in status and export output, with the values of names
+// matching an agent_env_mask glob (case-insensitively) blanked out
+pub fn masked_env(env: &BTreeMap<String, String>, mask: &[String]) -> BTreeMap<String, String> {
+    env.iter()
+        .map(|(key, value)| {
+            let secret = mask.iter().any(|pattern| glob::glob_match(&pattern.to_uppercase(), &key.to_uppercase()));
+            (key.clone(), if secret { MASK.to_string() } else { value.clone() })
+        })
+        .collect()
+}
+
+// What Ctrl+C escalates to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Terminate,
+    Kill,
+}
+
+impl Signal {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Terminate => "SIGTERM",
+            Self::Kill => "SIGKILL",
+        }
+    }
+}
+
+// The child process running in the terminal pane, plus its PTY handles
+pub struct Agent {
+    // Command line as typed, shown in the status bar
+    pub command: String,
+    // Tags PtyData so output from a replaced agent's reader is dropped
+    pub generation: u64,
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+}
+
+impl Agent {
+    // Open a fresh PTY pair, start `command` in it and stream its output to `tx`
+    pub fn spawn(command: &str, launch: &Launch, root: &Path, size: PtySize, generation: u64, tx: Sender<AppEvent>) -> Result<Self> {
+        let argv = launch.argv(command)?;
+        let mut cmd = CommandBuilder::from_argv(argv.iter().map(Into::into).collect());
+        for key in &launch.unset {
+            cmd.env_remove(key);
+        }
+        for (key, value) in &launch.env {
+            cmd.env(key, value);
+        }
+        cmd.cwd(launch.dir(root));
+
+        let pair = native_pty_system().openpty(size)?;
+        let child = pair.slave.spawn_command(cmd)?;
+        let mut reader = pair.master.try_clone_reader()?;
+        let writer = pair.master.take_writer()?;
+
+        thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(n) if n > 0 => {
+                        if tx.send(AppEvent::PtyData(generation, buf[..n].to_vec())).is_err() {
+                            break;
+                        }
+                    }
+                    _ => break,
+                }
+            }
+        });
+
+        Ok(Self {
+            command: command.to_string(),
+            generation,
+            master: pair.master,
+            writer,
+            child,
+        })
+    }
+
+    pub fn writer(&mut self) -> &mut dyn Write {
+        &mut self.writer
+    }
+
+    pub fn resize(&self, size: PtySize) -> Result<()> {
+        self.master.resize(size)
+    }
+
+    // Signal the agent's whole process group. It leads its own session in the
+    // PTY, so the group id is its pid; helpers it started go down with it.
+    // Elsewhere there's only killing the agent itself.
+    pub fn signal(&mut self, signal: Signal) -> std::io::Result<()> {
+        #[cfg(unix)]
+        {
+            let pid = self.child.process_id().ok_or_else(|| std::io::Error::other("agent has no pid"))?;
+            let number = match signal {
+                Signal::Terminate => libc::SIGTERM,
+                Signal::Kill => libc::SIGKILL,
+            };
+            // SAFETY: kill(2) takes plain integers and touches no memory of ours
+            if unsafe { libc::kill(-(pid as libc::pid_t), number) } == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = signal;
+            self.child.kill()
+        }
+    }
+
+    // Dropping the agent afterwards closes the PTY, which ends its reader thread
+    pub fn kill(&mut self) {
+        #[cfg(unix)]
+        let _ = self.signal(Signal::Kill);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn login_shell_hands_the_whole_command_to_the_shell() {
+        let direct = Launch::default();
+        assert_eq!(direct.argv("npx  opencode-ai --port 1").unwrap(), ["npx", "opencode-ai", "--port", "1"]);
+        assert!(direct.argv("  ").is_err());
+
+        let login = Launch { login_shell: true, ..Launch::default() };
+        let argv = login.argv("claude --model 'big one'").unwrap();
+        assert_eq!(argv[1..], ["-lc", "claude --model 'big one'"]);
+    }
+
+    #[test]
+    fn argv_from_the_command_line_keeps_its_words() {
+        let words: Vec<String> = ["claude", "--append-system-prompt", "be brief", "it's"].map(String::from).into();
+        let direct = Launch { argv: words.clone(), ..Launch::default() };
+        // The command string only matters to the login shell
+        assert_eq!(direct.argv("ignored").unwrap(), words);
+        let joined = shell_join(&words);
+        assert_eq!(joined, r#"claude --append-system-prompt 'be brief' 'it'\''s'"#);
+        let login = Launch { login_shell: true, ..direct };
+        assert_eq!(login.argv(&joined).unwrap()[2], joined);
+    }
+
+    #[test]
+    fn config_env_gets_the_marker_and_masks_secrets() {
+        let mut config = Config::default();
+        config.agent_env.insert("OPENAI_API_KEY".to_string(), "sk-123".to_string());
+        config.agent_env.insert("EDITOR".to_string(), "vim".to_string());
+        config.agent_cwd = Some(PathBuf::from("web"));
+        let launch = Launch::from_config(&config);
+        assert_eq!(launch.env.get("AI_TUI").map(String::as_str), Some("1"));
+        assert_eq!(launch.dir(Path::new("/src/app")), Path::new("/src/app/web"));
+        assert_eq!(Launch::default().dir(Path::new("/src/app")), Path::new("/src/app"));
+
+        let shown = masked_env(&launch.env, &config.agent_env_mask);
+        assert_eq!(shown["OPENAI_API_KEY"], MASK);
+        assert_eq!(shown["EDITOR"], "vim");
+        assert_eq!(masked_env(&launch.env, &["editor".to_string()])["EDITOR"], MASK);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawned_agent_sees_the_configured_environment() {
+        let dir = std::env::temp_dir().join(format!("aiui-agent-env-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        // Plain sh rather than a login shell, whose profile could set HOME again
+        let script = "echo \"[$AIUI_TEST_SET|${HOME-gone}|$(basename $PWD)]\"";
+        let launch = Launch {
+            login_shell: false,
+            env: BTreeMap::from([("AIUI_TEST_SET".to_string(), "yes".to_string())]),
+            unset: vec!["HOME".to_string()],
+            cwd: Some(PathBuf::from("sub")),
+            argv: ["sh", "-c", script].map(String::from).into(),
+        };
+        let (tx, rx) = std::sync::mpsc::channel();
+        let size = PtySize { rows: 5, cols: 80, pixel_width: 0, pixel_height: 0 };
+        let mut agent = Agent::spawn("sh -c", &launch, &dir, size, 0, tx).unwrap();
+        let mut output = Vec::new();
+        while let Ok(AppEvent::PtyData(_, bytes)) = rx.recv_timeout(std::time::Duration::from_secs(5)) {
+            output.extend(bytes);
+            if String::from_utf8_lossy(&output).contains(']') {
+                break;
+            }
+        }
+        agent.kill();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(String::from_utf8_lossy(&output).contains("[yes|gone|sub]"), "{}", String::from_utf8_lossy(&output));
+    }
+}
@@ -0,0 +1,133 @@
+// Drop CSI/OSC escape sequences. Stored diffs are always plain text; color is
+// applied at render time from the hunk model.
+pub fn strip(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters up to a final byte in @..~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: up to BEL or ST (ESC \)
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+// The cursor shape the agent asked for with DECSCUSR (`CSI Ps SP q`), which
+// vt100 doesn't keep. Fed the raw PTY bytes; a sequence split across reads
+// is picked up where it left off.
+#[derive(Default)]
+pub struct CursorShape {
+    // Ps of the last DECSCUSR; 0 is the terminal's own default
+    pub shape: u8,
+    scan: Scan,
+}
+
+#[derive(Default)]
+enum Scan {
+    #[default]
+    Text,
+    Escape,
+    // Parameter and intermediate bytes so far
+    Csi(Vec<u8>),
+}
+
+impl CursorShape {
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.scan = match std::mem::take(&mut self.scan) {
+                Scan::Text if byte == 0x1b => Scan::Escape,
+                Scan::Text => Scan::Text,
+                Scan::Escape => match byte {
+                    b'[' => Scan::Csi(Vec::new()),
+                    // RIS: a full reset puts the shape back too
+                    b'c' => {
+                        self.shape = 0;
+                        Scan::Text
+                    }
+                    0x1b => Scan::Escape,
+                    _ => Scan::Text,
+                },
+                Scan::Csi(mut params) => match byte {
+                    b'q' if params.ends_with(b" ") => {
+                        let digits = &params[..params.len() - 1];
+                        if let Some(shape) = std::str::from_utf8(digits).ok().and_then(|d| if d.is_empty() { Some(0) } else { d.parse().ok() }) {
+                            self.shape = shape;
+                        }
+                        Scan::Text
+                    }
+                    0x40..=0x7e => Scan::Text,
+                    0x1b => Scan::Escape,
+                    // Nobody sends a CSI this long; don't hold on to junk
+                    _ if params.len() >= 16 => Scan::Text,
+                    _ => {
+                        params.push(byte);
+                        Scan::Csi(params)
+                    }
+                },
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{strip, CursorShape};
+
+    #[test]
+    fn decscusr_is_tracked_across_reads() {
+        let mut cursor = CursorShape::default();
+        cursor.feed(b"prompt \x1b[5 q");
+        assert_eq!(cursor.shape, 5);
+        // Split mid-sequence, with other escapes around it
+        cursor.feed(b"\x1b[1;32mok\x1b[0m\x1b[");
+        cursor.feed(b"2 qmore");
+        assert_eq!(cursor.shape, 2);
+        // Other CSI sequences ending in q (DECLL) aren't DECSCUSR
+        cursor.feed(b"\x1b[1q");
+        assert_eq!(cursor.shape, 2);
+        cursor.feed(b"\x1b[ q");
+        assert_eq!(cursor.shape, 0);
+        cursor.feed(b"\x1b[6 q\x1bc");
+        assert_eq!(cursor.shape, 0);
+    }
+
+    #[test]
+    fn plain_text_is_untouched() {
+        assert_eq!(strip("+let x = 1;\n"), "+let x = 1;\n");
+    }
+
+    #[test]
+    fn removes_sgr_colors() {
+        assert_eq!(strip("\x1b[32m+added\x1b[0m"), "+added");
+        assert_eq!(strip("\x1b[1;38;5;196m-removed\x1b[m"), "-removed");
+    }
+
+    #[test]
+    fn removes_osc_with_bel_or_st() {
+        assert_eq!(strip("\x1b]0;title\x07text"), "text");
+        assert_eq!(strip("\x1b]8;;http://x\x1b\\link\x1b]8;;\x1b\\"), "link");
+    }
+}
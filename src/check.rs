@@ -0,0 +1,155 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+    thread,
+};
+use crate::process::{self, Exit};
+use crate::AppEvent;
+
+// Error lines kept for the approval modal
+const MAX_LINES: usize = 5;
+
+// What the modal shows about a queued change
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckStatus {
+    // Waiting for a run, or in one
+    Pending,
+    Passed,
+    // First error lines of the output
+    Failed(Vec<String>),
+    // The command couldn't be started
+    Error(String),
+}
+
+#[derive(Debug)]
+pub enum CheckOutcome {
+    Passed,
+    Failed(Vec<String>),
+    // Everything it covered was decided before it finished
+    Cancelled,
+    Error(String),
+}
+
+#[derive(Debug)]
+pub struct CheckResult {
+    pub run: u64,
+    pub outcome: CheckOutcome,
+}
+
+struct Run {
+    id: u64,
+    // Change ids this run answers for
+    covers: Vec<u64>,
+    cancel: Arc<AtomicBool>,
+}
+
+// check_command runner. A check looks at the whole tree, so there's only ever
+// one run: changes queued meanwhile wait and share the next one, and ten
+// queued changes cost two runs, not ten.
+pub struct Checker {
+    command: String,
+    cwd: PathBuf,
+    tx: Sender<AppEvent>,
+    next_run: u64,
+    running: Option<Run>,
+    waiting: Vec<u64>,
+}
+
+impl Checker {
+    pub fn new(command: String, cwd: PathBuf, tx: Sender<AppEvent>) -> Self {
+        Self { command, cwd, tx, next_run: 0, running: None, waiting: Vec::new() }
+    }
+
+    // `change_id` was queued (or its content changed): check it on the next run
+    pub fn request(&mut self, change_id: u64) {
+        if !self.waiting.contains(&change_id) {
+            self.waiting.push(change_id);
+        }
+        self.start_if_idle();
+    }
+
+    // Drop changes that were decided; a run none of its changes are waiting
+    // on anymore is killed
+    pub fn keep_only(&mut self, pending: &[u64]) {
+        self.waiting.retain(|id| pending.contains(id));
+        if let Some(run) = self.running.as_mut() {
+            run.covers.retain(|id| pending.contains(id));
+            if run.covers.is_empty() {
+                run.cancel.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // A run ended: the changes its result applies to. Ones that changed again
+    // since are left to the next run, which starts right away.
+    pub fn finished(&mut self, run: u64) -> Vec<u64> {
+        if self.running.as_ref().is_none_or(|r| r.id != run) {
+            return Vec::new();
+        }
+        let mut covers = self.running.take().map(|r| r.covers).unwrap_or_default();
+        covers.retain(|id| !self.waiting.contains(id));
+        self.start_if_idle();
+        covers
+    }
+
+    fn start_if_idle(&mut self) {
+        if self.running.is_some() || self.waiting.is_empty() {
+            return;
+        }
+        self.next_run += 1;
+        let run = Run { id: self.next_run, covers: std::mem::take(&mut self.waiting), cancel: Arc::new(AtomicBool::new(false)) };
+        let (id, cancel, tx) = (run.id, run.cancel.clone(), self.tx.clone());
+        let (command, cwd) = (self.command.clone(), self.cwd.clone());
+        self.running = Some(run);
+        thread::spawn(move || {
+            let outcome = execute(&command, &cwd, &cancel);
+            let _ = tx.send(AppEvent::Check(CheckResult { run: id, outcome }));
+        });
+    }
+}
+
+fn execute(command: &str, cwd: &Path, cancel: &AtomicBool) -> CheckOutcome {
+    let mut child = match process::spawn(command, &[], cwd) {
+        Ok(child) => child,
+        Err(e) => return CheckOutcome::Error(e.to_string()),
+    };
+    let stdout = process::drain(child.stdout.take());
+    let stderr = process::drain(child.stderr.take());
+    let status = match process::wait(&mut child, cancel, None) {
+        Ok(Exit::Exited(status)) => status,
+        Ok(_) => return CheckOutcome::Cancelled,
+        Err(e) => return CheckOutcome::Error(e.to_string()),
+    };
+    let output = format!("{}{}", stderr.join().unwrap_or_default(), stdout.join().unwrap_or_default());
+    if status.success() {
+        CheckOutcome::Passed
+    } else {
+        CheckOutcome::Failed(error_lines(&output))
+    }
+}
+
+// Lines that mention an error, else the last few lines of output
+pub fn error_lines(output: &str) -> Vec<String> {
+    let lines: Vec<&str> = output.lines().map(str::trim_end).filter(|l| !l.trim().is_empty()).collect();
+    let errors: Vec<String> = lines.iter().filter(|l| l.to_lowercase().contains("error")).take(MAX_LINES).map(|l| l.to_string()).collect();
+    if !errors.is_empty() {
+        return errors;
+    }
+    lines[lines.len().saturating_sub(MAX_LINES)..].iter().map(|l| l.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_lines_prefer_errors_over_noise() {
+        let cargo = "    Checking app v0.1.0\nsrc/a.rs:3:5: error[E0425]: cannot find value `x`\nsrc/b.rs:1:1: warning: unused\nerror: could not compile `app`\n";
+        assert_eq!(error_lines(cargo), ["src/a.rs:3:5: error[E0425]: cannot find value `x`", "error: could not compile `app`"]);
+        assert_eq!(error_lines("one\n\ntwo\n"), ["one", "two"]);
+    }
+}
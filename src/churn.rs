@@ -0,0 +1,100 @@
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use crate::path_key::PathKey;
+
+// One file's edits over the session
+#[derive(Debug, Clone)]
+pub struct FileChurn {
+    // As it was last shown in the sidebar
+    pub path: String,
+    pub changes: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub last: DateTime<Local>,
+}
+
+impl FileChurn {
+    pub fn lines(&self) -> usize {
+        self.insertions + self.deletions
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChurnOrder {
+    // Most edited first, then most lines
+    #[default]
+    Count,
+    // Most recently edited first
+    Recent,
+}
+
+impl ChurnOrder {
+    pub fn toggle(self) -> Self {
+        match self {
+            Self::Count => Self::Recent,
+            Self::Recent => Self::Count,
+        }
+    }
+}
+
+// How often the agent has gone back to each file. Kept apart from the change
+// log so trimming or dismissing entries doesn't lose count: a file rewritten
+// twenty times is what this is for spotting.
+#[derive(Default)]
+pub struct Churn {
+    files: HashMap<PathKey, FileChurn>,
+}
+
+impl Churn {
+    pub fn record(&mut self, key: &PathKey, path: &str, (insertions, deletions): (usize, usize)) {
+        let entry = self.files.entry(key.clone()).or_insert_with(|| FileChurn {
+            path: String::new(),
+            changes: 0,
+            insertions: 0,
+            deletions: 0,
+            last: Local::now(),
+        });
+        entry.path = path.to_string();
+        entry.changes += 1;
+        entry.insertions += insertions;
+        entry.deletions += deletions;
+        entry.last = Local::now();
+    }
+
+    pub fn ranked(&self, order: ChurnOrder) -> Vec<&FileChurn> {
+        let mut files: Vec<&FileChurn> = self.files.values().collect();
+        match order {
+            ChurnOrder::Count => files.sort_by(|a, b| {
+                b.changes.cmp(&a.changes).then(b.lines().cmp(&a.lines())).then_with(|| a.path.cmp(&b.path))
+            }),
+            ChurnOrder::Recent => files.sort_by(|a, b| b.last.cmp(&a.last).then_with(|| a.path.cmp(&b.path))),
+        }
+        files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn paths(files: Vec<&FileChurn>) -> Vec<&str> {
+        files.into_iter().map(|f| f.path.as_str()).collect()
+    }
+
+    #[test]
+    fn files_rank_by_edits_then_lines_or_by_recency() {
+        let mut churn = Churn::default();
+        let key = |p: &str| PathKey::exact(Path::new(p));
+        churn.record(&key("/repo/a.rs"), "a.rs", (1, 0));
+        churn.record(&key("/repo/b.rs"), "b.rs", (40, 2));
+        churn.record(&key("/repo//a.rs"), "a.rs", (3, 3));
+        churn.record(&key("/repo/c.rs"), "c.rs", (1, 1));
+        churn.record(&key("/repo/c.rs"), "c.rs", (1, 1));
+
+        let ranked = churn.ranked(ChurnOrder::Count);
+        assert_eq!(paths(ranked.clone()), ["a.rs", "c.rs", "b.rs"]);
+        assert_eq!((ranked[0].changes, ranked[0].insertions, ranked[0].deletions), (2, 4, 3));
+        assert_eq!(paths(churn.ranked(ChurnOrder::Recent))[0], "c.rs");
+    }
+}
@@ -0,0 +1,135 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "aiui", about = "Terminal control layer for AI coding agents")]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Args {
+    #[command(subcommand)]
+    pub subcommand: Option<Cmd>,
+
+    /// Directory to watch (repeatable); the agent starts in the first one
+    #[arg(long = "dir", value_name = "PATH")]
+    pub dirs: Vec<PathBuf>,
+
+    /// Append each finalized change as a JSON line to this file ("-" for stdout)
+    #[arg(long, visible_alias = "audit-file", value_name = "PATH")]
+    pub json_log: Option<PathBuf>,
+
+    /// Run without the TUI: the agent gets the real terminal, approvals are read from stdin
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Watch and approve only: the agent runs in its own terminal and isn't started here
+    #[arg(long, conflicts_with = "headless")]
+    pub tail: bool,
+
+    /// Accept changes to paths matching this glob without asking (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub auto_approve: Vec<String>,
+
+    /// Color depth for the UI chrome; auto checks COLORTERM and TERM
+    #[arg(long, value_enum, default_value_t = ColorMode::Auto)]
+    pub color: ColorMode,
+
+    /// Start in the High Contrast theme: no colors, ASCII only, everything spelled out (also set by NO_COLOR)
+    #[arg(long)]
+    pub no_color: bool,
+
+    /// Show approvals in a modal or an inline banner, overriding approval_ui from the config
+    #[arg(long, value_enum, value_name = "UI")]
+    pub approval_ui: Option<crate::config::ApprovalUi>,
+
+    /// Treat every change as the agent's, even with strict_origin = false in the config
+    #[arg(long)]
+    pub strict_origin: bool,
+
+    /// Start the agent through your login shell ($SHELL -lc) so nvm/pyenv/asdf shims are on PATH
+    #[arg(long)]
+    pub login_shell: bool,
+
+    /// Set an environment variable for the agent (repeatable), over agent_env from the config
+    #[arg(long = "env", value_name = "KEY=VAL", value_parser = env_pair)]
+    pub env: Vec<(String, String)>,
+
+    /// Remove an inherited environment variable from the agent's environment (repeatable)
+    #[arg(long = "unset", value_name = "KEY")]
+    pub unset: Vec<String>,
+
+    /// Write the change log to this file on exit (.json for JSON, otherwise Markdown)
+    #[arg(long, value_name = "PATH")]
+    pub export_on_exit: Option<PathBuf>,
+
+    /// Keep the agent's raw output for the PTY dump overlay (F12)
+    #[arg(long)]
+    pub debug: bool,
+
+    /// Agent command line, overriding agent_command from the config
+    #[arg(last = true, value_name = "AGENT CMD")]
+    pub command: Vec<String>,
+}
+
+// --env KEY=VAL; the value may itself contain `=`
+fn env_pair(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VAL, got `{}`", arg)),
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Cmd {
+    /// Talk to a running session over its control socket
+    Ctl {
+        /// Socket path; defaults to control_socket from the config
+        #[arg(long, value_name = "PATH")]
+        socket: Option<PathBuf>,
+
+        #[command(subcommand)]
+        command: CtlCommand,
+    },
+    /// Check the agent command, watch directories and terminal size, then exit
+    Doctor {
+        /// Directory to check (repeatable); defaults to watch_roots or the current directory
+        #[arg(long = "dir", value_name = "PATH")]
+        dirs: Vec<PathBuf>,
+
+        /// Agent command line to look up, overriding agent_command from the config
+        #[arg(last = true, value_name = "AGENT CMD")]
+        command: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CtlCommand {
+    /// Print the approval queue with diffs
+    ListPending,
+    /// Accept the pending change to PATH (or with --id, the one list-pending gave that id)
+    Approve {
+        #[arg(required_unless_present = "id")]
+        path: Option<String>,
+        #[arg(long, conflicts_with = "path")]
+        id: Option<u64>,
+    },
+    /// Reject the pending change to PATH (or --id), reverting it on disk
+    Reject {
+        #[arg(required_unless_present = "id")]
+        path: Option<String>,
+        #[arg(long, conflicts_with = "path")]
+        id: Option<u64>,
+    },
+    /// Print session counters
+    Status,
+    /// Write the change log (to PATH, relative to the session's primary root)
+    Export { path: Option<PathBuf> },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    Auto,
+    Truecolor,
+    #[value(name = "256")]
+    Ansi256,
+    #[value(name = "16")]
+    Ansi16,
+}
@@ -0,0 +1,199 @@
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+// Rows vt100 keeps above the screen, so the picker can reach commands that
+// have just scrolled off
+pub const SCROLLBACK_ROWS: usize = 500;
+// How far into the scrollback the picker looks
+const SCAN_SCROLLBACK: usize = 200;
+// One digit each
+pub const MAX_HINTS: usize = 9;
+
+// A line as the agent meant it: rows the terminal wrapped are joined back up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenLine {
+    pub text: String,
+    // Screen row it starts on; None while it's in the scrollback
+    pub row: Option<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub text: String,
+    pub row: Option<u16>,
+}
+
+// `command_patterns` in the config. Each regex is tried against every line;
+// its first capture group (or the whole match, without one) is the command.
+#[derive(Debug, Clone)]
+pub struct CommandPatterns(Vec<Regex>);
+
+impl CommandPatterns {
+    fn new(patterns: &[&str]) -> Result<Self, regex::Error> {
+        patterns.iter().map(|p| Regex::new(p)).collect::<Result<_, _>>().map(Self)
+    }
+
+    fn command<'a>(&self, line: &'a str) -> Option<&'a str> {
+        self.0.iter().find_map(|re| {
+            let caps = re.captures(line)?;
+            caps.get(1).or(caps.get(0)).map(|m| m.as_str())
+        })
+    }
+}
+
+impl Default for CommandPatterns {
+    fn default() -> Self {
+        // `$ cargo test`, and "run `cargo test`" in prose
+        Self::new(&[r"^\s*\$ (.+)$", r"(?i)\brun\s+`([^`]+)`"]).expect("default patterns compile")
+    }
+}
+
+impl<'de> Deserialize<'de> for CommandPatterns {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let patterns = Vec::<String>::deserialize(deserializer)?;
+        let patterns: Vec<&str> = patterns.iter().map(String::as_str).collect();
+        Self::new(&patterns).map_err(serde::de::Error::custom)
+    }
+}
+
+// The screen plus the last SCAN_SCROLLBACK rows above it, oldest first.
+// Looking at the scrollback means moving vt100's view, so it's put back after.
+pub fn screen_lines(parser: &mut vt100::Parser) -> Vec<ScreenLine> {
+    let (height, width) = parser.screen().size();
+    let mut rows: Vec<(String, bool, Option<u16>)> = Vec::new();
+
+    // Paged in from the top: at offset N the first rows shown are the N
+    // newest scrollback rows, so each page only contributes those
+    parser.set_scrollback(SCAN_SCROLLBACK);
+    let mut offset = parser.screen().scrollback();
+    while offset > 0 {
+        parser.set_scrollback(offset);
+        let take = offset.min(height as usize);
+        let screen = parser.screen();
+        for (i, text) in screen.rows(0, width).take(take).enumerate() {
+            rows.push((text, screen.row_wrapped(i as u16), None));
+        }
+        offset -= take;
+    }
+    parser.set_scrollback(0);
+
+    let screen = parser.screen();
+    for (i, text) in screen.rows(0, width).enumerate() {
+        rows.push((text, screen.row_wrapped(i as u16), Some(i as u16)));
+    }
+
+    let mut lines: Vec<ScreenLine> = Vec::new();
+    let mut continues = false;
+    for (text, wrapped, row) in rows {
+        match lines.last_mut() {
+            Some(line) if continues => line.text.push_str(&text),
+            _ => lines.push(ScreenLine { text, row }),
+        }
+        continues = wrapped;
+    }
+    lines
+}
+
+// Commands worth offering, newest last and at most MAX_HINTS of them: lines
+// the patterns match, and every line of a shell-flavoured ``` fence but its
+// comments. Prompt characters in front are dropped; a command seen twice is
+// offered once, where it was seen last.
+pub fn extract(lines: &[ScreenLine], patterns: &CommandPatterns) -> Vec<Candidate> {
+    let mut found: Vec<Candidate> = Vec::new();
+    let mut fence: Option<bool> = None;
+    for line in lines {
+        let trimmed = line.text.trim();
+        if let Some(lang) = trimmed.strip_prefix("```") {
+            fence = match fence {
+                Some(_) => None,
+                None => Some(matches!(lang.trim(), "" | "sh" | "bash" | "zsh" | "shell" | "console" | "fish")),
+            };
+            continue;
+        }
+        let command = match fence {
+            // In a fence `#` starts a comment rather than a root prompt
+            Some(true) if trimmed.starts_with('#') => None,
+            Some(true) => Some(strip_prompt(trimmed, false)),
+            Some(false) => None,
+            None => patterns.command(&line.text).map(|command| strip_prompt(command, true)),
+        };
+        let Some(command) = command.map(str::trim).filter(|c| !c.is_empty()) else { continue };
+        found.retain(|c| c.text != command);
+        found.push(Candidate { text: command.to_string(), row: line.row });
+    }
+    let skip = found.len().saturating_sub(MAX_HINTS);
+    found.split_off(skip)
+}
+
+fn strip_prompt(line: &str, root: bool) -> &str {
+    let prompts: &[&str] = if root { &["$ ", "% ", "# ", "> ", "❯ "] } else { &["$ ", "% ", "> ", "❯ "] };
+    prompts.iter().find_map(|p| line.strip_prefix(p)).unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(rows: u16, cols: u16, bytes: &[u8]) -> vt100::Parser {
+        let mut parser = vt100::Parser::new(rows, cols, SCROLLBACK_ROWS);
+        parser.process(bytes);
+        parser
+    }
+
+    fn texts(candidates: &[Candidate]) -> Vec<&str> {
+        candidates.iter().map(|c| c.text.as_str()).collect()
+    }
+
+    #[test]
+    fn wrapped_rows_join_and_scrollback_comes_first() {
+        // 10 columns: the long command wraps onto a second row
+        let mut parser = fixture(3, 10, b"$ ls\r\nold\r\nmore\r\n$ cargo test -q\r\nlast");
+        let lines = screen_lines(&mut parser);
+        let text: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(text, ["$ ls", "old", "more", "$ cargo test -q", "last"]);
+        assert_eq!(lines.iter().map(|l| l.row).collect::<Vec<_>>(), [None, None, None, Some(0), Some(2)]);
+        // The view is left where the terminal pane expects it
+        assert_eq!(parser.screen().scrollback(), 0);
+    }
+
+    #[test]
+    fn commands_come_from_prompts_prose_and_shell_fences() {
+        let screen = concat!(
+            "I'll check it compiles first.\r\n",
+            "$ cargo check\r\n",
+            "Then run `cargo test -q` to be sure.\r\n",
+            "```bash\r\n",
+            "# see what changed\r\n",
+            "$ git status\r\n",
+            "rm -rf target\r\n",
+            "```\r\n",
+            "```rust\r\n",
+            "fn main() {}\r\n",
+            "```\r\n",
+            "  $ cargo check\r\n",
+        );
+        let mut parser = fixture(14, 60, screen.as_bytes());
+        let found = extract(&screen_lines(&mut parser), &CommandPatterns::default());
+        assert_eq!(texts(&found), ["cargo test -q", "git status", "rm -rf target", "cargo check"]);
+        // The repeat is where it was seen last
+        assert_eq!(found[3].row, Some(11));
+
+        // Outside a fence `# ` is a root prompt
+        let patterns: CommandPatterns = toml::Value::Array(vec![r"^(# .+)$".into()]).try_into().unwrap();
+        let lines = [ScreenLine { text: "# make install".to_string(), row: Some(0) }];
+        assert_eq!(texts(&extract(&lines, &patterns)), ["make install"]);
+    }
+
+    #[test]
+    fn only_the_newest_nine_are_offered_and_patterns_are_configurable() {
+        let lines: Vec<ScreenLine> = (0..12).map(|n| ScreenLine { text: format!("$ echo {}", n), row: None }).collect();
+        let found = extract(&lines, &CommandPatterns::default());
+        assert_eq!(found.len(), MAX_HINTS);
+        assert_eq!(found[0].text, "echo 3");
+
+        let patterns: CommandPatterns = toml::from_str::<toml::Table>(r#"p = ['^>>> (.+)$']"#).unwrap()["p"].clone().try_into().unwrap();
+        let lines = [ScreenLine { text: ">>> make".to_string(), row: Some(0) }, ScreenLine { text: "$ ls".to_string(), row: Some(1) }];
+        assert_eq!(texts(&extract(&lines, &patterns)), ["make"]);
+        assert!(toml::Value::Array(vec!["(".into()]).try_into::<CommandPatterns>().is_err());
+    }
+}
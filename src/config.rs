@@ -0,0 +1,548 @@
+use anyhow::{Context, Result};
+use ratatui::style::Color;
+use serde::{Deserialize, Deserializer};
+use std::path::{Path, PathBuf};
+use crate::command_pick::CommandPatterns;
+use crate::export::ExportFormat;
+use crate::glob::glob_match;
+use crate::keymap::Keymap;
+
+// Project-local config wins over the user-level one
+const PROJECT_CONFIG: &str = ".ai-tui.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BinaryApproval {
+    // Log binary/oversized changes in the sidebar without asking
+    Skip,
+    // Queue them for approval based on name and size alone
+    Metadata,
+}
+
+// How sidebar timestamps are shown; cycled with `T` in the sidebar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeFormat {
+    // "4m" ago
+    Relative,
+    // "14:32" today, "Tue 14:32" this week, "05-03" before that
+    Absolute,
+    // Full RFC 3339
+    Iso,
+}
+
+impl TimeFormat {
+    pub fn cycle(&self) -> Self {
+        match self {
+            Self::Relative => Self::Absolute,
+            Self::Absolute => Self::Iso,
+            Self::Iso => Self::Relative,
+        }
+    }
+}
+
+// What the sidebar selection does when a new change comes in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SidebarSelection {
+    // Jump to the new entry
+    Newest,
+    // Stay on the entry that was selected
+    Sticky,
+}
+
+// Where pending approvals are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalUi {
+    // Centered modal that takes all keys until answered
+    Modal,
+    // Banner above the status bar; the terminal stays usable
+    Inline,
+}
+
+// What `n` does to the file on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RejectMode {
+    // Write the old content back (or delete a new file)
+    Revert,
+    // Leave the agent's version in place and stop asking about it
+    Dismiss,
+}
+
+impl RejectMode {
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Self::Revert => "[n] Reject (Revert)",
+            Self::Dismiss => "[n] Dismiss",
+        }
+    }
+}
+
+// What an approval left unanswered for approval_timeout_secs turns into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeoutAction {
+    Approve,
+    Reject,
+}
+
+impl TimeoutAction {
+    pub fn verb(&self) -> &'static str {
+        match self {
+            Self::Approve => "auto-approve",
+            Self::Reject => "auto-reject",
+        }
+    }
+}
+
+// Pieces of the status bar. When it's too narrow the least important go
+// first: hints, then theme, title, agent, ... and warning last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusSegment {
+    // Watcher errors and dropped events, while recent
+    Warning,
+    // Approvals waiting ("3 pending"), hidden when none
+    Pending,
+    // Agent working/idle, indexing progress, follow mode
+    Activity,
+    // test_command running or its last result
+    Tests,
+    // Short-lived feedback such as "Exported to ..."
+    Message,
+    // Created/modified/removed counts
+    Counts,
+    // Session line totals
+    Lines,
+    // Git branch of the (first) watch root, hidden outside a repository
+    Branch,
+    // Command line of the running agent
+    Agent,
+    // Terminal title set by the agent (OSC 0/2)
+    Title,
+    Theme,
+    // Keybinding reminders
+    Hints,
+}
+
+impl StatusSegment {
+    // Lower is kept longer
+    pub fn priority(self) -> u8 {
+        match self {
+            Self::Warning => 0,
+            Self::Pending => 1,
+            Self::Activity => 2,
+            Self::Tests => 3,
+            Self::Message => 4,
+            Self::Counts => 5,
+            Self::Lines => 6,
+            Self::Branch => 7,
+            Self::Agent => 8,
+            Self::Title => 9,
+            Self::Theme => 10,
+            Self::Hints => 11,
+        }
+    }
+}
+
+// [status_bar] table: which segments show on each side, in order. Leaving
+// one out hides it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StatusBar {
+    pub left: Vec<StatusSegment>,
+    pub right: Vec<StatusSegment>,
+}
+
+impl StatusBar {
+    pub fn shows(&self, segment: StatusSegment) -> bool {
+        self.left.contains(&segment) || self.right.contains(&segment)
+    }
+}
+
+impl Default for StatusBar {
+    fn default() -> Self {
+        use StatusSegment::*;
+        Self { left: vec![Tests, Warning, Activity, Pending, Message, Title, Agent, Branch, Theme, Counts, Lines], right: vec![Hints] }
+    }
+}
+
+// Diff colors in place of the theme's own, as a name ("green"), "#a3be8c" or
+// a 256-color index ("108"). Anything left out takes the theme's status color
+// for it, as diffs were drawn before themes had diff colors.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DiffColors {
+    #[serde(deserialize_with = "color")]
+    pub add: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    pub remove: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    pub context: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    pub header: Option<Color>,
+}
+
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<Color>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    text.parse().map(Some).map_err(|_| serde::de::Error::custom(format!("unknown color {:?}", text)))
+}
+
+// A line count, or "all" for the whole file
+fn context_lines<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<usize, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Lines {
+        Count(usize),
+        Word(String),
+    }
+    match Lines::deserialize(deserializer)? {
+        Lines::Count(n) => Ok(n),
+        Lines::Word(word) if word == "all" => Ok(usize::MAX),
+        Lines::Word(word) => Err(serde::de::Error::custom(format!("diff_context is a number or \"all\", not {:?}", word))),
+    }
+}
+
+// What happens to changes under a `protected` glob
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProtectedMode {
+    // Revert immediately and log it as blocked
+    Block,
+    // Queue for approval behind a warning banner
+    Quarantine,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Program and arguments to run in the terminal pane (split on whitespace)
+    pub agent_command: String,
+    // The agent command given after `--`, word for word. When set it's run as
+    // is, and agent_command holds it quoted for display and the login shell.
+    #[serde(skip)]
+    pub agent_argv: Vec<String>,
+    // Files larger than this (in bytes) are never cached or diffed
+    pub max_file_size: u64,
+    // How many leading bytes to inspect for NUL when sniffing binary content
+    pub binary_sniff_bytes: usize,
+    pub binary_approval: BinaryApproval,
+    // Directories to watch; overridden by --dir
+    pub watch_roots: Vec<PathBuf>,
+    // Sidebar history length; 0 keeps everything
+    pub max_history: usize,
+    // Diffs larger than this (in bytes) are kept in a temp file until viewed
+    pub spill_diff_bytes: usize,
+    // Total text the startup scan may cache before giving up
+    pub max_index_bytes: u64,
+    // Files the startup scan may look at before giving up
+    pub max_index_files: usize,
+    // Show the agent's terminal title in the status bar and the real window title
+    pub forward_title: bool,
+    // Flash the terminal pane border when the agent rings the bell
+    pub bell_flash: bool,
+    // Also ring the real terminal's bell
+    pub bell_audible: bool,
+    // Extensions the sidebar's file-type filter cycles to, e.g. ["rs", "toml"]
+    pub sidebar_extensions: Vec<String>,
+    pub sidebar_time_format: TimeFormat,
+    pub sidebar_selection: SidebarSelection,
+    // What Ctrl+E writes: session-report.md or session-report.json
+    pub export_format: ExportFormat,
+    // Path globs whose changes are accepted without asking (e.g. "docs/**")
+    pub auto_approve: Vec<String>,
+    // Unix socket for `aiui ctl` and other tools, relative to the primary root
+    pub control_socket: Option<PathBuf>,
+    // Shell command run after a change is accepted; {path} is the file, e.g. "rustfmt {path}"
+    pub on_accept: Option<String>,
+    // An on_accept hook still running after this long is killed; 0 never
+    pub on_accept_timeout_secs: u64,
+    // Shell command run in the background when a change is queued, e.g.
+    // "cargo check --message-format=short"; the modal shows pass/fail
+    pub check_command: Option<String>,
+    // Path globs whose changes trigger check_command; empty means every path
+    pub check_paths: Vec<String>,
+    // Shell command run once after each accept (or burst of accepts), e.g.
+    // "cargo test -q"; its output goes to the Checks panel. It used to be
+    // called on_accept_command, too close to the per-file on_accept hook.
+    #[serde(alias = "on_accept_command")]
+    pub test_command: Option<String>,
+    // Start agent_command through `$SHELL -lc` so profile PATH changes apply
+    pub agent_login_shell: bool,
+    // Environment variables set for the agent
+    pub agent_env: std::collections::BTreeMap<String, String>,
+    // Inherited variables the agent shouldn't see, e.g. "SSH_AUTH_SOCK"
+    pub agent_env_unset: Vec<String>,
+    // Names (globs, any case) whose agent_env values are shown masked in
+    // `aiui ctl status` and exports
+    pub agent_env_mask: Vec<String>,
+    // Directory the agent starts in, relative to the first watch root
+    pub agent_cwd: Option<PathBuf>,
+    // Lines of a new file previewed in its approval before it's cut; 0 shows
+    // all. The stored diff always has every line.
+    pub new_file_preview_lines: usize,
+    // [diff_colors] table; without one the theme's diff colors stand
+    pub diff_colors: Option<DiffColors>,
+    // [status_bar] table
+    pub status_bar: StatusBar,
+    // [keys] table: app bindings over the defaults
+    pub keys: Keymap,
+    // Path globs the agent must not touch (e.g. ".env", "secrets/**")
+    pub protected: Vec<String>,
+    pub protected_mode: ProtectedMode,
+    pub approval_ui: ApprovalUi,
+    pub reject_mode: RejectMode,
+    // Skip the prompt explaining what a revert does before the first one.
+    // Set by its "don't ask again".
+    pub revert_acknowledged: bool,
+    // Decide the front approval by itself after this many seconds; 0 waits forever
+    pub approval_timeout_secs: u64,
+    pub approval_timeout_action: TimeoutAction,
+    // With strict_origin off, a change with no agent output within this many
+    // seconds of it is taken to be the user's own edit: logged, but never
+    // queued for approval
+    pub user_edit_window_secs: u64,
+    // Treat every change as the agent's. On by default: an agent can write
+    // after a quiet spell, and the guess would adopt that unasked.
+    pub strict_origin: bool,
+    // Longest the TUI sleeps waiting for an event (ms). Keys and agent
+    // output wake it immediately either way; an idle screen is only
+    // redrawn about once a second.
+    pub poll_interval_ms: u64,
+    // Start Markdown copies of a diff (`m`) with its +/- line counts
+    pub markdown_summary: bool,
+    // Lines typed into the agent kept for the Ctrl+Y picker; 0 keeps none
+    pub prompt_history: usize,
+    // Press Enter after sending a prompt written in $EDITOR (Ctrl+G)
+    pub editor_submit: bool,
+    // Border the terminal pane with this title; {cmd}, {cwd}, {branch} and
+    // {pending} are filled in. None keeps the pane borderless.
+    pub pane_title: Option<String>,
+    // Message for Alt+G's commit of the approved files; {count}, {files},
+    // {insertions} and {deletions} are filled in
+    pub commit_message: String,
+    // Regexes for commands the agent prints, for the pick-command hints
+    // (Alt+O); the first capture group is what gets sent
+    pub command_patterns: CommandPatterns,
+    // Diff with CRLF read as LF, so a file that only changed line endings
+    // isn't a change at all. What's on disk is still what a revert writes back.
+    pub normalize_line_endings: bool,
+    // Changes that only touch line endings are accepted and logged without
+    // asking, as the noise some editors and formatters make
+    pub auto_approve_line_endings: bool,
+    // Unchanged lines kept around each change in the diff view; 0 shows only
+    // the changes, "all" the whole file. `+`/`-` change it while looking.
+    #[serde(deserialize_with = "context_lines")]
+    pub diff_context: usize,
+    // Ctrl+C twice within 2s sends SIGTERM to the agent, and twice more
+    // SIGKILL. Off, every Ctrl+C is just passed on.
+    pub interrupt_escalation: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            agent_command: "npx opencode-ai".to_string(),
+            agent_argv: Vec::new(),
+            max_file_size: 4 * 1024 * 1024,
+            binary_sniff_bytes: 8000,
+            binary_approval: BinaryApproval::Skip,
+            watch_roots: Vec::new(),
+            max_history: 50,
+            spill_diff_bytes: 256 * 1024,
+            max_index_bytes: 512 * 1024 * 1024,
+            max_index_files: 100_000,
+            forward_title: true,
+            bell_flash: true,
+            bell_audible: false,
+            sidebar_extensions: Vec::new(),
+            sidebar_time_format: TimeFormat::Relative,
+            sidebar_selection: SidebarSelection::Newest,
+            export_format: ExportFormat::Markdown,
+            auto_approve: Vec::new(),
+            control_socket: None,
+            on_accept: None,
+            on_accept_timeout_secs: 60,
+            check_command: None,
+            check_paths: Vec::new(),
+            test_command: None,
+            agent_login_shell: false,
+            agent_env: std::collections::BTreeMap::new(),
+            agent_env_unset: Vec::new(),
+            agent_env_mask: ["*KEY*", "*TOKEN*", "*SECRET*", "*PASSWORD*"].map(String::from).to_vec(),
+            agent_cwd: None,
+            new_file_preview_lines: 200,
+            diff_colors: None,
+            status_bar: StatusBar::default(),
+            keys: Keymap::default(),
+            protected: Vec::new(),
+            protected_mode: ProtectedMode::Block,
+            approval_ui: ApprovalUi::Modal,
+            reject_mode: RejectMode::Revert,
+            revert_acknowledged: false,
+            approval_timeout_secs: 0,
+            approval_timeout_action: TimeoutAction::Reject,
+            user_edit_window_secs: 3,
+            strict_origin: true,
+            poll_interval_ms: 50,
+            markdown_summary: true,
+            prompt_history: 50,
+            editor_submit: false,
+            pane_title: None,
+            commit_message: "Apply {count} reviewed changes (+{insertions} -{deletions})\n\n{files}".to_string(),
+            command_patterns: CommandPatterns::default(),
+            normalize_line_endings: false,
+            auto_approve_line_endings: false,
+            diff_context: crate::diff::DEFAULT_CONTEXT,
+            interrupt_escalation: true,
+        }
+    }
+}
+
+impl Config {
+    // `dir` is the directory to be watched, when --dir names one; its
+    // .ai-tui.toml is the project config, otherwise the current directory's
+    pub fn load(dir: Option<&Path>) -> Result<Self> {
+        let mut config: Self = match Self::locate(dir) {
+            Some(path) => {
+                let text = std::fs::read_to_string(&path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                toml::from_str(&text).with_context(|| format!("invalid config in {}", path.display()))?
+            }
+            None => Self::default(),
+        };
+        // A person's answer, not a project's: the user config's counts even
+        // when a project config is in use
+        config.revert_acknowledged |= Self::user_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| text.parse::<toml::Table>().ok())
+            .and_then(|table| table.get("revert_acknowledged")?.as_bool())
+            .unwrap_or(false);
+        Ok(config)
+    }
+
+    pub fn is_protected(&self, display_path: &str) -> bool {
+        self.protected.iter().any(|rule| glob_match(rule, display_path))
+    }
+
+    fn locate(dir: Option<&Path>) -> Option<PathBuf> {
+        let local = dir.map_or_else(|| PathBuf::from(PROJECT_CONFIG), |dir| dir.join(PROJECT_CONFIG));
+        if local.is_file() {
+            return Some(local);
+        }
+        let user = Self::user_path()?;
+        user.is_file().then_some(user)
+    }
+
+    fn user_path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("ai-tui").join("config.toml"))
+    }
+
+    // Record `revert_acknowledged = true` in the user-level config, leaving
+    // the rest of it as written. Never the project's .ai-tui.toml: that's
+    // inside the watched tree, and shared.
+    pub fn acknowledge_revert() -> Result<PathBuf> {
+        let path = Self::user_path().context("no config directory")?;
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+        };
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, set_top_level(&text, "revert_acknowledged", "true"))
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+// `text` with `key = value` set among its top-level keys: an existing line is
+// replaced, otherwise it goes in before the first [table] so it doesn't land
+// inside one. CRLF files stay CRLF.
+fn set_top_level(text: &str, key: &str, value: &str) -> String {
+    let line = format!("{} = {}", key, value);
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    // A line starting with `[` inside a multi-line array is an element, not a header
+    let mut depth = 0;
+    let table = lines
+        .iter()
+        .position(|l| {
+            let header = depth == 0 && l.trim_start().starts_with('[');
+            if !header {
+                depth += bracket_balance(l);
+            }
+            header
+        })
+        .unwrap_or(lines.len());
+    let existing = lines[..table].iter().position(|l| {
+        l.trim_start().strip_prefix(key).is_some_and(|rest| rest.trim_start().starts_with('='))
+    });
+    match existing {
+        Some(index) => lines[index] = line,
+        None => lines.insert(table, line),
+    }
+    let eol = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut text = lines.join(eol);
+    text.push_str(eol);
+    text
+}
+
+// Opening minus closing brackets on a line, outside strings and comments
+fn bracket_balance(line: &str) -> i32 {
+    let mut balance = 0;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => break,
+            (None, '[') => balance += 1,
+            (None, ']') => balance -= 1,
+            (None, _) => {}
+        }
+    }
+    balance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_top_level_stays_out_of_tables() {
+        assert_eq!(set_top_level("", "a", "true"), "a = true\n");
+        assert_eq!(set_top_level("x = 1\n[t]\na = false\n", "a", "true"), "x = 1\na = true\n[t]\na = false\n");
+        assert_eq!(set_top_level("a  = false # old\nab = 2\n", "a", "true"), "a = true\nab = 2\n");
+        assert_eq!(set_top_level("x = 1\r\n[t]\r\n", "a", "true"), "x = 1\r\na = true\r\n[t]\r\n");
+        // Elements of a multi-line array aren't table headers
+        let nested = "protected = [\n  [\"odd\"],\n  \"]#\",\n]\n[keys]\nquit = \"ctrl+q\"\n";
+        assert_eq!(
+            set_top_level(nested, "a", "true"),
+            "protected = [\n  [\"odd\"],\n  \"]#\",\n]\na = true\n[keys]\nquit = \"ctrl+q\"\n"
+        );
+    }
+
+    #[test]
+    fn the_old_name_for_test_command_still_works() {
+        let config: Config = toml::from_str("on_accept_command = \"make test\"\n").unwrap();
+        assert_eq!(config.test_command.as_deref(), Some("make test"));
+    }
+
+    #[test]
+    fn the_project_config_comes_from_the_watched_dir() {
+        let dir = std::env::temp_dir().join(format!("aiui-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(PROJECT_CONFIG), "test_command = \"make check\"\n").unwrap();
+        let config = Config::load(Some(&dir));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(config.unwrap().test_command.as_deref(), Some("make check"));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::config::Config;
+use crate::fs::FileSystem;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use std::{
+    borrow::Cow,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::Path,
+};
+
+// How a text file's bytes map to the String we diff and cache, so a revert
+// writes back the same bytes it read. Plain UTF-8 is the common case.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextEncoding {
+    pub encoding: &'static Encoding,
+    // The file started with a byte order mark
+    pub bom: bool,
+}
+
+impl Default for TextEncoding {
+    fn default() -> Self {
+        Self { encoding: UTF_8, bom: false }
+    }
+}
+
+impl TextEncoding {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn encode<'a>(&self, text: &'a str) -> Cow<'a, [u8]> {
+        if self.is_default() {
+            return Cow::Borrowed(text.as_bytes());
+        }
+        let mut bytes = Vec::with_capacity(text.len() + 3);
+        // encoding_rs only encodes UTF-16 as UTF-8 (per WHATWG), so do it by hand
+        if self.encoding == UTF_16LE || self.encoding == UTF_16BE {
+            let le = self.encoding == UTF_16LE;
+            let unit = |u: u16| if le { u.to_le_bytes() } else { u.to_be_bytes() };
+            if self.bom {
+                bytes.extend(unit(0xFEFF));
+            }
+            text.encode_utf16().for_each(|u| bytes.extend(unit(u)));
+        } else {
+            if self.bom {
+                bytes.extend_from_slice(b"\xEF\xBB\xBF");
+            }
+            bytes.extend_from_slice(&self.encoding.encode(text).0);
+        }
+        Cow::Owned(bytes)
+    }
+}
+
+// BOM first (UTF-8/UTF-16), then strict UTF-8, then Windows-1252 for legacy
+// Latin-1 files. None when the bytes don't decode as any of them.
+pub fn decode(bytes: &[u8]) -> Option<(String, TextEncoding)> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let text = encoding.decode_without_bom_handling_and_without_replacement(&bytes[bom_len..])?;
+        return Some((text.into_owned(), TextEncoding { encoding, bom: true }));
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Some((text.to_string(), TextEncoding::default()));
+    }
+    // Every byte decodes in Windows-1252, so refuse anything with control
+    // characters a text file wouldn't have
+    if bytes.iter().any(|&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b)) {
+        return None;
+    }
+    let text = WINDOWS_1252.decode_without_bom_handling(bytes).0;
+    Some((text.into_owned(), TextEncoding { encoding: WINDOWS_1252, bom: false }))
+}
+
+// What we could make of a file on disk
+pub enum FileContent {
+    Text(String, TextEncoding),
+    Binary(u64),
+    TooLarge(u64),
+}
+
+impl FileContent {
+    // Human-readable description used in place of a diff
+    pub fn notice(&self) -> String {
+        match self {
+            Self::Text(text, _) => format!("text file ({})", format_size(text.len() as u64)),
+            Self::Binary(size) => format!("binary file changed ({})", format_size(*size)),
+            Self::TooLarge(size) => format!("file too large to diff ({})", format_size(*size)),
+        }
+    }
+}
+
+pub fn read(fs: &dyn FileSystem, path: &Path, config: &Config) -> Option<FileContent> {
+    let size = fs.stat(path).ok()?.len;
+    if size > config.max_file_size {
+        return Some(FileContent::TooLarge(size));
+    }
+
+    let bytes = fs.read(path).ok()?;
+    // UTF-16 is full of NULs, so a BOM has to be honored before sniffing
+    let sniff = &bytes[..bytes.len().min(config.binary_sniff_bytes)];
+    if Encoding::for_bom(&bytes).is_none() && sniff.contains(&0) {
+        return Some(FileContent::Binary(size));
+    }
+
+    match decode(&bytes) {
+        Some((text, encoding)) => Some(FileContent::Text(text, encoding)),
+        None => Some(FileContent::Binary(size)),
+    }
+}
+
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Cheap identity for what's on disk right now; None when the file is gone.
+// Oversized files are identified by size and mtime rather than read in full.
+pub fn fingerprint(fs: &dyn FileSystem, path: &Path, config: &Config) -> Option<u64> {
+    let stat = fs.stat(path).ok()?;
+    if stat.len > config.max_file_size {
+        let mut hasher = DefaultHasher::new();
+        stat.len.hash(&mut hasher);
+        stat.modified.hash(&mut hasher);
+        return Some(hasher.finish());
+    }
+    fs.read(path).ok().map(|bytes| hash_bytes(&bytes))
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodings_round_trip() {
+        let samples: [&[u8]; 4] = [
+            b"plain utf-8 \xC3\xA9\n",
+            b"\xEF\xBB\xBFutf-8 with a bom\n",
+            b"caf\xE9 latin-1\r\n",
+            b"\xFF\xFEh\x00i\x00\n\x00",
+        ];
+        for bytes in samples {
+            let (text, encoding) = decode(bytes).unwrap();
+            assert_eq!(&*encoding.encode(&text), bytes, "{:?}", encoding);
+        }
+
+        let (text, encoding) = decode(b"\xFE\xFF\x00h\x00i").unwrap();
+        assert_eq!((text.as_str(), encoding.encoding), ("hi", UTF_16BE));
+        assert_eq!(decode(b"caf\xE9").unwrap().0, "café");
+        // Odd-length UTF-16 and control-laden junk don't decode
+        assert!(decode(b"\xFF\xFEh\x00i").is_none());
+        assert!(decode(b"\x01\x02\xFF").is_none());
+    }
+}
@@ -0,0 +1,246 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::mpsc::Sender,
+};
+#[cfg(unix)]
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    sync::mpsc,
+    thread,
+};
+use crate::agent::{self, Launch};
+use crate::path_key::PathKey;
+use crate::{AppEvent, AppState, Decided};
+
+// Control socket for external tooling: one JSON request per line in, one
+// JSON response per line out. Connections are served on their own threads,
+// but every request is forwarded to the main loop as AppEvent::Control so
+// all mutation happens in one place. The socket is a Unix domain socket, so
+// elsewhere serving and connecting fail with an explanation.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "kebab-case")]
+pub enum Request {
+    ListPending,
+    // By path, or by the id list-pending reports for it
+    Approve {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<u64>,
+    },
+    Reject {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<u64>,
+    },
+    Status,
+    // Defaults to the export_format file in the primary root
+    Export {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub data: Value,
+}
+
+impl Response {
+    fn ok(data: Value) -> Self {
+        Self { ok: true, error: None, data }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        Self { ok: false, error: Some(message.into()), data: Value::Null }
+    }
+}
+
+// Where a configured socket lives: relative paths are under the primary root,
+// for the session and `aiui ctl` alike
+pub fn socket_path(configured: &Path, primary_root: &Path) -> PathBuf {
+    primary_root.join(configured)
+}
+
+// Bind the socket (replacing a stale one) and accept connections in the background
+#[cfg(unix)]
+pub fn serve(socket: &Path, tx: Sender<AppEvent>) -> Result<()> {
+    if let Some(parent) = socket.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    // A socket left behind by a crashed session would make bind fail
+    if socket.exists() && UnixStream::connect(socket).is_err() {
+        let _ = std::fs::remove_file(socket);
+    }
+    let listener = UnixListener::bind(socket)
+        .with_context(|| format!("failed to bind control socket {}", socket.display()))?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let _ = serve_connection(stream, tx);
+            });
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn serve(_socket: &Path, _tx: Sender<AppEvent>) -> Result<()> {
+    Err(anyhow!("control_socket needs Unix domain sockets, which this platform lacks"))
+}
+
+#[cfg(unix)]
+fn serve_connection(stream: UnixStream, tx: Sender<AppEvent>) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                // The reply channel is used once, as a oneshot
+                let (reply_tx, reply_rx) = mpsc::channel();
+                if tx.send(AppEvent::Control(request, reply_tx)).is_err() {
+                    break;
+                }
+                reply_rx.recv().unwrap_or_else(|_| Response::error("app is shutting down"))
+            }
+            Err(e) => Response::error(format!("invalid request: {}", e)),
+        };
+        serde_json::to_writer(&mut writer, &response)?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+// Runs on the main loop
+pub fn handle(state: &mut AppState, request: Request) -> Response {
+    match request {
+        Request::ListPending => {
+            let pending: Vec<Value> = state.approval_queue.iter().map(|p| json!({
+                "id": p.change_id,
+                "path": p.path.to_string_lossy(),
+                "kind": p.kind,
+                "insertions": p.insertions,
+                "deletions": p.deletions,
+                "refreshed": p.refreshed,
+                "protected": p.protected,
+                "diff": p.diff_text.as_str(),
+            })).collect();
+            Response::ok(Value::Array(pending))
+        }
+        Request::Approve { path, id } => decide(state, path.as_deref(), id, true),
+        Request::Reject { path, id } => decide(state, path.as_deref(), id, false),
+        Request::Status => Response::ok(json!({
+            "pending": state.approval_queue.len(),
+            "changes": state.file_changes.len(),
+            "files": state.stats.files.len(),
+            "insertions": state.stats.insertions,
+            "deletions": state.stats.deletions,
+            "accepted": state.stats.accepted,
+            "rejected": state.stats.rejected,
+            "indexing": state.indexing.is_some(),
+            "agent": state.config.agent_command,
+            "agent_env": agent::masked_env(&Launch::from_config(&state.config).env, &state.config.agent_env_mask),
+            "agent_env_unset": state.config.agent_env_unset,
+        })),
+        Request::Export { path } => {
+            let format = path.as_deref().map_or(state.config.export_format, crate::export::ExportFormat::from_path);
+            let path = path.unwrap_or_else(|| format.default_file_name().into());
+            // Somewhere in the project, not anywhere a socket client names
+            if !path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+                return Response::error(format!("export path {} must be relative to the project root, without ..", path.display()));
+            }
+            let path = state.roots.primary().path.join(path);
+            match state.export(&path, format) {
+                Ok(()) => Response::ok(json!({ "path": path })),
+                Err(e) => Response::error(format!("export failed: {:#}", e)),
+            }
+        }
+    }
+}
+
+// Move the matching entry to the head of the queue and resolve it there
+fn decide(state: &mut AppState, path: Option<&str>, id: Option<u64>, accept: bool) -> Response {
+    let index = match (path, id) {
+        (_, Some(id)) => state.approval_queue.iter().position(|p| p.change_id == id),
+        (Some(path), None) => state.approval_queue.iter().position(|p| {
+            p.path == PathKey::from(path) || state.display_path(p.path.path()).as_deref() == Some(path)
+        }),
+        (None, None) => return Response::error("approve and reject need a path or an id"),
+    };
+    let Some(index) = index else {
+        let what = id.map_or_else(|| path.unwrap_or_default().to_string(), |id| format!("id {}", id));
+        return Response::error(format!("no pending change for {}", what));
+    };
+    let id = state.approval_queue[index].change_id;
+    if !accept && state.needs_revert_consent(&state.approval_queue[index]) {
+        let effect = state.approval_queue[index].revert_effect();
+        return Response::error(format!(
+            "rejecting would {}; confirm a first revert in the TUI or set revert_acknowledged = true",
+            effect
+        ));
+    }
+    let pending = state.approval_queue.remove(index).unwrap();
+    let full_path = pending.path.to_string_lossy().into_owned();
+    state.approval_queue.push_front(pending);
+    state.modal_cursor.reset();
+    state.revert_preview = None;
+
+    let decided = if accept { state.try_accept_front() } else { state.try_reject_front() };
+    match decided {
+        Decided::Resolved => {}
+        // A stale entry is re-queued with a fresh diff instead of being resolved
+        Decided::Refreshed => {
+            return Response::error(format!("{} changed again on disk; review the refreshed diff", full_path));
+        }
+        Decided::WriteFailed(error) => return Response::error(format!("accept failed: {}", error)),
+    }
+    Response::ok(json!({ "id": id, "path": full_path, "decision": if accept { "accepted" } else { "rejected" } }))
+}
+
+// Send one request and wait for its response
+#[cfg(unix)]
+pub fn request(socket: &Path, request: &Request) -> Result<Response> {
+    let mut stream = UnixStream::connect(socket)
+        .with_context(|| format!("failed to connect to {}", socket.display()))?;
+    serde_json::to_writer(&mut stream, request)?;
+    stream.write_all(b"\n")?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    if line.is_empty() {
+        return Err(anyhow!("connection closed without a response"));
+    }
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(not(unix))]
+pub fn request(_socket: &Path, _request: &Request) -> Result<Response> {
+    Err(anyhow!("aiui ctl needs Unix domain sockets, which this platform lacks"))
+}
+
+// `aiui ctl`: the reference client. Prints the response and fails on errors.
+pub fn run_client(socket: &Path, request: Request) -> Result<()> {
+    let response = self::request(socket, &request)?;
+    println!("{}", serde_json::to_string_pretty(&response)?);
+    if response.ok {
+        Ok(())
+    } else {
+        Err(anyhow!(response.error.unwrap_or_default()))
+    }
+}
@@ -0,0 +1,74 @@
+use std::{path::Path, process::{Command, Stdio}};
+
+// OSC 52 "set clipboard" sequence. Goes through the terminal itself, so it
+// also works over SSH; most modern terminals (and tmux with set-clipboard) honor it.
+pub fn osc52_copy(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+// Show `path`'s folder in the platform file manager, without waiting for it
+pub fn reveal(path: &Path) -> std::io::Result<()> {
+    let folder = path.parent().unwrap_or(path);
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(windows) {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    Command::new(opener)
+        .arg(folder)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Just enough of a decoder to check the encoder against
+    fn decode(text: &str) -> Vec<u8> {
+        const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let sextets: Vec<u32> = text.trim_end_matches('=').chars().map(|c| ALPHABET.find(c).unwrap() as u32).collect();
+        let mut out = Vec::new();
+        for chunk in sextets.chunks(4) {
+            let n = chunk.iter().enumerate().fold(0, |n, (i, s)| n | s << (18 - 6 * i));
+            out.extend(n.to_be_bytes()[1..chunk.len()].iter());
+        }
+        out
+    }
+
+    #[test]
+    fn base64_matches_the_rfc_and_round_trips() {
+        // RFC 4648 test vectors, covering each amount of padding
+        let vectors = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")];
+        for (plain, encoded) in vectors {
+            assert_eq!(base64(plain.as_bytes()), encoded);
+        }
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode(&base64(&bytes)), bytes);
+        assert_eq!(decode(&base64("/tmp/naïve ünïcode.rs".as_bytes())), "/tmp/naïve ünïcode.rs".as_bytes());
+
+        assert_eq!(osc52_copy("hi"), "\x1b]52;c;aGk=\x07");
+    }
+}
@@ -0,0 +1,809 @@
+use ratatui::{
+    style::{Modifier, Style},
+    text::{Line, Span},
+};
+use similar::{ChangeTag, TextDiff};
+use std::{borrow::Cow, ops::Range};
+use crate::ansi;
+use crate::syntax::Syntax;
+use crate::ui::theme::Theme;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineTag {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Clone, Debug)]
+pub struct DiffLine {
+    pub tag: LineTag,
+    pub text: String,
+}
+
+// One group of changes plus its surrounding context lines
+#[derive(Clone, Debug)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+impl Hunk {
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start + 1,
+            self.old_len,
+            self.new_start + 1,
+            self.new_len
+        )
+    }
+}
+
+// Unchanged lines around each change, unless the diff view is set otherwise
+pub const DEFAULT_CONTEXT: usize = 3;
+// What `+`/`-` in the diff view step through
+const CONTEXT_STEPS: [usize; 8] = [0, 1, 3, 5, 10, 20, 50, usize::MAX];
+
+// The next step up or down from `context`, which may be between steps
+pub fn step_context(context: usize, wider: bool) -> usize {
+    if wider {
+        CONTEXT_STEPS.into_iter().find(|&s| s > context).unwrap_or(usize::MAX)
+    } else {
+        CONTEXT_STEPS.into_iter().rev().find(|&s| s < context).unwrap_or(0)
+    }
+}
+
+pub fn context_label(context: usize) -> String {
+    match context {
+        usize::MAX => "whole file".to_string(),
+        n => format!("context {}", n),
+    }
+}
+
+pub fn compute_hunks(old: &str, new: &str, context: usize) -> Vec<Hunk> {
+    with_context(&full_diff(old, new), context)
+}
+
+// Both files line by line as one hunk. It's what `with_context` cuts hunks
+// from, so a diff can be shown again at another context width without
+// reading the file back.
+pub fn full_diff(old: &str, new: &str) -> Hunk {
+    let diff = TextDiff::from_lines(old, new);
+    let lines: Vec<DiffLine> = diff
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            tag: match change.tag() {
+                ChangeTag::Delete => LineTag::Delete,
+                ChangeTag::Insert => LineTag::Insert,
+                ChangeTag::Equal => LineTag::Equal,
+            },
+            // The model only ever holds plain text; see `ansi::strip`
+            text: ansi::strip(change.value().trim_end_matches(['\n', '\r'])),
+        })
+        .collect();
+    Hunk {
+        old_start: 0,
+        old_len: lines.iter().filter(|l| l.tag != LineTag::Insert).count(),
+        new_start: 0,
+        new_len: lines.iter().filter(|l| l.tag != LineTag::Delete).count(),
+        lines,
+    }
+}
+
+// Hunks with `context` unchanged lines around each change, like
+// `grouped_ops`: changes closer than twice that share a hunk. 0 is changed
+// lines only and usize::MAX the whole file.
+pub fn with_context(full: &Hunk, context: usize) -> Vec<Hunk> {
+    let changed = |i: usize| full.lines[i].tag != LineTag::Equal;
+    if !(0..full.lines.len()).any(changed) {
+        return Vec::new();
+    }
+    // How far each line is from the nearest change
+    let mut distance = vec![usize::MAX; full.lines.len()];
+    let mut last = None;
+    for (i, d) in distance.iter_mut().enumerate() {
+        last = if changed(i) { Some(i) } else { last };
+        if let Some(last) = last {
+            *d = i - last;
+        }
+    }
+    let mut next = None;
+    for (i, d) in distance.iter_mut().enumerate().rev() {
+        next = if changed(i) { Some(i) } else { next };
+        if let Some(next) = next {
+            *d = (*d).min(next - i);
+        }
+    }
+
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let (mut old_line, mut new_line) = (full.old_start, full.new_start);
+    let mut open = false;
+    for (line, &distance) in full.lines.iter().zip(&distance) {
+        if distance <= context {
+            if !open {
+                hunks.push(Hunk { old_start: old_line, old_len: 0, new_start: new_line, new_len: 0, lines: Vec::new() });
+                open = true;
+            }
+            let hunk = hunks.last_mut().expect("just opened");
+            hunk.old_len += usize::from(line.tag != LineTag::Insert);
+            hunk.new_len += usize::from(line.tag != LineTag::Delete);
+            hunk.lines.push(line.clone());
+        } else {
+            open = false;
+        }
+        old_line += usize::from(line.tag != LineTag::Insert);
+        new_line += usize::from(line.tag != LineTag::Delete);
+    }
+    hunks
+}
+
+// (insertions, deletions) across all hunks
+pub fn line_counts(hunks: &[Hunk]) -> (usize, usize) {
+    hunks
+        .iter()
+        .flat_map(|h| &h.lines)
+        .fold((0, 0), |(ins, del), line| match line.tag {
+            LineTag::Insert => (ins + 1, del),
+            LineTag::Delete => (ins, del + 1),
+            LineTag::Equal => (ins, del),
+        })
+}
+
+// Flatten hunks into the "+/-/ " prefixed text stored on FileChange
+pub fn render_text(hunks: &[Hunk]) -> String {
+    let mut output = String::new();
+    for hunk in hunks {
+        output.push_str(&hunk.header());
+        output.push('\n');
+        for line in &hunk.lines {
+            let sign = match line.tag {
+                LineTag::Delete => '-',
+                LineTag::Insert => '+',
+                LineTag::Equal => ' ',
+            };
+            output.push(sign);
+            output.push_str(&line.text);
+            output.push('\n');
+        }
+    }
+    output
+}
+
+// First line of a new file's listing; stands where the hunk header would be
+pub const NEW_FILE: &str = "New file";
+// Longer lines (minified JS, data blobs) are cut in the preview
+const PREVIEW_LINE_CHARS: usize = 300;
+
+// The diff of a brand-new file: a size header, then every line added. A
+// missing final newline is called out like git does. This is what's kept
+// and exported; the approval only previews the top of it.
+pub fn new_file_diff(content: &str, bytes: usize) -> String {
+    let total = content.lines().count();
+    if total == 0 {
+        return format!("{} (empty, {})\n", NEW_FILE, crate::content::format_size(bytes as u64));
+    }
+    let mut out = format!("{} ({} line{}, {})\n", NEW_FILE, total, if total == 1 { "" } else { "s" }, crate::content::format_size(bytes as u64));
+    for line in content.lines() {
+        out.push('+');
+        out.push_str(&ansi::strip(line.strip_suffix('\r').unwrap_or(line)));
+        out.push('\n');
+    }
+    if !content.ends_with('\n') {
+        out.push_str("\\ No newline at end of file\n");
+    }
+    out
+}
+
+// Roughly what `hunk` takes to hold, for keeping big ones out of memory
+pub fn text_bytes(hunk: &Hunk) -> usize {
+    hunk.lines.iter().map(|line| line.text.len() + 1).sum()
+}
+
+// Is `diff` a new file's listing rather than hunks?
+pub fn is_new_file(diff: &DiffText) -> bool {
+    diff.lines(0..1).next().is_some_and(|line| line.starts_with(NEW_FILE))
+}
+
+// How many lines of a new file's listing the approval shows: the header and
+// the first `max_lines` of the file (0 shows them all)
+pub fn preview_rows(diff: &DiffText, max_lines: usize) -> usize {
+    if max_lines == 0 { diff.line_count() } else { diff.line_count().min(max_lines + 1) }
+}
+
+// Lines in `range` of a new file's listing as the approval previews them:
+// long lines cut short and, for a language `syntax` knows, highlighted
+pub fn new_file_preview<'a>(diff: &'a DiffText, range: Range<usize>, syntax: Option<&Syntax>, theme: &Theme) -> Vec<Line<'a>> {
+    let first = range.start;
+    diff.lines(range)
+        .enumerate()
+        .map(|(row, line)| {
+            let Some(body) = line.strip_prefix('+').filter(|_| first + row > 0) else {
+                let color = if first + row == 0 { theme.diff_header } else { theme.diff_context };
+                return Line::from(Span::styled(line, Style::default().fg(color)));
+            };
+            let style = Style::default().fg(theme.diff_add);
+            let (body, cut) = match body.char_indices().nth(PREVIEW_LINE_CHARS) {
+                Some((at, _)) => (&body[..at], Some(body[at..].chars().count())),
+                None => (body, None),
+            };
+            let mut spans = vec![Span::styled("+", style)];
+            match syntax {
+                Some(syntax) => spans.extend(syntax.spans(body, style, theme)),
+                None => spans.push(Span::styled(body, style)),
+            }
+            if let Some(rest) = cut {
+                spans.push(Span::styled(format!("{} (+{} chars)", theme.symbol("…", "..."), rest), Style::default().fg(theme.text_muted)));
+            }
+            Line::from(spans)
+        })
+        .collect()
+}
+
+// Rebuild file content taking the new side of accepted hunks and the old
+// side of the rest. Lines are copied verbatim so line endings survive.
+pub fn apply_hunks(old: &str, new: &str, hunks: &[Hunk], accepted: &[bool]) -> String {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let mut output = String::with_capacity(new.len().max(old.len()));
+    let mut cursor = 0;
+
+    for (i, hunk) in hunks.iter().enumerate() {
+        let start = hunk.old_start.min(old_lines.len());
+        let end = (hunk.old_start + hunk.old_len).min(old_lines.len());
+        for line in &old_lines[cursor.min(start)..start] {
+            output.push_str(line);
+        }
+
+        if accepted.get(i).copied().unwrap_or(true) {
+            let new_start = hunk.new_start.min(new_lines.len());
+            let new_end = (hunk.new_start + hunk.new_len).min(new_lines.len());
+            for line in &new_lines[new_start..new_end] {
+                output.push_str(line);
+            }
+        } else {
+            for line in &old_lines[start..end] {
+                output.push_str(line);
+            }
+        }
+        cursor = end;
+    }
+
+    for line in old_lines.iter().skip(cursor) {
+        output.push_str(line);
+    }
+    output
+}
+
+// CRLF read as LF, for diffing. Line counts don't change, so hunks from the
+// normalized text still index the original's lines.
+pub fn normalize_eol(text: &str) -> Cow<'_, str> {
+    if text.contains("\r\n") { Cow::Owned(text.replace("\r\n", "\n")) } else { Cow::Borrowed(text) }
+}
+
+// 1-based line of the new side where the first hunk's change starts, for
+// opening an editor there. A deletion gives the line that followed it.
+pub fn first_changed_line(hunks: &[Hunk]) -> Option<usize> {
+    let hunk = hunks.first()?;
+    let context = hunk.lines.iter().take_while(|l| l.tag == LineTag::Equal).count();
+    Some(hunk.new_start + context + 1)
+}
+
+// Which line endings a file uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    // A single line, or empty
+    None,
+    Lf,
+    Crlf,
+    Mixed,
+}
+
+impl LineEnding {
+    pub fn detect(text: &str) -> Self {
+        let (lf, crlf) = eol_counts(text);
+        match (lf, crlf) {
+            (0, 0) => Self::None,
+            (_, 0) => Self::Lf,
+            (0, _) => Self::Crlf,
+            _ => Self::Mixed,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Lf => "LF",
+            Self::Crlf => "CRLF",
+            Self::Mixed => "mixed",
+        }
+    }
+}
+
+// Bare `\n` and `\r\n` line ends
+fn eol_counts(text: &str) -> (usize, usize) {
+    let crlf = text.matches("\r\n").count();
+    (text.matches('\n').count() - crlf, crlf)
+}
+
+// The ending most of the file's lines have, for lines written without one.
+// A tie goes to LF.
+pub fn dominant_eol(text: &str) -> &'static str {
+    let (lf, crlf) = eol_counts(text);
+    if crlf > lf { "\r\n" } else { "\n" }
+}
+
+// The line endings a change switched the file between, (from, to). None if
+// they're the same or either side has no line ending at all.
+pub fn eol_change(old: &str, new: &str) -> Option<(LineEnding, LineEnding)> {
+    let (from, to) = (LineEnding::detect(old), LineEnding::detect(new));
+    (from != to && from != LineEnding::None && to != LineEnding::None).then_some((from, to))
+}
+
+// `text` with every line ending made `eol` (Lf or Crlf; anything else
+// leaves it as it is)
+pub fn with_eol(text: &str, eol: LineEnding) -> String {
+    match eol {
+        LineEnding::Lf => normalize_eol(text).into_owned(),
+        LineEnding::Crlf => normalize_eol(text).replace('\n', "\r\n"),
+        LineEnding::None | LineEnding::Mixed => text.to_string(),
+    }
+}
+
+// For a change that left the text alone but not its line endings: what they
+// went from and to, and how many lines changed. None if anything else did.
+pub fn eol_only_summary(old: &str, new: &str) -> Option<String> {
+    if old == new || normalize_eol(old) != normalize_eol(new) {
+        return None;
+    }
+    let changed = old.split_inclusive('\n').zip(new.split_inclusive('\n')).filter(|(a, b)| a != b).count();
+    let (from, to) = (LineEnding::detect(old), LineEnding::detect(new));
+    Some(format!("Line ending changes only: {} → {} ({} line{})", from.label(), to.label(), changed, if changed == 1 { "" } else { "s" }))
+}
+
+// The content `hunks` were diffed from, given what they produced. None when
+// `new` isn't that anymore. Context lines are still in `new` and keep their
+// own endings; removed lines get the file's usual one.
+pub fn unapply(new: &str, hunks: &[Hunk]) -> Option<String> {
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let eol = dominant_eol(new);
+    let mut output = String::with_capacity(new.len());
+    let mut cursor = 0;
+
+    for hunk in hunks {
+        let (start, end) = (hunk.new_start, hunk.new_start + hunk.new_len);
+        if start < cursor || end > new_lines.len() {
+            return None;
+        }
+        new_lines[cursor..start].iter().for_each(|line| output.push_str(line));
+        let shown = hunk.lines.iter().filter(|l| l.tag != LineTag::Delete).map(|l| l.text.as_str());
+        if !shown.eq(new_lines[start..end].iter().map(|l| l.trim_end_matches(['\n', '\r']))) {
+            return None;
+        }
+        let mut kept = new_lines[start..end].iter();
+        for line in &hunk.lines {
+            match line.tag {
+                LineTag::Insert => {
+                    kept.next();
+                }
+                LineTag::Equal => output.push_str(kept.next().copied().unwrap_or_default()),
+                LineTag::Delete => {
+                    output.push_str(&line.text);
+                    output.push_str(eol);
+                }
+            }
+        }
+        cursor = end;
+    }
+    new_lines[cursor..].iter().for_each(|line| output.push_str(line));
+    Some(output)
+}
+
+// Inverse of `render_text`, used when a spilled diff is loaded back from disk
+pub fn parse_text(text: &str) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    for line in text.lines() {
+        if let Some(header) = line.strip_prefix("@@ -").and_then(|h| h.strip_suffix(" @@")) {
+            let Some((old, new)) = header.split_once(" +") else { continue };
+            let range = |r: &str| -> Option<(usize, usize)> {
+                let (start, len) = r.split_once(',')?;
+                Some((start.parse::<usize>().ok()?.saturating_sub(1), len.parse().ok()?))
+            };
+            let (Some((old_start, old_len)), Some((new_start, new_len))) = (range(old), range(new)) else {
+                continue;
+            };
+            hunks.push(Hunk { old_start, old_len, new_start, new_len, lines: Vec::new() });
+            continue;
+        }
+        if line.starts_with(NEW_FILE) && hunks.is_empty() {
+            hunks.push(Hunk { old_start: 0, old_len: 0, new_start: 0, new_len: 0, lines: Vec::new() });
+            continue;
+        }
+
+        let Some(hunk) = hunks.last_mut() else { continue };
+        let (tag, rest) = match line.chars().next() {
+            Some('+') => (LineTag::Insert, &line[1..]),
+            Some('-') => (LineTag::Delete, &line[1..]),
+            Some(' ') => (LineTag::Equal, &line[1..]),
+            _ => (LineTag::Equal, line),
+        };
+        hunk.lines.push(DiffLine { tag, text: rest.to_string() });
+    }
+    hunks
+}
+
+// How far past the visible rows to look for the other half of a -/+ pair
+const PAIR_LOOKAROUND: usize = 256;
+
+// Color `render_text` output by line prefix; shared by the modal and the diff view.
+// In ASCII-only mode added and removed lines are labelled as well.
+// `show_whitespace` makes whitespace in changed lines visible (trailing blanks
+// as `·`, tabs as `→ `) and marks -/+ pairs that differ only in whitespace.
+pub fn diff_lines_to_spans<'a>(diff: &'a DiffText, range: Range<usize>, theme: &Theme, show_whitespace: bool) -> Vec<Line<'a>> {
+    let whitespace_only = if show_whitespace { whitespace_only(diff, &range) } else { Vec::new() };
+    let first = range.start;
+    diff.lines(range)
+        .enumerate()
+        .map(|(row, line)| {
+            let (color, label) = match line.chars().next() {
+                Some('+') => (theme.diff_add, "[ADDED]   "),
+                Some('-') => (theme.diff_remove, "[REMOVED] "),
+                Some('@') => (theme.diff_header, ""),
+                _ if first + row == 0 && line.starts_with(NEW_FILE) => (theme.diff_header, ""),
+                _ => (theme.diff_context, ""),
+            };
+            let style = Style::default().fg(color);
+            let mut spans = Vec::new();
+            if theme.ascii_only && !label.is_empty() {
+                spans.push(Span::styled(label, style));
+            }
+            // Only added and removed lines have a label
+            if show_whitespace && !label.is_empty() {
+                spans.extend(visible_whitespace(line, style, theme));
+            } else {
+                spans.push(Span::styled(line, style));
+            }
+            if whitespace_only.get(row) == Some(&true) {
+                spans.push(Span::styled(" [whitespace only]", Style::default().fg(theme.status_warning)));
+            }
+            Line::from(spans)
+        })
+        .collect()
+}
+
+// A changed line with its tabs and trailing blanks drawn
+fn visible_whitespace<'a>(line: &'a str, style: Style, theme: &Theme) -> Vec<Span<'a>> {
+    let (sign, rest) = line.split_at(1);
+    let (body, trailing) = rest.split_at(rest.trim_end_matches([' ', '\t']).len());
+    let tab = theme.symbol("→ ", "> ");
+    let muted = Style::default().fg(theme.text_muted);
+    let mut spans = vec![Span::styled(sign, style)];
+    for (i, part) in body.split('\t').enumerate() {
+        if i > 0 {
+            spans.push(Span::styled(tab.to_string(), muted));
+        }
+        spans.push(Span::styled(part, style));
+    }
+    if !trailing.is_empty() {
+        let dot = theme.symbol("·", ".");
+        let shown: String = trailing.chars().map(|c| if c == '\t' { tab } else { dot }).collect();
+        spans.push(Span::styled(shown, Style::default().fg(theme.status_warning).add_modifier(Modifier::BOLD)));
+    }
+    spans
+}
+
+// For each line in `range`: is it half of a -/+ pair that only differs in
+// whitespace? Within a block of changes the n-th removed line is paired with
+// the n-th added one, like most side-by-side diff viewers do.
+fn whitespace_only(diff: &DiffText, range: &Range<usize>) -> Vec<bool> {
+    let end = range.end.min(diff.line_count());
+    let start = range.start.min(end);
+    let changed = |i: usize| diff.lines(i..i + 1).next().is_some_and(|l| l.starts_with(['+', '-']));
+    // The block may start above the window or end below it
+    let mut from = start;
+    while from > 0 && start - from < PAIR_LOOKAROUND && changed(from - 1) {
+        from -= 1;
+    }
+    let mut to = end;
+    while to < diff.line_count() && to - end < PAIR_LOOKAROUND && changed(to) {
+        to += 1;
+    }
+
+    let lines: Vec<&str> = diff.lines(from..to).collect();
+    let run = |at: usize, sign: char| lines[at..].iter().take_while(|l| l.starts_with(sign)).count();
+    let squash = |l: &str| l[1..].chars().filter(|c| !c.is_whitespace()).collect::<String>();
+    let mut flags = vec![false; lines.len()];
+    let mut i = 0;
+    while i < lines.len() {
+        let removed = run(i, '-');
+        let added = run(i + removed, '+');
+        for k in 0..removed.min(added) {
+            let (old, new) = (lines[i + k], lines[i + removed + k]);
+            if old[1..] != new[1..] && squash(old) == squash(new) {
+                flags[i + k] = true;
+                flags[i + removed + k] = true;
+            }
+        }
+        i += (removed + added).max(1);
+    }
+    flags[start - from..end - from].to_vec()
+}
+
+// Diff text plus the byte offset of every line, so a pane can style just
+// the rows it shows instead of the whole (possibly 50k-line) diff each frame
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiffText {
+    text: String,
+    starts: Vec<usize>,
+}
+
+impl DiffText {
+    pub fn new(text: String) -> Self {
+        let mut starts = Vec::new();
+        if !text.is_empty() {
+            starts.push(0);
+            starts.extend(text.match_indices('\n').map(|(i, _)| i + 1).filter(|&i| i < text.len()));
+        }
+        Self { text, starts }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.starts.len()
+    }
+
+    // Lines in `range` (clamped), without their line endings
+    pub fn lines(&self, range: Range<usize>) -> impl Iterator<Item = &str> {
+        let end = range.end.min(self.starts.len());
+        let start = range.start.min(end);
+        (start..end).map(move |i| {
+            let to = self.starts.get(i + 1).copied().unwrap_or(self.text.len());
+            let line = &self.text[self.starts[i]..to];
+            let line = line.strip_suffix('\n').unwrap_or(line);
+            line.strip_suffix('\r').unwrap_or(line)
+        })
+    }
+}
+
+impl From<String> for DiffText {
+    fn from(text: String) -> Self {
+        Self::new(text)
+    }
+}
+
+// Line index of each hunk header within `render_text` output
+pub fn hunk_offsets(hunks: &[Hunk]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(hunks.len());
+    let mut line = 0;
+    for hunk in hunks {
+        offsets.push(line);
+        line += 1 + hunk.lines.len();
+    }
+    offsets
+}
+
+// Tracks which hunk is currently scrolled to the top of a diff pane
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HunkCursor {
+    pub current: Option<usize>,
+    // Lines paged past the current hunk (or the top of the diff)
+    pub offset: usize,
+}
+
+impl HunkCursor {
+    pub fn next(&mut self, total: usize) {
+        if total == 0 {
+            return;
+        }
+        self.current = Some(match self.current {
+            Some(i) => (i + 1).min(total - 1),
+            None => 0,
+        });
+        self.offset = 0;
+    }
+
+    pub fn prev(&mut self, total: usize) {
+        if total == 0 {
+            return;
+        }
+        self.current = Some(self.current.map_or(0, |i| i.saturating_sub(1)));
+        self.offset = 0;
+    }
+
+    pub fn reset(&mut self) {
+        self.current = None;
+        self.offset = 0;
+    }
+
+    // PgUp/PgDn: move `rows` lines, never past the last of `line_count`
+    pub fn page(&mut self, forward: bool, rows: usize, offsets: &[usize], line_count: usize) {
+        let base = self.scroll(offsets) - self.offset;
+        self.offset = if forward {
+            (self.offset + rows).min(line_count.saturating_sub(base + 1))
+        } else {
+            self.offset.saturating_sub(rows)
+        };
+    }
+
+    // First diff text line to show: the current hunk's header, plus any paging
+    pub fn scroll(&self, offsets: &[usize]) -> usize {
+        let hunk = self.current.and_then(|i| offsets.get(i)).copied().unwrap_or(0);
+        hunk + self.offset
+    }
+
+    pub fn label(&self, total: usize) -> String {
+        match self.current {
+            Some(i) if total > 0 => format!("Hunk {}/{}", i + 1, total),
+            _ => format!("{} hunks", total),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_widths_are_cut_from_the_whole_file() {
+        let old: String = (1..=20).map(|n| format!("{}\n", n)).collect();
+        let new: String = (1..=21)
+            .filter(|&n| n != 12)
+            .map(|n| if n == 3 { "three\n".to_string() } else { format!("{}\n", n) })
+            .collect();
+        // Same hunks as similar's own grouping
+        let diff = TextDiff::from_lines(old.as_str(), new.as_str());
+        let theirs: Vec<(usize, usize)> = diff.grouped_ops(3).iter().map(|g| (g[0].old_range().start, g.last().unwrap().old_range().end)).collect();
+        let ours: Vec<(usize, usize)> = compute_hunks(&old, &new, 3).iter().map(|h| (h.old_start, h.old_start + h.old_len)).collect();
+        assert_eq!(ours, theirs);
+
+        let full = full_diff(&old, &new);
+        let headers = |context| with_context(&full, context).iter().map(Hunk::header).collect::<Vec<_>>();
+        assert_eq!(headers(0), ["@@ -3,1 +3,1 @@", "@@ -12,1 +12,0 @@", "@@ -21,0 +20,1 @@"]);
+        assert_eq!(headers(usize::MAX), ["@@ -1,20 +1,20 @@"]);
+        // Eight unchanged lines between each change: apart at 3, one hunk at 4
+        assert_eq!(headers(3).len(), 3);
+        assert_eq!(headers(4), ["@@ -1,20 +1,20 @@"]);
+        assert!(with_context(&full_diff(&old, &old), usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn new_file_previews() {
+        assert_eq!(new_file_diff("", 0), "New file (empty, 0 B)\n");
+        assert_eq!(new_file_diff("fn main() {}\n", 13), "New file (1 line, 13 B)\n+fn main() {}\n");
+        // No trailing newline: the last line is kept whole and flagged
+        assert_eq!(new_file_diff("a\r\nb", 4), "New file (2 lines, 4 B)\n+a\n+b\n\\ No newline at end of file\n");
+
+        // Kept whole; only the preview is cut
+        let theme = Theme::new(crate::ui::theme::ThemeVariant::Zinc);
+        let text = |lines: Vec<Line>| lines.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        let long = DiffText::from(new_file_diff(&"x".repeat(4000), 4000));
+        assert_eq!(long.lines(1..2).next().unwrap().len(), 4001);
+        assert_eq!(text(new_file_preview(&long, 1..2, None, &theme)), [format!("+{}… (+3700 chars)", "x".repeat(300))]);
+        let many: String = (0..500).map(|i| format!("line {}\n", i)).collect();
+        let many = DiffText::from(new_file_diff(&many, many.len()));
+        assert_eq!(many.line_count(), 501);
+        assert!(is_new_file(&many));
+        assert!(many.as_str().starts_with("New file (500 lines, 4.3 KB)\n+line 0\n"));
+        assert_eq!(preview_rows(&many, 200), 201);
+        assert_eq!(preview_rows(&many, 0), 501);
+        assert_eq!(preview_rows(&many, 1000), 501);
+
+        // Highlighted when the language is known
+        let rust = DiffText::from(new_file_diff("fn main() {}\n", 13));
+        let syntax = Syntax::for_path(std::path::Path::new("main.rs"));
+        let lines = new_file_preview(&rust, 0..2, syntax, &theme);
+        assert_eq!(text(lines.clone()), ["New file (1 line, 13 B)", "+fn main() {}"]);
+        assert_eq!(lines[1].spans[1].content, "fn");
+        assert!(lines[1].spans[1].style.add_modifier.contains(Modifier::BOLD));
+        assert!(!is_new_file(&DiffText::from("@@ -1 +1 @@\n-a\n+b\n".to_string())));
+    }
+
+    #[test]
+    fn unapply_recovers_the_old_side() {
+        let old = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\n";
+        let new = "one\n2\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\neleven\n";
+        let hunks = compute_hunks(old, new, 1);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(unapply(new, &hunks).as_deref(), Some(old));
+        // Line endings follow the file
+        let crlf = new.replace('\n', "\r\n");
+        assert_eq!(unapply(&crlf, &compute_hunks(&old.replace('\n', "\r\n"), &crlf, 1)), Some(old.replace('\n', "\r\n")));
+        // Edited since: the hunks no longer describe it
+        assert_eq!(unapply(&new.replace("2", "TWO"), &hunks), None);
+
+        // Mixed: context keeps its own ending, removed lines get the usual one
+        let old = "a\r\nb\r\nc\nd\r\n";
+        let new = "a\r\nB\r\nc\nd\r\n";
+        assert_eq!(unapply(new, &compute_hunks(old, new, 1)).as_deref(), Some(old));
+    }
+
+    #[test]
+    fn first_changed_line_skips_leading_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n";
+        assert_eq!(first_changed_line(&compute_hunks(old, &old.replace("6\n", "six\n"), 3)), Some(6));
+        // A deletion points at the line after it
+        assert_eq!(first_changed_line(&compute_hunks(old, &old.replace("2\n", ""), 3)), Some(2));
+        assert_eq!(first_changed_line(&[]), None);
+    }
+
+    #[test]
+    fn line_endings_are_detected_and_summarized() {
+        assert_eq!(LineEnding::detect("one line"), LineEnding::None);
+        assert_eq!(LineEnding::detect("a\nb\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\r\nb\r\n"), LineEnding::Crlf);
+        assert_eq!(LineEnding::detect("a\r\nb\n"), LineEnding::Mixed);
+        assert_eq!(dominant_eol("a\r\nb\r\nc\n"), "\r\n");
+        assert_eq!(dominant_eol("a\r\nb\n"), "\n");
+
+        let lf = "a\nb\nc";
+        assert_eq!(eol_only_summary(lf, &lf.replace('\n', "\r\n")).as_deref(), Some("Line ending changes only: LF → CRLF (2 lines)"));
+        assert_eq!(eol_only_summary("a\r\nb\r\n", "a\r\nb\n").as_deref(), Some("Line ending changes only: CRLF → mixed (1 line)"));
+        assert_eq!(eol_only_summary(lf, lf), None);
+        assert_eq!(eol_change("a\r\nb\r\n", "a\nB\n"), Some((LineEnding::Crlf, LineEnding::Lf)));
+        assert_eq!(eol_change("a\r\nb\r\n", "a\r\nB\r\n"), None);
+        assert_eq!(eol_change("a", "a\r\nb"), None);
+        assert_eq!(with_eol("a\r\nb\n", LineEnding::Crlf), "a\r\nb\r\n");
+        assert_eq!(with_eol("a\r\nb\n", LineEnding::Lf), "a\nb\n");
+        assert_eq!(eol_only_summary(lf, "a\r\nB\r\nc"), None);
+    }
+
+    #[test]
+    fn diff_text_slices_lines_like_str_lines() {
+        let text = "@@ -1 +1 @@\n-a\r\n+b\n";
+        let diff = DiffText::new(text.to_string());
+        assert_eq!(diff.line_count(), text.lines().count());
+        assert_eq!(diff.lines(1..3).collect::<Vec<_>>(), ["-a", "+b"]);
+        assert_eq!(diff.lines(2..99).collect::<Vec<_>>(), ["+b"]);
+        assert_eq!(DiffText::new(String::new()).lines(0..10).count(), 0);
+    }
+
+    #[test]
+    fn ascii_only_labels_added_and_removed_lines() {
+        let diff = DiffText::new("@@ -1 +1 @@\n-a\n+b\n c\n".to_string());
+        let text = |theme: &Theme| -> Vec<String> {
+            diff_lines_to_spans(&diff, 0..4, theme, false).iter().map(|l| l.to_string()).collect()
+        };
+        assert_eq!(text(&Theme::default()), ["@@ -1 +1 @@", "-a", "+b", " c"]);
+        let plain = Theme::new(crate::ui::theme::ThemeVariant::HighContrast);
+        assert_eq!(text(&plain), ["@@ -1 +1 @@", "[REMOVED] -a", "[ADDED]   +b", " c"]);
+    }
+
+    #[test]
+    fn whitespace_is_drawn_and_whitespace_only_pairs_are_marked() {
+        let diff = DiffText::new("@@ -1,3 +1,3 @@\n-if x {\n-\tcall();\n+if x {  \n+    call();\n context\n".to_string());
+        let text: Vec<String> = diff_lines_to_spans(&diff, 0..6, &Theme::default(), true).iter().map(|l| l.to_string()).collect();
+        assert_eq!(text, [
+            "@@ -1,3 +1,3 @@",
+            "-if x { [whitespace only]",
+            "-→ call(); [whitespace only]",
+            "+if x {·· [whitespace only]",
+            "+    call(); [whitespace only]",
+            " context",
+        ]);
+        // Pairs are still found when the window starts inside the block
+        let tail: Vec<String> = diff_lines_to_spans(&diff, 4..5, &Theme::default(), true).iter().map(|l| l.to_string()).collect();
+        assert_eq!(tail, ["+    call(); [whitespace only]"]);
+    }
+
+    #[test]
+    fn paging_stays_within_the_diff() {
+        let mut cursor = HunkCursor::default();
+        let offsets = [0, 40];
+        cursor.next(2);
+        cursor.next(2);
+        cursor.page(true, 30, &offsets, 100);
+        assert_eq!(cursor.scroll(&offsets), 70);
+        cursor.page(true, 30, &offsets, 100);
+        assert_eq!(cursor.scroll(&offsets), 99);
+        cursor.page(false, 200, &offsets, 100);
+        assert_eq!(cursor.scroll(&offsets), 40);
+        cursor.prev(2);
+        assert_eq!(cursor.scroll(&offsets), 0);
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    env,
+    path::{Component, Path, PathBuf},
+};
+use crate::agent::Launch;
+
+// Smallest terminal the layout stays usable in (sidebar + terminal pane + status bar)
+pub const MIN_COLS: u16 = 60;
+pub const MIN_ROWS: u16 = 15;
+
+// One preflight check: what was looked at, and what to do if it failed
+pub struct Check {
+    pub name: String,
+    pub problem: Option<String>,
+    pub hint: &'static str,
+}
+
+impl Check {
+    fn ok(name: String) -> Self {
+        Self { name, problem: None, hint: "" }
+    }
+
+    fn failed(name: String, problem: String, hint: &'static str) -> Self {
+        Self { name, problem: Some(problem), hint }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.problem.is_none()
+    }
+}
+
+// Everything the app needs before it takes over the terminal. The terminal
+// size only matters for the TUI, so headless runs skip it; --tail starts no agent.
+pub fn preflight(agent_command: Option<&str>, launch: &Launch, dirs: &[PathBuf], tui: bool) -> Vec<Check> {
+    let mut checks: Vec<Check> = agent_command.map(|command| agent(command, launch)).into_iter().collect();
+    if dirs.is_empty() {
+        checks.push(match env::current_dir() {
+            Ok(cwd) => directory(&cwd),
+            Err(e) => Check::failed("current directory".to_string(), e.to_string(), "cd into the project, or pass it with --dir"),
+        });
+    }
+    checks.extend(dirs.iter().map(|dir| directory(dir)));
+    // agent_cwd is under the first watch root
+    if agent_command.is_some() && launch.cwd.is_some()
+        && let Some(root) = dirs.first().cloned().or_else(|| env::current_dir().ok()) {
+        checks.push(agent_dir(launch, &root));
+    }
+    if tui {
+        checks.push(terminal_size());
+    }
+    checks
+}
+
+// Print failures (or, with `verbose`, every check) to stderr; true if all passed
+pub fn report(checks: &[Check], verbose: bool) -> bool {
+    for check in checks {
+        match &check.problem {
+            None if verbose => eprintln!("  ok    {}", check.name),
+            None => {}
+            Some(problem) => {
+                eprintln!("  FAIL  {}: {}", check.name, problem);
+                eprintln!("        {}", check.hint);
+            }
+        }
+    }
+    checks.iter().all(Check::passed)
+}
+
+// Through a login shell only the shell can be checked; the command itself
+// resolves against the PATH the shell's profile sets up
+fn agent(command: &str, launch: &Launch) -> Check {
+    let mut name = format!("agent command `{}`", command);
+    let Ok(argv) = launch.argv(command) else {
+        return Check::failed(name, "it's empty".to_string(), "set agent_command in the config or pass it after --");
+    };
+    let program = argv[0].as_str();
+    if launch.login_shell {
+        name.push_str(&format!(" (through {} -lc)", program));
+    }
+    match find_program(program) {
+        Some(_) => Check::ok(name),
+        None => Check::failed(
+            name,
+            format!("`{}` was not found on PATH", program),
+            "install it (e.g. Node.js for npx) or pass another agent after --, like `aiui -- claude`",
+        ),
+    }
+}
+
+// `which`/`where` without shelling out
+fn find_program(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return is_executable(path).then(|| path.to_path_buf());
+    }
+    // Windows resolves `npx` to npx.cmd and friends
+    let extensions: Vec<String> = if cfg!(windows) {
+        env::var("PATHEXT")
+            .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string())
+            .split(';')
+            .map(str::to_string)
+            .chain(std::iter::once(String::new()))
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+    env::split_paths(&env::var_os("PATH")?)
+        .flat_map(|dir| extensions.iter().map(move |ext| dir.join(format!("{}{}", program, ext))))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+fn directory(dir: &Path) -> Check {
+    let name = format!("watch directory {}", dir.display());
+    match std::fs::metadata(dir) {
+        Err(e) => Check::failed(name, e.to_string(), "create it, or pass an existing directory with --dir"),
+        Ok(meta) if !meta.is_dir() => {
+            Check::failed(name, "not a directory".to_string(), "pass a directory with --dir, not a file")
+        }
+        // Listing it is what the watcher and the indexer will do
+        Ok(_) => match std::fs::read_dir(dir) {
+            Ok(_) => Check::ok(name),
+            Err(e) => Check::failed(name, e.to_string(), "check its permissions (it must be readable and searchable)"),
+        },
+    }
+}
+
+// An absolute agent_cwd, or one climbing out with `..`, would start the agent
+// where nothing it writes is watched
+fn agent_dir(launch: &Launch, root: &Path) -> Check {
+    let dir = launch.dir(root);
+    let name = format!("agent directory {}", dir.display());
+    let outside = launch.cwd.as_ref()
+        .is_some_and(|cwd| cwd.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)));
+    if outside {
+        Check::failed(name, "it isn't under the first watch root".to_string(), "make agent_cwd a relative path without `..`")
+    } else if dir.is_dir() {
+        Check::ok(name)
+    } else {
+        Check::failed(name, "not a directory".to_string(), "point agent_cwd at a directory under the first watch root")
+    }
+}
+
+fn terminal_size() -> Check {
+    match crossterm::terminal::size() {
+        Ok((cols, rows)) => {
+            let name = format!("terminal size {}x{}", cols, rows);
+            if cols < MIN_COLS || rows < MIN_ROWS {
+                Check::failed(
+                    name,
+                    format!("at least {}x{} is needed", MIN_COLS, MIN_ROWS),
+                    "enlarge the window, or use --headless",
+                )
+            } else {
+                Check::ok(name)
+            }
+        }
+        Err(e) => Check::failed(
+            "terminal".to_string(),
+            format!("not a terminal ({})", e),
+            "run aiui in an interactive terminal, or use --headless",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_programs_and_directories_fail() {
+        let direct = Launch::default();
+        assert!(agent("sh -c true", &direct).passed());
+        assert!(!agent("definitely-not-an-agent-4821", &direct).passed());
+        assert!(!agent("", &direct).passed());
+
+        let dir = env::temp_dir();
+        assert!(directory(&dir).passed());
+        assert!(!directory(&dir.join("aiui-doctor-missing")).passed());
+
+        let under = |cwd: &str| Launch { cwd: Some(PathBuf::from(cwd)), ..Launch::default() };
+        assert!(agent_dir(&under("."), &dir).passed());
+        assert!(!agent_dir(&under("aiui-doctor-missing"), &dir).passed());
+        // Existing directories, but not under the root
+        assert!(!agent_dir(&under(".."), &dir).passed());
+        assert!(!agent_dir(&under(&dir.to_string_lossy()), &dir).passed());
+    }
+}
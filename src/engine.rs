@@ -0,0 +1,122 @@
+use anyhow::Result;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::{sync::mpsc::Sender, thread, time::Duration};
+use crate::{control, fs};
+use crate::roots::Roots;
+use crate::toasts::Severity;
+use crate::types::ChangeKind;
+use crate::{AppEvent, AppState};
+
+// The watch/approval core, independent of any front-end. The TUI and
+// --headless both start these threads and feed events through `handle`.
+
+// Turn notify events for every root into AppEvents. Keep the watcher alive.
+pub fn watch(roots: &Roots, tx: Sender<AppEvent>) -> Result<RecommendedWatcher> {
+    let mut watcher = RecommendedWatcher::new(
+        move |res: notify::Result<notify::Event>| forward(res, &tx),
+        notify::Config::default(),
+    )?;
+    // Watch every root recursively
+    for root in roots.iter() {
+        watcher.watch(&root.path, RecursiveMode::Recursive)?;
+    }
+    Ok(watcher)
+}
+
+// One notify callback's worth of AppEvents
+pub fn forward(res: notify::Result<notify::Event>, tx: &Sender<AppEvent>) {
+    use notify::event::{EventKind, ModifyKind, RenameMode};
+    let mut event = match res {
+        Ok(event) => event,
+        Err(e) => {
+            let _ = tx.send(AppEvent::WatchError(e.to_string()));
+            return;
+        }
+    };
+    // The OS queue overflowed (e.g. a big git checkout) and events were lost
+    if event.need_rescan() {
+        let _ = tx.send(AppEvent::WatchOverflow);
+        return;
+    }
+    // A revert staged in a temp file and renamed over the target:
+    // that's a write to the target
+    if let EventKind::Modify(ModifyKind::Name(RenameMode::Both)) = event.kind
+        && event.paths.len() == 2
+        && fs::is_temp_file(&event.paths[0]) {
+        let _ = tx.send(AppEvent::FileChange(event.paths.swap_remove(1), ChangeKind::Modify));
+        return;
+    }
+    event.paths.retain(|path| !fs::is_temp_file(path));
+    match event.kind {
+        EventKind::Create(_) => {
+            for path in event.paths {
+                let _ = tx.send(AppEvent::FileChange(path, ChangeKind::Create));
+            }
+        }
+        EventKind::Modify(ModifyKind::Data(_)) => {
+            for path in event.paths {
+                let _ = tx.send(AppEvent::FileChange(path, ChangeKind::Modify));
+            }
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+            let _ = tx.send(AppEvent::Rename(event.paths[0].clone(), event.paths[1].clone()));
+        }
+        // Half a rename: the app pairs these up by content
+        EventKind::Modify(ModifyKind::Name(_)) => {
+            for path in event.paths {
+                let kind = if path.exists() { ChangeKind::Create } else { ChangeKind::Remove };
+                let _ = tx.send(AppEvent::FileChange(path, kind));
+            }
+        }
+        EventKind::Remove(_) => {
+            for path in event.paths {
+                let _ = tx.send(AppEvent::FileChange(path, ChangeKind::Remove));
+            }
+        }
+        _ => {}
+    }
+}
+
+// Drives held-remove expiry and, in the TUI, animations
+pub fn spawn_ticker(tx: Sender<AppEvent>) {
+    thread::spawn(move || {
+        while tx.send(AppEvent::Tick).is_ok() {
+            thread::sleep(Duration::from_millis(100));
+        }
+    });
+}
+
+// Apply a core event. Events that belong to the front-end (PTY output,
+// input, ticks for animation) are handed back.
+pub fn handle(state: &mut AppState, event: AppEvent) -> Option<AppEvent> {
+    match event {
+        AppEvent::FileChange(path, kind) => state.add_change(path, kind),
+        AppEvent::Rename(from, to) => state.rename_event(from, to),
+        AppEvent::Index(event) => state.apply_index_event(event),
+        AppEvent::Control(request, reply) => {
+            let _ = reply.send(control::handle(state, request));
+        }
+        AppEvent::Hook(result) => state.hook_finished(result),
+        AppEvent::Check(result) => state.check_finished(result),
+        AppEvent::Test(event) => state.test_event(event),
+        AppEvent::Observed(observed) => state.observed(observed),
+        AppEvent::WatchError(error) => {
+            state.notify(Severity::Warning, format!("Watcher error: {}", error));
+        }
+        AppEvent::WatchOverflow => state.rescan_cache(),
+        AppEvent::GitStatus(dirty) => state.git_status.finished(dirty),
+        AppEvent::GitCommit(result) => state.commit_finished(result),
+        AppEvent::Tick => {
+            state.flush_held_removes();
+            state.toasts.tick(std::time::Instant::now());
+            state.output.tick(std::time::Instant::now());
+            state.expire_approval();
+            if let Some(tests) = state.tests.as_mut() {
+                tests.tick();
+            }
+            return Some(AppEvent::Tick);
+        }
+        other => return Some(other),
+    }
+    None
+}
@@ -0,0 +1,224 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, collections::BTreeMap, fmt::Write as _, path::Path};
+use crate::ansi;
+use crate::diff::NEW_FILE;
+use crate::path_key::PathKey;
+use crate::types::{ChangeKind, ChangeStatus, FileChange, Origin, TestRun};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Patch,
+}
+
+impl ExportFormat {
+    // Guess from the extension; anything that isn't .json, .patch or .diff is Markdown
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            Some(ext) if ext.eq_ignore_ascii_case("patch") || ext.eq_ignore_ascii_case("diff") => Self::Patch,
+            _ => Self::Markdown,
+        }
+    }
+
+    pub fn default_file_name(&self) -> &'static str {
+        match self {
+            Self::Markdown => "session-report.md",
+            Self::Json => "session-report.json",
+            Self::Patch => "session.patch",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ExportEntry<'a> {
+    id: u64,
+    path: &'a str,
+    abs_path: Cow<'a, str>,
+    kind: &'a ChangeKind,
+    status: ChangeStatus,
+    origin: Origin,
+    insertions: usize,
+    deletions: usize,
+    timestamp: String,
+    note: Option<&'a str>,
+    // test_command run that followed accepting it
+    tests: Option<&'a TestRun>,
+    diff: Option<String>,
+}
+
+// Session totals heading a JSON export
+#[derive(Serialize)]
+pub struct Summary {
+    pub files: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+    // The agent's changes by kind, e.g. {"modify": 12}
+    pub changes: BTreeMap<String, usize>,
+    pub accepted: usize,
+    pub rejected: usize,
+    pub average_decision_secs: Option<f64>,
+    pub output_bytes: u64,
+    pub output_lines: u64,
+    // What the agent was started with on top of the inherited environment,
+    // agent_env_mask values blanked
+    pub agent_env: BTreeMap<String, String>,
+}
+
+// Bumped whenever the JSON report's shape changes. Version 1 was a bare array
+// of changes; 2 wrapped it as `changes` next to `stats`.
+const JSON_REPORT_VERSION: u32 = 2;
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    version: u32,
+    stats: &'a Summary,
+    changes: Vec<ExportEntry<'a>>,
+}
+
+// The whole change log, oldest first. A patch's paths are relative to `root`.
+pub fn render(format: ExportFormat, changes: &[&FileChange], summary: &Summary, root: &Path) -> Result<String> {
+    Ok(match format {
+        ExportFormat::Markdown => markdown(changes),
+        ExportFormat::Json => json(changes, summary).context("cannot build the JSON report")?,
+        ExportFormat::Patch => patch(changes, root).0,
+    })
+}
+
+// Spilled diffs are read back from their temp file
+fn diff_text(change: &FileChange) -> Option<String> {
+    let text = match (&change.diff, &change.spill) {
+        (Some(diff), _) => diff.as_str().to_string(),
+        (None, Some(spill)) => std::fs::read_to_string(spill).ok()?,
+        (None, None) => return None,
+    };
+    Some(ansi::strip(&text))
+}
+
+fn markdown(changes: &[&FileChange]) -> String {
+    let mut out = String::from("# Session report\n\n| File | Kind | By | Lines | Time | Decision |\n|------|------|----|-------|------|----------|\n");
+    for change in changes {
+        let _ = writeln!(
+            out,
+            "| `{}` | {:?} | {:?} | +{} −{} | {} | {} |",
+            change.path,
+            change.kind,
+            change.origin,
+            change.insertions,
+            change.deletions,
+            change.timestamp.format("%H:%M:%S"),
+            change.status.label()
+        );
+    }
+
+    for change in changes {
+        let _ = write!(out, "\n## {} ({:?})\n\n", change.path, change.kind);
+        if let Some(tests) = &change.tests {
+            let _ = writeln!(out, "Tests after accepting: {}\n", tests.summary());
+        }
+        fenced_diff(&mut out, change);
+    }
+    out
+}
+
+// One change for pasting into a PR or chat: the file as a heading, optionally
+// the line counts, then the diff
+pub fn markdown_change(change: &FileChange, summary: bool) -> String {
+    let mut out = format!("### {} ({:?})\n\n", change.path, change.kind);
+    if summary {
+        let _ = writeln!(out, "+{} −{} lines\n", change.insertions, change.deletions);
+    }
+    fenced_diff(&mut out, change);
+    out
+}
+
+fn fenced_diff(out: &mut String, change: &FileChange) {
+    if let Some(note) = &change.note {
+        let _ = writeln!(out, "_{}_\n", note);
+    }
+    match diff_text(change) {
+        Some(diff) => {
+            let _ = write!(out, "```diff\n{}", diff);
+            if !diff.ends_with('\n') {
+                out.push('\n');
+            }
+            out.push_str("```\n");
+        }
+        None => out.push_str("_No diff available._\n"),
+    }
+}
+
+// A git-style diff `git apply` takes in `root`, and how many changes it
+// leaves out: those with no line diff (binary files, removals, directories)
+// and those outside `root`. New files are added from /dev/null and renames
+// carry their source.
+pub fn patch(changes: &[&FileChange], root: &Path) -> (String, usize) {
+    let relative = |key: &PathKey| key.path().strip_prefix(root).ok().map(|p| p.to_string_lossy().replace('\\', "/"));
+    let mut out = String::new();
+    let mut skipped = 0;
+    for change in changes {
+        let Some(to) = relative(&change.abs_path).filter(|_| !change.dir) else {
+            skipped += 1;
+            continue;
+        };
+        let diff = diff_text(change);
+        let from = match change.kind {
+            ChangeKind::Rename => change.from.as_ref().and_then(relative),
+            _ => Some(to.clone()),
+        };
+        match (&change.kind, from, diff.as_deref()) {
+            // A new file's listing: a header, then every line added
+            (ChangeKind::Create, _, Some(listing)) if listing.starts_with(NEW_FILE) => {
+                let body: Vec<&str> = listing.lines().skip(1).collect();
+                let added = body.iter().filter(|line| line.starts_with('+')).count();
+                let _ = write!(out, "diff --git a/{0} b/{0}\nnew file mode 100644\n", to);
+                if added > 0 {
+                    let _ = writeln!(out, "--- /dev/null\n+++ b/{}\n@@ -0,0 +1,{} @@\n{}", to, added, body.join("\n"));
+                }
+            }
+            (ChangeKind::Rename, Some(from), diff) => {
+                let _ = write!(out, "diff --git a/{0} b/{1}\nrename from {0}\nrename to {1}\n", from, to);
+                if let Some(diff) = diff.filter(|diff| diff.starts_with("@@ ")) {
+                    push_hunks(&mut out, &from, &to, diff);
+                }
+            }
+            (ChangeKind::Create | ChangeKind::Modify, Some(from), Some(diff)) if diff.starts_with("@@ ") => {
+                let _ = writeln!(out, "diff --git a/{} b/{}", from, to);
+                push_hunks(&mut out, &from, &to, diff);
+            }
+            _ => skipped += 1,
+        }
+    }
+    (out, skipped)
+}
+
+fn push_hunks(out: &mut String, from: &str, to: &str, diff: &str) {
+    let _ = write!(out, "--- a/{}\n+++ b/{}\n{}", from, to, diff);
+    if !diff.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+fn json<'a>(changes: &[&'a FileChange], summary: &'a Summary) -> Result<String> {
+    let entries: Vec<ExportEntry> = changes
+        .iter()
+        .map(|change| ExportEntry {
+            id: change.id,
+            path: &change.path,
+            abs_path: change.abs_path.to_string_lossy(),
+            kind: &change.kind,
+            status: change.status,
+            origin: change.origin,
+            insertions: change.insertions,
+            deletions: change.deletions,
+            timestamp: change.timestamp.to_rfc3339(),
+            note: change.note.as_deref(),
+            tests: change.tests.as_ref(),
+            diff: diff_text(change),
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&JsonReport { version: JSON_REPORT_VERSION, stats: summary, changes: entries })?)
+}
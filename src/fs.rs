@@ -0,0 +1,207 @@
+use std::{fs::Permissions, io, path::{Path, PathBuf}, time::SystemTime};
+
+// What we need to know about a file without reading it
+pub struct FileStat {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    // Mode bits (Unix) / read-only flag (Windows); None where that isn't tracked
+    pub permissions: Option<Permissions>,
+    pub is_dir: bool,
+}
+
+// File access used by the approval core, so tests can run it against memory.
+// Spill files and the watcher still go straight to disk.
+pub trait FileSystem: Send {
+    fn stat(&self, path: &Path) -> io::Result<FileStat>;
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    // Write all of `contents` or nothing: readers (and an agent writing at the
+    // same time) never see a half-written file
+    fn replace(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+    fn remove(&self, path: &Path) -> io::Result<()>;
+    fn set_permissions(&self, path: &Path, permissions: Permissions) -> io::Result<()>;
+
+    fn exists(&self, path: &Path) -> bool {
+        self.stat(path).is_ok()
+    }
+}
+
+pub struct RealFs;
+
+impl FileSystem for RealFs {
+    fn stat(&self, path: &Path) -> io::Result<FileStat> {
+        let meta = std::fs::metadata(path)?;
+        Ok(FileStat {
+            len: meta.len(),
+            modified: meta.modified().ok(),
+            permissions: Some(meta.permissions()),
+            is_dir: meta.is_dir(),
+        })
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    // Temp file next to the target, then rename over it
+    fn replace(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        use std::io::Write;
+        let temp = temp_path(path);
+        // Restoring a file from a deleted directory brings the directory back
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let result = (|| {
+            let mut file = std::fs::File::create(&temp)?;
+            file.write_all(contents)?;
+            // Keep the target's mode; a rename would otherwise swap in the temp file's
+            if let Ok(meta) = std::fs::metadata(path) {
+                file.set_permissions(meta.permissions())?;
+            }
+            // Without this a crash right after the rename can leave an empty file
+            #[cfg(unix)]
+            file.sync_all()?;
+            drop(file);
+            std::fs::rename(&temp, path)
+        })();
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+        result
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn set_permissions(&self, path: &Path, permissions: Permissions) -> io::Result<()> {
+        std::fs::set_permissions(path, permissions)
+    }
+}
+
+const TEMP_SUFFIX: &str = ".aiui-tmp";
+
+// Where `replace` stages a file: same directory, so the rename stays on one file system
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}{}", name, std::process::id(), TEMP_SUFFIX))
+}
+
+// Our own staging files; the watcher drops them
+pub fn is_temp_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|n| n.to_string_lossy().ends_with(TEMP_SUFFIX))
+}
+
+// Let the owner write to `path` again, e.g. before restoring or deleting a
+// read-only file
+pub fn make_writable(fs: &dyn FileSystem, path: &Path) -> io::Result<()> {
+    let Some(mut permissions) = fs.stat(path)?.permissions else { return Ok(()) };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    fs.set_permissions(path, permissions)
+}
+
+// Whether the mode has an execute bit; None where permissions don't say
+pub fn executable(permissions: &std::fs::Permissions) -> Option<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Some(permissions.mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = permissions;
+        None
+    }
+}
+
+// In-memory files for tests; clones share the same files. Writing a file
+// creates its parent directories, which stay until removed.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MemFs {
+    files: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<std::path::PathBuf, Vec<u8>>>>,
+    dirs: std::sync::Arc<std::sync::Mutex<std::collections::HashSet<std::path::PathBuf>>>,
+    // Paths our writes and removes are refused on, like a locked file
+    locked: std::sync::Arc<std::sync::Mutex<std::collections::HashSet<std::path::PathBuf>>>,
+}
+
+#[cfg(test)]
+impl MemFs {
+    pub fn put(&self, path: &Path, contents: &str) {
+        self.put_bytes(path, contents.as_bytes());
+    }
+
+    pub fn put_bytes(&self, path: &Path, contents: &[u8]) {
+        self.dirs.lock().unwrap().extend(path.ancestors().skip(1).map(Path::to_path_buf));
+        self.files.lock().unwrap().insert(path.to_path_buf(), contents.to_vec());
+    }
+
+    // `rm -r`: the directory and everything under it
+    pub fn delete_dir(&self, dir: &Path) {
+        self.files.lock().unwrap().retain(|path, _| !path.starts_with(dir));
+        self.dirs.lock().unwrap().retain(|path| !path.starts_with(dir));
+    }
+
+    pub fn get_bytes(&self, path: &Path) -> Option<Vec<u8>> {
+        self.files.lock().unwrap().get(path).cloned()
+    }
+
+    pub fn get(&self, path: &Path) -> Option<String> {
+        let files = self.files.lock().unwrap();
+        files.get(path).map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    pub fn delete(&self, path: &Path) {
+        self.files.lock().unwrap().remove(path);
+    }
+
+    pub fn lock(&self, path: &Path) {
+        self.locked.lock().unwrap().insert(path.to_path_buf());
+    }
+
+    fn check_unlocked(&self, path: &Path) -> io::Result<()> {
+        if self.locked.lock().unwrap().contains(path) {
+            return Err(io::ErrorKind::PermissionDenied.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl FileSystem for MemFs {
+    fn stat(&self, path: &Path) -> io::Result<FileStat> {
+        if self.dirs.lock().unwrap().contains(path) {
+            return Ok(FileStat { len: 0, modified: None, permissions: None, is_dir: true });
+        }
+        let files = self.files.lock().unwrap();
+        let bytes = files.get(path).ok_or(io::ErrorKind::NotFound)?;
+        Ok(FileStat { len: bytes.len() as u64, modified: None, permissions: None, is_dir: false })
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let files = self.files.lock().unwrap();
+        files.get(path).cloned().ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+
+    fn replace(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.check_unlocked(path)?;
+        self.put_bytes(path, contents);
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.check_unlocked(path)?;
+        let mut files = self.files.lock().unwrap();
+        files.remove(path).map(|_| ()).ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+
+    fn set_permissions(&self, _path: &Path, _permissions: Permissions) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+// Subsequence matcher for quick-open: every query character has to appear in
+// order (case-insensitive). Consecutive runs, word starts and hits in the file
+// name score higher; longer paths score a little lower.
+
+const MATCH: i64 = 16;
+const CONSECUTIVE: i64 = 24;
+const WORD_START: i64 = 20;
+const IN_FILE_NAME: i64 = 8;
+
+// None if `query` isn't a subsequence of `candidate`; an empty query matches everything
+pub fn score(query: &str, candidate: &str) -> Option<i64> {
+    let name_start = candidate.rfind(['/', '\\']).map_or(0, |i| i + 1);
+    let chars: Vec<(usize, char)> = candidate.char_indices().collect();
+    let mut score = -(chars.len() as i64);
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+
+    for q in query.chars().flat_map(char::to_lowercase) {
+        // Greedy: take the first occurrence after the last match
+        let found = (next..chars.len()).find(|&i| chars[i].1.to_lowercase().eq(std::iter::once(q)))?;
+        let (byte, _) = chars[found];
+        score += MATCH;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += CONSECUTIVE;
+        }
+        let boundary = found == 0 || matches!(chars[found - 1].1, '/' | '\\' | '_' | '-' | '.' | ' ');
+        if boundary || (chars[found].1.is_uppercase() && chars[found - 1].1.is_lowercase()) {
+            score += WORD_START;
+        }
+        if byte >= name_start {
+            score += IN_FILE_NAME;
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subsequences_match_and_file_names_rank_first() {
+        assert!(score("mnrs", "src/main.rs").is_some());
+        assert!(score("xyz", "src/main.rs").is_none());
+        assert!(score("", "anything").is_some());
+        assert!(score("MAIN", "src/main.rs").is_some());
+
+        // A hit in the file name beats one spread over the directories
+        assert!(score("main", "src/main.rs") > score("main", "m/a/i/n/lib.rs"));
+        // Consecutive beats scattered
+        assert!(score("diff", "ui/diff_view.rs") > score("diff", "do/it/for/fun.rs"));
+        // Word starts: "dv" prefers diff_view over divider
+        assert!(score("dv", "diff_view.rs") > score("dv", "divider.rs"));
+    }
+}
@@ -0,0 +1,429 @@
+use anyhow::{Context, Result};
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+// `git status` runs this often while nothing changes...
+const STATUS_REFRESH: Duration = Duration::from_secs(10);
+// ...and this long after the last run once files have changed
+const STATUS_SETTLE: Duration = Duration::from_secs(1);
+
+// Current branch of the repository `dir` is in, read straight from .git/HEAD
+// (no git process, no libgit2). A detached HEAD gives the short commit hash.
+pub fn branch(dir: &Path) -> Option<String> {
+    let head = std::fs::read_to_string(head_file(dir)?).ok()?;
+    parse_head(&head)
+}
+
+// Walk up to the nearest .git. Worktrees and submodules have a .git file
+// pointing at the real git directory instead.
+fn head_file(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().find_map(|dir| {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Some(dot_git.join("HEAD"));
+        }
+        let link = std::fs::read_to_string(&dot_git).ok()?;
+        let git_dir = link.trim().strip_prefix("gitdir:")?.trim();
+        Some(dir.join(git_dir).join("HEAD"))
+    })
+}
+
+// Whether the working tree has changes (untracked files included). None
+// outside a repository or without git; that's what `git status` is for, as
+// the index can't be read cheaply by hand. Without optional locks, so a
+// status in the background never makes a git command of the user's fail on
+// index.lock.
+pub fn dirty(dir: &Path) -> Option<bool> {
+    let output = Command::new("git")
+        .args(["--no-optional-locks", "status", "--porcelain", "--untracked-files=normal"])
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output.status.success().then_some(!output.stdout.is_empty())
+}
+
+// One file for `commit`, relative to its `dir`: the content to record, or
+// None to record it deleted
+#[derive(Debug, PartialEq, Eq)]
+pub struct CommitFile {
+    pub path: PathBuf,
+    pub content: Option<Vec<u8>>,
+    // None where the mode can't be told; a tracked file keeps the one it has
+    pub executable: Option<bool>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Commit {
+    // Short hash, or None when HEAD already had all of it
+    pub hash: Option<String>,
+    // Files recorded in it; a rename counts twice
+    pub files: usize,
+    // Left out: ignored by git, symlinks, or gone without ever being tracked
+    pub skipped: Vec<PathBuf>,
+}
+
+// How a file goes into the index. Paths are from the top of the work tree:
+// --cacheinfo ignores the directory git runs in, so it always runs at the top.
+enum IndexEntry<'a> {
+    Blob { mode: &'a str, hash: String, path: PathBuf },
+    Removed(PathBuf),
+}
+
+// Commit exactly `files`, with the content given rather than what's in the
+// working tree now. The commit is built in a scratch index, so a failure
+// leaves the user's index as it was and anything else they staged stays
+// staged; on success their index has the committed files as committed.
+pub fn commit(dir: &Path, files: &[CommitFile], message: &str) -> Result<Commit> {
+    let paths = files.iter().map(|f| f.path.as_os_str());
+    let input: Vec<u8> = paths.clone().flat_map(|p| p.as_encoded_bytes().iter().copied().chain([0])).collect();
+    // Tracked files are never reported; exit status 1 means none are ignored
+    let output = run(dir, ["check-ignore", "-z", "--stdin"].map(OsStr::new), None, Some(input))?;
+    if !matches!(output.status.code(), Some(0 | 1)) {
+        return Err(failure(&output));
+    }
+    let ignored = nul_split(&output.stdout);
+    // "<mode> <hash> <stage>\t<path>"
+    let listing = run(dir, ["ls-files", "-s", "-z", "--"].into_iter().map(OsStr::new).chain(paths), None, None)?;
+    let listing = nul_split(&listing.stdout);
+    let tracked: Vec<(&str, &str)> = listing
+        .iter()
+        .filter_map(|line| {
+            let (info, path) = line.split_once('\t')?;
+            Some((info.split(' ').next()?, path))
+        })
+        .collect();
+
+    // `dir` may be a subdirectory of the repository, such as one package of many
+    let top = PathBuf::from(git(dir, ["rev-parse", "--show-toplevel"].map(OsStr::new))?);
+    let prefix = PathBuf::from(git(dir, ["rev-parse", "--show-prefix"].map(OsStr::new))?);
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+    for file in files {
+        let path = prefix.join(&file.path);
+        let name = file.path.to_string_lossy();
+        let mode = tracked.iter().find(|(_, p)| *p == name).map(|(mode, _)| *mode);
+        let entry = match &file.content {
+            _ if mode == Some("120000") => None,
+            _ if mode.is_none() && ignored.contains(&name.to_string()) => None,
+            None => mode.map(|_| IndexEntry::Removed(path)),
+            Some(bytes) => {
+                let hash = checked(run(dir, ["hash-object", "-w", "--stdin"].map(OsStr::new), None, Some(bytes.clone()))?)?;
+                let mode = match file.executable {
+                    Some(true) => "100755",
+                    Some(false) => "100644",
+                    None => mode.unwrap_or("100644"),
+                };
+                Some(IndexEntry::Blob { mode, hash, path })
+            }
+        };
+        match entry {
+            Some(entry) => entries.push(entry),
+            None => skipped.push(file.path.clone()),
+        }
+    }
+    if entries.is_empty() {
+        return Ok(Commit { hash: None, files: 0, skipped });
+    }
+
+    let scratch = dir.join(git(dir, ["rev-parse", "--git-path", "aiui-commit-index"].map(OsStr::new))?);
+    let built = build_commit(&top, &scratch, &entries, message);
+    let _ = std::fs::remove_file(&scratch);
+    let hash = built?;
+    if hash.is_some() {
+        // Bring the real index up to the new HEAD for these files only
+        update_index(&top, None, &entries)?;
+    }
+    Ok(Commit { hash, files: entries.len(), skipped })
+}
+
+// HEAD plus `entries` in the scratch index, committed on top of HEAD
+fn build_commit(dir: &Path, scratch: &Path, entries: &[IndexEntry], message: &str) -> Result<Option<String>> {
+    let head = git(dir, ["rev-parse", "-q", "--verify", "HEAD"].map(OsStr::new)).ok();
+    let index = Some(scratch);
+    let base = head.as_deref().unwrap_or("--empty");
+    checked(run(dir, ["read-tree", base].map(OsStr::new), index, None)?)?;
+    update_index(dir, index, entries)?;
+    let tree = checked(run(dir, ["write-tree"].map(OsStr::new), index, None)?)?;
+    let mut args = vec!["commit-tree", tree.as_str(), "-F", "-"];
+    if let Some(head) = &head {
+        let head_tree = format!("{}^{{tree}}", head);
+        if git(dir, ["rev-parse", head_tree.as_str()].map(OsStr::new))? == tree {
+            return Ok(None);
+        }
+        args.extend(["-p", head.as_str()]);
+    }
+    let commit = checked(run(dir, args.into_iter().map(OsStr::new), None, Some(message.as_bytes().to_vec()))?)?;
+    let reflog = format!("commit: {}", message.lines().next().unwrap_or_default());
+    // Refuses if HEAD moved since it was read
+    let old = head.as_deref().unwrap_or("");
+    git(dir, ["update-ref", "-m", &reflog, "HEAD", &commit, old].map(OsStr::new))?;
+    git(dir, ["rev-parse", "--short", &commit].map(OsStr::new)).map(Some)
+}
+
+fn update_index(dir: &Path, index: Option<&Path>, entries: &[IndexEntry]) -> Result<()> {
+    for entry in entries {
+        let args = match entry {
+            IndexEntry::Blob { mode, hash, path } => vec![OsStr::new("update-index"), OsStr::new("--add"), OsStr::new("--cacheinfo"), OsStr::new(mode), OsStr::new(hash), path.as_os_str()],
+            IndexEntry::Removed(path) => vec![OsStr::new("update-index"), OsStr::new("--force-remove"), OsStr::new("--"), path.as_os_str()],
+        };
+        checked(run(dir, args, index, None)?)?;
+    }
+    Ok(())
+}
+
+// Its stdout, or what it said on stderr as the error
+fn git<'a>(dir: &Path, args: impl IntoIterator<Item = &'a OsStr>) -> Result<String> {
+    checked(run(dir, args, None, None)?)
+}
+
+// Run git in `dir`, optionally on another index file and with something on stdin
+fn run<'a>(dir: &Path, args: impl IntoIterator<Item = &'a OsStr>, index: Option<&Path>, input: Option<Vec<u8>>) -> Result<std::process::Output> {
+    let mut command = Command::new("git");
+    command.args(args).current_dir(dir).stdout(Stdio::piped()).stderr(Stdio::piped());
+    command.stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() });
+    if let Some(index) = index {
+        command.env("GIT_INDEX_FILE", index);
+    }
+    let mut child = command.spawn().context("cannot run git")?;
+    // Fed from another thread so git filling its stdout can't deadlock us
+    let writer = child.stdin.take().zip(input).map(|(mut stdin, input)| {
+        std::thread::spawn(move || {
+            use std::io::Write;
+            let _ = stdin.write_all(&input);
+        })
+    });
+    let output = child.wait_with_output().context("cannot run git")?;
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    Ok(output)
+}
+
+fn checked(output: std::process::Output) -> Result<String> {
+    if !output.status.success() {
+        return Err(failure(&output));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn failure(output: &std::process::Output) -> anyhow::Error {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    anyhow::anyhow!("{}", stderr.lines().find(|l| !l.trim().is_empty()).unwrap_or("git failed").trim())
+}
+
+// -z output
+fn nul_split(bytes: &[u8]) -> Vec<String> {
+    bytes.split(|&b| b == 0).filter(|s| !s.is_empty()).map(|s| String::from_utf8_lossy(s).into_owned()).collect()
+}
+
+// Fill in commit_message: {count} files, {files} one "- path" line each,
+// {insertions} and {deletions}
+pub fn commit_message(template: &str, files: &[String], insertions: usize, deletions: usize) -> String {
+    let list: Vec<String> = files.iter().map(|f| format!("- {}", f)).collect();
+    template
+        .replace("{count}", &files.len().to_string())
+        .replace("{files}", &list.join("\n"))
+        .replace("{insertions}", &insertions.to_string())
+        .replace("{deletions}", &deletions.to_string())
+}
+
+// The status bar's dirty marker, kept fresh by `dirty` on a background thread
+#[derive(Debug, Default)]
+pub struct GitStatus {
+    // None outside a repository, or before the first run comes back
+    pub dirty: Option<bool>,
+    last_run: Option<Instant>,
+    running: bool,
+    // Files changed since the last run started
+    stale: bool,
+}
+
+impl GitStatus {
+    pub fn touched(&mut self) {
+        self.stale = true;
+    }
+
+    // Whether to start a run now; if so it counts as started. Only one at a
+    // time, so a slow `git status` on a big repository can't pile up.
+    pub fn start(&mut self, now: Instant) -> bool {
+        let due = !self.running && self.last_run.is_none_or(|at| {
+            let since = now.duration_since(at);
+            since >= STATUS_REFRESH || (self.stale && since >= STATUS_SETTLE)
+        });
+        if due {
+            (self.running, self.stale, self.last_run) = (true, false, Some(now));
+        }
+        due
+    }
+
+    pub fn finished(&mut self, dirty: Option<bool>) {
+        self.running = false;
+        self.dirty = dirty;
+    }
+}
+
+fn parse_head(head: &str) -> Option<String> {
+    let head = head.trim();
+    match head.strip_prefix("ref:") {
+        Some(reference) => {
+            let reference = reference.trim();
+            Some(reference.strip_prefix("refs/heads/").unwrap_or(reference).to_string())
+        }
+        None if head.len() >= 7 && head.chars().all(|c| c.is_ascii_hexdigit()) => Some(head[..7].to_string()),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn branches_and_detached_heads() {
+        assert_eq!(parse_head("ref: refs/heads/main\n").as_deref(), Some("main"));
+        assert_eq!(parse_head("ref: refs/heads/feature/login\n").as_deref(), Some("feature/login"));
+        assert_eq!(parse_head("4b825dc642cb6eb9a060e54bf8d69288fbee4904\n").as_deref(), Some("4b825dc"));
+        assert_eq!(parse_head("garbage"), None);
+    }
+
+    #[test]
+    fn status_runs_on_a_timer_sooner_after_changes_and_one_at_a_time() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut status = GitStatus::default();
+        assert!(status.start(at(0)));
+        // Still running: nothing new starts, however long it takes
+        assert!(!status.start(at(20_000)));
+        status.finished(Some(false));
+        assert!(!status.start(at(500)));
+        assert!(status.start(at(10_000)));
+        status.finished(Some(true));
+
+        // A burst of changes is picked up a second after the last run
+        status.touched();
+        assert!(!status.start(at(10_500)));
+        assert!(status.start(at(11_000)));
+        status.finished(Some(false));
+        assert!(!status.start(at(12_500)));
+        assert_eq!(status.dirty, Some(false));
+    }
+
+    #[test]
+    fn commit_messages_fill_in_the_files_and_counts() {
+        let files = ["src/a.rs".to_string(), "README.md".to_string()];
+        assert_eq!(
+            commit_message("Apply {count} files (+{insertions} -{deletions})\n\n{files}", &files, 7, 2),
+            "Apply 2 files (+7 -2)\n\n- src/a.rs\n- README.md"
+        );
+        assert_eq!(commit_message("wip {other}", &files, 0, 0), "wip {other}");
+    }
+
+    #[test]
+    fn commit_takes_only_the_paths_given() {
+        let dir = std::env::temp_dir().join(format!("aiui-git-commit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let run = |args: &[&str]| git(&dir, args.iter().map(OsStr::new));
+        if run(&["init", "-q"]).is_err() {
+            let _ = std::fs::remove_dir_all(&dir);
+            return;
+        }
+        run(&["config", "user.email", "test@example.com"]).unwrap();
+        run(&["config", "user.name", "Test"]).unwrap();
+        std::fs::write(dir.join("approved.txt"), "written since\n").unwrap();
+        std::fs::write(dir.join("staged.txt"), "mine\n").unwrap();
+        run(&["add", "staged.txt"]).unwrap();
+
+        let done = commit(&dir, &[file("approved.txt", Some("yes\n"))], "Apply 1 file").unwrap();
+        assert!(done.hash.as_ref().is_some_and(|h| h.len() >= 7), "{:?}", done);
+        assert_eq!(run(&["log", "--format=%s", "--name-only"]).unwrap(), "Apply 1 file\n\napproved.txt");
+        // The content given, not what the working tree has by now
+        assert_eq!(run(&["show", "HEAD:approved.txt"]).unwrap(), "yes");
+        // What the user staged is still staged, not committed
+        assert_eq!(run(&["diff", "--cached", "--name-only"]).unwrap(), "staged.txt");
+
+        // Nothing new: no empty commit
+        assert_eq!(commit(&dir, &[file("approved.txt", Some("yes\n"))], "again").unwrap().hash, None);
+
+        // Watching one package of a bigger repository: paths are the package's
+        std::fs::create_dir_all(dir.join("pkg")).unwrap();
+        std::fs::write(dir.join("pkg/old.txt"), "old\n").unwrap();
+        run(&["add", "pkg/old.txt"]).unwrap();
+        run(&["commit", "-q", "-m", "pkg", "--", "pkg/old.txt"]).unwrap();
+        let files = [file("approved.txt", Some("pkg's\n")), file("old.txt", None)];
+        commit(&dir.join("pkg"), &files, "In pkg").unwrap();
+        assert_eq!(run(&["show", "--format=", "--name-status", "HEAD"]).unwrap(), "A\tpkg/approved.txt\nD\tpkg/old.txt");
+        assert_eq!(run(&["show", "HEAD:approved.txt"]).unwrap(), "yes");
+        assert_eq!(run(&["diff", "--cached", "--name-only"]).unwrap(), "staged.txt");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    fn file(path: &str, content: Option<&str>) -> CommitFile {
+        CommitFile { path: PathBuf::from(path), content: content.map(|c| c.as_bytes().to_vec()), executable: None }
+    }
+
+    #[test]
+    fn commit_leaves_out_ignored_and_vanished_files_and_the_index_alone_on_failure() {
+        let dir = std::env::temp_dir().join(format!("aiui-git-skip-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let run = |args: &[&str]| git(&dir, args.iter().map(OsStr::new));
+        if run(&["init", "-q"]).is_err() {
+            let _ = std::fs::remove_dir_all(&dir);
+            return;
+        }
+        run(&["config", "user.email", "test@example.com"]).unwrap();
+        run(&["config", "user.name", "Test"]).unwrap();
+        std::fs::write(dir.join(".gitignore"), ".env\n").unwrap();
+        std::fs::write(dir.join("old.rs"), "old\n").unwrap();
+        run(&["add", ".gitignore", "old.rs"]).unwrap();
+        run(&["commit", "-q", "-m", "base"]).unwrap();
+
+        // A protected .env git ignores, a file created and removed again, and
+        // a rename: the source is recorded deleted
+        std::fs::rename(dir.join("old.rs"), dir.join("new.rs")).unwrap();
+        let files = [
+            file(".env", Some("KEY=1\n")),
+            file("scratch.txt", None),
+            file("old.rs", None),
+            file("new.rs", Some("old\n")),
+        ];
+        let done = commit(&dir, &files, "Apply").unwrap();
+        assert!(done.hash.is_some());
+        assert_eq!(done.files, 2);
+        assert_eq!(done.skipped, [PathBuf::from(".env"), PathBuf::from("scratch.txt")]);
+        assert_eq!(run(&["show", "--format=", "--name-status", "HEAD"]).unwrap(), "R100\told.rs\tnew.rs");
+        // The index matches the new HEAD for the committed files
+        assert_eq!(run(&["status", "--porcelain"]).unwrap(), "");
+
+        // A failed commit (the branch is locked) leaves nothing half-staged
+        std::fs::write(dir.join("staged.txt"), "mine\n").unwrap();
+        run(&["add", "staged.txt"]).unwrap();
+        let branch = run(&["rev-parse", "--symbolic-full-name", "HEAD"]).unwrap();
+        std::fs::write(dir.join(".git").join(format!("{}.lock", branch)), "").unwrap();
+        assert!(commit(&dir, &[file("new.rs", Some("changed\n"))], "Apply").is_err());
+        assert_eq!(run(&["diff", "--cached", "--name-only"]).unwrap(), "staged.txt");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn dirty_follows_the_working_tree() {
+        let dir = std::env::temp_dir().join(format!("aiui-git-dirty-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let git = |args: &[&str]| Command::new("git").args(args).current_dir(&dir).output().is_ok_and(|o| o.status.success());
+        // No git here: nothing to check
+        if !git(&["init", "-q"]) {
+            let _ = std::fs::remove_dir_all(&dir);
+            return;
+        }
+        assert_eq!(dirty(&dir), Some(false));
+        std::fs::write(dir.join("new.txt"), "x\n").unwrap();
+        assert_eq!(dirty(&dir), Some(true));
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(dirty(&dir), None);
+    }
+}
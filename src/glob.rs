@@ -0,0 +1,24 @@
+// Minimal glob: `*` and `?` stay within a path segment, `**` crosses them.
+// Patterns without a slash are matched against the file name only.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let target = if pattern.contains('/') { path } else { path.rsplit('/').next().unwrap_or(path) };
+    let pattern: Vec<char> = pattern.chars().collect();
+    let target: Vec<char> = target.chars().collect();
+    glob_at(&pattern, &target)
+}
+
+fn glob_at(p: &[char], t: &[char]) -> bool {
+    match p {
+        [] => t.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            // Zero or more whole directories
+            (0..=t.len()).any(|i| (i == 0 || t[i - 1] == '/') && glob_at(rest, &t[i..]))
+        }
+        ['*', '*', rest @ ..] => (0..=t.len()).any(|i| glob_at(rest, &t[i..])),
+        ['*', rest @ ..] => (0..=t.len())
+            .take_while(|&i| i == 0 || t[i - 1] != '/')
+            .any(|i| glob_at(rest, &t[i..])),
+        ['?', rest @ ..] => t.first().is_some_and(|&c| c != '/') && glob_at(rest, &t[1..]),
+        [c, rest @ ..] => t.first() == Some(c) && glob_at(rest, &t[1..]),
+    }
+}
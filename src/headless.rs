@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use std::{
+    io::Write,
+    process::Command,
+    sync::{atomic::{AtomicBool, Ordering}, mpsc::{Receiver, RecvTimeoutError}},
+    time::{Duration, Instant},
+};
+use crate::agent::Launch;
+use crate::{engine, AppEvent, AppState, RENAME_WINDOW};
+
+// --headless: the agent inherits the real terminal (no PTY, no ratatui) and
+// every change that needs approval is printed to stderr and answered with
+// y/n on stdin. Returns whether anything was rejected.
+pub fn run(state: &mut AppState, command: &str, rx: Receiver<AppEvent>, terminate: &AtomicBool) -> Result<bool> {
+    let launch = Launch::from_config(&state.config);
+    let argv = launch.argv(command)?;
+    let mut child = Command::new(&argv[0]);
+    for key in &launch.unset {
+        child.env_remove(key);
+    }
+    let mut child = child
+        .args(&argv[1..])
+        .envs(&launch.env)
+        .current_dir(launch.dir(&state.roots.primary().path))
+        .spawn()
+        .with_context(|| format!("failed to start {}", command))?;
+
+    let mut exited_at: Option<Instant> = None;
+    // Notifications already printed
+    let mut seen = 0;
+    loop {
+        if terminate.load(Ordering::Relaxed) {
+            let _ = child.kill();
+            break;
+        }
+        match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(event) => {
+                let _ = engine::handle(state, event);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        // Hook results and the like
+        if let Some((message, _)) = state.status_message.take() {
+            eprintln!("{}", message);
+        }
+        for toast in state.toasts.since(&mut seen) {
+            eprintln!("{}", toast.text);
+        }
+
+        while let Some(pending) = state.approval_queue.front() {
+            let mut err = std::io::stderr().lock();
+            let _ = writeln!(err, "\n=== {} ===", pending.path);
+            if pending.protected {
+                let _ = writeln!(err, "!! PROTECTED PATH (quarantined) !!");
+            }
+            if pending.refreshed {
+                let _ = writeln!(err, "(changed again on disk - diff refreshed)");
+            }
+            let _ = writeln!(err, "{}", pending.diff_text.as_str());
+            let _ = write!(err, "Accept? [y/n] ");
+            let _ = err.flush();
+            drop(err);
+
+            // EOF counts as a rejection: nobody approved it
+            let mut answer = String::new();
+            let read = std::io::stdin().read_line(&mut answer).unwrap_or(0);
+            if read > 0 && matches!(answer.trim(), "y" | "Y" | "yes") {
+                state.accept_front();
+            } else if state.needs_revert_consent(pending) {
+                // The same explanation the TUI gives before its first revert.
+                // Without a yes the change stays on disk, dismissed.
+                eprint!(
+                    "Rejecting undoes the change on disk: this one will {}. Revert? [y/a(lways)/N] ",
+                    pending.revert_effect()
+                );
+                let mut answer = String::new();
+                let _ = std::io::stdin().read_line(&mut answer);
+                match answer.trim() {
+                    "y" | "Y" | "yes" => {
+                        state.confirm_revert(false);
+                    }
+                    "a" | "A" | "always" => {
+                        state.confirm_revert(true);
+                    }
+                    _ => {
+                        eprintln!("Left on disk (dismissed)");
+                        state.dismiss_front();
+                    }
+                }
+            } else {
+                state.reject_front();
+            }
+        }
+
+        // Once the agent is gone, let trailing events and held removes land
+        if exited_at.is_none() && child.try_wait()?.is_some() {
+            exited_at = Some(Instant::now());
+        }
+        if exited_at.is_some_and(|t| t.elapsed() > RENAME_WINDOW * 2) && state.held_removes.is_empty() {
+            break;
+        }
+    }
+
+    Ok(state.stats.rejected > 0)
+}
@@ -0,0 +1,113 @@
+use std::{
+    ffi::OsStr,
+    path::PathBuf,
+    sync::{atomic::AtomicBool, mpsc::Sender},
+    thread,
+    time::Duration,
+};
+use crate::path_key::PathKey;
+use crate::process::{self, Exit};
+use crate::AppEvent;
+
+// Outcome of one hook run, sent back to the main loop
+#[derive(Debug)]
+pub struct HookResult {
+    // Cache key of the file the hook ran on
+    pub path: PathKey,
+    // Exit code, or the reason the command couldn't run / produced no code
+    pub outcome: Result<i32, String>,
+    // First line of stderr, for the status bar
+    pub stderr: String,
+}
+
+// The on_accept hook: a shell command template run after each accepted change
+pub struct Hooks {
+    template: String,
+    cwd: PathBuf,
+    // A hook still running after this is killed; None waits for ever
+    timeout: Option<Duration>,
+    tx: Sender<AppEvent>,
+}
+
+impl Hooks {
+    pub fn new(template: String, cwd: PathBuf, timeout: Option<Duration>, tx: Sender<AppEvent>) -> Self {
+        Self { template, cwd, timeout, tx }
+    }
+
+    // Run the hook for `path` on its own thread; the result comes back as AppEvent::Hook
+    pub fn run(&self, path: &PathKey) {
+        let command = expand(&self.template, &path.to_string_lossy());
+        let (cwd, timeout) = (self.cwd.clone(), self.timeout);
+        let tx = self.tx.clone();
+        let path = path.clone();
+        thread::spawn(move || {
+            // sh gets the path as $1, bytes and all (see shell_quote)
+            let args = if cfg!(windows) { Vec::new() } else { vec![OsStr::new("sh"), path.path().as_os_str()] };
+            // Output is captured so it never lands on top of the TUI
+            let (outcome, stderr) = match process::spawn(&command, &args, &cwd) {
+                Ok(mut child) => {
+                    let _stdout = process::drain(child.stdout.take());
+                    let stderr = process::drain(child.stderr.take());
+                    let outcome = match process::wait(&mut child, &AtomicBool::new(false), timeout) {
+                        Ok(Exit::Exited(status)) => status.code().ok_or_else(|| "killed by a signal".to_string()),
+                        Ok(_) => Err(format!("killed after {:?} (on_accept_timeout_secs)", timeout.unwrap_or_default())),
+                        Err(e) => Err(e.to_string()),
+                    };
+                    (outcome, stderr.join().unwrap_or_default().lines().next().unwrap_or_default().to_string())
+                }
+                Err(e) => (Err(e.to_string()), String::new()),
+            };
+            let _ = tx.send(AppEvent::Hook(HookResult { path, outcome, stderr }));
+        });
+    }
+}
+
+// Fill in {path}. cmd gets it in double quotes so spaces survive. sh reads
+// it from $1 instead, which takes a name that isn't UTF-8 as it is, quoted
+// to suit where {path} sits: "$1" on its own, $1 inside double quotes, and
+// inside single quotes (where nothing expands) those are closed around it.
+fn expand(template: &str, path: &str) -> String {
+    if cfg!(windows) {
+        return template.replace("{path}", &format!("\"{}\"", path));
+    }
+    let mut out = String::with_capacity(template.len());
+    let (mut single, mut double) = (false, false);
+    let mut rest = template;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("{path}") {
+            out.push_str(match (single, double) {
+                (true, _) => "'\"$1\"'",
+                (_, true) => "$1",
+                _ => "\"$1\"",
+            });
+            rest = after;
+            continue;
+        }
+        let mut len = c.len_utf8();
+        match c {
+            '\'' if !double => single = !single,
+            '"' if !single => double = !double,
+            // The next character is taken as it is
+            '\\' if !single => len += rest[1..].chars().next().map_or(0, char::len_utf8),
+            _ => {}
+        }
+        out.push_str(&rest[..len]);
+        rest = &rest[len..];
+    }
+    out
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_is_quoted_to_suit_where_it_sits() {
+        assert_eq!(expand("rustfmt {path}", "a b.rs"), "rustfmt \"$1\"");
+        assert_eq!(expand("echo \"saw {path}\"", ""), "echo \"saw $1\"");
+        assert_eq!(expand("sh -c 'fmt {path} && git add {path}'", ""), "sh -c 'fmt '\"$1\"' && git add '\"$1\"''");
+        // Quotes that are escaped, or inside the other kind, don't count
+        assert_eq!(expand("echo \\' {path}", ""), "echo \\' \"$1\"");
+        assert_eq!(expand("echo \"it's {path}\"", ""), "echo \"it's $1\"");
+    }
+}
@@ -0,0 +1,92 @@
+use crate::glob::glob_match;
+
+// Per-project exclusions on top of the built-in ones, in .gitignore syntax
+pub const IGNORE_FILE: &str = ".ai-tui-ignore";
+
+#[derive(Clone)]
+struct Rule {
+    pattern: String,
+    // `!pattern`: takes back an earlier match
+    negated: bool,
+    // `pattern/`: directories only
+    dir_only: bool,
+    // A slash anywhere but the end ties the pattern to the root
+    anchored: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+impl IgnoreRules {
+    pub fn parse(text: &str) -> Self {
+        let rules = text
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (negated, line) = match line.strip_prefix('!') {
+                    Some(rest) => (true, rest),
+                    None => (false, line.strip_prefix('\\').unwrap_or(line)),
+                };
+                let (dir_only, line) = match line.strip_suffix('/') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                let anchored = line.contains('/');
+                let pattern = line.strip_prefix('/').unwrap_or(line).to_string();
+                Rule { pattern, negated, dir_only, anchored }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    // `rel` is relative to the root, `/`-separated. Like git, a file under an
+    // ignored directory stays ignored whatever later rules say.
+    pub fn is_ignored(&self, rel: &str) -> bool {
+        let parts: Vec<&str> = rel.split('/').filter(|p| !p.is_empty()).collect();
+        (1..=parts.len()).any(|end| self.matches(&parts[..end].join("/"), end < parts.len()))
+    }
+
+    // Last matching rule wins
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        let mut ignored = false;
+        for rule in self.rules.iter().filter(|r| is_dir || !r.dir_only) {
+            // glob_match compares slash-less patterns with the file name only
+            let hit = if rule.anchored && !rule.pattern.contains('/') {
+                !path.contains('/') && glob_match(&rule.pattern, path)
+            } else {
+                glob_match(&rule.pattern, path)
+            };
+            if hit {
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gitignore_style_rules() {
+        let rules = IgnoreRules::parse("# generated\n*.lock\n/dist\nbuild/\nproto/**/*.pb.go\n*.log\n!keep.log\n");
+        assert!(rules.is_ignored("Cargo.lock"));
+        assert!(rules.is_ignored("web/yarn.lock"));
+        // Anchored: only at the root
+        assert!(rules.is_ignored("dist/app.js"));
+        assert!(!rules.is_ignored("web/dist/app.js"));
+        // Directory-only: a file called build is fine
+        assert!(rules.is_ignored("web/build/out.txt"));
+        assert!(!rules.is_ignored("scripts/build"));
+        assert!(rules.is_ignored("proto/api/v1/user.pb.go"));
+        assert!(!rules.is_ignored("src/user.pb.go"));
+        // Negation takes back the earlier match
+        assert!(rules.is_ignored("debug.log"));
+        assert!(!rules.is_ignored("keep.log"));
+        assert!(!rules.is_ignored("src/main.rs"));
+    }
+}
@@ -0,0 +1,88 @@
+use std::{fs::Permissions, sync::mpsc::Sender, thread};
+use walkdir::WalkDir;
+use crate::config::Config;
+use crate::content::{self, FileContent, TextEncoding};
+use crate::fs::RealFs;
+use crate::roots::WatchRoot;
+use crate::path_key::PathKey;
+use crate::{normalize_path, AppEvent};
+
+// Entries are streamed to the main loop in batches of this size
+const BATCH_SIZE: usize = 64;
+
+// One file cached by the scan, with what a revert needs to write it back
+pub struct IndexedFile {
+    pub key: PathKey,
+    pub text: String,
+    pub encoding: TextEncoding,
+    pub permissions: Option<Permissions>,
+}
+
+pub enum IndexEvent {
+    Batch(Vec<IndexedFile>),
+    // `truncated` is set when the file or memory budget stopped the scan early
+    Done { truncated: bool },
+}
+
+// Populate the file cache in the background so the TUI comes up immediately
+pub fn spawn(roots: Vec<WatchRoot>, config: Config, tx: Sender<AppEvent>) {
+    thread::spawn(move || {
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        let mut cached_bytes = 0u64;
+        let mut seen_files = 0usize;
+        let mut truncated = false;
+
+        'roots: for root in &roots {
+            for entry in WalkDir::new(&root.path).into_iter().filter_map(|e| e.ok()) {
+                let path = entry.path();
+                if !path.is_file() {
+                    continue;
+                }
+                // Filter noise
+                let rel = path.strip_prefix(&root.path).unwrap_or(path);
+                if root.is_ignored(rel) && !is_protected(&config, root, rel) {
+                    continue;
+                }
+                seen_files += 1;
+                if seen_files > config.max_index_files {
+                    truncated = true;
+                    break 'roots;
+                }
+
+                // Binary and oversized files are never cached
+                let Some(FileContent::Text(text, encoding)) = content::read(&RealFs, path, &config) else {
+                    continue;
+                };
+                cached_bytes += text.len() as u64;
+                if cached_bytes > config.max_index_bytes {
+                    truncated = true;
+                    break 'roots;
+                }
+
+                // Store normalized absolute path
+                batch.push(IndexedFile {
+                    key: normalize_path(path),
+                    text,
+                    encoding,
+                    permissions: entry.metadata().ok().map(|m| m.permissions()),
+                });
+                if batch.len() == BATCH_SIZE {
+                    let full = std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE));
+                    if tx.send(AppEvent::Index(IndexEvent::Batch(full))).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+
+        let _ = tx.send(AppEvent::Index(IndexEvent::Batch(batch)));
+        let _ = tx.send(AppEvent::Index(IndexEvent::Done { truncated }));
+    });
+}
+
+// Protected files need a baseline to be restored from, dotfiles included.
+// Checked with and without the multi-root prefix since we don't know which applies.
+fn is_protected(config: &Config, root: &WatchRoot, rel: &std::path::Path) -> bool {
+    let rel = rel.to_string_lossy();
+    config.is_protected(&rel) || config.is_protected(&format!("{}/{}", root.name, rel))
+}
@@ -0,0 +1,78 @@
+use std::time::{Duration, Instant};
+
+// Presses further apart than this start over at a plain interrupt
+pub const WINDOW: Duration = Duration::from_secs(2);
+
+// What a Ctrl+C press does, besides the ETX byte the agent always gets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    // Just the byte
+    Interrupt,
+    // SIGTERM to the agent's process group
+    Terminate,
+    // A toast saying one more press sends SIGKILL
+    OfferKill,
+    Kill,
+}
+
+// Ctrl+C presses in a row, for escalating on an agent that ignores them
+#[derive(Debug, Default)]
+pub struct Interrupts {
+    last: Option<(Step, Instant)>,
+}
+
+impl Interrupts {
+    // With `escalate` off every press is a plain interrupt, for agents that
+    // make their own sense of a double Ctrl+C
+    pub fn press(&mut self, now: Instant, escalate: bool) -> Step {
+        let step = match self.current(now) {
+            _ if !escalate => Step::Interrupt,
+            None | Some(Step::Kill) => Step::Interrupt,
+            Some(Step::Interrupt) => Step::Terminate,
+            Some(Step::Terminate) => Step::OfferKill,
+            Some(Step::OfferKill) => Step::Kill,
+        };
+        self.last = Some((step, now));
+        step
+    }
+
+    // The last press, while the next one would still follow on from it
+    pub fn current(&self, now: Instant) -> Option<Step> {
+        self.last.filter(|&(_, at)| now.duration_since(at) < WINDOW).map(|(step, _)| step)
+    }
+
+    // For the status bar; empty before anything's been escalated
+    pub fn label(&self, now: Instant) -> &'static str {
+        match self.current(now) {
+            Some(Step::Terminate) => "SIGTERM sent - Ctrl+C again to offer SIGKILL",
+            Some(Step::OfferKill) => "Ctrl+C once more sends SIGKILL",
+            Some(Step::Kill) => "SIGKILL sent",
+            Some(Step::Interrupt) | None => "",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quick_presses_escalate_and_a_pause_starts_over() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut presses = Interrupts::default();
+        let steps: Vec<Step> = [0, 500, 1000, 1500].into_iter().map(|ms| presses.press(at(ms), true)).collect();
+        assert_eq!(steps, [Step::Interrupt, Step::Terminate, Step::OfferKill, Step::Kill]);
+        assert_eq!(presses.label(at(1600)), "SIGKILL sent");
+        assert_eq!(presses.label(at(3600)), "");
+
+        // Each press only has to follow the one before it
+        assert_eq!(presses.press(at(5000), true), Step::Interrupt);
+        assert_eq!(presses.press(at(6900), true), Step::Terminate);
+        assert_eq!(presses.press(at(9000), true), Step::Interrupt);
+
+        let mut plain = Interrupts::default();
+        assert!((0..4).all(|n| plain.press(at(n * 100), false) == Step::Interrupt));
+        assert_eq!(plain.label(at(400)), "");
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{io::Write, path::Path};
+use crate::types::{ChangeKind, Decision, Origin};
+
+// One finalized change, written as a single JSON line
+#[derive(Serialize)]
+pub struct ChangeRecord<'a> {
+    // The FileChange id, as `aiui ctl` and exports show it
+    pub id: u64,
+    pub path: &'a str,
+    pub kind: &'a ChangeKind,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub decision: Decision,
+    pub origin: Origin,
+    pub timestamp: String,
+}
+
+pub struct JsonLog {
+    out: Box<dyn Write + Send>,
+}
+
+impl JsonLog {
+    // "-" streams to stdout (the TUI then draws on stderr)
+    pub fn open(path: &Path) -> Result<Self> {
+        let out: Box<dyn Write + Send> = if path == Path::new("-") {
+            Box::new(std::io::stdout())
+        } else {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("cannot open JSON log {}", path.display()))?;
+            Box::new(file)
+        };
+        Ok(Self { out })
+    }
+
+    pub fn record(&mut self, record: &ChangeRecord) {
+        if let Ok(line) = serde_json::to_string(record) {
+            let _ = writeln!(self.out, "{}", line);
+            let _ = self.out.flush();
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+
+// App-wide bindings that can be moved or switched off with [keys]. A key no
+// action is bound to goes to the agent like any other. CopyPath and Reveal
+// only act in the diff view, where they take their keys over from the rest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    RestartAgent,
+    ToggleDiff,
+    ToggleSidebar,
+    ToggleFocus,
+    ClearLog,
+    Export,
+    Stats,
+    Diffstat,
+    QuickOpen,
+    Snippets,
+    Editor,
+    CycleTheme,
+    Notifications,
+    Churn,
+    PickCommand,
+    Rescan,
+    Commit,
+    PtyDump,
+    SelectPrev,
+    SelectNext,
+    PagePrev,
+    PageNext,
+    ReviewBanner,
+    CopyPath,
+    Reveal,
+}
+
+impl Action {
+    fn diff_view_only(self) -> bool {
+        matches!(self, Action::CopyPath | Action::Reveal)
+    }
+}
+
+const DEFAULTS: [(Action, &str); 26] = [
+    (Action::Quit, "ctrl+q"),
+    (Action::RestartAgent, "ctrl+shift+r"),
+    (Action::ToggleDiff, "ctrl+k"),
+    (Action::ToggleSidebar, "ctrl+h"),
+    (Action::ToggleFocus, "ctrl+f"),
+    (Action::ClearLog, "ctrl+l"),
+    (Action::Export, "ctrl+e"),
+    (Action::Stats, "ctrl+s"),
+    (Action::Diffstat, "alt+s"),
+    (Action::QuickOpen, "ctrl+o"),
+    (Action::Snippets, "ctrl+y"),
+    (Action::Editor, "ctrl+g"),
+    (Action::CycleTheme, "ctrl+t"),
+    (Action::Notifications, "alt+n"),
+    (Action::Churn, "alt+c"),
+    (Action::PickCommand, "alt+o"),
+    (Action::Rescan, "alt+r"),
+    (Action::Commit, "alt+g"),
+    (Action::PtyDump, "f12"),
+    (Action::SelectPrev, "ctrl+up"),
+    (Action::SelectNext, "ctrl+down"),
+    (Action::PagePrev, "ctrl+pageup"),
+    (Action::PageNext, "ctrl+pagedown"),
+    (Action::ReviewBanner, "ctrl+a"),
+    (Action::CopyPath, "ctrl+y"),
+    (Action::Reveal, "ctrl+g"),
+];
+
+// A key plus modifiers. Letters are kept lowercase with Shift as a modifier,
+// so "ctrl+shift+r" matches however the terminal reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Chord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Chord {
+    fn from_key(key: &KeyEvent) -> Self {
+        let mut modifiers = key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        let code = match key.code {
+            KeyCode::Char(c) if c.is_ascii_uppercase() => {
+                modifiers |= KeyModifiers::SHIFT;
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+            code => code,
+        };
+        Self { code, modifiers }
+    }
+
+    // "ctrl+l", "ctrl+shift+r", "alt+pageup", "f5"
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let key = parts.pop().unwrap_or_default().to_lowercase();
+        for part in parts {
+            modifiers |= match part.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier {:?} in {:?}", part, text)),
+            };
+        }
+        let code = match key.as_str() {
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "tab" => KeyCode::Tab,
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "space" => KeyCode::Char(' '),
+            f if f.len() > 1 && f.starts_with('f') && let Ok(n @ 1..=12) = f[1..].parse::<u8>() => KeyCode::F(n),
+            c if c.chars().count() == 1 => KeyCode::Char(c.chars().next().unwrap_or_default()),
+            _ => return Err(format!("unknown key {:?}", text)),
+        };
+        Ok(Self { code, modifiers })
+    }
+
+    // As the status bar and modal show it: "Ctrl+Shift+R"
+    fn label(&self) -> String {
+        let mut label = String::new();
+        for (modifier, name) in [(KeyModifiers::CONTROL, "Ctrl+"), (KeyModifiers::ALT, "Alt+"), (KeyModifiers::SHIFT, "Shift+")] {
+            if self.modifiers.contains(modifier) {
+                label.push_str(name);
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => label.push_str("Space"),
+            KeyCode::Char(c) => label.push(c.to_ascii_uppercase()),
+            KeyCode::F(n) => label.push_str(&format!("F{}", n)),
+            KeyCode::PageUp => label.push_str("PgUp"),
+            KeyCode::PageDown => label.push_str("PgDn"),
+            code => label.push_str(&format!("{:?}", code)),
+        }
+        label
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Chord, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::with(&BTreeMap::new()).unwrap_or_else(|_| Self { bindings: Vec::new() })
+    }
+}
+
+impl Keymap {
+    // The defaults with `overrides` on top. "none" (or "") unbinds an action.
+    // One key can't do two things in the same place.
+    fn with(overrides: &BTreeMap<Action, String>) -> Result<Self, String> {
+        let mut bindings: Vec<(Chord, Action)> = Vec::new();
+        for (action, default) in DEFAULTS {
+            let text = overrides.get(&action).map_or(default, String::as_str);
+            if text.is_empty() || text.eq_ignore_ascii_case("none") {
+                continue;
+            }
+            let chord = Chord::parse(text)?;
+            if let Some((_, other)) = bindings.iter().find(|(bound, other)| {
+                *bound == chord && other.diff_view_only() == action.diff_view_only()
+            }) {
+                return Err(format!("{} is bound to both {:?} and {:?}", chord.label(), other, action));
+            }
+            bindings.push((chord, action));
+        }
+        Ok(Self { bindings })
+    }
+
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        self.find(key, false)
+    }
+
+    // CopyPath or Reveal, checked ahead of `action` while the diff view is up
+    pub fn diff_view_action(&self, key: &KeyEvent) -> Option<Action> {
+        self.find(key, true)
+    }
+
+    fn find(&self, key: &KeyEvent, diff_view: bool) -> Option<Action> {
+        let chord = Chord::from_key(key);
+        self.bindings.iter()
+            .find(|(bound, action)| *bound == chord && action.diff_view_only() == diff_view)
+            .map(|(_, action)| *action)
+    }
+
+    // Key for `action`, for hints; None when it's unbound
+    pub fn label(&self, action: Action) -> Option<String> {
+        self.bindings.iter().find(|(_, bound)| *bound == action).map(|(chord, _)| chord.label())
+    }
+
+    // "Ctrl+H: Sidebar  Ctrl+K: Diff  ..." for the bound ones
+    pub fn hints(&self) -> String {
+        [
+            (Action::ToggleSidebar, "Sidebar"),
+            (Action::ToggleDiff, "Diff"),
+            (Action::ToggleFocus, "Focus"),
+            (Action::ClearLog, "Clear"),
+            (Action::Stats, "Stats"),
+            (Action::CycleTheme, "Theme"),
+        ]
+        .iter()
+        .filter_map(|(action, name)| Some(format!("{}: {}", self.label(*action)?, name)))
+        .collect::<Vec<_>>()
+        .join("  ")
+    }
+}
+
+// [keys] table: action = "ctrl+shift+l", or "none"
+impl<'de> Deserialize<'de> for Keymap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let overrides = BTreeMap::<Action, String>::deserialize(deserializer)?;
+        Self::with(&overrides).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn rebinding_frees_the_default_key() {
+        let keys: Keymap = toml::from_str("clear_log = \"ctrl+shift+l\"\ncycle_theme = \"none\"").unwrap();
+        // Ctrl+L now reaches the shell
+        assert_eq!(keys.action(&key(KeyCode::Char('l'), KeyModifiers::CONTROL)), None);
+        assert_eq!(keys.action(&key(KeyCode::Char('L'), KeyModifiers::CONTROL | KeyModifiers::SHIFT)), Some(Action::ClearLog));
+        assert_eq!(keys.action(&key(KeyCode::Char('t'), KeyModifiers::CONTROL)), None);
+        assert_eq!(keys.label(Action::CycleTheme), None);
+        // The rest keep their defaults
+        assert_eq!(keys.action(&key(KeyCode::Char('h'), KeyModifiers::CONTROL)), Some(Action::ToggleSidebar));
+        assert_eq!(keys.hints(), "Ctrl+H: Sidebar  Ctrl+K: Diff  Ctrl+F: Focus  Ctrl+Shift+L: Clear  Ctrl+S: Stats");
+    }
+
+    #[test]
+    fn shifted_letters_match_either_way() {
+        let keys = Keymap::default();
+        let restart = Some(Action::RestartAgent);
+        assert_eq!(keys.action(&key(KeyCode::Char('R'), KeyModifiers::CONTROL | KeyModifiers::SHIFT)), restart);
+        assert_eq!(keys.action(&key(KeyCode::Char('r'), KeyModifiers::CONTROL | KeyModifiers::SHIFT)), restart);
+        assert_eq!(keys.action(&key(KeyCode::Char('r'), KeyModifiers::CONTROL)), None);
+        assert_eq!(keys.action(&key(KeyCode::PageUp, KeyModifiers::CONTROL)), Some(Action::PagePrev));
+    }
+
+    #[test]
+    fn bad_bindings_are_config_errors() {
+        assert!(toml::from_str::<Keymap>("quit = \"hyper+q\"").is_err());
+        assert!(toml::from_str::<Keymap>("quit = \"ctrl+nope\"").is_err());
+        assert!(toml::from_str::<Keymap>("fly = \"ctrl+q\"").is_err());
+        let keys: Keymap = toml::from_str("export = \"f5\"").unwrap();
+        assert_eq!(keys.label(Action::Export).as_deref(), Some("F5"));
+    }
+
+    #[test]
+    fn one_key_cant_do_two_things() {
+        let clash = toml::from_str::<Keymap>("export = \"ctrl+q\"").unwrap_err();
+        assert!(clash.to_string().contains("Ctrl+Q is bound to both Quit and Export"), "{}", clash);
+        // Unless the other one moves out of the way
+        assert!(toml::from_str::<Keymap>("export = \"ctrl+q\"\nquit = \"none\"").is_ok());
+        assert!(toml::from_str::<Keymap>("copy_path = \"ctrl+g\"").is_err());
+
+        // The diff view's own keys shadow app-wide ones only there
+        let keys = Keymap::default();
+        let ctrl_y = key(KeyCode::Char('y'), KeyModifiers::CONTROL);
+        assert_eq!(keys.action(&ctrl_y), Some(Action::Snippets));
+        assert_eq!(keys.diff_view_action(&ctrl_y), Some(Action::CopyPath));
+        assert_eq!(keys.diff_view_action(&key(KeyCode::Char('s'), KeyModifiers::CONTROL)), None);
+        assert_eq!(keys.action(&key(KeyCode::Char('a'), KeyModifiers::CONTROL)), Some(Action::ReviewBanner));
+    }
+}
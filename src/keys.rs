@@ -0,0 +1,112 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyEventState};
+
+// Input modes the child can switch on with DECSET, read off the vt100 screen
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyModes {
+    // DECCKM: cursor keys send SS3 (`ESC O A`) instead of CSI (`ESC [ A`)
+    pub application_cursor: bool,
+    // DECKPAM: keypad keys send SS3 sequences instead of their characters
+    pub application_keypad: bool,
+    // Mode 2004: pasted text arrives wrapped in ESC [ 200~ ... ESC [ 201~
+    pub bracketed_paste: bool,
+}
+
+impl KeyModes {
+    pub fn from_screen(screen: &vt100::Screen) -> Self {
+        Self {
+            application_cursor: screen.application_cursor(),
+            application_keypad: screen.application_keypad(),
+            bracketed_paste: screen.bracketed_paste(),
+        }
+    }
+}
+
+// Bytes to send the child for a navigation or keypad key; None for keys
+// that don't depend on the child's modes
+pub fn encode(key: &KeyEvent, modes: KeyModes) -> Option<Vec<u8>> {
+    // Keypad keys are only reported as such by terminals with the kitty protocol
+    if modes.application_keypad && key.state.contains(KeyEventState::KEYPAD) {
+        let final_byte = match key.code {
+            KeyCode::Char(c @ '0'..='9') => b'p' + (c as u8 - b'0'),
+            KeyCode::Char('*') => b'j',
+            KeyCode::Char('+') => b'k',
+            KeyCode::Char(',') => b'l',
+            KeyCode::Char('-') => b'm',
+            KeyCode::Char('.') => b'n',
+            KeyCode::Char('/') => b'o',
+            KeyCode::Char('=') => b'X',
+            KeyCode::Enter => b'M',
+            _ => 0,
+        };
+        if final_byte != 0 {
+            return Some(vec![0x1b, b'O', final_byte]);
+        }
+    }
+
+    let final_byte = match key.code {
+        KeyCode::Up => b'A',
+        KeyCode::Down => b'B',
+        KeyCode::Right => b'C',
+        KeyCode::Left => b'D',
+        KeyCode::Home => b'H',
+        KeyCode::End => b'F',
+        _ => return None,
+    };
+    let intro = if modes.application_cursor { b'O' } else { b'[' };
+    Some(vec![0x1b, intro, final_byte])
+}
+
+// Text typed elsewhere (Ctrl+G), sent the way a terminal sends a paste: one
+// write, newlines as CR, wrapped in markers if the child asked for them so it
+// doesn't run each line. The editor's final newline is dropped; `enter`
+// submits explicitly.
+pub fn paste(text: &str, modes: KeyModes, enter: bool) -> Vec<u8> {
+    let body = text.trim_end_matches(['\n', '\r']).replace("\r\n", "\n").replace('\n', "\r");
+    let mut bytes = Vec::with_capacity(body.len() + 13);
+    if modes.bracketed_paste {
+        bytes.extend_from_slice(b"\x1b[200~");
+    }
+    bytes.extend_from_slice(body.as_bytes());
+    if modes.bracketed_paste {
+        bytes.extend_from_slice(b"\x1b[201~");
+    }
+    if enter {
+        bytes.push(b'\r');
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyEventKind, KeyModifiers};
+
+    #[test]
+    fn pastes_are_bracketed_only_when_asked_for() {
+        let bracketed = KeyModes { bracketed_paste: true, ..KeyModes::default() };
+        assert_eq!(paste("one\ntwo\n", bracketed, false), b"\x1b[200~one\rtwo\x1b[201~");
+        assert_eq!(paste("one\r\ntwo\n\n", KeyModes::default(), true), b"one\rtwo\r");
+    }
+
+    #[test]
+    fn cursor_and_keypad_keys_follow_the_childs_modes() {
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let keypad = |code| KeyEvent::new_with_kind_and_state(code, KeyModifiers::NONE, KeyEventKind::Press, KeyEventState::KEYPAD);
+        let normal = KeyModes::default();
+        let decckm = KeyModes { application_cursor: true, ..KeyModes::default() };
+        let deckpam = KeyModes { application_keypad: true, ..KeyModes::default() };
+
+        assert_eq!(encode(&key(KeyCode::Up), normal).as_deref(), Some(&b"\x1b[A"[..]));
+        assert_eq!(encode(&key(KeyCode::Up), decckm).as_deref(), Some(&b"\x1bOA"[..]));
+        assert_eq!(encode(&key(KeyCode::End), decckm).as_deref(), Some(&b"\x1bOF"[..]));
+        assert_eq!(encode(&key(KeyCode::Char('a')), decckm), None);
+
+        assert_eq!(encode(&keypad(KeyCode::Char('5')), deckpam).as_deref(), Some(&b"\x1bOu"[..]));
+        assert_eq!(encode(&keypad(KeyCode::Enter), deckpam).as_deref(), Some(&b"\x1bOM"[..]));
+        // The main keyboard's digits, or the keypad without DECKPAM, type as usual
+        assert_eq!(encode(&key(KeyCode::Char('5')), deckpam), None);
+        assert_eq!(encode(&keypad(KeyCode::Char('5')), normal), None);
+        // Keypad arrows still follow DECCKM
+        assert_eq!(encode(&keypad(KeyCode::Down), KeyModes { application_cursor: true, ..deckpam }).as_deref(), Some(&b"\x1bOB"[..]));
+    }
+}
@@ -1,600 +1,4254 @@
-use anyhow::Result;
-use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use anyhow::{Context, Result};
+use clap::Parser;
+use portable_pty::PtySize;
 use crossterm::{
+    cursor::SetCursorStyle,
     event::{self, Event, KeyCode, KeyModifiers},
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen, SetTitle},
 };
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout, Position},
-    style::{Color, Modifier, Style},
-    widgets::ListState,
+    layout::{Constraint, Direction, Layout, Position, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, ListState, Paragraph},
     Terminal,
 };
-use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
 use chrono::Local;
-use similar::{ChangeTag, TextDiff};
-use walkdir::WalkDir;
 use std::{
-    collections::VecDeque,
-    io::{Read, Write},
+    collections::{HashSet, VecDeque},
+    io::{IsTerminal, Write},
     path::PathBuf,
-    sync::{Arc, Mutex, mpsc},
-    thread,
+    sync::{Arc, Mutex, mpsc, atomic::{AtomicBool, Ordering}},
     time::{Duration, Instant},
 };
 
+mod agent;
+mod ansi;
+mod check;
+mod churn;
+mod command_pick;
+mod cli;
+mod config;
+mod content;
+mod control;
+mod desktop;
+mod diff;
+mod doctor;
+mod engine;
+mod export;
+mod fs;
+mod fuzzy;
+mod git;
+mod glob;
+mod headless;
+mod hooks;
+mod ignore;
+mod indexer;
+mod interrupt;
+mod json_log;
+mod keys;
+mod keymap;
+mod observer;
+mod path_key;
+mod process;
+mod pty_dump;
+mod roots;
+mod snippets;
+mod syntax;
+mod test_run;
+mod throughput;
+mod toasts;
+#[cfg(test)]
+mod tests;
+mod spill;
 mod types;
 mod ui;
-use types::{ChangeKind, FileChange};
-use ui::theme::{Theme, ThemeVariant};
+use agent::{Agent, Launch};
+use config::{ApprovalUi, BinaryApproval, Config, ProtectedMode, RejectMode, SidebarSelection, StatusSegment, TimeFormat, TimeoutAction};
+use content::{FileContent, TextEncoding};
+use fs::{FileSystem, RealFs};
+use check::{CheckOutcome, CheckResult, CheckStatus, Checker};
+use churn::{Churn, ChurnOrder};
+use hooks::{HookResult, Hooks};
+use observer::{ObservePool, Observed};
+use path_key::PathKey;
+use pty_dump::PtyDump;
+use test_run::{TestEvent, TestRunner};
+use throughput::Throughput;
+use toasts::{Severity, Toasts};
+use diff::{DiffText, HunkCursor};
+use export::ExportFormat;
+use indexer::IndexEvent;
+use roots::Roots;
+use spill::SpillStore;
+use keys::KeyModes;
+use keymap::Action;
+use json_log::{ChangeRecord, JsonLog};
+use types::{ChangeKind, ChangeStatus, Decision, FileChange, Origin, SessionStats};
+use ui::components::agent_prompt::AgentPrompt;
+use ui::components::approval_banner::{BannerHead, BannerMode};
+use ui::components::command_pick::{CommandPick, PickOutcome};
+use ui::components::diff_view::Shown;
+use ui::components::pty_dump::DumpView;
+use ui::components::toasts::LogView;
+use ui::components::quick_open::QuickOpen;
+use ui::components::snippet_picker::{Filling, SnippetPicker};
+use ui::components::sidebar::{SidebarFilter, SidebarView, TypeFilter};
+use ui::components::status_bar::StatusInfo;
+use ui::theme::{ColorDepth, Theme, ThemeVariant};
 
 // Unified event type for our application
 enum AppEvent {
-    PtyData(Vec<u8>),
+    // Output from the agent of the given generation
+    PtyData(u64, Vec<u8>),
     FileChange(PathBuf, ChangeKind),
+    // Both ends of a rename, on platforms that report them together
+    Rename(PathBuf, PathBuf),
+    // From the ticker thread: held-remove expiry, spinner
     Tick,
+    // Keys, pastes and resizes from the input thread
     Input(Event),
+    Index(IndexEvent),
+    // From the control socket; the reply goes back on the sender
+    Control(control::Request, mpsc::Sender<control::Response>),
+    // An on_accept hook finished
+    Hook(HookResult),
+    // A check_command run finished (or was cancelled)
+    Check(CheckResult),
+    // Output and exit of a test_command run
+    Test(TestEvent),
+    // The observer pool read and diffed a changed file
+    Observed(Observed),
+    // The watcher reported an error (lost watch, inotify limit, ...)
+    WatchError(String),
+    // The OS dropped events; cached baselines may be out of date
+    WatchOverflow,
+    // A background `git status` finished: dirty or not, None outside a repository
+    GitStatus(Option<bool>),
+    // Alt+G's commit finished, with the new commit's short hash
+    GitCommit(Result<git::Commit>),
 }
 
 
 
 #[derive(Clone)]
 struct PendingChange {
-    path: String,
+    path: PathKey,
     old_content: String,
     new_content: String,
-    diff_text: String,
+    // Bytes on disk <-> the two texts above, for writing either one back
+    old_encoding: TextEncoding,
+    new_encoding: TextEncoding,
+    // Restored along with old_content
+    old_permissions: Option<std::fs::Permissions>,
+    diff_text: DiffText,
+    hunks: Vec<diff::Hunk>,
+    // Lines added/removed; a delete counts every line of the old file
+    insertions: usize,
+    deletions: usize,
+    kind: ChangeKind,
+    // New content is binary/oversized and was never cached
+    binary: bool,
+    // Only line endings changed; diff_text is a one-line summary of that
+    eol_only: bool,
+    // Line endings went from .0 to .1, alone or along with the text. The
+    // diff is taken without them, so this is the only sign of it.
+    line_endings: Option<(diff::LineEnding, diff::LineEnding)>,
+    // Fingerprint of the file when the diff was computed (None = absent)
+    disk_hash: Option<u64>,
+    // Re-queued because the file changed again before a decision
+    refreshed: bool,
+    // Per-hunk keep/drop toggles; missing entries mean "keep"
+    hunk_decisions: Vec<bool>,
+    // Sidebar entry whose status follows this approval
+    change_id: u64,
+    // First queued; a refresh keeps it, for the time-to-decision average
+    queued_at: Instant,
+    // Whole-file diff, handed on to the sidebar entry
+    full: Option<diff::Hunk>,
+    // Queued while the startup scan hadn't cached this file yet
+    baseline_missing: bool,
+    // Matches a `protected` glob (quarantine mode)
+    protected: bool,
+    // A removed directory: the cached files that were under it, which a
+    // reject writes back
+    dir_files: Vec<PathKey>,
+}
+
+// Outcome of comparing a file on disk against its cached baseline
+enum Observation {
+    Queue(Box<PendingChange>),
+    // Logged without approval (binary/oversized in skip mode)
+    Skip(String),
+    // Disk matches the baseline
+    Unchanged,
+    // Unreadable, or a deletion we had no baseline for
+    LogOnly,
+}
+
+// What deciding the front approval came to
+#[derive(Debug, PartialEq)]
+enum Decided {
+    Resolved,
+    // Changed again on disk: back at the front with a fresh diff
+    Refreshed,
+    // Couldn't write the result; still at the front, undecided
+    WriteFailed(String),
+}
+
+// A change between its event and its observation, which the observer pool
+// may take a moment over
+struct Recording {
+    path: PathBuf,
+    cache_key: PathKey,
+    display_path: String,
+    kind: ChangeKind,
+    // Mid-scan, a missing baseline means "not indexed yet", not "new file"
+    baseline_missing: bool,
+    change_id: u64,
+    protected: bool,
+    origin: Origin,
+    dir: bool,
+}
+
+// What pressing `n` will do to the file on disk
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RevertAction {
+    Delete,
+    Leave,
+    Restore,
+    RestoreDir,
+}
+
+// Dry run of a reject: current disk content vs what would be written back
+struct RevertPreview {
+    diff_text: DiffText,
+    hunks: Vec<diff::Hunk>,
+    // Disk no longer matches the content the approval diff was computed against
+    stale: bool,
+}
+
+impl PendingChange {
+    fn revert_action(&self) -> RevertAction {
+        if self.baseline_missing {
+            // Not indexed yet: the empty old content isn't what was there
+            RevertAction::Leave
+        } else if !self.dir_files.is_empty() {
+            RevertAction::RestoreDir
+        } else if self.old_content.is_empty() && self.kind == ChangeKind::Create {
+            // It was a new file, so delete it
+            RevertAction::Delete
+        } else if self.binary && self.old_content.is_empty() {
+            // Nothing cached to restore
+            RevertAction::Leave
+        } else {
+            RevertAction::Restore
+        }
+    }
+
+    // What the sidebar says next to the path: the summary standing in for
+    // a diff, or that line endings changed along with the text
+    fn note(&self) -> Option<String> {
+        if self.binary || self.eol_only {
+            return Some(self.diff_text.as_str().trim_end().to_string());
+        }
+        self.line_endings.map(|(from, to)| format!("also changes line endings {} → {}", from.label(), to.label()))
+    }
+
+    // What a revert does, for the first-revert prompt
+    fn revert_effect(&self) -> String {
+        match self.revert_action() {
+            RevertAction::Delete => format!("delete {}", self.path),
+            RevertAction::Leave => format!("leave {} untouched", self.path),
+            RevertAction::Restore if self.kind == ChangeKind::Remove => format!("recreate {}", self.path),
+            RevertAction::Restore => format!("overwrite {}", self.path),
+            RevertAction::RestoreDir => format!("recreate {} with its {} cached files", self.path, self.dir_files.len()),
+        }
+    }
+
+    // Without a baseline the diff is one all-insert hunk against "", so
+    // leaving any of it out would write a truncated file
+    fn toggle_hunk(&mut self, index: usize) {
+        if index >= self.hunks.len() || self.baseline_missing {
+            return;
+        }
+        if self.hunk_decisions.len() < self.hunks.len() {
+            self.hunk_decisions.resize(self.hunks.len(), true);
+        }
+        self.hunk_decisions[index] = !self.hunk_decisions[index];
+    }
+
+    fn is_partial(&self) -> bool {
+        self.hunk_decisions.iter().any(|keep| !keep)
+    }
+
+    // (insertions, deletions) that an accept would keep
+    fn kept_line_counts(&self) -> (usize, usize) {
+        if !self.is_partial() {
+            return (self.insertions, self.deletions);
+        }
+        let kept: Vec<diff::Hunk> = self.hunks.iter().enumerate()
+            .filter(|(i, _)| self.hunk_decisions.get(*i).copied().unwrap_or(true))
+            .map(|(_, h)| h.clone())
+            .collect();
+        diff::line_counts(&kept)
+    }
+
+    fn is_stale(&self, fs: &dyn FileSystem, config: &Config) -> bool {
+        content::fingerprint(fs, self.path.path(), config) != self.disk_hash
+    }
+
+    fn revert_preview(&self, fs: &dyn FileSystem, config: &Config) -> RevertPreview {
+        let path = self.path.path();
+        let on_disk = match content::read(fs, path, config) {
+            Some(FileContent::Text(text, _)) => Some(text),
+            _ => None,
+        };
+
+        let stale = self.is_stale(fs, config);
+
+        let current = on_disk.unwrap_or_default();
+        let (hunks, fallback) = match self.revert_action() {
+            RevertAction::Delete => (
+                diff::compute_hunks(&current, "", 3),
+                format!("Reject will delete {}", self.path),
+            ),
+            RevertAction::Leave => (
+                Vec::new(),
+                format!("Reject leaves {} untouched (no cached content to restore)", self.path),
+            ),
+            RevertAction::Restore => (
+                diff::compute_hunks(&current, &self.old_content, 3),
+                "Reject writes back content identical to disk".to_string(),
+            ),
+            RevertAction::RestoreDir => (
+                Vec::new(),
+                format!("Reject recreates {} with its {} cached files", self.path, self.dir_files.len()),
+            ),
+        };
+
+        let mut diff_text = diff::render_text(&hunks);
+        if diff_text.is_empty() {
+            diff_text = fallback;
+        }
+
+        RevertPreview { diff_text: diff_text.into(), hunks, stale }
+    }
+}
+
+// Queued changes listed (and reachable with 1-9) in the approval modal
+const QUEUE_LIST: usize = 9;
+
+// How often the pane title re-reads .git/HEAD
+const BRANCH_REFRESH: Duration = Duration::from_secs(2);
+
+// How long a remove waits for a matching create before it's logged as a delete
+const RENAME_WINDOW: Duration = Duration::from_millis(500);
+
+// A remove held back in case it's the first half of a rename
+struct HeldRemove {
+    path: PathBuf,
+    cache_key: PathKey,
+    // Hash of the cached content, matched against the content of later creates
+    hash: Option<u64>,
+    at: Instant,
+}
+
+// The selected change re-cut to diff_context for the diff view. It's kept
+// off the change itself, so exports, copies and requeues get the diff as it
+// was recorded.
+struct Recut {
+    id: u64,
+    context: usize,
+    diff: Option<DiffText>,
+    hunks: Vec<diff::Hunk>,
+}
+
+// An Alt+R re-scan in progress. The scan fills these maps on the side while
+// the live baselines keep serving diffs and reverts; they're swapped in when
+// it's done.
+#[derive(Default)]
+struct Rescan {
+    file_cache: std::collections::HashMap<PathKey, String>,
+    encodings: std::collections::HashMap<PathKey, TextEncoding>,
+    permissions: std::collections::HashMap<PathKey, std::fs::Permissions>,
+    // Paths that changed, or had their baseline set, since the scan began:
+    // the scan may have read them either side of that, so the live baseline wins
+    touched: std::collections::HashSet<PathKey>,
+}
+
+// How long events for a file we wrote ourselves are checked against our write
+const OWN_WRITE_WINDOW: Duration = Duration::from_secs(2);
+
+// A write (or delete) of ours. One rename can surface as several notify
+// events, so every event for the path within the window is compared with what
+// we left on disk; only a file that differs (the agent wrote again) is a change.
+struct OwnWrite {
+    // Fingerprint of the file as we left it; None if we deleted it
+    fingerprint: Option<u64>,
+    // An on_accept hook is rewriting the file: swallow everything until it exits
+    hook: bool,
+    // Past this, events for the path are changes again. A write can come back
+    // as no event at all (same content, coalesced), so the entry can't wait
+    // for its echo to be cleared.
+    until: Instant,
+}
+
+// Which pane receives plain (non-Ctrl) keys
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Terminal,
+    Sidebar,
+}
+
+// Follow mode (`F`): new changes open in the diff view as they arrive
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Follow {
+    Off,
+    On,
+    // Suspended by manual navigation until `F` is pressed again
+    Paused,
+}
+
+impl Follow {
+    fn toggle(self) -> Self {
+        match self {
+            Self::On => Self::Off,
+            Self::Off | Self::Paused => Self::On,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Off => "",
+            Self::On => "Follow",
+            Self::Paused => "Follow paused (F)",
+        }
+    }
 }
 
 struct AppState {
     file_changes: VecDeque<FileChange>,
-    debounce_map: std::collections::HashMap<(String, ChangeKind), Instant>,
+    // Sidebar history length (from config); 0 keeps everything
+    history_cap: usize,
+    next_change_id: u64,
+    debounce_map: std::collections::HashMap<(PathKey, ChangeKind), Instant>,
+    // The highlighted entry, by FileChange id. Its row in the *filtered*
+    // sidebar is worked out when needed, since pins, filters and new entries
+    // all shift rows; list_state only carries the scroll offset.
+    selected: Option<u64>,
     list_state: ListState,
     show_sidebar: bool,
+    sidebar_filter: SidebarFilter,
+    focus: Focus,
+    // How the terminal's current size is laid out; kept up to date on resize
+    fit: Fit,
+    // Rows available inside the sidebar border, for paging
+    sidebar_rows: usize,
+    time_format: TimeFormat,
+
+    // Large diffs are parked on disk; at most one is loaded back at a time
+    spill_store: SpillStore,
+    loaded_spill: Option<PathBuf>,
+    recut: Option<Recut>,
     
-    file_cache: std::collections::HashMap<String, String>,
+    file_cache: std::collections::HashMap<PathKey, String>,
+    // Baselines that aren't plain UTF-8; absent means UTF-8
+    encodings: std::collections::HashMap<PathKey, TextEncoding>,
+    // Mode bits of each baseline, so a revert doesn't leave a script non-executable
+    permissions: std::collections::HashMap<PathKey, std::fs::Permissions>,
+    held_removes: Vec<HeldRemove>,
+    // Renames already logged (cache keys), so a late combined event is ignored
+    recent_renames: Vec<(PathKey, PathKey, Instant)>,
+    // Files cached so far while the startup scan runs; None once done
+    indexing: Option<usize>,
+    // The scan running is a re-scan asked for with Alt+R, not the startup one
+    rescan: Option<Rescan>,
+    index_truncated: bool,
     
     // Approval System
     approval_queue: VecDeque<PendingChange>,
-    ignore_next_write: std::collections::HashSet<String>,
+    // Change id at the front of the queue and since when, for approval_timeout_secs
+    front_since: Option<(u64, Instant)>,
+    // Our own writes by cache key, so their echoes aren't queued as changes.
+    // Events are handled one at a time, so an entry also keeps a revert and an
+    // incoming event for the same path from being confused.
+    own_writes: std::collections::HashMap<PathKey, OwnWrite>,
     modal_active: bool,
+    approval_ui: ApprovalUi,
+    // Inline banner is taking y/n/d (Ctrl+A); otherwise they go to the agent
+    banner_armed: bool,
     
     show_diff_view: bool,
+    // Fold long diff lines instead of clipping them (`w`)
+    diff_wrap: bool,
+    // Show tabs and trailing blanks in changed lines (`s`)
+    diff_whitespace: bool,
+    // Unchanged lines around changes in the diff view (`+`/`-`)
+    diff_context: usize,
+    follow: Follow,
+    diff_cursor: HunkCursor,
+    modal_cursor: HunkCursor,
+    revert_preview: Option<RevertPreview>,
     parser: vt100::Parser,
     
     current_theme: ThemeVariant,
+    color_depth: ColorDepth,
+
+    // Agent activity indicator
+    last_pty_data: Option<Instant>,
+    spinner_frame: usize,
+    // For the pane title's {branch} and the status bar; re-read every BRANCH_REFRESH
+    git_branch: String,
+    branch_read: Option<Instant>,
+    // Working tree dirty, for the status bar's branch segment
+    git_status: git::GitStatus,
+    // Log entries whose files Alt+G has committed, and those it's committing now
+    committed: std::collections::HashSet<u64>,
+    committing: Option<Vec<u64>>,
+
+    // Title and bell forwarded from the child terminal
+    child_title: String,
+    bells_seen: usize,
+    bell_flash_until: Option<Instant>,
+
+    stats: SessionStats,
+    show_stats: bool,
+    // Per-file churn bars (Alt+S); one of these two overlays at a time
+    show_diffstat: bool,
+    agent_prompt: Option<AgentPrompt>,
+    quick_open: Option<QuickOpen>,
+    // Ctrl+Y; takes every key while open
+    snippet_picker: Option<SnippetPicker>,
+    // Lines typed into the agent, newest first, and the one being typed now.
+    // A guess from the keys we forward: anything that edits the line in ways
+    // we can't follow (arrows, Tab, Ctrl+U) throws the current one away.
+    prompt_history: VecDeque<String>,
+    typed_line: String,
+    // Text for the real terminal's clipboard, sent by the main loop (OSC 52)
+    clipboard: Option<String>,
+    // Transient status bar message (export results, ...)
+    status_message: Option<(String, Instant)>,
+    // Transient messages above the status bar, and their log
+    toasts: Toasts,
+    // The notification log, while it's open
+    notifications: Option<LogView>,
+    // Edits per file, and the ranking overlay's order while it's open
+    churn: Churn,
+    churn_view: Option<ChurnOrder>,
+    // Ctrl+C presses in a row, for escalating to signals
+    interrupts: interrupt::Interrupts,
+    command_pick: Option<CommandPick>,
+    // What the agent writes per second, for the stats panel
+    output: Throughput,
+    // --debug: the agent's raw output, and the overlay showing it
+    pty_dump: Option<PtyDump>,
+    dump_view: Option<DumpView>,
+    // DECSCUSR from the agent, which vt100 doesn't track
+    cursor_shape: ansi::CursorShape,
+    // Sidebar entries marked with Space, by FileChange id
+    marked: HashSet<u64>,
+    mark_menu: bool,
+    // Ctrl+Q with approvals still queued
+    quit_prompt: bool,
+    // `n` is waiting on the first-revert explanation
+    revert_prompt: bool,
+    // Seen and confirmed it this session
+    revert_confirmed: bool,
+    // The revert prompt came from the quit prompt's [r]: confirming it
+    // rejects everything and quits
+    quit_after_revert: bool,
+    json_log: Option<JsonLog>,
+    // on_accept command, if configured
+    hooks: Option<Hooks>,
+    // check_command runner, and where each queued change's check stands
+    checker: Option<Checker>,
+    checks: std::collections::HashMap<u64, CheckStatus>,
+    // Reads and diffs files off the main thread; None (tests, --headless)
+    // does it inline
+    observer: Option<ObservePool>,
+    // Files the pool is on, with later events for them held back until it's
+    // done so each file's events are still applied in order
+    observing: std::collections::HashMap<PathKey, (Recording, VecDeque<(PathBuf, ChangeKind)>)>,
+    // test_command runner; its output fills the Checks panel
+    tests: Option<TestRunner>,
+    // Checks panel folded down to its summary line (`k` in the sidebar)
+    checks_collapsed: bool,
+
+    config: Config,
+    roots: Roots,
+    // Disk access for the approval core; swapped for memory in tests
+    fs: Box<dyn FileSystem>,
 }
 
 impl AppState {
-    fn new() -> Self {
-        let mut cache = std::collections::HashMap::new();
-        
-        // Initial Scan to populate cache
-        for entry in WalkDir::new(".").into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
-            if path.is_file() {
-                // Filter noise
-                 if path.components().any(|c| c.as_os_str() == ".git" || c.as_os_str() == "target") {
-                    continue;
-                }
-                
-                // Store normalized absolute path
-                let key = normalize_path(path);
-                if let Ok(content) = std::fs::read_to_string(path) {
-                     cache.insert(key, content);
-                }
-            }
-        }
+    // Stream a finalized change to --json-log, if enabled
+    fn log_decision(&mut self, id: u64, path: &PathKey, kind: &ChangeKind, (insertions, deletions): (usize, usize), decision: Decision, origin: Origin) {
+        let Some(log) = self.json_log.as_mut() else { return };
+        log.record(&ChangeRecord {
+            id,
+            path: &path.to_string_lossy(),
+            kind,
+            insertions,
+            deletions,
+            decision,
+            origin,
+            timestamp: Local::now().to_rfc3339(),
+        });
+    }
 
-        Self {
-            file_changes: VecDeque::with_capacity(50),
-            debounce_map: std::collections::HashMap::new(),
-            list_state: ListState::default(),
-            show_sidebar: true,
-            file_cache: cache,
-            
-            approval_queue: VecDeque::new(),
-            ignore_next_write: std::collections::HashSet::new(),
-            modal_active: false,
-            
-            show_diff_view: false,
-            parser: vt100::Parser::new(24, 80, 0), // Initial size, will be updated
-            current_theme: ThemeVariant::Zinc,
+    fn visible_indices(&self) -> Vec<usize> {
+        self.sidebar_filter.visible_indices(&self.file_changes)
+    }
+
+    // Log index of the highlighted entry, if the filter shows it
+    fn selected_index(&self) -> Option<usize> {
+        let id = self.selected?;
+        self.visible_indices().into_iter().find(|&i| self.file_changes[i].id == id)
+    }
+
+    // Sidebar row of the highlighted entry
+    fn selected_row(&self) -> Option<usize> {
+        let id = self.selected?;
+        self.visible_indices().iter().position(|&i| self.file_changes[i].id == id)
+    }
+
+    fn select_row(&mut self, row: Option<usize>) {
+        self.selected = row.and_then(|row| self.visible_indices().get(row).map(|&i| self.file_changes[i].id));
+    }
+
+    fn selected_change(&self) -> Option<&FileChange> {
+        self.selected_index().and_then(|i| self.file_changes.get(i))
+    }
+
+    fn select_prev(&mut self) {
+        self.pause_follow();
+        let i = self.selected_row().map_or(0, |i| i.saturating_sub(1));
+        self.select_row(Some(i));
+        self.diff_cursor.reset();
+    }
+
+    fn select_next(&mut self) {
+        self.pause_follow();
+        let visible = self.visible_indices().len();
+        let i = self.selected_row().map_or(0, |i| (i + 1).min(visible.saturating_sub(1)));
+        self.select_row(Some(i));
+        self.diff_cursor.reset();
+    }
+
+    // Re-point the selection after the filter changed
+    fn refilter(&mut self) {
+        if self.sidebar_filter.is_active() {
+            self.select_row(Some(0));
         }
+        self.diff_cursor.reset();
     }
 
-    fn add_change(&mut self, path: PathBuf, kind: ChangeKind) {
-        let file_name = path.file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown")
-            .to_string();
+    fn handle_sidebar_key(&mut self, key: event::KeyEvent) {
+        if self.mark_menu {
+            if let KeyCode::Char(c) = key.code {
+                self.act_on_marked(c);
+            }
+            self.mark_menu = false;
+            return;
+        }
 
-        // 1. Filter Noise
-        if path.components().any(|c| c.as_os_str() == ".git" || c.as_os_str() == "target" || c.as_os_str() == "node_modules") {
+        // Typing a custom file-type glob
+        if self.sidebar_filter.editing_glob {
+            match (key.code, &mut self.sidebar_filter.file_type) {
+                (KeyCode::Char(c), TypeFilter::Glob(pattern)) => pattern.push(c),
+                (KeyCode::Backspace, TypeFilter::Glob(pattern)) => {
+                    pattern.pop();
+                }
+                (KeyCode::Enter, _) => self.sidebar_filter.editing_glob = false,
+                (KeyCode::Esc, _) => {
+                    self.sidebar_filter.file_type = TypeFilter::All;
+                    self.sidebar_filter.editing_glob = false;
+                }
+                _ => {}
+            }
+            self.refilter();
             return;
         }
-        if file_name.starts_with('.') && file_name != ".gitignore" {
-             return;
+
+        // Typing into the filter box
+        if self.sidebar_filter.editing {
+            match key.code {
+                KeyCode::Char(c) => self.sidebar_filter.query.push(c),
+                KeyCode::Backspace => {
+                    self.sidebar_filter.query.pop();
+                }
+                KeyCode::Enter => self.sidebar_filter.editing = false,
+                KeyCode::Esc => self.clear_filter(),
+                _ => {}
+            }
+            self.refilter();
+            return;
         }
 
-        // 3. Debounce
-        let key = (file_name.clone(), kind.clone());
-        if let Some(last_time) = self.debounce_map.get(&key) {
-            if last_time.elapsed() < Duration::from_millis(500) {
-                return;
+        match key.code {
+            KeyCode::Up => self.select_prev(),
+            KeyCode::Down => self.select_next(),
+            KeyCode::PageUp => self.select_page(false),
+            KeyCode::PageDown => self.select_page(true),
+            KeyCode::Home => {
+                self.pause_follow();
+                self.select_row(Some(0));
+                self.diff_cursor.reset();
+            }
+            KeyCode::End => {
+                self.pause_follow();
+                let visible = self.visible_indices().len();
+                self.select_row(visible.checked_sub(1));
+                self.diff_cursor.reset();
+            }
+            KeyCode::Char('f') => {
+                self.save_selection();
+                self.sidebar_filter.editing = true;
+            }
+            KeyCode::Char(c @ '1'..='3') => {
+                self.save_selection();
+                let kind = match c {
+                    '1' => ChangeKind::Create,
+                    '2' => ChangeKind::Modify,
+                    _ => ChangeKind::Remove,
+                };
+                self.sidebar_filter.toggle_kind(kind);
+                self.refilter();
             }
+            KeyCode::Char('p') => self.toggle_pin(),
+            KeyCode::Char('d') | KeyCode::Delete => self.dismiss_selected(),
+            KeyCode::Char('c') => self.copy_selected_path(),
+            KeyCode::Char('m') => self.copy_selected_markdown(),
+            KeyCode::Char('o') => self.reveal_selected(),
+            KeyCode::Char('T') => self.time_format = self.time_format.cycle(),
+            KeyCode::Char('F') => self.follow = self.follow.toggle(),
+            KeyCode::Char('k') if self.tests.is_some() => self.checks_collapsed = !self.checks_collapsed,
+            KeyCode::Char(' ') => self.toggle_mark(),
+            KeyCode::Char('a') if !self.marked.is_empty() => self.mark_menu = true,
+            // Marks first; with none, `x` is the Checks panel's cancel
+            KeyCode::Char('x') if !self.marked.is_empty() => self.marked.clear(),
+            KeyCode::Char('x') => self.cancel_tests(),
+            KeyCode::Char('t') => {
+                self.save_selection();
+                self.sidebar_filter.cycle_type(&self.config.sidebar_extensions);
+                self.refilter();
+            }
+            KeyCode::Esc if self.sidebar_filter.is_active() => self.clear_filter(),
+            KeyCode::Esc => self.focus = Focus::Terminal,
+            _ => {}
         }
-        self.debounce_map.insert(key, Instant::now());
+    }
 
-        // 3. Add to UI List
-        if self.file_changes.len() >= 50 {
-            self.file_changes.pop_back();
+    // Remember where we were before the first filter narrowed the list
+    fn save_selection(&mut self) {
+        if !self.sidebar_filter.is_active() {
+            self.sidebar_filter.saved_selection = self.selected;
         }
+    }
 
-        // Compute Diff
-        let cache_key = normalize_path(&path);
-        
-        // Debug Log
-        // let _ = std::fs::OpenOptions::new().create(true).append(true).open("aiui_debug.log")
-        //     .and_then(|mut f| writeln!(f, "Change detected: {:?} {:?}", path, kind));
+    fn clear_filter(&mut self) {
+        let saved = self.sidebar_filter.saved_selection.take();
+        self.sidebar_filter = SidebarFilter::default();
+        // Back to the entry from before, unless it's since been dismissed
+        if let Some(id) = saved.filter(|&id| self.file_changes.iter().any(|c| c.id == id)) {
+            self.selected = Some(id);
+        }
+        self.diff_cursor.reset();
+    }
 
-        let mut diff_output = None;
-        let mut needs_approval = false;
-
-        if kind == ChangeKind::Modify || kind == ChangeKind::Create {
-            if let Ok(content) = std::fs::read_to_string(&path) {
-                new_content = content;
-                
-                // If content hasn't effectively changed from our cache, ignore it
-                if new_content == old_content {
-                    return; 
-                }
-
-                // Generate Diff
-                let diff = TextDiff::from_lines(&old_content, &new_content);
-                let mut output = String::new();
-                for (idx, group) in diff.grouped_ops(3).iter().enumerate() {
-                    if idx > 0 { output.push_str("...\n"); }
-                    for op in group {
-                        for change in diff.iter_changes(op) {
-                            let (sign, _) = match change.tag() {
-                                ChangeTag::Delete => ("-", Color::Red),
-                                ChangeTag::Insert => ("+", Color::Green),
-                                ChangeTag::Equal => (" ", Color::Reset),
-                            };
-                            output.push_str(&format!("{}{}", sign, change));
-                        }
-                    }
+    // Read `path` now and describe how it differs from `old_content`
+    fn observe(&self, path: &std::path::Path, cache_key: &PathKey, old_content: String, kind: ChangeKind) -> Observation {
+        if kind == ChangeKind::Remove {
+            // Handle Deletion Approval
+            // If we accept, we remove from cache; if we reject, we write old_content back.
+            // Nothing to restore and not a file we knew: a directory. A cached
+            // empty file is still a file.
+            if old_content.is_empty() && !self.file_cache.contains_key(cache_key) {
+                return self.observe_dir_removal(cache_key);
+            }
+            let file_name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+            return Observation::Queue(Box::new(PendingChange {
+                path: cache_key.clone(),
+                insertions: 0,
+                deletions: old_content.lines().count(),
+                old_content,
+                new_content: String::new(), // Empty means deleted
+                old_encoding: self.encoding_of(cache_key),
+                new_encoding: TextEncoding::default(),
+                old_permissions: self.permissions_of(path, cache_key),
+                diff_text: format!("File Deleted: {}", file_name).into(),
+                hunks: Vec::new(),
+                kind,
+                binary: false,
+                eol_only: false,
+                line_endings: None,
+                disk_hash: None,
+                refreshed: false,
+                hunk_decisions: Vec::new(),
+                queued_at: Instant::now(),
+                full: None,
+                change_id: 0,
+                baseline_missing: false,
+                protected: false,
+                dir_files: Vec::new(),
+            }));
+        }
+        let read = observer::read_and_diff(&*self.fs, path, &old_content, &self.config);
+        self.observe_read(path, cache_key, old_content, kind, read)
+    }
+
+    // The rest of `observe` once the file has been read and diffed, here or
+    // by the observer pool
+    fn observe_read(&self, path: &std::path::Path, cache_key: &PathKey, old_content: String, kind: ChangeKind, read: observer::Read) -> Observation {
+        match read.content {
+            Some(FileContent::Text(new_content, new_encoding)) => {
+                if new_content == old_content
+                    || (self.config.normalize_line_endings && diff::normalize_eol(&new_content) == diff::normalize_eol(&old_content)) {
+                    return Observation::Unchanged;
                 }
-                
-                if output.is_empty() && !new_content.is_empty() {
-                     output = format!("+{}", new_content.replace('\n', "\n+"));
-                } else if output.is_empty() {
+
+                let hunks = read.hunks;
+                let (insertions, deletions) = diff::line_counts(&hunks);
+                let bytes = new_encoding.encode(&new_content);
+                let preview = kind == ChangeKind::Create && old_content.is_empty();
+                // The listing isn't cut from hunks, so there's nothing to re-cut
+                let full = read.full.filter(|_| !preview);
+                let eol_only = diff::eol_only_summary(&old_content, &new_content);
+                let line_endings = diff::eol_change(&old_content, &new_content);
+                let mut output = if preview {
+                    diff::new_file_diff(&new_content, bytes.len())
+                } else if let Some(summary) = &eol_only {
+                    format!("{}\n", summary)
+                } else {
+                    diff::render_text(&hunks)
+                };
+                if output.is_empty() {
                     output = "No Content Changes".to_string();
                 }
 
-                diff_output = Some(output.clone());
-                
-                // QUEUE FOR APPROVAL
-                self.approval_queue.push_back(PendingChange {
+                Observation::Queue(Box::new(PendingChange {
                     path: cache_key.clone(), // Store full path for revert
-                    old_content: old_content,
-                    new_content: new_content, // Don't update cache yet
-                    diff_text: output,
-                });
-                self.modal_active = true;
-                needs_approval = true;
-            }
-        } else if kind == ChangeKind::Remove {
-             // Handle Deletion Approval
-             // logic: new_content is empty
-             if !old_content.is_empty() {
-                let diff = format!("File Deleted: {}", file_name);
-                diff_output = Some(diff.clone());
-                
-                self.approval_queue.push_back(PendingChange {
+                    disk_hash: Some(content::hash_bytes(&bytes)),
+                    old_content,
+                    new_content, // Don't update cache yet
+                    old_encoding: self.encoding_of(cache_key),
+                    new_encoding,
+                    old_permissions: self.permissions_of(path, cache_key),
+                    diff_text: output.into(),
+                    hunks,
+                    insertions,
+                    deletions,
+                    kind,
+                    binary: false,
+                    eol_only: eol_only.is_some(),
+                    line_endings,
+                    refreshed: false,
+                    hunk_decisions: Vec::new(),
+                    queued_at: Instant::now(),
+                    full,
+                    change_id: 0,
+                    baseline_missing: false,
+                    protected: false,
+                    dir_files: Vec::new(),
+                }))
+            }
+            Some(skipped) => {
+                // Binary or oversized: log it, but never diff it
+                let notice = skipped.notice();
+                if self.config.binary_approval != BinaryApproval::Metadata {
+                    return Observation::Skip(notice);
+                }
+                // Keep any old text baseline so a reject can still restore it
+                Observation::Queue(Box::new(PendingChange {
                     path: cache_key.clone(),
-                    old_content: old_content,
-                    new_content: String::new(), // Empty means deleted logic?
-                    // Actually, if we reject deletion, we need to write old_content back.
-                    // If we accept, we remove from cache.
-                    diff_text: diff,
-                });
-                self.modal_active = true;
-                needs_approval = true;
-             }
+                    old_content,
+                    new_content: String::new(),
+                    old_encoding: self.encoding_of(cache_key),
+                    new_encoding: TextEncoding::default(),
+                    old_permissions: self.permissions_of(path, cache_key),
+                    diff_text: notice.into(),
+                    hunks: Vec::new(),
+                    insertions: 0,
+                    deletions: 0,
+                    kind,
+                    binary: true,
+                    eol_only: false,
+                    line_endings: None,
+                    disk_hash: content::fingerprint(&*self.fs, path, &self.config),
+                    refreshed: false,
+                    hunk_decisions: Vec::new(),
+                    queued_at: Instant::now(),
+                    full: None,
+                    change_id: 0,
+                    baseline_missing: false,
+                    protected: false,
+                    dir_files: Vec::new(),
+                }))
+            }
+            None => Observation::LogOnly,
         }
+    }
 
-        // Add to Sidebar (Visual Log)
-        if self.file_changes.len() >= 50 {
-            self.file_changes.pop_back();
+    // A directory went away. Everything cached beneath it went with it, so
+    // that's what the approval lists (and a reject writes back). A path with
+    // nothing cached under it is an unknown file or an empty directory.
+    fn observe_dir_removal(&self, cache_key: &PathKey) -> Observation {
+        let dir = cache_key.path();
+        let mut dir_files: Vec<PathKey> = self.file_cache.keys()
+            .filter(|k| k.starts_with(cache_key))
+            .cloned()
+            .collect();
+        if dir_files.is_empty() {
+            return Observation::LogOnly;
         }
-        self.file_changes.push_front(FileChange {
-            path: file_name,
-            kind,
-            timestamp: Local::now(),
-            diff: diff_output,
-        });
-        self.list_state.select(Some(0));
+        dir_files.sort();
+
+        let lines = |key: &PathKey| self.file_cache[key].lines().count();
+        let name = dir.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        let mut listing = format!("Directory Deleted: {}/ ({} cached files)", name, dir_files.len());
+        for key in &dir_files {
+            let rel = key.path().strip_prefix(dir).unwrap_or(dir);
+            listing.push_str(&format!("\n-{} ({} lines)", rel.display(), lines(key)));
+        }
+        Observation::Queue(Box::new(PendingChange {
+            path: cache_key.clone(),
+            old_content: String::new(),
+            new_content: String::new(),
+            old_encoding: TextEncoding::default(),
+            new_encoding: TextEncoding::default(),
+            old_permissions: None,
+            diff_text: listing.into(),
+            hunks: Vec::new(),
+            insertions: 0,
+            deletions: dir_files.iter().map(lines).sum(),
+            kind: ChangeKind::Remove,
+            binary: false,
+            eol_only: false,
+            line_endings: None,
+            disk_hash: None,
+            refreshed: false,
+            hunk_decisions: Vec::new(),
+            queued_at: Instant::now(),
+            full: None,
+            change_id: 0,
+            baseline_missing: false,
+            protected: false,
+            dir_files,
+        }))
     }
-}
 
-fn main() -> Result<()> {
-    // 1. Setup PTY
-    let pty_system = native_pty_system();
-    let mut pair = pty_system.openpty(PtySize {
-        rows: 24,
-        cols: 80,
-        pixel_width: 0,
-        pixel_height: 0,
-    })?;
-    let cwd = std::env::current_dir()?;
-    let mut cmd = CommandBuilder::new("npx");
-    cmd.args(&["opencode-ai"]);
-    cmd.cwd(&cwd);
-    let mut child = pair.slave.spawn_command(cmd)?;
-
-    // 2. Setup Channel for Events
-    let (tx, rx) = mpsc::channel::<AppEvent>();
+    // Accept the change at the head of the approval queue
+    fn accept_front(&mut self) {
+        self.try_accept_front();
+    }
 
-    // 3. PTY Reader Thread
-    let mut reader = pair.master.try_clone_reader()?;
-    let tx_pty = tx.clone();
-    thread::spawn(move || {
-        let mut buf = [0u8; 4096];
-        loop {
-            match reader.read(&mut buf) {
-                Ok(n) if n > 0 => {
-                    if tx_pty
-                        .send(AppEvent::PtyData(buf[..n].to_vec()))
-                        .is_err()
-                    {
-                        break;
-                    }
+    fn try_accept_front(&mut self) -> Decided {
+        let mut decided = Decided::Resolved;
+        if let Some(pending) = self.approval_queue.pop_front() {
+            if pending.is_stale(&*self.fs, &self.config) {
+                // Don't accept content we never showed
+                decided = self.requeue_stale(pending);
+            } else if pending.is_partial() {
+                // Accept only the kept hunks and write the result back
+                let merged = diff::apply_hunks(&pending.old_content, &pending.new_content, &pending.hunks, &pending.hunk_decisions);
+                if let Err(e) = self.fs.replace(pending.path.path(), &pending.new_encoding.encode(&merged)) {
+                    // Nothing changed on disk, so it's still waiting for a decision
+                    let error = format!("couldn't write {}: {}", pending.path, e);
+                    self.notify(Severity::Error, format!("Accept failed: {}", error));
+                    self.approval_queue.push_front(pending);
+                    self.after_decision();
+                    return Decided::WriteFailed(error);
                 }
-                _ => break,
+                self.note_own_write(&pending.path);
+                self.log_decision(pending.change_id, &pending.path, &pending.kind, pending.kept_line_counts(), Decision::Accepted, Origin::Agent);
+                self.set_status(pending.change_id, ChangeStatus::Accepted);
+                self.run_hook(&pending.path);
+                self.queue_tests(pending.change_id);
+                self.set_baseline(pending.path.clone(), merged, pending.new_encoding);
+                self.stats.accepted += 1;
+                self.stats.decided(pending.queued_at.elapsed());
+            } else {
+                // Accept: Update Cache
+                self.log_decision(pending.change_id, &pending.path, &pending.kind, (pending.insertions, pending.deletions), Decision::Accepted, Origin::Agent);
+                self.set_status(pending.change_id, ChangeStatus::Accepted);
+                self.run_hook(&pending.path);
+                self.queue_tests(pending.change_id);
+                self.set_baseline(pending.path.clone(), pending.new_content, pending.new_encoding);
+                self.stats.accepted += 1;
+                self.stats.decided(pending.queued_at.elapsed());
             }
         }
-    });
+        self.after_decision();
+        decided
+    }
 
-    // 4. File Watcher
-    let tx_watcher = tx.clone();
-    let mut watcher = RecommendedWatcher::new(
-        move |res: notify::Result<notify::Event>| {
-            if let Ok(event) = res {
-                use notify::event::{EventKind, ModifyKind};
-                match event.kind {
-                    EventKind::Create(_) => {
-                        for path in event.paths {
-                            let _ = tx_watcher.send(AppEvent::FileChange(path, ChangeKind::Create));
-                        }
-                    }
-                    EventKind::Modify(ModifyKind::Data(_)) => {
-                        for path in event.paths {
-                            let _ = tx_watcher.send(AppEvent::FileChange(path, ChangeKind::Modify));
-                        }
-                    }
-                    EventKind::Modify(ModifyKind::Name(_)) => {
-                        for path in event.paths {
-                            let _ = tx_watcher.send(AppEvent::FileChange(path, ChangeKind::Modify));
-                        }
-                    }
-                    EventKind::Remove(_) => {
-                        for path in event.paths {
-                            let _ = tx_watcher.send(AppEvent::FileChange(path, ChangeKind::Remove));
-                        }
-                    }
-                    _ => {}
+    // reject_mode, except that a quarantined protected file is always put
+    // back: dismissing it would let the write the rule forbids stand
+    fn reject_mode_for(&self, pending: &PendingChange) -> RejectMode {
+        if pending.protected { RejectMode::Revert } else { self.config.reject_mode }
+    }
+
+    // Reject the change at the head of the approval queue, reverting it on disk
+    // (or, with reject_mode = "dismiss", leaving it there)
+    fn reject_front(&mut self) {
+        self.try_reject_front();
+    }
+
+    fn try_reject_front(&mut self) -> Decided {
+        if self.approval_queue.front().is_some_and(|p| self.reject_mode_for(p) == RejectMode::Dismiss) {
+            self.dismiss_front();
+            return Decided::Resolved;
+        }
+        let mut decided = Decided::Resolved;
+        if let Some(pending) = self.approval_queue.pop_front() {
+            if pending.is_stale(&*self.fs, &self.config) {
+                // Don't clobber edits made after the diff was shown
+                decided = self.requeue_stale(pending);
+            } else {
+                // Reject: Revert to Old Content
+                self.stats.rejected += 1;
+                self.stats.decided(pending.queued_at.elapsed());
+                self.log_decision(pending.change_id, &pending.path, &pending.kind, (pending.insertions, pending.deletions), Decision::Rejected, Origin::Agent);
+                self.set_status(pending.change_id, ChangeStatus::Rejected);
+                self.revert(&pending);
+                if pending.baseline_missing {
+                    // Left as it is, which is what the scan would have cached
+                    self.notify(Severity::Warning, format!("Left {} as it is - no baseline to restore yet", pending.path));
+                    self.set_baseline(pending.path.clone(), pending.new_content, pending.new_encoding);
                 }
             }
-        },
-        Config::default(),
-    )?;
-    // Watch current directory recursively
-    watcher.watch(".".as_ref(), RecursiveMode::Recursive)?;
+        }
+        self.after_decision();
+        decided
+    }
 
-    // 5. Setup TUI
-    enable_raw_mode()?;
-    let mut stdout = std::io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    // Reject the change at the head of the queue but leave it on disk. Nothing
+    // is written, so staleness doesn't matter. What we showed becomes the
+    // baseline so it isn't asked about again.
+    fn dismiss_front(&mut self) {
+        if let Some(pending) = self.approval_queue.pop_front() {
+            self.stats.rejected += 1;
+            self.stats.decided(pending.queued_at.elapsed());
+            self.log_decision(pending.change_id, &pending.path, &pending.kind, (pending.insertions, pending.deletions), Decision::Rejected, Origin::Agent);
+            self.set_status(pending.change_id, ChangeStatus::Dismissed);
+            self.set_baseline(pending.path.clone(), pending.new_content, pending.new_encoding);
+        }
+        self.after_decision();
+    }
 
-    // 6. Setup App State and Logger
-    let app_state = Arc::new(Mutex::new(AppState::new()));
+    // 1-9 in the modal: bring that queued change to the front so y/n act on
+    // it. The others keep their order.
+    fn review_pending(&mut self, index: usize) {
+        if index == 0 {
+            return;
+        }
+        if let Some(pending) = self.approval_queue.remove(index) {
+            self.approval_queue.push_front(pending);
+            self.modal_cursor.reset();
+            self.revert_preview = None;
+        }
+    }
 
-    // Write handle for forwarding input to PTY
-    let mut writer = pair.master.take_writer()?;
+    // Whether rejecting `pending` now would write to disk before anyone has
+    // seen what that means: once per session, unless revert_acknowledged.
+    // Every reject path asks this first.
+    fn needs_revert_consent(&self, pending: &PendingChange) -> bool {
+        self.reject_mode_for(pending) == RejectMode::Revert
+            && pending.revert_action() != RevertAction::Leave
+            && !self.config.revert_acknowledged
+            && !self.revert_confirmed
+    }
 
-    // 7. Main Loop
-    let loop_result = run_app(
-        &mut terminal,
-        app_state.clone(),
-        rx,
-        &mut writer,
-        &mut *pair.master,
-    );
+    // `n`: a reject that would write to disk is explained first
+    fn request_reject(&mut self) {
+        if self.approval_queue.front().is_some_and(|p| self.needs_revert_consent(p)) {
+            self.revert_prompt = true;
+        } else {
+            self.reject_front();
+        }
+    }
 
-    // 8. Cleanup
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
-    let _ = child.kill();
+    // [r] in the quit prompt; true once everything is rejected and it's
+    // time to quit, false while the revert prompt asks first
+    fn request_reject_all(&mut self) -> bool {
+        if self.approval_queue.iter().any(|p| self.needs_revert_consent(p)) {
+            self.quit_prompt = false;
+            self.quit_after_revert = true;
+            self.revert_prompt = true;
+            return false;
+        }
+        self.resolve_all(false);
+        true
+    }
 
-    loop_result
-}
+    // [y] / [a] in the revert prompt; `always` writes the acknowledgment to
+    // the config file. True if it came from the quit prompt and it's time to quit.
+    fn confirm_revert(&mut self, always: bool) -> bool {
+        let quit = std::mem::take(&mut self.quit_after_revert);
+        self.revert_prompt = false;
+        self.revert_confirmed = true;
+        if always {
+            self.config.revert_acknowledged = true;
+            match Config::acknowledge_revert() {
+                Ok(path) => self.notify(Severity::Info, format!("Won't ask before reverting again (revert_acknowledged in {})", path.display())),
+                Err(e) => self.notify(Severity::Error, format!("Couldn't save revert_acknowledged: {:#}", e)),
+            }
+        }
+        if quit {
+            self.resolve_all(false);
+        } else {
+            self.reject_front();
+        }
+        quit
+    }
 
-fn run_app(
-    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
-    app_state: Arc<Mutex<AppState>>,
-    rx: mpsc::Receiver<AppEvent>,
-    writer: &mut dyn Write,
-    master: &mut dyn portable_pty::MasterPty,
-) -> Result<()> {
-    loop {
-        // A. Process all available events (non-blocking)
-        while let Ok(event) = rx.try_recv() {
-            match event {
-                AppEvent::PtyData(data) => {
-                     // Only process PTY data if modal is NOT active? 
-                     // No, background PTY should still run/update, just input blocked.
-                    let mut state = app_state.lock().unwrap();
-                    state.parser.process(&data);
+    // Put the file back the way it was before `pending`, swallowing our own write
+    fn revert(&mut self, pending: &PendingChange) {
+        match pending.revert_action() {
+            RevertAction::Delete => {
+                let path = pending.path.path();
+                let mut removed = self.fs.remove(path);
+                // Windows won't delete a read-only file
+                if removed.is_err() && fs::make_writable(&*self.fs, path).is_ok() {
+                    removed = self.fs.remove(path);
                 }
-                AppEvent::FileChange(path, kind) => {
-                    let mut state = app_state.lock().unwrap();
-                    state.add_change(path.clone(), kind.clone());
+                if let Err(e) = removed {
+                    self.notify(Severity::Error, format!("Revert failed: couldn't delete {}: {}", pending.path, e));
                 }
-                AppEvent::Tick => {
-                    // Just trigger re-render
+                self.note_own_write(&pending.path);
+            }
+            RevertAction::Leave => {}
+            RevertAction::Restore => {
+                let path = pending.path.path();
+                let bytes = pending.old_encoding.encode(&pending.old_content);
+                let mut written = self.fs.replace(path, &bytes);
+                // Windows won't rename over a read-only file
+                if written.is_err() && fs::make_writable(&*self.fs, path).is_ok() {
+                    written = self.fs.replace(path, &bytes);
                 }
-                AppEvent::Input(_key) => {
-                    // Handle internal app input if necessary
+                if let Err(e) = written {
+                    self.notify(Severity::Error, format!("Revert failed: couldn't write {}: {}", pending.path, e));
                 }
+                // A recreated (or agent-chmodded) file gets the baseline's mode back
+                if let Some(permissions) = &pending.old_permissions {
+                    let _ = self.fs.set_permissions(path, permissions.clone());
+                }
+                self.note_own_write(&pending.path);
             }
-        }
-
-        // B. Render
-        terminal.draw(|frame| {
-             // Lock state for rendering
-            let mut state = app_state.lock().unwrap();
-            
-            // Resolve Theme
-            let theme = Theme::new(state.current_theme);
-
-            let area = frame.area();
-            
-            // 1. Vertical Split
-            let v_chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Min(1), Constraint::Length(1)])
-                .split(area);
-                
-            let main_area = v_chunks[0];
-            let status_area = v_chunks[1];
-
-            // 2. Horizontal Split
-            let (term_area, side_area) = if state.show_sidebar {
-                let h_chunks = Layout::default()
-                    .direction(Direction::Horizontal)
-                    .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
-                    .split(main_area);
-                (h_chunks[0], Some(h_chunks[1]))
-            } else {
-                (main_area, None)
-            };
-
-            // --- Render Terminal OR Diff View ---
-            if state.show_diff_view {
-                 let selected_index = state.list_state.selected();
-                 let selected_change = selected_index.and_then(|i| state.file_changes.get(i));
-                 ui::components::diff_view::render(frame, term_area, selected_change, &theme);
-            } else {
-                // Render VT100
-                let screen = state.parser.screen();
-                let (rows, cols) = screen.size();
-                let buffer = frame.buffer_mut();
-                for row in 0..rows.min(term_area.height) {
-                    for col in 0..cols.min(term_area.width) {
-                        if let Some(cell) = screen.cell(row, col) {
-                             let fg = convert_color(cell.fgcolor());
-                             let bg = convert_color(cell.bgcolor());
-                             let mut style = Style::default().fg(fg).bg(bg);
-                             if cell.bold() { style = style.add_modifier(Modifier::BOLD); }
-                             if cell.italic() { style = style.add_modifier(Modifier::ITALIC); }
-                             if cell.underline() { style = style.add_modifier(Modifier::UNDERLINED); }
-                             if cell.inverse() { style = style.add_modifier(Modifier::REVERSED); }
-                             let contents = cell.contents();
-                             if !contents.is_empty() { buffer.set_string(term_area.x + col, term_area.y + row, contents, style); }
-                             else { buffer.set_string(term_area.x + col, term_area.y + row, " ", style); }
-                        }
+            RevertAction::RestoreDir => {
+                let dir = pending.path.path();
+                let mut failed = Vec::new();
+                for key in &pending.dir_files {
+                    let path = key.path();
+                    let Some(text) = self.file_cache.get(key) else { continue };
+                    if let Err(e) = self.fs.replace(path, &self.encoding_of(key).encode(text)) {
+                        failed.push(format!("{}: {}", key, e));
+                        continue;
+                    }
+                    if let Some(permissions) = self.permissions.get(key) {
+                        let _ = self.fs.set_permissions(path, permissions.clone());
+                    }
+                    self.note_own_write(key);
+                    // The directories recreated on the way are ours too
+                    for parent in path.ancestors().skip(1).take_while(|p| p.starts_with(dir)) {
+                        self.note_own_write(&PathKey::exact(parent));
                     }
                 }
-                if !screen.hide_cursor() && !state.modal_active {
-                     let (crow, ccol) = screen.cursor_position();
-                     if ccol < term_area.width && crow < term_area.height {
-                          frame.set_cursor_position(Position { x: term_area.x + ccol, y: term_area.y + crow });
-                     }
+                if let Some(first) = failed.first() {
+                    let more = if failed.len() > 1 { format!(" (and {} more)", failed.len() - 1) } else { String::new() };
+                    self.notify(Severity::Error, format!("Revert failed: couldn't restore {}{}", first, more));
                 }
             }
-            
-            // --- Render Sidebar ---
-            if let Some(area) = side_area {
-                // Use the new component
-                // We need to convert VecDeque to slice. 
-                // `make_contiguous` makes it a single slice, but mutates.
-                // Or just iterate. 
-                // Our component expects `&[FileChange]`.
-                // VecDeque doesn't easily coerce to &[FileChange] unless we use make_contiguous.
-                // Let's change the component signature to accept `&VecDeque` or `impl Iterator` or just convert here.
-                // Converting here is creating a Vec, which is allocations in hot loop.
-                // Converting the component to accept `VecDeque` is better.
-                // *Self Correction*: I don't want to edit component files again right now.
-                // I'll make the component accept `&VecDeque` in the next step if compilation fails, 
-                // or just modify `state.file_changes` to be a `Vec`? No, we need push_front efficiently.
-                // I will use `make_contiguous` here since we have mutable access to state? No we have locked it. 
-                // But `state` is `MutexGuard`. We can mutate it.
-                state.file_changes.make_contiguous();
-                 let inner = &mut *state;
-                 let (slice, _) = inner.file_changes.as_slices();
-                 ui::components::sidebar::render(frame, area, slice, &mut inner.list_state, &theme);
-            }
-
-            // --- Render Status Bar ---
-            // Just pass the slice
-            let (slice, _) = state.file_changes.as_slices();
-             // We can re-use the make_contiguous result from above or call it again (it's cheap if already contiguous)
-             // But careful, verify if scope above dropped `inner`. Yes it did.
-             ui::components::status_bar::render(frame, status_area, slice, &theme);
-            
-        })?;
-
-        // C. Poll Input
-        if event::poll(Duration::from_millis(50))? {
-             let mut state = app_state.lock().unwrap();
-            match event::read()? {
-                 Event::Resize(cols, rows) => {
-                     // We need to handle resize carefully with split panes.
-                     // The PTY size should match the *Terminal Pane* size, not the full window.
-                     // Simple approximation: calc what 70% is.
-                     
-                     let term_cols = (cols as f32 * 0.7) as u16;
-                     let term_rows = rows; // Full height
-                     
-                     master.resize(PtySize {
-                        rows: term_rows,
-                        cols: term_cols,
-                        pixel_width: 0,
-                        pixel_height: 0,
-                    })?;
-                    state.parser = vt100::Parser::new(term_rows, term_cols, 0);
-                }
-                Event::Key(key) => {
-                    // *** MODAL INTERCEPTION ***
-                    if state.modal_active {
-                        match key.code {
-                            KeyCode::Char('y') => {
-                                if let Some(pending) = state.approval_queue.pop_front() {
-                                    // Accept: Update Cache
-                                    if pending.new_content.is_empty() {
-                                        state.file_cache.remove(&pending.path);
-                                    } else {
-                                        state.file_cache.insert(pending.path, pending.new_content);
-                                    }
-                                }
-                                state.modal_active = !state.approval_queue.is_empty();
-                            }
-                            KeyCode::Char('n') => {
-                                if let Some(pending) = state.approval_queue.pop_front() {
-                                    // Reject: Revert to Old Content
-                                    state.ignore_next_write.insert(pending.path.clone());
-                                    
-                                    if pending.old_content.is_empty() {
-                                        // It was a new file, so delete it
-                                        let _ = std::fs::remove_file(&pending.path);
-                                    } else {
-                                        // Revert content
-                                        let _ = std::fs::write(&pending.path, &pending.old_content);
-                                    }
-                                }
-                                state.modal_active = !state.approval_queue.is_empty();
-                            }
-                            _ => {} // Consume other keys
-                        }
-                        return Ok(()); // SKIP NORMAL PROCESSING
-                    }
+        }
+    }
+
+    // The terminal was resized. Keys shouldn't go to a sidebar that isn't there.
+    fn set_fit(&mut self, fit: Fit) {
+        self.fit = fit;
+        if fit != Fit::Full && self.focus == Focus::Sidebar {
+            self.focus = Focus::Terminal;
+        }
+    }
 
-                    // *** NORMAL PROCESSING ***
-                    match key.code {
-                        KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
-                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => writer.write_all(&[3])?, // ETX
-                        KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => writer.write_all(&[4])?, // EOT
+    // A toast above the status bar, kept in the notification log
+    fn notify(&mut self, severity: Severity, text: String) {
+        self.toasts.push(severity, text);
+    }
 
-                        // UI Control
-                        KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                             state.show_diff_view = !state.show_diff_view;
-                        }
-                        KeyCode::Char('h') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            state.show_sidebar = !state.show_sidebar;
-                        }
-                        KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            state.file_changes.clear();
-                            state.list_state.select(None);
-                        }
-                        KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            // Cycle Theme
-                            state.current_theme = state.current_theme.cycle();
-                        }
+    // Remember what we just left at `path` so the watcher's echo is swallowed
+    fn note_own_write(&mut self, path: &PathKey) {
+        self.touch_rescan(path);
+        let fingerprint = content::fingerprint(&*self.fs, path.path(), &self.config);
+        self.own_writes.insert(path.clone(), OwnWrite { fingerprint, hook: false, until: Instant::now() + OWN_WRITE_WINDOW });
+    }
 
-                        KeyCode::Up if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            let i = state.list_state.selected().map_or(0, |i| i.saturating_sub(1));
-                            state.list_state.select(Some(i));
-                        }
-                        KeyCode::Down if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                             let i = state.list_state.selected().map_or(0, |i| (i + 1).min(state.file_changes.len().saturating_sub(1)));
-                             state.list_state.select(Some(i));
-                        }
-                        // Pass through to PTY
-                        KeyCode::Char(c) => writer.write_all(c.to_string().as_bytes())?,
-                        KeyCode::Enter => writer.write_all(b"\r")?,
-                        KeyCode::Backspace => writer.write_all(&[127])?,
-                        KeyCode::Tab => writer.write_all(&[9])?,
-                        KeyCode::Esc => writer.write_all(&[27])?,
-                        KeyCode::Up => writer.write_all(b"\x1b[A")?,
-                        KeyCode::Down => writer.write_all(b"\x1b[B")?,
-                        KeyCode::Right => writer.write_all(b"\x1b[C")?,
-                        KeyCode::Left => writer.write_all(b"\x1b[D")?,
-                        _ => {}
-                    }
-                    writer.flush()?;
-                }
-                _ => {}
-            }
+    // True if the event for `path` is just our own write coming back
+    fn is_own_write(&mut self, path: &std::path::Path, cache_key: &PathKey) -> bool {
+        let Some(own) = self.own_writes.get(cache_key) else { return false };
+        let expired = !own.hook && Instant::now() >= own.until;
+        if !expired && (own.hook || content::fingerprint(&*self.fs, path, &self.config) == own.fingerprint) {
+            return true;
         }
+        // Too old, or written again since: from here on it's someone else's change
+        self.own_writes.remove(cache_key);
+        false
     }
-}
 
-// Helper for centering modal
-fn centered_rect(percent_x: u16, percent_y: u16, r: ratatui::layout::Rect) -> ratatui::layout::Rect {
-    let popup_layout = Layout::default()
+    // Resolve the whole queue the same way. Stale entries come back refreshed
+    // and are resolved on the next pass; ones that can't be written stay
+    // queued. True if nothing is left waiting.
+    fn resolve_all(&mut self, accept: bool) -> bool {
+        let mut failed = Vec::new();
+        while !self.approval_queue.is_empty() {
+            let decided = if accept { self.try_accept_front() } else { self.try_reject_front() };
+            if let Decided::WriteFailed(_) = decided {
+                failed.extend(self.approval_queue.pop_front());
+            }
+        }
+        let resolved = failed.is_empty();
+        self.approval_queue.extend(failed);
+        self.after_decision();
+        resolved
+    }
+
+    // Start the on_accept hook for a file that still exists. Its own writes
+    // are swallowed until it exits; then the file is looked at again (see
+    // hook_finished).
+    fn run_hook(&mut self, path: &PathKey) {
+        let Some(hooks) = &self.hooks else { return };
+        if !self.fs.exists(path.path()) {
+            return;
+        }
+        self.own_writes.insert(path.clone(), OwnWrite { fingerprint: None, hook: true, until: Instant::now() + OWN_WRITE_WINDOW });
+        hooks.run(path);
+    }
+
+    fn hook_finished(&mut self, result: HookResult) {
+        self.own_writes.remove(&result.path);
+        // The agent may have written the file while the hook ran, and those
+        // writes were swallowed with the hook's. Compare it with the accepted
+        // content again, so whatever changed, whoever did it, is asked about.
+        let path = result.path.path().to_path_buf();
+        let kind = if self.fs.exists(&path) { ChangeKind::Modify } else { ChangeKind::Remove };
+        self.debounce_map.remove(&(result.path.clone(), kind.clone()));
+        self.record_change(path, kind);
+
+        let name = self.display_path(result.path.path()).unwrap_or_else(|| result.path.to_string_lossy().into_owned());
+        let message = match &result.outcome {
+            Ok(0) => format!("Hook ok: {}", name),
+            Ok(code) if result.stderr.is_empty() => format!("Hook failed (exit {}): {}", code, name),
+            Ok(code) => format!("Hook failed (exit {}): {} - {}", code, name, result.stderr),
+            Err(e) => format!("Hook failed: {} - {}", name, e),
+        };
+        self.status_message = Some((message, Instant::now()));
+    }
+
+    fn after_decision(&mut self) {
+        self.modal_active = !self.approval_queue.is_empty();
+        self.banner_armed &= self.modal_active;
+        self.modal_cursor.reset();
+        self.revert_preview = None;
+        // The change it asked about was decided some other way (timeout, ctl)
+        self.revert_prompt = false;
+        self.quit_after_revert = false;
+        // Decided changes need no check; a run only they were waiting on stops
+        let pending: Vec<u64> = self.approval_queue.iter().map(|p| p.change_id).collect();
+        self.checks.retain(|id, _| pending.contains(id));
+        if let Some(checker) = self.checker.as_mut() {
+            checker.keep_only(&pending);
+        }
+    }
+
+    // A change was queued or re-diffed: have check_command look at it, if its
+    // path is one check_paths covers
+    fn request_check(&mut self, change_id: u64, path: &PathKey) {
+        let display = self.display_path(path.path()).unwrap_or_else(|| path.to_string_lossy().into_owned());
+        let Some(checker) = self.checker.as_mut() else { return };
+        let globs = &self.config.check_paths;
+        if !globs.is_empty() && !globs.iter().any(|g| glob::glob_match(g, &display)) {
+            return;
+        }
+        self.checks.insert(change_id, CheckStatus::Pending);
+        checker.request(change_id);
+    }
+
+    // An accepted change: test_command runs once the accepts settle
+    fn queue_tests(&mut self, change_id: u64) {
+        if let Some(tests) = self.tests.as_mut() {
+            tests.accepted(change_id);
+        }
+    }
+
+    fn test_event(&mut self, event: TestEvent) {
+        let Some(tests) = self.tests.as_mut() else { return };
+        match event {
+            TestEvent::Line(run, line) => tests.line(run, line),
+            TestEvent::Done { run, code, cancelled } => {
+                let Some((result, after)) = tests.finished(run, code, cancelled) else { return };
+                // Kept with the changes it followed, for the session export
+                for change in self.file_changes.iter_mut().filter(|c| after.contains(&c.id)) {
+                    change.tests = Some(result.clone());
+                }
+            }
+        }
+    }
+
+    fn cancel_tests(&mut self) {
+        let cancelled = self.tests.as_mut().is_some_and(|t| t.cancel());
+        let message = if cancelled { "Cancelling test_command" } else { "No test_command running" };
+        self.status_message = Some((message.to_string(), Instant::now()));
+    }
+
+    fn check_finished(&mut self, result: CheckResult) {
+        let Some(checker) = self.checker.as_mut() else { return };
+        let covered = checker.finished(result.run);
+        let status = match result.outcome {
+            CheckOutcome::Passed => CheckStatus::Passed,
+            CheckOutcome::Failed(lines) => CheckStatus::Failed(lines),
+            CheckOutcome::Error(e) => CheckStatus::Error(e),
+            CheckOutcome::Cancelled => return,
+        };
+        for id in covered {
+            if let Some(check) = self.checks.get_mut(&id) {
+                *check = status.clone();
+            }
+        }
+    }
+
+    // Time left before the front approval decides itself, if a timeout is set
+    fn approval_time_left(&self) -> Option<Duration> {
+        let timeout = Duration::from_secs(self.config.approval_timeout_secs);
+        let front = self.approval_queue.front()?;
+        // Protected paths and changes without a baseline are never decided
+        // unattended: approving would skip the quarantine, rejecting has
+        // nothing to restore
+        if timeout.is_zero() || front.protected || front.baseline_missing {
+            return None;
+        }
+        // Nobody is there to see the first-revert explanation
+        if self.config.approval_timeout_action == TimeoutAction::Reject && self.needs_revert_consent(front) {
+            return None;
+        }
+        match self.front_since {
+            Some((id, since)) if id == front.change_id => Some(timeout.saturating_sub(since.elapsed())),
+            // Not seen by a tick yet; the clock starts then
+            _ => Some(timeout),
+        }
+    }
+
+    // Something on screen moves by itself and wants every tick drawn: the
+    // spinner, a bell flash, toasts, an escalating Ctrl+C, a pending rename
+    fn animating(&self) -> bool {
+        let now = Instant::now();
+        self.last_pty_data.is_some_and(|t| now.duration_since(t) < Duration::from_secs(1))
+            || self.tests.as_ref().is_some_and(|t| t.elapsed().is_some())
+            || self.bell_flash_until.is_some_and(|t| now < t)
+            || self.toasts.visible().next().is_some()
+            || !self.interrupts.label(now).is_empty()
+            || !self.held_removes.is_empty()
+    }
+
+    // Called every tick: start the clock on a new front approval, or apply
+    // approval_timeout_action once it has waited long enough
+    fn expire_approval(&mut self) {
+        let Some(left) = self.approval_time_left() else {
+            self.front_since = None;
+            return;
+        };
+        let id = self.approval_queue[0].change_id;
+        if self.front_since.is_none_or(|(front, _)| front != id) {
+            self.front_since = Some((id, Instant::now()));
+            return;
+        }
+        if !left.is_zero() {
+            return;
+        }
+        let action = self.config.approval_timeout_action;
+        match action {
+            TimeoutAction::Approve => self.accept_front(),
+            TimeoutAction::Reject => self.reject_front(),
+        }
+        // A stale change goes back to the front with a fresh diff and a fresh clock
+        if self.approval_queue.front().is_some_and(|p| p.change_id == id) {
+            self.front_since = Some((id, Instant::now()));
+            return;
+        }
+        let note = format!("auto-decided: {} after {}s without an answer", action.verb(), self.config.approval_timeout_secs);
+        if let Some(change) = self.file_changes.iter_mut().find(|c| c.id == id) {
+            self.status_message = Some((format!("{}: {}", change.path, note), Instant::now()));
+            change.note = Some(note);
+        }
+    }
+
+    // Disk moved on since `pending` was queued; rebuild it against the same baseline
+    fn requeue_stale(&mut self, pending: PendingChange) -> Decided {
+        let key = pending.path.clone();
+        let path = key.path().to_path_buf();
+        let kind = self.kind_now(&pending);
+        match self.observe(&path, &key, pending.old_content, kind) {
+            Observation::Queue(mut fresh) => {
+                fresh.refreshed = true;
+                fresh.change_id = pending.change_id;
+                fresh.protected = pending.protected;
+                fresh.queued_at = pending.queued_at;
+                fresh.baseline_missing = pending.baseline_missing;
+                self.request_check(fresh.change_id, &fresh.path);
+                self.approval_queue.push_front(*fresh);
+                return Decided::Refreshed;
+            }
+            Observation::Skip(_) => {
+                self.drop_baseline(&key);
+                self.set_status(pending.change_id, ChangeStatus::Info);
+            }
+            // Back to the baseline (or gone with nothing to restore): nothing to decide
+            Observation::Unchanged | Observation::LogOnly => self.set_status(pending.change_id, ChangeStatus::Info),
+        }
+        Decided::Resolved
+    }
+
+    // What `pending` amounts to with the file as it is on disk now
+    fn kind_now(&self, pending: &PendingChange) -> ChangeKind {
+        if !self.fs.exists(pending.path.path()) {
+            ChangeKind::Remove
+        } else if pending.kind == ChangeKind::Remove {
+            ChangeKind::Modify
+        } else {
+            pending.kind.clone()
+        }
+    }
+
+    // Another save of a file that's still waiting for approval: re-diff it
+    // against the same baseline and update the queued entry (and its sidebar
+    // row) in place, rather than asking about every intermediate state
+    fn merge_pending(&mut self, index: usize) {
+        let Some(pending) = self.approval_queue.remove(index) else { return };
+        let key = pending.path.clone();
+        let path = key.path().to_path_buf();
+        let kind = self.kind_now(&pending);
+        let (old_insertions, old_deletions) = (pending.insertions, pending.deletions);
+        match self.observe(&path, &key, pending.old_content, kind) {
+            Observation::Queue(mut merged) => {
+                merged.refreshed = true;
+                merged.change_id = pending.change_id;
+                merged.protected = pending.protected;
+                merged.baseline_missing = pending.baseline_missing;
+                let note = merged.note();
+                let (diff, hunks, full, spill) = self.park_diff(Some(merged.diff_text.clone()), merged.hunks.clone(), merged.full.clone());
+                if let Some(change) = self.file_changes.iter_mut().find(|c| c.id == merged.change_id) {
+                    change.kind = merged.kind.clone();
+                    change.timestamp = Local::now();
+                    change.diff = diff;
+                    change.hunks = hunks;
+                    change.full = full;
+                    change.context = diff::DEFAULT_CONTEXT;
+                    change.spill = spill;
+                    change.note = note;
+                    change.line_endings = merged.line_endings;
+                    change.insertions = merged.insertions;
+                    change.deletions = merged.deletions;
+                    if change.origin == Origin::Agent {
+                        // Stats reset since the old count went in can't go below zero
+                        self.stats.insertions = (self.stats.insertions + merged.insertions).saturating_sub(old_insertions);
+                        self.stats.deletions = (self.stats.deletions + merged.deletions).saturating_sub(old_deletions);
+                    }
+                }
+                self.request_check(merged.change_id, &merged.path);
+                self.approval_queue.insert(index, *merged);
+                // The diff view may have it re-cut from the old diff
+                self.recut = None;
+                // The modal is showing it: its hunks and preview changed
+                if index == 0 {
+                    self.modal_cursor.reset();
+                    self.revert_preview = None;
+                }
+            }
+            Observation::Skip(_) => {
+                self.drop_baseline(&key);
+                self.set_status(pending.change_id, ChangeStatus::Info);
+                self.after_decision();
+            }
+            // Saved back to the baseline: nothing left to decide
+            Observation::Unchanged | Observation::LogOnly => {
+                self.set_status(pending.change_id, ChangeStatus::Info);
+                self.after_decision();
+            }
+        }
+    }
+
+    // Park oversized diffs on disk; they're loaded back when viewed. The
+    // whole-file diff kept for re-cutting goes when the diff does, and on its
+    // own when it's past the limit too (a one-line change to a huge file).
+    fn park_diff(&mut self, diff: Option<DiffText>, hunks: Vec<diff::Hunk>, full: Option<diff::Hunk>) -> (Option<DiffText>, Vec<diff::Hunk>, Option<diff::Hunk>, Option<PathBuf>) {
+        if diff.as_ref().is_some_and(|d| d.as_str().len() > self.config.spill_diff_bytes)
+            && let Ok(path) = self.spill_store.write(diff.as_ref().map_or("", DiffText::as_str)) {
+            return (None, Vec::new(), None, Some(path));
+        }
+        let full = full.filter(|full| diff::text_bytes(full) <= self.config.spill_diff_bytes);
+        (diff, hunks, full, None)
+    }
+
+    // Alt+R, for when the cache and the disk have parted ways (a branch
+    // switch, a big rewrite outside the agent): let the caller start the scan
+    // again. The current baselines stay in use until it's done and are then
+    // replaced (see finish_rescan). The debounce window is dropped and the
+    // ignore files read again. False while a scan is already running.
+    fn begin_rescan(&mut self) -> bool {
+        if self.indexing.is_some() {
+            self.notify(Severity::Info, "Still scanning - wait for it to finish".to_string());
+            return false;
+        }
+        self.debounce_map.clear();
+        self.roots.reload_ignores();
+        self.indexing = Some(0);
+        self.index_truncated = false;
+        self.rescan = Some(Rescan::default());
+        true
+    }
+
+    // Alt+G: the files of accepted changes not committed yet, and the message
+    // for them, to hand to `git::commit` off the main thread. Only files under
+    // the primary root, and none that's changed again since: what's on disk
+    // there hasn't been reviewed. The content committed is the accepted
+    // baseline, not whatever is on disk by the time git runs.
+    fn begin_commit(&mut self) -> Option<(PathBuf, Vec<git::CommitFile>, String)> {
+        if self.committing.is_some() {
+            self.status_message = Some(("Still committing the last batch".to_string(), Instant::now()));
+            return None;
+        }
+        let root = self.roots.primary().path.clone();
+        let mut unreviewed: std::collections::HashSet<&PathKey> = self.approval_queue.iter().map(|p| &p.path).collect();
+        unreviewed.extend(self.observing.keys());
+        unreviewed.extend(self.held_removes.iter().map(|r| &r.cache_key));
+        let mut ids = Vec::new();
+        let mut keys: Vec<&PathKey> = Vec::new();
+        let mut files = Vec::new();
+        let (mut insertions, mut deletions) = (0, 0);
+        // Oldest first, so the list reads in the order they were approved
+        for change in self.file_changes.iter().rev() {
+            // Renames never ask; the agent's are committed with the rest
+            let rename = change.kind == ChangeKind::Rename && change.origin == Origin::Agent;
+            if !(rename || matches!(change.status, ChangeStatus::Accepted | ChangeStatus::AutoApproved))
+                || change.dir
+                || self.committed.contains(&change.id)
+                || unreviewed.contains(&change.abs_path)
+                || change.from.as_ref().is_some_and(|from| unreviewed.contains(from)) {
+                continue;
+            }
+            let ends: Vec<&PathKey> = change.from.iter().chain([&change.abs_path]).collect();
+            if ends.iter().any(|key| !key.path().starts_with(&root)) {
+                continue;
+            }
+            ids.push(change.id);
+            (insertions, deletions) = (insertions + change.insertions, deletions + change.deletions);
+            for key in ends {
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+            let relative = change.abs_path.path().strip_prefix(&root).unwrap_or(change.abs_path.path());
+            let name = relative.to_string_lossy().into_owned();
+            if !files.contains(&name) {
+                files.push(name);
+            }
+        }
+        if keys.is_empty() {
+            self.status_message = Some(("Nothing approved to commit".to_string(), Instant::now()));
+            return None;
+        }
+        let commit_files = keys.iter().map(|key| self.commit_file(&root, key)).collect();
+        let message = git::commit_message(&self.config.commit_message, &files, insertions, deletions);
+        self.status_message = Some((format!("Committing {} files…", files.len()), Instant::now()));
+        self.committing = Some(ids);
+        Some((root, commit_files, message))
+    }
+
+    // What a commit records for `key`: the cached baseline, or for a binary
+    // file (never cached) what's on disk; a missing file is recorded deleted
+    fn commit_file(&self, root: &std::path::Path, key: &PathKey) -> git::CommitFile {
+        let path = key.path();
+        let content = match self.file_cache.get(key) {
+            Some(text) => Some(self.encoding_of(key).encode(text).into_owned()),
+            None => self.fs.read(path).ok(),
+        };
+        git::CommitFile {
+            path: path.strip_prefix(root).unwrap_or(path).to_path_buf(),
+            content,
+            executable: self.permissions_of(path, key).as_ref().and_then(fs::executable),
+        }
+    }
+
+    fn commit_finished(&mut self, result: Result<git::Commit>) {
+        let ids = self.committing.take().unwrap_or_default();
+        let message = match result {
+            Ok(commit) => {
+                let mut message = match commit.hash {
+                    Some(hash) => format!("Committed {} files as {}", commit.files, hash),
+                    None => "Nothing new to commit".to_string(),
+                };
+                if let Some(first) = commit.skipped.first() {
+                    let more = if commit.skipped.len() > 1 { format!(" and {} more", commit.skipped.len() - 1) } else { String::new() };
+                    message = format!("{} - left out {}{} (ignored by git, a symlink, or gone)", message, first.display(), more);
+                }
+                // Either way they're done with; a skipped file would only be skipped again
+                self.committed.extend(ids);
+                self.git_status.touched();
+                message
+            }
+            Err(e) => {
+                let message = format!("Commit failed: {:#}", e);
+                self.notify(Severity::Error, message.clone());
+                message
+            }
+        };
+        self.status_message = Some((message, Instant::now()));
+    }
+
+    // The watcher lost events, so any cached file may have changed unseen.
+    // Re-read every cached path and adopt what's on disk; files with a pending
+    // approval keep their baseline (the stale check covers those). This runs
+    // on the event thread, so no change is handled until it's done.
+    fn rescan_cache(&mut self) {
+        let pending: std::collections::HashSet<PathKey> = self.approval_queue.iter().map(|p| p.path.clone()).collect();
+        let keys: Vec<PathKey> = self.file_cache.keys().filter(|k| !pending.contains(k)).cloned().collect();
+        let mut changed = 0;
+        for key in &keys {
+            match content::read(&*self.fs, key.path(), &self.config) {
+                Some(FileContent::Text(text, encoding)) => {
+                    if self.file_cache.get(key) != Some(&text) || encoding != self.encoding_of(key) {
+                        changed += 1;
+                        self.set_baseline(key.clone(), text, encoding);
+                    }
+                }
+                // Gone, or no longer text we'd cache
+                _ => {
+                    changed += 1;
+                    self.drop_baseline(key);
+                }
+            }
+        }
+        let message = format!("Watcher dropped events - re-read {} files, {} changed unseen", keys.len(), changed);
+        self.notify(Severity::Warning, message);
+    }
+
+    fn apply_index_event(&mut self, event: IndexEvent) {
+        match event {
+            IndexEvent::Batch(entries) if self.rescan.is_some() => {
+                let count = entries.len();
+                let rescan = self.rescan.as_mut().expect("checked by the guard");
+                for file in entries {
+                    if !file.encoding.is_default() {
+                        rescan.encodings.insert(file.key.clone(), file.encoding);
+                    }
+                    if let Some(permissions) = file.permissions {
+                        rescan.permissions.insert(file.key.clone(), permissions);
+                    }
+                    rescan.file_cache.insert(file.key, file.text);
+                }
+                if let Some(files) = self.indexing.as_mut() {
+                    *files += count;
+                }
+            }
+            IndexEvent::Batch(entries) => {
+                let count = entries.len();
+                for file in entries {
+                    // Never clobber a baseline set by an approval during the scan
+                    if !self.file_cache.contains_key(&file.key) {
+                        self.store_baseline(file.key, file.text, file.encoding, file.permissions);
+                    }
+                }
+                if let Some(files) = self.indexing.as_mut() {
+                    *files += count;
+                }
+            }
+            IndexEvent::Done { truncated } => {
+                self.indexing = None;
+                self.index_truncated = truncated;
+                let rescan = self.rescan.take().map(|scanned| self.finish_rescan(scanned)).is_some();
+                let scan = if rescan { "Re-scan" } else { "Startup scan" };
+                if truncated {
+                    let message = format!(
+                        "{} stopped at max_index_files/max_index_bytes after {} files - later files diff against an empty baseline",
+                        scan,
+                        self.file_cache.len()
+                    );
+                    self.notify(Severity::Warning, message);
+                } else if rescan {
+                    let message = format!("Re-scan done - {} files cached", self.file_cache.len());
+                    self.notify(Severity::Success, message);
+                }
+            }
+        }
+    }
+
+    // Swap in the baselines a re-scan found, except where something happened
+    // to the path while it ran and under pending approvals (the stale check
+    // covers those)
+    fn finish_rescan(&mut self, scanned: Rescan) {
+        let Rescan { mut file_cache, mut encodings, mut permissions, mut touched } = scanned;
+        touched.extend(self.approval_queue.iter().map(|p| p.path.clone()));
+        for key in &touched {
+            // A directory key stands for everything beneath it
+            file_cache.retain(|k, _| !k.starts_with(key));
+            encodings.retain(|k, _| !k.starts_with(key));
+            permissions.retain(|k, _| !k.starts_with(key));
+            for (k, text) in self.file_cache.iter().filter(|(k, _)| k.starts_with(key)) {
+                file_cache.insert(k.clone(), text.clone());
+            }
+            for (k, encoding) in self.encodings.iter().filter(|(k, _)| k.starts_with(key)) {
+                encodings.insert(k.clone(), *encoding);
+            }
+            for (k, mode) in self.permissions.iter().filter(|(k, _)| k.starts_with(key)) {
+                permissions.insert(k.clone(), mode.clone());
+            }
+        }
+        self.file_cache = file_cache;
+        self.encodings = encodings;
+        self.permissions = permissions;
+    }
+
+    // A re-scan running now may have read `key` before or after this
+    fn touch_rescan(&mut self, key: &PathKey) {
+        if let Some(rescan) = self.rescan.as_mut() {
+            rescan.touched.insert(key.clone());
+        }
+    }
+
+    // Adopt `text` as what's on disk for `key`; empty means the file is gone.
+    // Permissions are read from disk now, as the file stands.
+    fn set_baseline(&mut self, key: PathKey, text: String, encoding: TextEncoding) {
+        let permissions = self.fs.stat(key.path()).ok().and_then(|s| s.permissions);
+        self.store_baseline(key, text, encoding, permissions);
+    }
+
+    fn store_baseline(&mut self, key: PathKey, text: String, encoding: TextEncoding, permissions: Option<std::fs::Permissions>) {
+        self.touch_rescan(&key);
+        // Empty text is a deleted file's baseline, or an empty file that's
+        // still worth knowing about (its removal is a file's, not a directory's)
+        if text.is_empty() && !self.fs.exists(key.path()) {
+            self.drop_baseline(&key);
+            return;
+        }
+        if encoding.is_default() {
+            self.encodings.remove(&key);
+        } else {
+            self.encodings.insert(key.clone(), encoding);
+        }
+        match permissions {
+            Some(permissions) => self.permissions.insert(key.clone(), permissions),
+            None => self.permissions.remove(&key),
+        };
+        self.file_cache.insert(key, text);
+    }
+
+    // For a directory (never cached itself) that's everything beneath it
+    fn drop_baseline(&mut self, key: &PathKey) {
+        self.touch_rescan(key);
+        if self.file_cache.remove(key).is_none() {
+            self.file_cache.retain(|k, _| !k.starts_with(key));
+            self.encodings.retain(|k, _| !k.starts_with(key));
+            self.permissions.retain(|k, _| !k.starts_with(key));
+        }
+        self.encodings.remove(key);
+        self.permissions.remove(key);
+    }
+
+    fn encoding_of(&self, key: &PathKey) -> TextEncoding {
+        self.encodings.get(key).copied().unwrap_or_default()
+    }
+
+    // The baseline's permissions, or failing that whatever the file has now
+    fn permissions_of(&self, path: &std::path::Path, key: &PathKey) -> Option<std::fs::Permissions> {
+        self.permissions.get(key).cloned()
+            .or_else(|| self.fs.stat(path).ok().and_then(|s| s.permissions))
+    }
+
+    fn set_status(&mut self, change_id: u64, status: ChangeStatus) {
+        if let Some(change) = self.file_changes.iter_mut().find(|c| c.id == change_id) {
+            change.status = status;
+        }
+    }
+
+    // The cache starts empty and fills in from the background indexer
+    fn new(config: Config, roots: Roots) -> Self {
+        Self::with_fs(config, roots, Box::new(RealFs))
+    }
+
+    fn with_fs(config: Config, roots: Roots, fs: Box<dyn FileSystem>) -> Self {
+        Self {
+            file_changes: VecDeque::with_capacity(config.max_history),
+            history_cap: config.max_history,
+            next_change_id: 0,
+            debounce_map: std::collections::HashMap::new(),
+            selected: None,
+            list_state: ListState::default(),
+            show_sidebar: true,
+            sidebar_filter: SidebarFilter::default(),
+            focus: Focus::Terminal,
+            fit: Fit::Full,
+            sidebar_rows: 0,
+            time_format: config.sidebar_time_format,
+            spill_store: SpillStore::new(),
+            loaded_spill: None,
+            recut: None,
+            file_cache: std::collections::HashMap::new(),
+            encodings: std::collections::HashMap::new(),
+            permissions: std::collections::HashMap::new(),
+            held_removes: Vec::new(),
+            recent_renames: Vec::new(),
+            indexing: Some(0),
+            rescan: None,
+            index_truncated: false,
+            
+            approval_queue: VecDeque::new(),
+            front_since: None,
+            own_writes: std::collections::HashMap::new(),
+            modal_active: false,
+            
+            show_diff_view: false,
+            follow: Follow::Off,
+            diff_wrap: false,
+            diff_whitespace: false,
+            diff_context: config.diff_context,
+            approval_ui: config.approval_ui,
+            banner_armed: false,
+            diff_cursor: HunkCursor::default(),
+            modal_cursor: HunkCursor::default(),
+            revert_preview: None,
+            parser: vt100::Parser::new(24, 80, command_pick::SCROLLBACK_ROWS), // Initial size, will be updated
+            current_theme: ThemeVariant::Zinc,
+            color_depth: ColorDepth::TrueColor,
+            last_pty_data: None,
+            spinner_frame: 0,
+            git_branch: String::new(),
+            branch_read: None,
+            git_status: git::GitStatus::default(),
+            committed: std::collections::HashSet::new(),
+            committing: None,
+            child_title: String::new(),
+            bells_seen: 0,
+            bell_flash_until: None,
+            stats: SessionStats::default(),
+            show_stats: false,
+            show_diffstat: false,
+            agent_prompt: None,
+            quick_open: None,
+            snippet_picker: None,
+            prompt_history: VecDeque::new(),
+            typed_line: String::new(),
+            clipboard: None,
+            hooks: None,
+            checker: None,
+            checks: std::collections::HashMap::new(),
+            observer: None,
+            observing: std::collections::HashMap::new(),
+            tests: None,
+            checks_collapsed: false,
+            status_message: None,
+            toasts: Toasts::default(),
+            notifications: None,
+            churn: Churn::default(),
+            churn_view: None,
+            interrupts: interrupt::Interrupts::default(),
+            command_pick: None,
+            output: Throughput::default(),
+            pty_dump: None,
+            dump_view: None,
+            cursor_shape: ansi::CursorShape::default(),
+            marked: HashSet::new(),
+            mark_menu: false,
+            quit_prompt: false,
+            revert_prompt: false,
+            revert_confirmed: false,
+            quit_after_revert: false,
+            json_log: None,
+            config,
+            roots,
+            fs,
+        }
+    }
+
+    // Removes are held for RENAME_WINDOW so a create with the same content can
+    // turn the pair into a single rename entry
+    fn add_change(&mut self, path: PathBuf, kind: ChangeKind) {
+        match kind {
+            ChangeKind::Remove => {
+                let cache_key = normalize_path(&path);
+                let hash = self.file_cache.get(&cache_key).map(|text| content::hash_bytes(text.as_bytes()));
+                self.held_removes.push(HeldRemove { path, cache_key, hash, at: Instant::now() });
+            }
+            ChangeKind::Create if !self.held_removes.is_empty() => {
+                let hash = content::fingerprint(&*self.fs, &path, &self.config);
+                match hash.and_then(|h| self.held_removes.iter().position(|r| r.hash == Some(h))) {
+                    Some(i) => {
+                        let held = self.held_removes.remove(i);
+                        self.record_rename(held.path, path);
+                    }
+                    None => self.record_change(path, kind),
+                }
+            }
+            _ => self.record_change(path, kind),
+        }
+    }
+
+    // Held removes that found no matching create are real deletes
+    fn flush_held_removes(&mut self) {
+        let (expired, held): (Vec<_>, Vec<_>) = std::mem::take(&mut self.held_removes)
+            .into_iter()
+            .partition(|r| r.at.elapsed() >= RENAME_WINDOW);
+        self.held_removes = held;
+        for removed in expired {
+            self.record_change(removed.path, ChangeKind::Remove);
+        }
+        self.recent_renames.retain(|(_, _, at)| at.elapsed() < RENAME_WINDOW);
+        let now = Instant::now();
+        self.own_writes.retain(|_, own| own.hook || now < own.until);
+    }
+
+    // Rename reported with both paths at once (inotify follows the two halves
+    // with this, so it may already have been paired up)
+    fn rename_event(&mut self, from: PathBuf, to: PathBuf) {
+        let (from_key, to_key) = (normalize_path(&from), normalize_path(&to));
+        if self.recent_renames.iter().any(|(f, t, _)| *f == from_key && *t == to_key) {
+            return;
+        }
+        self.held_removes.retain(|r| r.cache_key != from_key);
+        // The "to" half may already be logged as a create we couldn't pair
+        if let Some(to_display) = self.display_path(&to) {
+            self.file_changes.retain(|c| {
+                !(c.path == to_display && c.kind == ChangeKind::Create && c.status == ChangeStatus::Info)
+            });
+        }
+        self.record_rename(from, to);
+    }
+
+    fn record_rename(&mut self, from: PathBuf, to: PathBuf) {
+        let (from_key, to_key) = (normalize_path(&from), normalize_path(&to));
+        self.recent_renames.push((from_key.clone(), to_key.clone(), Instant::now()));
+
+        // A read of either end is still in flight: its result would land after
+        // the rename and undo it. Both halves wait their turn behind it instead.
+        if self.observing.contains_key(&from_key) || self.observing.contains_key(&to_key) {
+            self.record_change(from, ChangeKind::Remove);
+            self.record_change(to, ChangeKind::Create);
+            return;
+        }
+
+        // An atomic save: a temp file renamed over a file we know. That's an
+        // edit of the file, asked about against its baseline; the temp file's
+        // own create is gone with it.
+        if self.file_cache.contains_key(&to_key) {
+            self.approval_queue.retain(|p| p.path != from_key);
+            self.file_changes.retain(|c| {
+                !(c.abs_path == from_key && c.kind == ChangeKind::Create && matches!(c.status, ChangeStatus::Pending | ChangeStatus::Info))
+            });
+            self.after_decision();
+            if self.file_cache.contains_key(&from_key) {
+                self.record_change(from, ChangeKind::Remove);
+            }
+            self.debounce_map.remove(&(to_key, ChangeKind::Modify));
+            self.record_change(to, ChangeKind::Modify);
+            return;
+        }
+
+        // Moved in or out of the watched tree: only one end is ours
+        let (Some(from_display), Some(to_display)) = (self.display_path(&from), self.display_path(&to)) else {
+            if self.display_path(&from).is_some() {
+                self.record_change(from, ChangeKind::Remove);
+            } else {
+                self.record_change(to, ChangeKind::Create);
+            }
+            return;
+        };
+
+        // Renames involving a protected path go through the normal (blocking) flow
+        if self.config.is_protected(&from_display) || self.config.is_protected(&to_display) {
+            self.record_change(from, ChangeKind::Remove);
+            self.record_change(to, ChangeKind::Create);
+            return;
+        }
+
+        self.git_status.touched();
+        // Move the baseline over; diff only if the content changed on the way
+        let old_content = self.file_cache.get(&from_key).cloned();
+        self.drop_baseline(&from_key);
+        let mut full = None;
+        if let Some(FileContent::Text(text, encoding)) = content::read(&*self.fs, &to, &self.config) {
+            if let Some(old) = &old_content
+                && *old != text {
+                full = Some(diff::full_diff(old, &text));
+            }
+            self.set_baseline(to_key.clone(), text, encoding);
+        }
+
+        let hunks = full.as_ref().map_or_else(Vec::new, |full| diff::with_context(full, diff::DEFAULT_CONTEXT));
+        let (insertions, deletions) = diff::line_counts(&hunks);
+        let origin = self.origin_of(false);
+        self.next_change_id += 1;
+        self.log_decision(self.next_change_id, &to_key, &ChangeKind::Rename, (insertions, deletions), Decision::Logged, origin);
+        if origin == Origin::Agent {
+            self.stats.files.insert(to_key.clone());
+            *self.stats.changes.entry(ChangeKind::Rename).or_default() += 1;
+            self.stats.insertions += insertions;
+            self.stats.deletions += deletions;
+            self.churn.record(&to_key, &to_display, (insertions, deletions));
+        }
+
+        self.push_change(FileChange {
+            id: self.next_change_id,
+            path: format!("{} → {}", from_display, to_display),
+            abs_path: to_key.clone(),
+            from: Some(from_key),
+            kind: ChangeKind::Rename,
+            timestamp: Local::now(),
+            diff: (!hunks.is_empty()).then(|| diff::render_text(&hunks).into()),
+            hunks,
+            full,
+            context: diff::DEFAULT_CONTEXT,
+            insertions,
+            deletions,
+            note: None,
+            line_endings: None,
+            escapes_dropped: false,
+            spill: None,
+            status: ChangeStatus::Info,
+            origin,
+            pinned: false,
+            dir: self.fs.stat(&to).is_ok_and(|s| s.is_dir),
+            tests: None,
+        });
+    }
+
+    // Re-read a root's .ai-tui-ignore; a deleted one clears its rules
+    fn reload_ignore(&mut self, path: &std::path::Path) {
+        let Some(root) = path.parent() else { return };
+        let text = self.fs.read(path).map(|bytes| String::from_utf8_lossy(&bytes).into_owned()).unwrap_or_default();
+        self.roots.set_ignore(root, ignore::IgnoreRules::parse(&text));
+    }
+
+    // Sidebar name for `path`, or None if it's outside the roots or ignored.
+    // Protected paths are never ignored (`.env` is a dotfile).
+    fn display_path(&self, path: &std::path::Path) -> Option<String> {
+        let (root, rel) = self.roots.locate(path)?;
+        let display = self.roots.display(root, &rel);
+        (!root.is_ignored(&rel) || self.config.is_protected(&display)).then_some(display)
+    }
+
+    // Guess who made a change from when the agent last printed anything: file
+    // events land right after the write, so output just before it counts.
+    // Protected paths never get the benefit of the doubt.
+    fn origin_of(&self, protected: bool) -> Origin {
+        let window = Duration::from_secs(self.config.user_edit_window_secs);
+        if self.config.strict_origin || protected || self.last_pty_data.is_some_and(|t| t.elapsed() <= window) {
+            Origin::Agent
+        } else {
+            Origin::User
+        }
+    }
+
+    fn record_change(&mut self, path: PathBuf, kind: ChangeKind) {
+        // Edited exclusions apply from the next event on
+        if path.file_name().is_some_and(|n| n == ignore::IGNORE_FILE) {
+            self.reload_ignore(&path);
+        }
+
+        // The pool is still on an earlier event for this file; this one waits its turn
+        let cache_key = normalize_path(&path);
+        if let Some((_, waiting)) = self.observing.get_mut(&cache_key) {
+            waiting.push_back((path, kind));
+            return;
+        }
+
+        // 1. Filter Noise (relative to whichever root the path lives under)
+        let Some(display_path) = self.display_path(&path) else {
+            return;
+        };
+        self.touch_rescan(&cache_key);
+        self.git_status.touched();
+
+        // Our own revert just touched this file; swallow the echo. Checked
+        // before debouncing so it can't hide the agent writing right after.
+        if self.is_own_write(&path, &cache_key) {
+            return;
+        }
+
+        // Files of a directory that's gone are listed under the directory's own event
+        if kind == ChangeKind::Remove && path.parent().is_some_and(|parent| !self.fs.exists(parent)) {
+            return;
+        }
+
+        // Saves can keep coming for longer than the debounce window; fold them
+        // into the entry that's already waiting
+        if let Some(index) = self.approval_queue.iter().position(|p| p.path == cache_key) {
+            self.merge_pending(index);
+            return;
+        }
+
+        // 2. Debounce
+        let key = (cache_key.clone(), kind.clone());
+        if let Some(last_time) = self.debounce_map.get(&key)
+            && last_time.elapsed() < Duration::from_millis(500) {
+            return;
+        }
+        self.debounce_map.insert(key, Instant::now());
+
+        let old_content = self.file_cache.get(&cache_key).cloned().unwrap_or_default();
+        self.next_change_id += 1;
+        let protected = self.config.is_protected(&display_path);
+        let recording = Recording {
+            baseline_missing: self.indexing.is_some() && !self.file_cache.contains_key(&cache_key),
+            change_id: self.next_change_id,
+            origin: self.origin_of(protected),
+            dir: self.fs.stat(&path).is_ok_and(|s| s.is_dir),
+            path,
+            cache_key,
+            display_path,
+            kind,
+            protected,
+        };
+
+        // Reading and diffing is the slow part; the pool does it when there is one
+        if recording.kind != ChangeKind::Remove && let Some(pool) = &self.observer {
+            pool.submit(recording.cache_key.clone(), recording.path.clone(), old_content);
+            self.observing.insert(recording.cache_key.clone(), (recording, VecDeque::new()));
+            return;
+        }
+        let observation = self.observe(&recording.path, &recording.cache_key, old_content, recording.kind.clone());
+        self.log_observation(recording, observation);
+    }
+
+    // The pool finished a file: record it, then the events that waited on it
+    fn observed(&mut self, observed: Observed) {
+        let Some((recording, waiting)) = self.observing.remove(&observed.cache_key) else { return };
+        let old_content = self.file_cache.get(&observed.cache_key).cloned().unwrap_or_default();
+        let observation = if old_content == observed.old_content {
+            self.observe_read(&observed.path, &observed.cache_key, old_content, recording.kind.clone(), observed.read)
+        } else {
+            // The baseline moved while it was being read (a hook, a rescan)
+            self.observe(&observed.path, &observed.cache_key, old_content, recording.kind.clone())
+        };
+        self.log_observation(recording, observation);
+        for (path, kind) in waiting {
+            self.record_change(path, kind);
+        }
+    }
+
+    // Act on what a change turned out to be and log it in the sidebar
+    fn log_observation(&mut self, recording: Recording, observation: Observation) {
+        let Recording { path: _, cache_key, display_path, kind, baseline_missing, change_id, protected, origin, mut dir } = recording;
+        let mut diff_output = None;
+        let mut note = baseline_missing.then(|| "still indexing - diffed against an empty baseline".to_string());
+        let mut hunks = Vec::new();
+        let mut full = None;
+        let mut line_endings = None;
+        let mut escapes_dropped = false;
+        let mut line_counts = (0, 0);
+        let mut status = ChangeStatus::Info;
+
+        match observation {
+            // If content hasn't effectively changed from our cache, ignore it
+            Observation::Unchanged => return,
+            Observation::Queue(mut pending) => {
+                pending.baseline_missing = baseline_missing;
+                dir |= !pending.dir_files.is_empty();
+                diff_output = Some(pending.diff_text.clone());
+                hunks = pending.hunks.clone();
+                full = pending.full.clone();
+                line_endings = pending.line_endings;
+                escapes_dropped = pending.old_content.contains('\x1b');
+                line_counts = (pending.insertions, pending.deletions);
+                if let Some(summary) = pending.note() {
+                    note = Some(summary);
+                }
+                // Without a baseline there's nothing to put back, so a block
+                // falls back to quarantine
+                let block = protected && self.config.protected_mode == ProtectedMode::Block;
+                if block && !baseline_missing {
+                    // Undo it before anything else can read it
+                    status = ChangeStatus::Blocked;
+                    note = Some("BLOCKED - protected path, reverted".to_string());
+                    self.revert(&pending);
+                    self.log_decision(change_id, &pending.path, &pending.kind, (pending.insertions, pending.deletions), Decision::Rejected, Origin::Agent);
+                    self.stats.rejected += 1;
+                    self.status_message = Some((format!("Blocked change to protected path {}", display_path), Instant::now()));
+                } else if origin == Origin::User && !block {
+                    // The user's own edit: it becomes the new baseline without asking
+                    self.log_decision(change_id, &pending.path, &pending.kind, (pending.insertions, pending.deletions), Decision::Logged, origin);
+                    self.set_baseline(pending.path.clone(), pending.new_content, pending.new_encoding);
+                } else if !protected
+                    && (self.config.auto_approve.iter().any(|rule| glob::glob_match(rule, &display_path))
+                        || (pending.eol_only && self.config.auto_approve_line_endings)) {
+                    // Trusted paths never reach the modal, nor does line-ending noise when that's asked for
+                    status = ChangeStatus::AutoApproved;
+                    self.log_decision(change_id, &pending.path, &pending.kind, (pending.insertions, pending.deletions), Decision::Accepted, origin);
+                    self.run_hook(&pending.path);
+                    self.queue_tests(change_id);
+                    self.set_baseline(pending.path.clone(), pending.new_content, pending.new_encoding);
+                    self.stats.accepted += 1;
+                } else {
+                    // QUEUE FOR APPROVAL
+                    status = ChangeStatus::Pending;
+                    pending.change_id = change_id;
+                    pending.protected = protected;
+                    if block {
+                        self.status_message = Some((format!("Quarantined change to protected path {} (no baseline to restore yet)", display_path), Instant::now()));
+                    } else if protected {
+                        self.status_message = Some((format!("Quarantined change to protected path {}", display_path), Instant::now()));
+                    }
+                    self.request_check(change_id, &pending.path);
+                    self.approval_queue.push_back(*pending);
+                    self.modal_active = true;
+                }
+            }
+            Observation::Skip(notice) => {
+                diff_output = Some(notice.clone().into());
+                note = Some(notice);
+                // The cached text no longer describes what's on disk
+                self.drop_baseline(&cache_key);
+                self.log_decision(change_id, &cache_key, &kind, (0, 0), Decision::Logged, origin);
+            }
+            Observation::LogOnly => self.log_decision(change_id, &cache_key, &kind, (0, 0), Decision::Logged, origin),
+        }
+        // Binary or uncached: there's nothing to put back, so just shout
+        if protected && status == ChangeStatus::Info {
+            self.status_message = Some((format!("Protected path {} changed (nothing cached to restore)", display_path), Instant::now()));
+        }
+
+        // Session totals are the agent's work only
+        let (insertions, deletions) = line_counts;
+        if origin == Origin::Agent {
+            self.stats.files.insert(cache_key.clone());
+            *self.stats.changes.entry(kind.clone()).or_default() += 1;
+            self.stats.insertions += insertions;
+            self.stats.deletions += deletions;
+            self.churn.record(&cache_key, &display_path, (insertions, deletions));
+        }
+
+        let (diff_output, hunks, full, spill) = self.park_diff(diff_output, hunks, full);
+
+        // Add to Sidebar (Visual Log)
+        self.push_change(FileChange {
+            id: change_id,
+            path: display_path,
+            abs_path: cache_key.clone(),
+            from: None,
+            kind,
+            timestamp: Local::now(),
+            diff: diff_output,
+            hunks,
+            full,
+            context: diff::DEFAULT_CONTEXT,
+            insertions,
+            deletions,
+            note,
+            line_endings,
+            escapes_dropped,
+            spill,
+            status,
+            origin,
+            pinned: false,
+            dir,
+            tests: None,
+        });
+    }
+
+    fn push_change(&mut self, change: FileChange) {
+        let followed = matches!(change.status, ChangeStatus::Pending | ChangeStatus::AutoApproved);
+        let id = change.id;
+        // An open diff is being read: it stays put whatever the setting
+        let kept = match self.config.sidebar_selection {
+            SidebarSelection::Sticky => self.selected,
+            SidebarSelection::Newest if self.show_diff_view => self.selected,
+            SidebarSelection::Newest => None,
+        };
+        self.file_changes.push_front(change);
+        self.trim_history();
+        // Selection is by id, so a kept entry stays selected wherever the new
+        // one pushes it, unless history trimming dropped it
+        self.selected = kept.filter(|&kept| self.file_changes.iter().any(|c| c.id == kept)).or(Some(id));
+
+        // The diff view sits under the modal, so a required approval still comes first
+        if followed && self.follow == Follow::On {
+            self.select_entry(0);
+            self.show_diff_view = true;
+            let hunks = self.file_changes[0].hunks.len();
+            self.diff_cursor.next(hunks);
+        }
+    }
+
+    fn pause_follow(&mut self) {
+        if self.follow == Follow::On {
+            self.follow = Follow::Paused;
+        }
+    }
+
+    // Evict the oldest unpinned entries down to `history_cap`; pinned ones never
+    // count against it. Safe to call after the cap shrinks: the selection is
+    // pulled back inside whatever is left.
+    fn trim_history(&mut self) {
+        let limit = self.history_cap;
+        while limit > 0 && self.file_changes.iter().filter(|c| !c.pinned).count() > limit {
+            let Some(oldest) = self.file_changes.iter().rposition(|c| !c.pinned) else { break };
+            self.remove_entry(oldest);
+        }
+        let visible = self.visible_indices().len();
+        if self.selected.is_some() && self.selected_index().is_none() {
+            self.select_row(visible.checked_sub(1));
+        }
+    }
+
+    // Write the change log (oldest first) to `path`
+    fn export(&mut self, path: &std::path::Path, format: ExportFormat) -> Result<()> {
+        let changes: Vec<&FileChange> = self.file_changes.iter().rev().collect();
+        let stats = &self.stats;
+        let summary = export::Summary {
+            files: stats.files.len(),
+            insertions: stats.insertions,
+            deletions: stats.deletions,
+            changes: stats.changes.iter().map(|(kind, n)| (format!("{:?}", kind).to_lowercase(), *n)).collect(),
+            accepted: stats.accepted,
+            rejected: stats.rejected,
+            average_decision_secs: stats.average_decision().map(|d| d.as_secs_f64()),
+            output_bytes: self.output.total.bytes,
+            output_lines: self.output.total.lines,
+            agent_env: agent::masked_env(&Launch::from_config(&self.config).env, &self.config.agent_env_mask),
+        };
+        let text = export::render(format, &changes, &summary, &self.roots.primary().path)?;
+        self.write_report(path, &text)
+    }
+
+    // Exports usually land in the project; their watch events are ours
+    fn write_report(&mut self, path: &std::path::Path, text: &str) -> Result<()> {
+        self.fs.replace(path, text.as_bytes()).with_context(|| format!("cannot write {}", path.display()))?;
+        self.note_own_write(&normalize_path(path));
+        Ok(())
+    }
+
+    fn copy_selected_path(&mut self) {
+        self.clipboard = self.selected_change().map(|c| c.abs_path.to_string_lossy().into_owned());
+    }
+
+    // The selected diff as a fenced Markdown block, for PRs and chat
+    fn copy_selected_markdown(&mut self) {
+        let Some(change) = self.selected_change() else { return };
+        let text = export::markdown_change(change, self.config.markdown_summary);
+        let message = format!("Copied the diff of {} as Markdown", change.path);
+        self.clipboard = Some(text);
+        self.notify(Severity::Success, message);
+    }
+
+    fn reveal_selected(&self) {
+        if let Some(change) = self.selected_change() {
+            let _ = desktop::reveal(change.abs_path.path());
+        }
+    }
+
+    // Drop the selected entry from the log once it's been looked at. Only the
+    // view changes: baselines and queued approvals stay as they are.
+    fn dismiss_selected(&mut self) {
+        let (Some(i), row) = (self.selected_index(), self.selected_row()) else { return };
+        self.remove_entry(i);
+        self.after_removal(row);
+    }
+
+    // Ctrl+L: start the log (and its stats) over
+    fn clear_log(&mut self) {
+        while !self.file_changes.is_empty() {
+            self.remove_entry(0);
+        }
+        self.selected = None;
+        self.diff_cursor.reset();
+        self.stats = SessionStats::default();
+        self.churn = Churn::default();
+    }
+
+    fn remove_entry(&mut self, i: usize) {
+        let Some(dismissed) = self.file_changes.remove(i) else { return };
+        self.marked.remove(&dismissed.id);
+        if let Some(path) = dismissed.spill {
+            if self.loaded_spill.as_ref() == Some(&path) {
+                self.loaded_spill = None;
+            }
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    // If the selected entry went, the next one slides into its `row`; past
+    // the end, the one before it
+    fn after_removal(&mut self, row: Option<usize>) {
+        if self.selected_index().is_none() {
+            let visible = self.visible_indices().len();
+            self.select_row(row.map(|row| row.min(visible.saturating_sub(1))).filter(|_| visible > 0));
+        }
+        self.diff_cursor.reset();
+    }
+
+    // Space in the sidebar. Marks are by entry id, so they stay on their
+    // entries as new ones arrive.
+    fn toggle_mark(&mut self) {
+        let Some(id) = self.selected_change().map(|c| c.id) else { return };
+        if !self.marked.remove(&id) {
+            self.marked.insert(id);
+        }
+    }
+
+    // Marked entries, oldest first
+    fn marked_changes(&self) -> Vec<&FileChange> {
+        self.file_changes.iter().rev().filter(|c| self.marked.contains(&c.id)).collect()
+    }
+
+    // A key in the marked-entries menu; anything else just closes it
+    fn act_on_marked(&mut self, key: char) {
+        match key {
+            'e' => {
+                let root = self.roots.primary().path.clone();
+                let path = root.join("marked.patch");
+                let (patch, skipped) = export::patch(&self.marked_changes(), &root);
+                let skipped = if skipped > 0 { format!(" ({} left out: no line diff, or outside the project)", skipped) } else { String::new() };
+                match self.write_report(&path, &patch) {
+                    Ok(()) => self.notify(Severity::Success, format!("Wrote {}{}", path.display(), skipped)),
+                    Err(e) => self.notify(Severity::Error, format!("Couldn't write {}: {:#}", path.display(), e)),
+                }
+            }
+            'c' => {
+                let paths: Vec<String> = self.marked_changes().iter().map(|c| c.abs_path.to_string_lossy().into_owned()).collect();
+                let message = format!("Copied {} paths", paths.len());
+                self.clipboard = Some(paths.join("\n"));
+                self.notify(Severity::Success, message);
+            }
+            'r' => {
+                let (queued, skipped) = self.requeue_marked();
+                let skipped = if skipped > 0 { format!("; {} couldn't be (undecided, gone, binary or edited since)", skipped) } else { String::new() };
+                self.notify(if queued > 0 { Severity::Info } else { Severity::Warning }, format!("Re-queued {} for approval{}", queued, skipped));
+            }
+            'd' => {
+                let row = self.selected_row();
+                for i in (0..self.file_changes.len()).rev() {
+                    if self.marked.contains(&self.file_changes[i].id) {
+                        self.remove_entry(i);
+                    }
+                }
+                self.after_removal(row);
+            }
+            _ => return,
+        }
+        self.marked.clear();
+    }
+
+    // Put decided changes back up for approval: the content from before each
+    // one becomes the baseline again and the file as it is now is diffed
+    // against it. Returns (queued, skipped).
+    fn requeue_marked(&mut self) -> (usize, usize) {
+        let ids: Vec<u64> = self.marked_changes().iter().map(|c| c.id).collect();
+        let mut queued = 0;
+        for &id in &ids {
+            let Some(change) = self.file_changes.iter().find(|c| c.id == id) else { continue };
+            let key = change.abs_path.clone();
+            let decided = matches!(change.status, ChangeStatus::Accepted | ChangeStatus::AutoApproved | ChangeStatus::Dismissed);
+            // A note means there's no line diff to go back through (binary,
+            // skipped), unless it's only about line endings. Nor does one
+            // that dropped the escape sequences of what was there.
+            let replayable = (change.note.is_none() || change.line_endings.is_some()) && !change.escapes_dropped;
+            if !decided || change.dir || !replayable || self.approval_queue.iter().any(|p| p.path == key) {
+                continue;
+            }
+            let hunks = match &change.spill {
+                Some(spill) => std::fs::read_to_string(spill).map(|text| diff::parse_text(&text)).unwrap_or_default(),
+                None => change.hunks.clone(),
+            };
+            // A removal left nothing behind to rebuild the file from
+            let old = match change.kind {
+                ChangeKind::Create => Some(String::new()),
+                ChangeKind::Modify => self.file_cache.get(&key).and_then(|now| diff::unapply(now, &hunks)),
+                ChangeKind::Remove | ChangeKind::Rename => None,
+            };
+            // The hunks were taken without line endings; the ones from before
+            // can only be put back if they were all the same
+            let old = match change.line_endings {
+                Some((from @ (diff::LineEnding::Lf | diff::LineEnding::Crlf), _)) => old.map(|old| diff::with_eol(&old, from)),
+                Some(_) => None,
+                None => old,
+            };
+            let Some(old) = old else { continue };
+            let kind = change.kind.clone();
+            if let Observation::Queue(mut pending) = self.observe(key.path(), &key, old.clone(), kind) {
+                pending.change_id = id;
+                let encoding = self.encoding_of(&key);
+                self.set_baseline(key, old, encoding);
+                self.request_check(id, &pending.path);
+                self.approval_queue.push_back(*pending);
+                self.set_status(id, ChangeStatus::Pending);
+                self.modal_active = true;
+                queued += 1;
+            }
+        }
+        (queued, ids.len() - queued)
+    }
+
+    fn toggle_pin(&mut self) {
+        let Some(i) = self.selected_index() else { return };
+        self.file_changes[i].pinned = !self.file_changes[i].pinned;
+    }
+
+    // Lines per PgUp/PgDn in the diff panes, roughly the terminal pane's height
+    fn diff_page_rows(&self) -> usize {
+        (self.parser.screen().size().0 as usize).saturating_sub(4).max(1)
+    }
+
+    // Swap the modal for the full diff of the change under review; Ctrl+K comes back
+    fn open_full_diff(&mut self) {
+        let Some(id) = self.approval_queue.front().map(|p| p.change_id) else { return };
+        if let Some(i) = self.file_changes.iter().position(|c| c.id == id) {
+            self.select_entry(i);
+        }
+        self.modal_active = false;
+        self.show_diff_view = true;
+        self.diff_cursor.reset();
+    }
+
+    // Select log entry `i`, clearing the sidebar filter if it hides it
+    fn select_entry(&mut self, i: usize) {
+        if !self.visible_indices().contains(&i) {
+            self.clear_filter();
+        }
+        self.selected = self.file_changes.get(i).map(|c| c.id);
+        self.diff_cursor.reset();
+    }
+
+    // Keys while the approval modal is up; all of them are taken. `o` hands
+    // back the file (and first changed line) to open in the editor.
+    fn handle_modal_key(&mut self, key: event::KeyEvent) -> Option<(PathBuf, Option<usize>)> {
+        match key.code {
+            _ if self.config.keys.action(&key) == Some(Action::Quit) => self.quit_prompt = true,
+            _ if self.config.keys.action(&key) == Some(Action::ToggleDiff) => self.open_full_diff(),
+            // Down to a banner until the diff view closes again
+            KeyCode::Char('d') => self.open_full_diff(),
+            KeyCode::Char('o') => {
+                let target = self.approval_queue.front()
+                    .filter(|p| !p.binary && p.kind != ChangeKind::Remove)
+                    .map(|p| (p.path.path().to_path_buf(), diff::first_changed_line(&p.hunks)));
+                if target.is_none() {
+                    self.status_message = Some(("Nothing on disk to open".to_string(), Instant::now()));
+                }
+                return target;
+            }
+            KeyCode::Char('y') => self.accept_front(),
+            KeyCode::Char('n') => self.request_reject(),
+            KeyCode::Char(c @ '1'..='9') => self.review_pending(c as usize - '1' as usize),
+            KeyCode::Char(' ') if self.revert_preview.is_none() => {
+                let total = self.approval_queue.front().map_or(0, |p| p.hunks.len());
+                if self.modal_cursor.current.is_none() {
+                    self.modal_cursor.next(total);
+                }
+                if let Some(index) = self.modal_cursor.current
+                    && let Some(pending) = self.approval_queue.front_mut() {
+                    if pending.baseline_missing {
+                        self.status_message = Some(("No baseline yet - accept or reject it whole".to_string(), Instant::now()));
+                    }
+                    pending.toggle_hunk(index);
+                }
+            }
+            KeyCode::Char('r') if self.approval_queue.front().is_some_and(|p| self.reject_mode_for(p) == RejectMode::Revert) => {
+                self.revert_preview = match self.revert_preview {
+                    Some(_) => None,
+                    None => self.approval_queue.front().map(|p| p.revert_preview(&*self.fs, &self.config)),
+                };
+                self.modal_cursor.reset();
+            }
+            KeyCode::PageUp | KeyCode::PageDown => {
+                let (offsets, line_count) = match &self.revert_preview {
+                    Some(preview) => (diff::hunk_offsets(&preview.hunks), preview.diff_text.line_count()),
+                    None => self.approval_queue.front()
+                        .map_or((Vec::new(), 0), |p| (diff::hunk_offsets(&p.hunks), p.diff_text.line_count())),
+                };
+                let rows = self.diff_page_rows();
+                self.modal_cursor.page(key.code == KeyCode::PageDown, rows, &offsets, line_count);
+            }
+            KeyCode::Tab | KeyCode::BackTab => {
+                let total = match &self.revert_preview {
+                    Some(preview) => preview.hunks.len(),
+                    None => self.approval_queue.front().map_or(0, |p| p.hunks.len()),
+                };
+                if key.code == KeyCode::Tab {
+                    self.modal_cursor.next(total);
+                } else {
+                    self.modal_cursor.prev(total);
+                }
+            }
+            _ => {} // Consume other keys
+        }
+        None
+    }
+
+    fn handle_quick_open_key(&mut self, key: event::KeyEvent) {
+        let Some(prompt) = self.quick_open.as_mut() else { return };
+        let total = prompt.matches(&self.file_changes).len();
+        match key.code {
+            KeyCode::Esc => self.quick_open = None,
+            _ if self.config.keys.action(&key) == Some(Action::QuickOpen) => self.quick_open = None,
+            KeyCode::Enter => {
+                let chosen = prompt.matches(&self.file_changes).get(prompt.selected).copied();
+                self.quick_open = None;
+                if let Some(i) = chosen {
+                    self.pause_follow();
+                    self.select_entry(i);
+                    self.show_diff_view = true;
+                }
+            }
+            KeyCode::Up => prompt.move_selection(false, total),
+            KeyCode::Down => prompt.move_selection(true, total),
+            KeyCode::Char(c) => {
+                prompt.query.push(c);
+                prompt.selected = 0;
+            }
+            KeyCode::Backspace => {
+                prompt.query.pop();
+                prompt.selected = 0;
+            }
+            _ => {}
+        }
+    }
+
+    // Bring the selected entry's spilled diff back into memory (and drop the previous one)
+    fn load_selected_spill(&mut self) {
+        let Some(i) = self.selected_index() else { return };
+        let Some(path) = self.file_changes[i].spill.clone() else { return };
+        if self.loaded_spill.as_ref() == Some(&path) {
+            return;
+        }
+
+        if let Some(prev) = self.loaded_spill.take() {
+            for change in self.file_changes.iter_mut().filter(|c| c.spill.as_ref() == Some(&prev)) {
+                change.diff = None;
+                change.hunks = Vec::new();
+            }
+        }
+
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            let change = &mut self.file_changes[i];
+            change.hunks = diff::parse_text(&text);
+            change.diff = Some(text.into());
+            self.loaded_spill = Some(path);
+        }
+    }
+
+    // What the diff view shows: the spill loaded, and the hunks re-cut to
+    // diff_context from the whole-file diff. Spilled diffs stay as they were.
+    fn prepare_selected_diff(&mut self) {
+        self.load_selected_spill();
+        let context = self.diff_context;
+        let Some(change) = self.selected_change() else { return };
+        let Some(full) = change.full.as_ref().filter(|_| change.context != context) else {
+            self.recut = None;
+            return;
+        };
+        if self.recut.as_ref().is_some_and(|r| r.id == change.id && r.context == context) {
+            return;
+        }
+        let hunks = diff::with_context(full, context);
+        let diff = (!hunks.is_empty()).then(|| diff::render_text(&hunks).into());
+        self.recut = Some(Recut { id: change.id, context, diff, hunks });
+    }
+
+    // The selected change as the diff view shows it
+    fn shown_diff(&self) -> Option<Shown<'_>> {
+        let change = self.selected_change()?;
+        Some(match self.recut.as_ref().filter(|r| r.id == change.id) {
+            Some(recut) => Shown { change, diff: recut.diff.as_ref(), hunks: &recut.hunks, context: recut.context },
+            None => Shown::of(change),
+        })
+    }
+
+    fn open_snippets(&mut self) {
+        let (snippets, error) = match snippets::load() {
+            Ok(snippets) => (snippets, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        self.snippet_picker = Some(SnippetPicker::new(snippets, self.prompt_history.iter().cloned(), error));
+    }
+
+    // Keys while the snippet picker is open. Returns the text to send once a
+    // snippet is chosen and every placeholder has a value.
+    fn handle_snippet_key(&mut self, key: event::KeyEvent) -> Option<String> {
+        let picker = self.snippet_picker.as_mut()?;
+        if let Some(filling) = picker.filling.as_mut() {
+            match key.code {
+                KeyCode::Esc => self.snippet_picker = None,
+                KeyCode::Char(c) => filling.input.push(c),
+                KeyCode::Backspace => {
+                    filling.input.pop();
+                }
+                KeyCode::Enter => {
+                    let filling = picker.filling.take()?;
+                    return self.fill_snippet(filling.text.replace(filling.placeholder, &filling.input));
+                }
+                _ => {}
+            }
+            return None;
+        }
+        let matches = picker.matches();
+        match key.code {
+            KeyCode::Esc => self.snippet_picker = None,
+            _ if self.config.keys.action(&key) == Some(Action::Snippets) => self.snippet_picker = None,
+            KeyCode::Up => picker.move_selection(false, matches.len()),
+            KeyCode::Down => picker.move_selection(true, matches.len()),
+            KeyCode::Char(c) => {
+                picker.query.push(c);
+                picker.selected = 0;
+            }
+            KeyCode::Backspace => {
+                picker.query.pop();
+                picker.selected = 0;
+            }
+            KeyCode::Enter => {
+                let i = *matches.get(picker.selected)?;
+                let text = picker.entries[i].text.clone();
+                return self.fill_snippet(text);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    // Fill what the selection can; ask for the first placeholder it can't
+    fn fill_snippet(&mut self, mut text: String) -> Option<String> {
+        while let Some(placeholder) = snippets::next_placeholder(&text) {
+            match self.placeholder_value(placeholder) {
+                Some(value) => text = text.replace(placeholder, &value),
+                None => {
+                    let picker = self.snippet_picker.as_mut()?;
+                    picker.filling = Some(Filling { text, placeholder, input: String::new() });
+                    return None;
+                }
+            }
+        }
+        self.snippet_picker = None;
+        Some(text)
+    }
+
+    // {file} is the selected change's path; {selection} the hunk picked in the
+    // diff view, or else the change's whole diff
+    fn placeholder_value(&mut self, placeholder: &str) -> Option<String> {
+        self.prepare_selected_diff();
+        let shown = self.shown_diff()?;
+        if placeholder == "{file}" {
+            return Some(shown.change.path.clone());
+        }
+        match self.diff_cursor.current.and_then(|h| shown.hunks.get(h)) {
+            Some(hunk) => Some(diff::render_text(std::slice::from_ref(hunk))),
+            None => shown.change.diff.as_ref().map(|d| d.as_str().to_string()),
+        }
+    }
+
+    // Follow what's typed into the agent, for the prompt history
+    fn note_typed(&mut self, key: &event::KeyEvent) {
+        let plain = !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Char(c) if plain => self.typed_line.push(c),
+            KeyCode::Backspace => {
+                self.typed_line.pop();
+            }
+            KeyCode::Enter => {
+                let line = std::mem::take(&mut self.typed_line);
+                snippets::remember(&mut self.prompt_history, &line, self.config.prompt_history);
+            }
+            KeyCode::Char('c' | 'u' | 'w') if !plain => self.typed_line.clear(),
+            KeyCode::Esc | KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right | KeyCode::Tab | KeyCode::Home | KeyCode::End => {
+                self.typed_line.clear()
+            }
+            _ => {}
+        }
+    }
+
+    // Numbered hints over the commands on screen and just above it
+    fn open_command_pick(&mut self) {
+        let lines = command_pick::screen_lines(&mut self.parser);
+        let candidates = command_pick::extract(&lines, &self.config.command_patterns);
+        if candidates.is_empty() {
+            self.notify(Severity::Info, "No commands on screen to pick from".to_string());
+        } else {
+            self.command_pick = Some(CommandPick::new(candidates));
+        }
+    }
+
+    fn select_page(&mut self, forward: bool) {
+        self.pause_follow();
+        let page = self.sidebar_rows.max(1);
+        let visible = self.visible_indices().len();
+        let current = self.selected_row().unwrap_or(0);
+        let i = if forward {
+            (current + page).min(visible.saturating_sub(1))
+        } else {
+            current.saturating_sub(page)
+        };
+        self.select_row(Some(i));
+        self.diff_cursor.reset();
+    }
+}
+
+fn main() -> Result<()> {
+    let args = cli::Args::parse();
+    // The project config lives in the watched directory, not wherever aiui was started
+    let project = match &args.subcommand {
+        Some(cli::Cmd::Doctor { dirs, .. }) => dirs.first(),
+        _ => args.dirs.first(),
+    };
+    let mut config = Config::load(project.map(PathBuf::as_path))?;
+    match args.subcommand {
+        Some(cli::Cmd::Ctl { socket, command }) => return run_ctl(&config, socket, command),
+        Some(cli::Cmd::Doctor { dirs, command }) => run_doctor(config, dirs, command),
+        None => {}
+    }
+    if let Some(ui) = args.approval_ui {
+        config.approval_ui = ui;
+    }
+    config.auto_approve.extend(args.auto_approve.iter().cloned());
+    // Headless and --tail have no PTY output to go by, so every change is the agent's
+    if args.strict_origin || args.headless || args.tail {
+        config.strict_origin = true;
+    }
+    if !args.command.is_empty() {
+        config.agent_command = agent::shell_join(&args.command);
+        config.agent_argv = args.command.clone();
+    }
+    config.agent_login_shell |= args.login_shell;
+    // --unset also drops a config agent_env entry; --env comes last and wins
+    for key in &args.unset {
+        config.agent_env.remove(key);
+    }
+    config.agent_env_unset.extend(args.unset.iter().cloned());
+    config.agent_env.extend(args.env.iter().cloned());
+
+    // --dir wins over the config file; default to the current directory
+    let dirs = if !args.dirs.is_empty() {
+        args.dirs.clone()
+    } else {
+        config.watch_roots.clone()
+    };
+
+    // Catch the usual setup problems while errors can still be read
+    let agent_command = (!args.tail).then_some(config.agent_command.as_str());
+    if !doctor::report(&doctor::preflight(agent_command, &Launch::from_config(&config), &dirs, !args.headless), false) {
+        eprintln!("aiui can't start; `aiui doctor` runs the full check");
+        std::process::exit(1);
+    }
+    let roots = Roots::new(&dirs)?;
+    // Directories named with --dir or watch_roots were picked on purpose
+    if dirs.is_empty() && !confirm_scan(&roots.primary().path) {
+        eprintln!("Nothing watched; cd into a project or pass --dir");
+        std::process::exit(1);
+    }
+
+    // 1. Setup Channel for Events
+    let (tx, rx) = mpsc::channel::<AppEvent>();
+
+    // 2. Tick Thread (held renames, animations)
+    engine::spawn_ticker(tx.clone());
+
+    // 3. Background Indexer (fills the file cache)
+    indexer::spawn(roots.to_vec(), config.clone(), tx.clone());
+
+    // 4. File Watcher (kept alive until we return)
+    let _watcher = engine::watch(&roots, tx.clone())?;
+
+    // Control socket for external tools (answered on the main loop)
+    let control_socket = config.control_socket.as_ref().map(|p| control::socket_path(p, &roots.primary().path));
+    if let Some(socket) = &control_socket {
+        control::serve(socket, tx.clone())?;
+    }
+
+    // SIGTERM/SIGINT, and on Unix SIGHUP (terminal closed), end the loop so
+    // cleanup still runs. The JSON log is flushed per record, so nothing is
+    // lost on the way out.
+    let terminate = Arc::new(AtomicBool::new(false));
+    for signal in [signal_hook::consts::SIGTERM, signal_hook::consts::SIGINT] {
+        signal_hook::flag::register(signal, terminate.clone())?;
+    }
+    #[cfg(unix)]
+    signal_hook::flag::register(signal_hook::consts::SIGHUP, terminate.clone())?;
+
+    // JSON log is opened before raw mode so errors print cleanly
+    let json_log = args.json_log.as_deref().map(JsonLog::open).transpose()?;
+    let json_to_stdout = args.json_log.as_deref() == Some(std::path::Path::new("-"));
+
+    // 5. Setup App State and Logger
+    let agent_command = config.agent_command.clone();
+    let on_accept = config.on_accept.clone();
+    let hook_timeout = Some(Duration::from_secs(config.on_accept_timeout_secs)).filter(|t| !t.is_zero());
+    let check_command = config.check_command.clone();
+    let test_command = config.test_command.clone();
+    let mut state = AppState::new(config, roots);
+    if args.debug {
+        state.pty_dump = Some(PtyDump::default());
+    }
+    state.json_log = json_log;
+    state.hooks = on_accept.map(|command| Hooks::new(command, state.roots.primary().path.clone(), hook_timeout, tx.clone()));
+    state.checker = check_command.map(|command| Checker::new(command, state.roots.primary().path.clone(), tx.clone()));
+    state.tests = test_command.map(|command| TestRunner::new(command, state.roots.primary().path.clone(), tx.clone()));
+    if !args.headless {
+        state.observer = Some(ObservePool::new(Arc::new(RealFs), state.config.clone(), tx.clone()));
+    }
+
+    if args.headless {
+        let rejected = headless::run(&mut state, &agent_command, rx, &terminate)?;
+        if let Some(socket) = &control_socket {
+            let _ = std::fs::remove_file(socket);
+        }
+        if let Some(path) = &args.export_on_exit {
+            state.export(path, ExportFormat::from_path(path))?;
+        }
+        // Non-zero when anything was turned down, for CI
+        std::process::exit(if rejected { 1 } else { 0 });
+    }
+
+    // 6. Spawn the agent in its PTY (reader thread included); --tail leaves it to the user
+    let size = PtySize { rows: 24, cols: 80, pixel_width: 0, pixel_height: 0 };
+    let primary = state.roots.primary().path.clone();
+    let mut agent = (!args.tail)
+        .then(|| Agent::spawn(&agent_command, &Launch::from_config(&state.config), &primary, size, 0, tx.clone()))
+        .transpose()?;
+    if agent.is_none() {
+        // Nothing to type into
+        state.focus = Focus::Sidebar;
+    }
+
+    // 7. Setup TUI (on stderr when stdout carries the JSON log)
+    let guard = TerminalGuard::enter(json_to_stdout)?;
+    let backend = CrosstermBackend::new(terminal_out(json_to_stdout));
+    let mut terminal = Terminal::new(backend)?;
+    if let Ok(size) = terminal.size() {
+        state.set_fit(Fit::of(size.width, size.height));
+    }
+
+    state.color_depth = match args.color {
+        cli::ColorMode::Auto => ColorDepth::detect(),
+        cli::ColorMode::Truecolor => ColorDepth::TrueColor,
+        cli::ColorMode::Ansi256 => ColorDepth::Ansi256,
+        cli::ColorMode::Ansi16 => ColorDepth::Ansi16,
+    };
+    // https://no-color.org: any non-empty value
+    if args.no_color || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        state.current_theme = ThemeVariant::HighContrast;
+    }
+    let app_state = Arc::new(Mutex::new(state));
+
+    // 8. Main Loop
+    let input_paused = Arc::new(AtomicBool::new(false));
+    spawn_input_reader(tx.clone(), input_paused.clone());
+    let loop_result = run_app(
+        &mut terminal,
+        app_state.clone(),
+        rx,
+        tx,
+        &mut agent,
+        &terminate,
+        &input_paused,
+    );
+
+    // 9. Cleanup
+    drop(guard);
+    if let Some(agent) = &mut agent {
+        agent.kill();
+    }
+    if let Some(socket) = &control_socket {
+        let _ = std::fs::remove_file(socket);
+    }
+
+    if let Some(path) = &args.export_on_exit {
+        app_state.lock().unwrap().export(path, ExportFormat::from_path(path))?;
+    }
+
+    loop_result
+}
+
+// Raw mode and the alternate screen, undone however the TUI exits: a normal
+// return, an early `?`, or a panic (whose message would otherwise be wiped)
+struct TerminalGuard {
+    to_stderr: bool,
+}
+
+impl TerminalGuard {
+    fn enter(to_stderr: bool) -> Result<Self> {
+        enable_raw_mode()?;
+        let guard = Self { to_stderr };
+        execute!(terminal_out(to_stderr), EnterAlternateScreen)?;
+
+        // Panic hooks run before unwinding, so restore first and then print.
+        // Background threads leave the TUI alone; only the main thread owns it.
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if std::thread::current().name() == Some("main") {
+                restore_terminal(to_stderr);
+            }
+            default_hook(info);
+        }));
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal(self.to_stderr);
+    }
+}
+
+fn terminal_out(to_stderr: bool) -> Box<dyn Write> {
+    if to_stderr {
+        Box::new(std::io::stderr())
+    } else {
+        Box::new(std::io::stdout())
+    }
+}
+
+// Safe to run twice (panic hook, then the guard while unwinding)
+fn restore_terminal(to_stderr: bool) {
+    let _ = disable_raw_mode();
+    let _ = execute!(terminal_out(to_stderr), LeaveAlternateScreen, SetCursorStyle::DefaultUserShape, crossterm::cursor::Show);
+}
+
+// DECSCUSR Ps as crossterm has it; unknown values get the user's default
+fn cursor_style(shape: u8) -> SetCursorStyle {
+    match shape {
+        1 => SetCursorStyle::BlinkingBlock,
+        2 => SetCursorStyle::SteadyBlock,
+        3 => SetCursorStyle::BlinkingUnderScore,
+        4 => SetCursorStyle::SteadyUnderScore,
+        5 => SetCursorStyle::BlinkingBar,
+        6 => SetCursorStyle::SteadyBar,
+        _ => SetCursorStyle::DefaultUserShape,
+    }
+}
+
+// Started somewhere that isn't a project (home, `/`, no checkout): ask
+// before watching and scanning all of it. Without a terminal to ask on, the
+// index budgets are the only guard.
+fn confirm_scan(dir: &std::path::Path) -> bool {
+    let Some(reason) = roots::not_a_project(dir) else {
+        return true;
+    };
+    eprintln!("{} doesn't look like a project: {}.", dir.display(), reason);
+    if !std::io::stdin().is_terminal() {
+        eprintln!("Scanning it anyway, up to max_index_files files");
+        return true;
+    }
+    eprint!("Watch and scan everything under it? [y/N] ");
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).is_ok_and(|_| matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+// `aiui doctor`: every preflight check, passed or not
+fn run_doctor(mut config: Config, dirs: Vec<PathBuf>, command: Vec<String>) -> ! {
+    if !command.is_empty() {
+        config.agent_command = agent::shell_join(&command);
+        config.agent_argv = command;
+    }
+    let dirs = if dirs.is_empty() { config.watch_roots.clone() } else { dirs };
+    let ok = doctor::report(&doctor::preflight(Some(&config.agent_command), &Launch::from_config(&config), &dirs, true), true);
+    eprintln!("{}", if ok { "All checks passed." } else { "Some checks failed." });
+    std::process::exit(if ok { 0 } else { 1 });
+}
+
+// `aiui ctl`: one request to a running session
+fn run_ctl(config: &Config, socket: Option<PathBuf>, command: cli::CtlCommand) -> Result<()> {
+    // A configured socket resolves against the primary root (the first of
+    // watch_roots, else the current directory), just as the session binds it
+    let socket = match (socket, &config.control_socket) {
+        (Some(socket), _) => socket,
+        (None, Some(configured)) => control::socket_path(configured, &Roots::new(&config.watch_roots)?.primary().path),
+        (None, None) => anyhow::bail!("no control socket: pass --socket or set control_socket"),
+    };
+    let request = match command {
+        cli::CtlCommand::ListPending => control::Request::ListPending,
+        cli::CtlCommand::Approve { path, id } => control::Request::Approve { path, id },
+        cli::CtlCommand::Reject { path, id } => control::Request::Reject { path, id },
+        cli::CtlCommand::Status => control::Request::Status,
+        cli::CtlCommand::Export { path } => control::Request::Export { path },
+    };
+    control::run_client(&socket, request)
+}
+
+// Terminal input on its own thread, so the main loop can sleep on the channel
+// and still wake the moment a key arrives. `paused` hands the keyboard to
+// something else (the Ctrl+G editor) without losing keys to this thread.
+fn spawn_input_reader(tx: mpsc::Sender<AppEvent>, paused: Arc<AtomicBool>) {
+    std::thread::spawn(move || loop {
+        if paused.load(Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(20));
+            continue;
+        }
+        match event::poll(Duration::from_millis(50)) {
+            Ok(false) => continue,
+            Ok(true) if paused.load(Ordering::Relaxed) => continue,
+            Ok(true) => {}
+            Err(_) => break,
+        }
+        let Ok(event) = event::read() else { break };
+        if tx.send(AppEvent::Input(event)).is_err() {
+            break;
+        }
+    });
+}
+
+// Hand the terminal to $VISUAL/$EDITOR (or vi) until it exits. The agent
+// keeps running meanwhile; its output waits in the channel and is replayed
+// onto the screen as soon as the loop resumes.
+fn run_editor(terminal: &mut Terminal<CrosstermBackend<Box<dyn Write>>>, args: &[&std::ffi::OsStr]) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .or_else(|| std::env::var("EDITOR").ok().filter(|e| !e.trim().is_empty()))
+        .unwrap_or_else(|| "vi".to_string());
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, SetCursorStyle::DefaultUserShape, crossterm::cursor::Show)?;
+    let mut words = editor.split_whitespace();
+    let status = std::process::Command::new(words.next().unwrap_or("vi")).args(words).args(args).status();
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+    // Whatever the editor left on screen isn't ours
+    terminal.clear()?;
+
+    let status = status.with_context(|| format!("cannot run {}", editor))?;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", editor, status);
+    }
+    Ok(())
+}
+
+// Ctrl+G: the editor on a temp file, returning what was written
+fn compose_in_editor(terminal: &mut Terminal<CrosstermBackend<Box<dyn Write>>>) -> Result<String> {
+    let path = create_prompt_file()?;
+    let ran = run_editor(terminal, &[path.as_os_str()]);
+    let text = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+    ran?;
+    Ok(text?)
+}
+
+// An empty file under a random name in the temp dir, made by us alone:
+// create_new refuses anything already there, a planted symlink included, and
+// on Unix only we can read it
+fn create_prompt_file() -> Result<PathBuf> {
+    use std::hash::{BuildHasher, RandomState};
+    for _ in 0..8 {
+        let name = format!("aiui-prompt-{:016x}.md", RandomState::new().hash_one(std::process::id()));
+        let path = std::env::temp_dir().join(name);
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).context("cannot create a file for the prompt"),
+        }
+    }
+    Err(anyhow::anyhow!("cannot create a file for the prompt: every name tried was taken"))
+}
+
+// `o` in the approval modal: the pending file at its first change (`+N`, which
+// vi, nano and emacs all take). Anything saved there comes back through the
+// watcher as a refresh of the same pending change.
+fn edit_pending(terminal: &mut Terminal<CrosstermBackend<Box<dyn Write>>>, path: &std::path::Path, line: Option<usize>) -> Result<()> {
+    let line = line.map(|n| std::ffi::OsString::from(format!("+{}", n)));
+    let args: Vec<&std::ffi::OsStr> = line.as_deref().into_iter().chain([path.as_os_str()]).collect();
+    run_editor(terminal, &args)
+}
+
+// Most events handled between two redraws, and most time spent on them
+const EVENT_BUDGET: usize = 50;
+const FRAME_BUDGET: Duration = Duration::from_millis(10);
+// How often an idle screen is redrawn anyway, for relative times and countdowns
+const IDLE_REDRAW: Duration = Duration::from_secs(1);
+
+fn run_app(
+    terminal: &mut Terminal<CrosstermBackend<Box<dyn Write>>>,
+    app_state: Arc<Mutex<AppState>>,
+    rx: mpsc::Receiver<AppEvent>,
+    tx: mpsc::Sender<AppEvent>,
+    // None in --tail mode
+    agent: &mut Option<Agent>,
+    terminate: &AtomicBool,
+    // Set while Ctrl+G's editor owns the keyboard
+    input_paused: &AtomicBool,
+) -> Result<()> {
+    let poll_interval = Duration::from_millis(app_state.lock().unwrap().config.poll_interval_ms.max(1));
+    // The event that woke the loop, handled first on the next pass
+    let mut waiting: Option<AppEvent> = None;
+    // Cursor shape last sent to the real terminal; None once something else
+    // (the editor) has had it, so any shape, default included, is sent again
+    let mut applied_shape = None;
+    let mut last_draw: Option<Instant> = None;
+    loop {
+        if terminate.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        // A. Process the event that woke us and what's queued behind it, up to
+        // a budget so a flood of events still lets the screen redraw
+        let started = Instant::now();
+        let mut handled = 0;
+        let mut backlog = false;
+        // Ticks and timeouts only redraw what moves on its own; anything else
+        // may have changed what's on screen
+        let mut redraw = app_state.lock().unwrap().animating();
+        let queued = std::iter::from_fn(|| {
+            if handled >= EVENT_BUDGET || started.elapsed() >= FRAME_BUDGET {
+                backlog = true;
+                return None;
+            }
+            handled += 1;
+            rx.try_recv().ok()
+        });
+        for event in waiting.take().into_iter().chain(queued) {
+            redraw |= !matches!(event, AppEvent::Tick);
+            // File events, renames and indexing go to the shared core
+            let Some(event) = engine::handle(&mut app_state.lock().unwrap(), event) else {
+                continue;
+            };
+            match event {
+                // Leftovers from an agent we've since replaced
+                AppEvent::PtyData(generation, _) if agent.as_ref().is_none_or(|a| generation != a.generation) => {}
+                AppEvent::PtyData(_, data) => {
+                     // Only process PTY data if modal is NOT active? 
+                     // No, background PTY should still run/update, just input blocked.
+                    let mut state = app_state.lock().unwrap();
+                    if let Some(dump) = state.pty_dump.as_mut() {
+                        dump.push(&data);
+                    }
+                    state.cursor_shape.feed(&data);
+                    state.output.record(&data, Instant::now());
+                    state.parser.process(&data);
+                    state.last_pty_data = Some(Instant::now());
+
+                    // OSC 0/2 title changes
+                    let title = state.parser.screen().title();
+                    if state.config.forward_title && title != state.child_title {
+                        let title = title.to_string();
+                        execute!(terminal.backend_mut(), SetTitle(&title))?;
+                        state.child_title = title;
+                    }
+
+                    // BEL
+                    let bells = state.parser.screen().audible_bell_count();
+                    if bells != state.bells_seen {
+                        state.bells_seen = bells;
+                        if state.config.bell_flash {
+                            state.bell_flash_until = Some(Instant::now() + Duration::from_millis(300));
+                        }
+                        if state.config.bell_audible {
+                            terminal.backend_mut().write_all(b"\x07")?;
+                            terminal.backend_mut().flush()?;
+                        }
+                    }
+                }
+                AppEvent::Tick => {
+                    // Advance the spinner; the redraw below picks it up
+                    let mut state = app_state.lock().unwrap();
+                    state.spinner_frame = state.spinner_frame.wrapping_add(1);
+                    // Only a pane title or status bar that shows the branch pays for reading it
+                    let wants_branch = state.config.pane_title.as_ref().is_some_and(|t| t.contains("{branch}"))
+                        || state.config.status_bar.shows(StatusSegment::Branch);
+                    if wants_branch && state.branch_read.is_none_or(|t| t.elapsed() >= BRANCH_REFRESH) {
+                        state.git_branch = git::branch(&state.roots.primary().path).unwrap_or_default();
+                        state.branch_read = Some(Instant::now());
+                    }
+                    // Outside a repository there's no branch, and nothing to ask git
+                    if state.config.status_bar.shows(StatusSegment::Branch) && !state.git_branch.is_empty()
+                        && state.git_status.start(Instant::now()) {
+                        let (root, tx) = (state.roots.primary().path.clone(), tx.clone());
+                        std::thread::spawn(move || {
+                            let _ = tx.send(AppEvent::GitStatus(git::dirty(&root)));
+                        });
+                    }
+                }
+                AppEvent::Input(event) => {
+                     let mut state = app_state.lock().unwrap();
+                    match event {
+                         Event::Resize(cols, rows) => {
+                             // We need to handle resize carefully with split panes.
+                             // The PTY size should match the *Terminal Pane* size, not the full window.
+                             // Simple approximation: calc what 70% is.
+                     
+                             // The pane border, if any, takes a cell on each side
+                             let border = if state.config.pane_title.is_some() { 2 } else { 0 };
+                             // Full width when the sidebar is left out for lack of room
+                             state.set_fit(Fit::of(cols, rows));
+                             let share = if state.fit == Fit::Full { 0.7 } else { 1.0 };
+                             let term_cols = ((cols as f32 * share) as u16).saturating_sub(border).max(1);
+                             let term_rows = rows.saturating_sub(border).max(1); // Full height
+                     
+                             if let Some(agent) = agent.as_ref() {
+                                 agent.resize(PtySize {
+                                    rows: term_rows,
+                                    cols: term_cols,
+                                    pixel_width: 0,
+                                    pixel_height: 0,
+                                })?;
+                             }
+                            state.parser = vt100::Parser::new(term_rows, term_cols, command_pick::SCROLLBACK_ROWS);
+                            // The new parser starts counting bells from zero
+                            state.bells_seen = 0;
+                        }
+                        Event::Key(key) => {
+                            // *** INTERRUPT *** Ctrl+C reaches the agent whatever is open;
+                            // pressed again quickly it escalates to signals
+                            if key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL
+                                && state.config.keys.action(&key).is_none()
+                                && let Some(agent) = agent.as_mut() {
+                                agent.writer().write_all(&[3])?; // ETX
+                                agent.writer().flush()?;
+                                let escalate = state.config.interrupt_escalation;
+                                let signal = match state.interrupts.press(Instant::now(), escalate) {
+                                    interrupt::Step::Interrupt => continue,
+                                    interrupt::Step::OfferKill => {
+                                        state.notify(Severity::Warning, "Agent still running - Ctrl+C once more sends SIGKILL".to_string());
+                                        continue;
+                                    }
+                                    interrupt::Step::Terminate => agent::Signal::Terminate,
+                                    interrupt::Step::Kill => agent::Signal::Kill,
+                                };
+                                let name = signal.name();
+                                match agent.signal(signal) {
+                                    Ok(()) => state.notify(Severity::Warning, format!("Sent {} to the agent", name)),
+                                    Err(e) => state.notify(Severity::Error, format!("Couldn't send {} to the agent: {}", name, e)),
+                                }
+                                continue;
+                            }
+
+                            // *** TOO SMALL *** Nothing but the size warning is drawn, so
+                            // only Ctrl+Q goes anywhere: the quit prompt waits for a bigger
+                            // terminal, and a second Ctrl+Q quits as-is
+                            if state.fit == Fit::TooSmall {
+                                if state.config.keys.action(&key) == Some(Action::Quit) {
+                                    if state.quit_prompt || state.approval_queue.is_empty() {
+                                        return Ok(());
+                                    }
+                                    state.quit_prompt = true;
+                                }
+                                continue;
+                            }
+
+                            // *** QUIT PROMPT *** (a second Ctrl+Q quits as-is)
+                            if state.quit_prompt {
+                                match key.code {
+                                    _ if state.config.keys.action(&key) == Some(Action::Quit) => return Ok(()),
+                                    KeyCode::Char('a') => {
+                                        if state.resolve_all(true) {
+                                            return Ok(());
+                                        }
+                                        // What couldn't be written is back in the modal, with its error
+                                        state.quit_prompt = false;
+                                    }
+                                    KeyCode::Char('r') => {
+                                        let done = state.request_reject_all();
+                                        if done {
+                                            return Ok(());
+                                        }
+                                    }
+                                    KeyCode::Esc | KeyCode::Char('c') => state.quit_prompt = false,
+                                    _ => {}
+                                }
+                                continue;
+                            }
+
+                            // *** FIRST-REVERT PROMPT ***
+                            if state.revert_prompt {
+                                match key.code {
+                                    KeyCode::Char(c @ ('y' | 'a')) => {
+                                        let quit = state.confirm_revert(c == 'a');
+                                        if quit {
+                                            return Ok(());
+                                        }
+                                    }
+                                    KeyCode::Esc | KeyCode::Char('n') => {
+                                        state.revert_prompt = false;
+                                        state.quit_after_revert = false;
+                                    }
+                                    _ => {}
+                                }
+                                continue;
+                            }
+
+                            // *** AGENT RESTART/SWAP PROMPT ***
+                            if let Some(prompt) = state.agent_prompt.as_mut() && let Some(agent) = agent.as_mut() {
+                                match (prompt, key.code) {
+                                    (AgentPrompt::Confirm, KeyCode::Char('y')) => {
+                                        let command = agent.command.clone();
+                                        restart_agent(&mut state, agent, &command, &tx);
+                                    }
+                                    (AgentPrompt::Confirm, KeyCode::Char('e')) => {
+                                        state.agent_prompt = Some(AgentPrompt::Edit(agent.command.clone()));
+                                    }
+                                    (AgentPrompt::Edit(input), KeyCode::Char(c)) => input.push(c),
+                                    (AgentPrompt::Edit(input), KeyCode::Backspace) => {
+                                        input.pop();
+                                    }
+                                    (AgentPrompt::Edit(input), KeyCode::Enter) => {
+                                        let command = std::mem::take(input);
+                                        restart_agent(&mut state, agent, &command, &tx);
+                                    }
+                                    (AgentPrompt::Error(_), _) | (_, KeyCode::Esc) => state.agent_prompt = None,
+                                    _ => {}
+                                }
+                                continue;
+                            }
+
+                            // *** SNIPPET PICKER ***
+                            if state.snippet_picker.is_some() {
+                                if let Some(text) = state.handle_snippet_key(key)
+                                    && let Some(agent) = agent.as_mut() {
+                                    let modes = KeyModes::from_screen(state.parser.screen());
+                                    agent.writer().write_all(&keys::paste(&text, modes, false))?;
+                                    agent.writer().flush()?;
+                                }
+                                continue;
+                            }
+
+                            // *** NOTIFICATION LOG ***
+                            if let Some(view) = state.notifications.as_mut() {
+                                if !view.handle_key(key) || state.config.keys.action(&key) == Some(Action::Notifications) {
+                                    state.notifications = None;
+                                }
+                                continue;
+                            }
+
+                            // *** PTY DUMP ***
+                            if let Some(view) = state.dump_view.as_mut() {
+                                if !view.handle_key(key) || state.config.keys.action(&key) == Some(Action::PtyDump) {
+                                    state.dump_view = None;
+                                }
+                                continue;
+                            }
+
+                            // *** PICK COMMAND ***
+                            if let Some(pick) = state.command_pick.as_mut() {
+                                match pick.handle_key(key) {
+                                    PickOutcome::Open => {}
+                                    PickOutcome::Close => state.command_pick = None,
+                                    PickOutcome::Send(text) => {
+                                        state.command_pick = None;
+                                        if let Some(agent) = agent.as_mut() {
+                                            let modes = KeyModes::from_screen(state.parser.screen());
+                                            agent.writer().write_all(&keys::paste(&text, modes, true))?;
+                                            agent.writer().flush()?;
+                                        }
+                                    }
+                                }
+                                continue;
+                            }
+
+                            // *** CHURN RANKING ***
+                            if let Some(order) = state.churn_view {
+                                state.churn_view = match key.code {
+                                    KeyCode::Char('s') => Some(order.toggle()),
+                                    KeyCode::Esc | KeyCode::Char('q') => None,
+                                    _ if state.config.keys.action(&key) == Some(Action::Churn) => None,
+                                    _ => Some(order),
+                                };
+                                continue;
+                            }
+
+                            // *** QUICK OPEN ***
+                            if state.quick_open.is_some() {
+                                state.handle_quick_open_key(key);
+                                continue;
+                            }
+
+                            // *** INLINE APPROVAL BANNER *** (only review_banner, or y/n/d/Esc while armed, leave the PTY)
+                            if state.approval_ui == ApprovalUi::Inline && !state.approval_queue.is_empty() {
+                                if state.config.keys.action(&key) == Some(Action::ReviewBanner) {
+                                    state.banner_armed = !state.banner_armed;
+                                    continue;
+                                }
+                                // Everything else still types into the agent, armed or not
+                                if state.banner_armed && key.modifiers.is_empty()
+                                    && matches!(key.code, KeyCode::Char('y' | 'n' | 'd') | KeyCode::Esc) {
+                                    match key.code {
+                                        KeyCode::Char('y') => state.accept_front(),
+                                        KeyCode::Char('n') => state.request_reject(),
+                                        KeyCode::Char('d') => state.open_full_diff(),
+                                        _ => state.banner_armed = false,
+                                    }
+                                    continue;
+                                }
+                            }
+
+                            // *** MODAL INTERCEPTION ***
+                            if state.modal_active && state.approval_ui == ApprovalUi::Modal {
+                                if let Some((path, line)) = state.handle_modal_key(key) {
+                                    input_paused.store(true, Ordering::Relaxed);
+                                    let edited = edit_pending(terminal, &path, line);
+                                    input_paused.store(false, Ordering::Relaxed);
+                                    applied_shape = None;
+                                    if let Err(e) = edited {
+                                        state.status_message = Some((format!("Editor failed: {:#}", e), Instant::now()));
+                                    }
+                                }
+                                continue; // SKIP NORMAL PROCESSING
+                            }
+
+                            // *** DIFF VIEW HUNK NAVIGATION ***
+                            if state.show_diff_view && matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+                                state.pause_follow();
+                                state.prepare_selected_diff();
+                                let total = state.shown_diff().map_or(0, |s| s.hunks.len());
+                                if key.code == KeyCode::Tab {
+                                    state.diff_cursor.next(total);
+                                } else {
+                                    state.diff_cursor.prev(total);
+                                }
+                                continue;
+                            }
+
+                            // *** DIFF VIEW PAGING *** (the sidebar keeps PgUp/PgDn when focused)
+                            if state.show_diff_view && state.focus == Focus::Terminal && key.modifiers.is_empty()
+                                && matches!(key.code, KeyCode::PageUp | KeyCode::PageDown) {
+                                state.pause_follow();
+                                state.prepare_selected_diff();
+                                let (offsets, line_count) = state.shown_diff().map_or((Vec::new(), 0), |s| {
+                                    (diff::hunk_offsets(s.hunks), s.diff.map_or(0, DiffText::line_count))
+                                });
+                                let rows = state.diff_page_rows();
+                                state.diff_cursor.page(key.code == KeyCode::PageDown, rows, &offsets, line_count);
+                                continue;
+                            }
+
+                            // *** DIFF VIEW WRAP / WHITESPACE TOGGLES, MARKDOWN COPY ***
+                            if state.show_diff_view && key.code == KeyCode::Char('w') && key.modifiers.is_empty() {
+                                state.diff_wrap = !state.diff_wrap;
+                                continue;
+                            }
+                            if state.show_diff_view && key.code == KeyCode::Char('s') && key.modifiers.is_empty() {
+                                state.diff_whitespace = !state.diff_whitespace;
+                                continue;
+                            }
+                            if state.show_diff_view && key.code == KeyCode::Char('m') && key.modifiers.is_empty() {
+                                state.copy_selected_markdown();
+                                continue;
+                            }
+
+                            // *** DIFF VIEW CONTEXT WIDTH *** (`=` is `+` without Shift)
+                            if state.show_diff_view && matches!(key.code, KeyCode::Char('+' | '=' | '-'))
+                                && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+                                state.diff_context = diff::step_context(state.diff_context, key.code != KeyCode::Char('-'));
+                                state.diff_cursor = HunkCursor::default();
+                                state.prepare_selected_diff();
+                                continue;
+                            }
+
+                            // *** FOLLOW TOGGLE *** (also `F` in the sidebar)
+                            if state.show_diff_view && key.code == KeyCode::Char('F') && !key.modifiers.contains(KeyModifiers::CONTROL) {
+                                state.follow = state.follow.toggle();
+                                continue;
+                            }
+
+                            // *** DIFF VIEW PATH ACTIONS ***
+                            if state.show_diff_view && let Some(action) = state.config.keys.diff_view_action(&key) {
+                                match action {
+                                    Action::CopyPath => state.copy_selected_path(),
+                                    _ => state.reveal_selected(),
+                                }
+                                continue;
+                            }
+
+                            // *** SIDEBAR FOCUS *** (Ctrl and [keys] bindings still work below)
+                            if state.focus == Focus::Sidebar && !key.modifiers.contains(KeyModifiers::CONTROL)
+                                && state.config.keys.action(&key).is_none() {
+                                state.handle_sidebar_key(key);
+                                continue;
+                            }
+
+                            // *** NORMAL PROCESSING ***
+                            // In --tail mode keys meant for the agent go nowhere
+                            let tail = agent.is_none();
+                            let mut sink = std::io::sink();
+                            let writer: &mut dyn Write = match agent.as_mut() {
+                                Some(agent) => agent.writer(),
+                                None => &mut sink,
+                            };
+                            if !tail {
+                                state.note_typed(&key);
+                            }
+                            // With no inline approval waiting, review_banner's key is the
+                            // agent's (Ctrl+A is start of line in most shells)
+                            let action = state.config.keys.action(&key).filter(|&a| a != Action::ReviewBanner);
+                            match action {
+                                Some(Action::RestartAgent) if !tail => {
+                                    state.agent_prompt = Some(AgentPrompt::Confirm);
+                                }
+                                Some(Action::Quit) => {
+                                    if state.approval_queue.is_empty() {
+                                        return Ok(());
+                                    }
+                                    state.quit_prompt = true;
+                                }
+                                Some(Action::ToggleDiff) => {
+                                     state.show_diff_view = !state.show_diff_view;
+                                     state.diff_cursor.reset();
+                                     // Back to the approval we left with Ctrl+K
+                                     if !state.show_diff_view && !state.approval_queue.is_empty() {
+                                         state.modal_active = true;
+                                     }
+                                }
+                                Some(Action::ToggleSidebar) => {
+                                    state.show_sidebar = !state.show_sidebar;
+                                    if !state.show_sidebar {
+                                        state.focus = Focus::Terminal;
+                                    }
+                                }
+                                Some(Action::ToggleFocus) => {
+                                    state.focus = match state.focus {
+                                        Focus::Terminal if state.show_sidebar && state.fit == Fit::Full => Focus::Sidebar,
+                                        _ => Focus::Terminal,
+                                    };
+                                }
+                                Some(Action::ClearLog) => state.clear_log(),
+                                Some(Action::Export) => {
+                                    let format = state.config.export_format;
+                                    let path = state.roots.primary().path.join(format.default_file_name());
+                                    match state.export(&path, format) {
+                                        Ok(()) => state.notify(Severity::Success, format!("Exported to {}", path.display())),
+                                        Err(e) => state.notify(Severity::Error, format!("Export failed: {:#}", e)),
+                                    }
+                                }
+                                Some(Action::Stats) => {
+                                    state.show_stats = !state.show_stats;
+                                    state.show_diffstat = false;
+                                }
+                                Some(Action::Diffstat) => {
+                                    state.show_diffstat = !state.show_diffstat;
+                                    state.show_stats = false;
+                                }
+                                Some(Action::Notifications) => state.notifications = Some(LogView::default()),
+                                Some(Action::Churn) => state.churn_view = Some(ChurnOrder::default()),
+                                Some(Action::Commit) => {
+                                    if let Some((root, paths, message)) = state.begin_commit() {
+                                        let tx = tx.clone();
+                                        std::thread::spawn(move || {
+                                            let _ = tx.send(AppEvent::GitCommit(git::commit(&root, &paths, &message)));
+                                        });
+                                    }
+                                }
+                                Some(Action::Rescan) => {
+                                    if state.begin_rescan() {
+                                        indexer::spawn(state.roots.to_vec(), state.config.clone(), tx.clone());
+                                    }
+                                }
+                                Some(Action::PtyDump) if state.pty_dump.is_some() => state.dump_view = Some(DumpView::default()),
+                                Some(Action::QuickOpen) => {
+                                    state.quick_open = Some(QuickOpen::default());
+                                }
+                                Some(Action::Snippets) if !tail => state.open_snippets(),
+                                Some(Action::PickCommand) if !tail && !state.show_diff_view => state.open_command_pick(),
+                                Some(Action::Editor) if !tail => {
+                                    input_paused.store(true, Ordering::Relaxed);
+                                    let prompt = compose_in_editor(terminal);
+                                    input_paused.store(false, Ordering::Relaxed);
+                                    // The editor had the cursor in its own shape
+                                    applied_shape = None;
+                                    match prompt {
+                                        Ok(text) if !text.trim().is_empty() => {
+                                            let cap = state.config.prompt_history;
+                                            snippets::remember(&mut state.prompt_history, &text, cap);
+                                            let modes = KeyModes::from_screen(state.parser.screen());
+                                            writer.write_all(&keys::paste(&text, modes, state.config.editor_submit))?;
+                                        }
+                                        Ok(_) => state.status_message = Some(("Empty prompt - nothing sent".to_string(), Instant::now())),
+                                        Err(e) => state.status_message = Some((format!("Editor failed: {:#}", e), Instant::now())),
+                                    }
+                                }
+                                Some(Action::CycleTheme) => state.current_theme = state.current_theme.cycle(),
+
+                                Some(Action::SelectPrev) => state.select_prev(),
+                                Some(Action::SelectNext) => state.select_next(),
+                                Some(Action::PagePrev) => state.select_page(false),
+                                Some(Action::PageNext) => state.select_page(true),
+                                // Agent-only actions in --tail mode
+                                Some(_) => {}
+                                None => match key.code {
+                                    // Plain Ctrl+C is taken above; this is Ctrl+Shift+C and the like
+                                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => writer.write_all(&[3])?, // ETX
+                                    KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => writer.write_all(&[4])?, // EOT
+                                    // Pass through to PTY, honoring the child's cursor/keypad modes
+                                    _ if let Some(bytes) = keys::encode(&key, KeyModes::from_screen(state.parser.screen())) => {
+                                        writer.write_all(&bytes)?
+                                    }
+                                    KeyCode::Char(c) => writer.write_all(c.to_string().as_bytes())?,
+                                    KeyCode::Enter => writer.write_all(b"\r")?,
+                                    KeyCode::Backspace => writer.write_all(&[127])?,
+                                    KeyCode::Tab => writer.write_all(&[9])?,
+                                    KeyCode::Esc => writer.write_all(&[27])?,
+                                    _ => {}
+                                },
+                            }
+                            writer.flush()?;
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Copy requests go straight to the real terminal
+        if let Some(text) = app_state.lock().unwrap().clipboard.take() {
+            terminal.backend_mut().write_all(desktop::osc52_copy(&text).as_bytes())?;
+            terminal.backend_mut().flush()?;
+        }
+
+        // The agent's cursor shape, carried over to the real cursor
+        let shape = app_state.lock().unwrap().cursor_shape.shape;
+        if applied_shape != Some(shape) {
+            execute!(terminal.backend_mut(), cursor_style(shape))?;
+            applied_shape = Some(shape);
+        }
+
+        // B. Render, unless nothing on screen could have changed
+        redraw |= app_state.lock().unwrap().animating();
+        if redraw || last_draw.is_none_or(|t| t.elapsed() >= IDLE_REDRAW) {
+            terminal.draw(|frame| {
+                let mut state = app_state.lock().unwrap();
+                draw(frame, &mut state, agent.as_ref());
+            })?;
+            last_draw = Some(Instant::now());
+        }
+
+        // C. Sleep until something arrives, waking at least every poll
+        // interval. Leftovers from A go straight on.
+        waiting = if backlog { None } else { rx.recv_timeout(poll_interval).ok() };
+    }
+}
+
+// One frame of the whole UI
+fn draw(frame: &mut ratatui::Frame, state: &mut AppState, agent: Option<&Agent>) {
+    // Resolve Theme
+    let theme = Theme::new(state.current_theme).with_diff_colors(state.config.diff_colors.as_ref()).degrade(state.color_depth);
+
+    let area = frame.area();
+    let fit = Fit::of(area.width, area.height);
+    if fit == Fit::TooSmall {
+        ui::components::too_small::render(frame, area, MIN_SIZE, &theme);
+        return;
+    }
+    let compact = fit == Fit::Compact;
+
+    // 1. Vertical Split
+    // Inline approvals get a row of their own, always reserved so the pane doesn't jump.
+    // The modal gets one too while `d` has it minimized under the diff view.
+    let inline = state.approval_ui == ApprovalUi::Inline;
+    let minimized = !inline && !state.modal_active && state.show_diff_view && !state.approval_queue.is_empty();
+    let v_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Percentage((100 - percent_y) / 2),
-            Constraint::Percentage(percent_y),
-            Constraint::Percentage((100 - percent_y) / 2),
-        ])
-        .split(r);
-
-    Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage((100 - percent_x) / 2),
-            Constraint::Percentage(percent_x),
-            Constraint::Percentage((100 - percent_x) / 2),
-        ])
-        .split(popup_layout[1])[1]
-}
-
-fn convert_color(c: vt100::Color) -> Color {
-    match c {
-        vt100::Color::Default => Color::Reset,
-        vt100::Color::Idx(i) => Color::Indexed(i),
-        vt100::Color::Rgb(r, g, b) => Color::Rgb(r, g, b),
-    }
-}
-
-fn normalize_path(path: &std::path::Path) -> String {
-    // Attempt canonicalization to resolve symlinks/relativity
-    if let Ok(abs) = std::fs::canonicalize(path) {
-        return abs.to_string_lossy()
-            .trim_start_matches(r"\\?\")
-            .to_string();
-    }
-    // Fallback if file missing (e.g. deleted)
-    // Assume path is already absolute (from notify) or close to it
-    path.to_string_lossy()
-        .trim_start_matches(r"\\?\")
-        .to_string()
+        .constraints([Constraint::Min(1), Constraint::Length((inline || minimized) as u16), Constraint::Length(1)])
+        .split(area);
+        
+    let main_area = v_chunks[0];
+    let banner_area = v_chunks[1];
+    let status_area = v_chunks[2];
+
+    // 2. Horizontal Split
+    // A small terminal leaves the sidebar out; Ctrl+K still shows the selected diff
+    let (term_area, side_area) = if state.show_sidebar && !compact {
+        let h_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
+            .split(main_area);
+        (h_chunks[0], Some(h_chunks[1]))
+    } else {
+        (main_area, None)
+    };
+    // The Checks panel takes the bottom of the sidebar column
+    let (side_area, checks_area) = match side_area {
+        Some(side) if state.tests.is_some() => {
+            let wanted = ui::components::checks_panel::height(state.checks_collapsed).min(side.height / 2);
+            let v = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(1), Constraint::Length(wanted)])
+                .split(side);
+            (Some(v[0]), Some(v[1]))
+        }
+        other => (other, None),
+    };
+
+    // The approval modal floats over everything; the agent keeps running behind it
+    let modal_up = state.modal_active && !inline;
+    // Where the agent's cursor is, so the modal can keep clear of it
+    let mut agent_cursor = None;
+
+    // --- Render Terminal OR Diff View ---
+    if state.show_diff_view {
+         state.prepare_selected_diff();
+         ui::components::diff_view::render(frame, term_area, state.shown_diff(), &state.diff_cursor, state.diff_wrap, state.diff_whitespace, &theme);
+    } else if agent.is_none() {
+        let roots: Vec<String> = state.roots.iter().map(|r| r.path.display().to_string()).collect();
+        ui::components::tail_panel::render(frame, term_area, &roots, &theme);
+    } else {
+        let pane = match (&state.config.pane_title, agent) {
+            (Some(template), Some(agent)) => {
+                let cwd = ui::components::terminal::tilde(&state.roots.primary().path, dirs::home_dir().as_deref());
+                let title = ui::components::terminal::expand_title(template, &ui::components::terminal::PaneTitle {
+                    cmd: &agent.command,
+                    cwd: &cwd,
+                    branch: &state.git_branch,
+                    pending: state.approval_queue.len(),
+                });
+                let border = if state.focus == Focus::Terminal { theme.border_focus } else { theme.border_dim };
+                let block = Block::default().title(title).borders(Borders::ALL).border_set(theme.border_set()).border_style(Style::default().fg(border));
+                let inner = block.inner(term_area);
+                frame.render_widget(block, term_area);
+                inner
+            }
+            _ => term_area,
+        };
+        let screen = state.parser.screen();
+        ui::components::terminal::render(frame.buffer_mut(), pane, screen);
+        if let Some(pick) = &state.command_pick {
+            ui::components::command_pick::render(frame, pane, pick, &theme);
+        }
+        if modal_up {
+            // Still live, just out of the way
+            frame.buffer_mut().set_style(pane, Style::default().add_modifier(Modifier::DIM));
+        }
+        if !screen.hide_cursor() {
+            let (crow, ccol) = screen.cursor_position();
+            if ccol < pane.width && crow < pane.height {
+                agent_cursor = Some(Position { x: pane.x + ccol, y: pane.y + crow });
+            }
+        }
+        // Keys go to the modal while it's up; the cursor comes back with
+        // the first frame after it closes. Inline approvals leave it be.
+        if let Some(position) = agent_cursor.filter(|_| !modal_up) {
+            frame.set_cursor_position(position);
+        }
+        // The pane has no border of its own; draw one briefly over the edge on BEL
+        if state.bell_flash_until.is_some_and(|t| Instant::now() < t) {
+            let flash = Block::default()
+                .borders(Borders::ALL)
+                .border_set(theme.border_set())
+                .border_style(Style::default().fg(theme.status_warning));
+            frame.render_widget(flash, term_area);
+        }
+    }
+    
+    // --- Render Sidebar ---
+    // Only entries passing the sidebar filter are shown or counted
+    let inner = &mut *state;
+    let visible: Vec<&FileChange> = inner.visible_indices().into_iter()
+        .map(|i| &inner.file_changes[i])
+        .collect();
+    if let Some(area) = side_area {
+        inner.sidebar_rows = area.height.saturating_sub(2) as usize;
+        let row = inner.selected_row();
+        inner.list_state.select(row);
+        let view = SidebarView {
+            focused: inner.focus == Focus::Sidebar,
+            time_format: inner.time_format,
+            marked: &inner.marked,
+        };
+        ui::components::sidebar::render(frame, area, &visible, &mut inner.list_state, &inner.sidebar_filter, &view, &theme);
+    }
+    if let (Some(area), Some(tests)) = (checks_area, &inner.tests) {
+        ui::components::checks_panel::render(frame, area, tests, inner.checks_collapsed, &theme);
+    }
+
+    // --- Render Status Bar ---
+    let hints = inner.config.keys.hints();
+    let info = StatusInfo {
+        filtered: inner.sidebar_filter.is_active(),
+        working: inner.last_pty_data.is_some_and(|t| t.elapsed() < Duration::from_millis(500)),
+        spinner_frame: inner.spinner_frame,
+        indexing: inner.indexing,
+        rescanning: inner.rescan.is_some(),
+        index_truncated: inner.index_truncated,
+        title: &inner.child_title,
+        agent: agent.map_or("none (--tail)", |a| a.command.as_str()),
+        branch: &inner.git_branch,
+        dirty: inner.git_status.dirty == Some(true),
+        follow: inner.follow.label(),
+        interrupt: inner.interrupts.label(Instant::now()),
+        watch_problem: inner.toasts.latest_problem(Duration::from_secs(30)),
+        insertions: inner.stats.insertions,
+        deletions: inner.stats.deletions,
+        tests: inner.tests.as_ref().map(|t| (t.elapsed(), t.last.as_ref())),
+        message: inner.status_message.as_ref()
+            .filter(|(_, at)| at.elapsed() < Duration::from_secs(5))
+            .map(|(text, _)| text.as_str()),
+        pending: inner.approval_queue.len(),
+        hints: &hints,
+    };
+    if compact {
+        ui::components::status_bar::render_compact(frame, status_area, &visible, &info, &inner.config.status_bar, &theme);
+    } else {
+        ui::components::status_bar::render(frame, status_area, &visible, &info, &inner.config.status_bar, &theme);
+    }
+
+    // --- Render Inline Approval Banner ---
+    if inline || minimized {
+        let head = state.approval_queue.front().map(|p| (p, state.display_path(p.path.path()).unwrap_or_else(|| p.path.to_string_lossy().into_owned())));
+        let head = head.as_ref().map(|(p, display)| BannerHead {
+            path: display,
+            insertions: p.insertions,
+            deletions: p.deletions,
+            protected: p.protected,
+        });
+        let back = state.config.keys.label(Action::ToggleDiff).unwrap_or_default();
+        let review = state.config.keys.label(Action::ReviewBanner).unwrap_or_default();
+        let mode = match (minimized, state.banner_armed) {
+            (true, _) => BannerMode::Minimized { back: &back },
+            (false, true) => BannerMode::Armed,
+            (false, false) => BannerMode::Unarmed { review: &review },
+        };
+        let reject_mode = state.approval_queue.front().map_or(state.config.reject_mode, |p| state.reject_mode_for(p));
+        ui::components::approval_banner::render(frame, banner_area, state.approval_queue.len(), head.as_ref(), mode, reject_mode, &theme);
+    }
+
+    // --- Render Stats Overlay ---
+    if state.show_stats {
+        let quiet = state.last_pty_data.map(|t| t.elapsed());
+        ui::components::stats::render(frame, centered_rect(50, 60, area), &state.stats, &state.output, quiet, &theme);
+    }
+    if state.show_diffstat {
+        let stats = ui::components::diffstat::aggregate(&state.file_changes);
+        ui::components::diffstat::render(frame, centered_rect(80, 70, area), &stats, &theme);
+    }
+    if let Some(view) = state.notifications.as_mut() {
+        let key = state.config.keys.label(Action::Notifications).unwrap_or_default();
+        ui::components::toasts::render_log(frame, centered_rect(70, 60, area), state.toasts.log(), view, &key, &theme);
+    }
+
+    if let Some(order) = state.churn_view {
+        let key = state.config.keys.label(Action::Churn).unwrap_or_default();
+        ui::components::churn::render(frame, centered_rect(70, 60, area), &state.churn.ranked(order), order, &key, &theme);
+    }
+
+    let inner = &mut *state;
+    if let (Some(dump), Some(view)) = (&inner.pty_dump, &mut inner.dump_view) {
+        let key = inner.config.keys.label(Action::PtyDump).unwrap_or_default();
+        ui::components::pty_dump::render(frame, centered_rect(90, 80, area), dump, view, &key, &theme);
+    }
+
+    if state.mark_menu {
+        let menu = centered_rect(30, 30, area);
+        ui::components::mark_menu::render(frame, Rect { height: menu.height.min(7), ..menu }, state.marked.len(), &theme);
+    }
+
+    // --- Render Quit Prompt ---
+    if state.quit_prompt {
+        ui::components::quit_prompt::render(frame, centered_rect(40, 30, area), state.approval_queue.len(), state.config.reject_mode, &theme);
+    }
+
+    // --- Render Agent Restart/Swap Prompt ---
+    if let Some(prompt) = &state.agent_prompt && let Some(agent) = agent {
+        ui::components::agent_prompt::render(frame, centered_rect(50, 30, area), prompt, &agent.command, &theme);
+    }
+
+    // --- Render Quick Open ---
+    if let Some(prompt) = &state.quick_open {
+        let matches: Vec<&FileChange> = prompt.matches(&state.file_changes).into_iter()
+            .map(|i| &state.file_changes[i])
+            .collect();
+        ui::components::quick_open::render(frame, centered_rect(60, 50, area), prompt, &matches, &theme);
+    }
+    if let Some(picker) = &state.snippet_picker {
+        ui::components::snippet_picker::render(frame, centered_rect(60, 50, area), picker, &theme);
+    }
+
+    // --- Render Approval Modal ---
+    if modal_up && let Some(pending) = state.approval_queue.front() {
+        // Room for the diff plus the warnings that can sit above it
+        let content_rows = pending.diff_text.line_count().max(state.revert_preview.as_ref().map_or(0, |p| p.diff_text.line_count()))
+            + 6 + if state.approval_queue.len() > 1 { state.approval_queue.len().min(QUEUE_LIST + 1) + 1 } else { 0 };
+        let modal_area = modal_rect(area, content_rows, agent_cursor);
+        frame.render_widget(Clear, modal_area);
+
+        // Either the approval diff or the dry run of a reject
+        let (mut title, diff_text, hunks, stale) = match &state.revert_preview {
+            Some(preview) => (
+                format!(" Reject preview: {} ", pending.path),
+                &preview.diff_text,
+                &preview.hunks,
+                preview.stale,
+            ),
+            None => (
+                format!(" Approve change: {} ", pending.path),
+                &pending.diff_text,
+                &pending.hunks,
+                false,
+            ),
+        };
+        if pending.insertions + pending.deletions > 0 {
+            title.push_str(&format!("+{} {}{} ", pending.insertions, theme.symbol("−", "-"), pending.deletions));
+        }
+        if state.approval_queue.len() > 1 {
+            title.push_str(&format!("(1 of {}) ", state.approval_queue.len()));
+        }
+        if pending.protected {
+            title.insert_str(0, " QUARANTINED");
+        }
+        if pending.refreshed {
+            title.push_str("(changed again on disk - diff refreshed) ");
+        }
+        if pending.baseline_missing {
+            title.push_str("(no baseline yet - still indexing) ");
+        }
+        if let Some(left) = state.approval_time_left() {
+            let secs = left.as_secs();
+            title.push_str(&format!("({} in {}:{:02}) ", state.config.approval_timeout_action.verb(), secs / 60, secs % 60));
+        }
+        let check = state.checks.get(&pending.change_id);
+        match check {
+            Some(CheckStatus::Pending) => title.push_str(&format!("(checking{}) ", theme.symbol("…", "..."))),
+            Some(CheckStatus::Passed) => title.push_str(&format!("[check {}] ", theme.symbol("✓", "ok"))),
+            Some(CheckStatus::Failed(_) | CheckStatus::Error(_)) => title.push_str(&format!("[check {}] ", theme.symbol("✗", "FAILED"))),
+            None => {}
+        }
+        // Dropped hunks only apply to the approval diff, not the preview
+        let decisions: &[bool] = if state.revert_preview.is_some() { &[] } else { &pending.hunk_decisions };
+        if pending.is_partial() {
+            let kept = (0..pending.hunks.len()).filter(|&i| decisions.get(i).copied().unwrap_or(true)).count();
+            title.push_str(&format!("(keeping {} of {} hunks) ", kept, pending.hunks.len()));
+        }
+        // Nothing to preview when rejecting leaves the file alone
+        let reject_mode = state.reject_mode_for(pending);
+        let preview_hint = match (reject_mode, &state.revert_preview) {
+            (RejectMode::Dismiss, _) => "",
+            (_, Some(_)) => "[r] Back to diff  ",
+            (_, None) => "[r] Preview reject  ",
+        };
+        let pick_hint = if state.approval_queue.len() > 1 { "[1-9] Pick  " } else { "" };
+        // `d`, and toggle_diff wherever it's bound
+        let diff_keys = state.config.keys.label(Action::ToggleDiff).map_or_else(|| "d".to_string(), |key| format!("d/{}", key));
+        let footer = format!(
+            " [y] Accept  {}  [{}] Full diff  [o] Edit  {}{}[Space] Keep/drop hunk  [Tab/Shift+Tab] Next/Prev hunk  {} ",
+            reject_mode.hint(),
+            diff_keys,
+            preview_hint,
+            pick_hint,
+            state.modal_cursor.label(hunks.len())
+        );
+
+        let block = Block::default()
+            .title(title)
+            .title_bottom(footer)
+            .borders(Borders::ALL)
+            .border_set(theme.border_set())
+            .border_style(Style::default().fg(if stale || pending.protected { theme.status_error } else { theme.border_focus }))
+            .style(Style::default().bg(theme.bg_primary));
+
+        let mut lines: Vec<Line> = Vec::new();
+        // The queue, numbered for picking out of order; ▸ is the one on screen
+        if state.approval_queue.len() > 1 {
+            for (i, queued) in state.approval_queue.iter().take(QUEUE_LIST).enumerate() {
+                let path = state.display_path(queued.path.path()).unwrap_or_else(|| queued.path.to_string_lossy().into_owned());
+                let marker = if i == 0 { theme.symbol("▸", ">") } else { " " };
+                let style = if i == 0 {
+                    Style::default().fg(theme.text_main).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(theme.text_muted)
+                };
+                lines.push(Line::from(Span::styled(
+                    format!("{} [{}] {}  +{} {}{}", marker, i + 1, path, queued.insertions, theme.symbol("−", "-"), queued.deletions),
+                    style,
+                )));
+            }
+            let more = state.approval_queue.len().saturating_sub(QUEUE_LIST);
+            if more > 0 {
+                lines.push(Line::from(Span::styled(format!("    {} {} more", theme.symbol("…", "..."), more), Style::default().fg(theme.text_muted))));
+            }
+            lines.push(Line::from(""));
+        }
+        if pending.protected {
+            lines.push(Line::from(Span::styled(
+                "!! PROTECTED PATH - this file matches a `protected` rule; accepting lets the agent's edit stand !!",
+                Style::default().fg(theme.bg_primary).bg(theme.status_error).add_modifier(Modifier::BOLD),
+            )));
+            lines.push(Line::from(""));
+        }
+        if stale {
+            lines.push(Line::from(Span::styled(
+                "!! File changed on disk since this diff was computed - rejecting will overwrite those edits !!",
+                Style::default().fg(theme.status_error).add_modifier(Modifier::BOLD),
+            )));
+            lines.push(Line::from(""));
+        }
+        // The diff below is taken without them
+        if let Some((from, to)) = pending.line_endings.filter(|_| !pending.eol_only) {
+            lines.push(Line::from(Span::styled(
+                format!("Also changes line endings {} {} {}", from.label(), theme.symbol("→", "->"), to.label()),
+                Style::default().fg(theme.status_warning).add_modifier(Modifier::BOLD),
+            )));
+            lines.push(Line::from(""));
+        }
+        // Never blocks approval; just says what the check made of it
+        let failure: Vec<String> = match check {
+            Some(CheckStatus::Failed(output)) => output.clone(),
+            Some(CheckStatus::Error(e)) => vec![format!("check_command didn't run: {}", e)],
+            _ => Vec::new(),
+        };
+        if !failure.is_empty() {
+            lines.extend(failure.into_iter().map(|l| Line::from(Span::styled(l, Style::default().fg(theme.status_error)))));
+            lines.push(Line::from(""));
+        }
+        // Warnings stay pinned above the diff. The diff itself is one page
+        // starting at the selected hunk; only that page is styled.
+        // A new file shows only the top of its listing here.
+        let offsets = diff::hunk_offsets(hunks);
+        let start = state.modal_cursor.scroll(&offsets);
+        let total = diff_text.line_count();
+        let new_file = diff::is_new_file(diff_text);
+        let shown = if new_file { diff::preview_rows(diff_text, state.config.new_file_preview_lines) } else { total };
+        let mut room = (modal_area.height.saturating_sub(2) as usize).saturating_sub(lines.len());
+        if total > start + room {
+            room = room.saturating_sub(1); // for the hint
+        }
+        let end = (start + room).min(shown);
+        let more = total.saturating_sub(end);
+
+        let colored = if new_file {
+            diff::new_file_preview(diff_text, start..end, syntax::Syntax::for_path(pending.path.path()), &theme)
+        } else {
+            diff::diff_lines_to_spans(diff_text, start..end, &theme, state.diff_whitespace)
+        };
+        lines.extend(colored.into_iter().zip(diff_text.lines(start..end)).enumerate().map(|(row, (line, line_str))| {
+            // Hunk this line belongs to, and whether it was dropped
+            let idx = start + row;
+            let hunk = offsets.partition_point(|&o| o <= idx).checked_sub(1);
+            let dropped = hunk.is_some_and(|h| decisions.get(h) == Some(&false));
+            if !dropped {
+                return line;
+            }
+            let mut line = Line::from(Span::styled(
+                line_str,
+                Style::default().fg(theme.text_muted).add_modifier(Modifier::CROSSED_OUT),
+            ));
+            if hunk.and_then(|h| offsets.get(h)) == Some(&idx) {
+                line.push_span(Span::styled(" [dropped]", Style::default().fg(theme.status_error)));
+            }
+            line
+        }));
+        if more > 0 {
+            let cut = if end == shown { "truncated: " } else { "" };
+            lines.push(Line::from(Span::styled(
+                format!("{} {}{} more lines - {} opens the full diff", theme.symbol("…", "..."), cut, more, diff_keys),
+                Style::default().fg(theme.text_muted).add_modifier(Modifier::ITALIC),
+            )));
+        }
+
+        frame.render_widget(Paragraph::new(lines).block(block), modal_area);
+    }
+
+    // --- Render First-Revert Prompt --- (over the approval it's about)
+    if state.revert_prompt && let Some(pending) = state.approval_queue.front() {
+        let effect = if state.quit_after_revert {
+            format!("undo all {} waiting changes, then quit", state.approval_queue.len())
+        } else {
+            pending.revert_effect()
+        };
+        ui::components::revert_prompt::render(frame, centered_rect(60, 40, area), &effect, &theme);
+    }
+
+    // --- Render Toasts --- (over everything, just above the banner and status bar)
+    ui::components::toasts::render(frame, main_area, banner_area.y, state.toasts.visible(), &theme);
+}
+
+// Kill the current agent and start `command` in a fresh PTY of the same size.
+// AppState (cache, change log, approvals) is left alone; on failure the error
+// is shown in the prompt and the old agent stays dead.
+fn restart_agent(state: &mut AppState, agent: &mut Agent, command: &str, tx: &mpsc::Sender<AppEvent>) {
+    agent.kill();
+    let (rows, cols) = state.parser.screen().size();
+    let size = PtySize { rows, cols, pixel_width: 0, pixel_height: 0 };
+    let cwd = state.roots.primary().path.clone();
+    let mut launch = Launch::from_config(&state.config);
+    // The words given after `--` belong to the original command, not an edited one
+    if command != state.config.agent_command {
+        launch.argv.clear();
+    }
+    match Agent::spawn(command, &launch, &cwd, size, agent.generation + 1, tx.clone()) {
+        Ok(new_agent) => {
+            *agent = new_agent;
+            state.parser = vt100::Parser::new(rows, cols, command_pick::SCROLLBACK_ROWS);
+            state.cursor_shape = ansi::CursorShape::default();
+            state.bells_seen = 0;
+            state.child_title.clear();
+            state.agent_prompt = None;
+        }
+        Err(e) => {
+            // Keep generations moving so the dead agent's output is still dropped
+            agent.generation += 1;
+            state.agent_prompt = Some(AgentPrompt::Error(format!("{}: {:#}", command, e)));
+        }
+    }
+}
+
+// Smallest terminal the layout works in at all; startup refuses anything
+// smaller, this covers shrinking the window afterwards
+const MIN_SIZE: (u16, u16) = (doctor::MIN_COLS, doctor::MIN_ROWS);
+// Below this the sidebar is left out and the status bar cut to one segment,
+// so the agent keeps what room there is
+const COMPACT_SIZE: (u16, u16) = (80, 20);
+// Overlays and the approval modal don't shrink past this, room allowing
+const OVERLAY_MIN: (u16, u16) = (40, 8);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fit {
+    TooSmall,
+    Compact,
+    Full,
+}
+
+impl Fit {
+    fn of(width: u16, height: u16) -> Self {
+        if width < MIN_SIZE.0 || height < MIN_SIZE.1 {
+            Self::TooSmall
+        } else if width < COMPACT_SIZE.0 || height < COMPACT_SIZE.1 {
+            Self::Compact
+        } else {
+            Self::Full
+        }
+    }
+}
+
+// Helper for centering modal: a share of `r`, but never under OVERLAY_MIN
+// (unless `r` itself is) and never past its edges
+fn centered_rect(percent_x: u16, percent_y: u16, r: ratatui::layout::Rect) -> ratatui::layout::Rect {
+    let share = |len: u16, percent: u16, min: u16| ((len as u32 * percent.min(100) as u32 / 100) as u16).max(min).min(len);
+    let width = share(r.width, percent_x, OVERLAY_MIN.0);
+    let height = share(r.height, percent_y, OVERLAY_MIN.1);
+    Rect::new(r.x + (r.width - width) / 2, r.y + (r.height - height) / 2, width, height)
+}
+
+// The approval modal: 70% wide and as tall as its content wants, between 40%
+// and 85% of the screen. Centered, unless that covers the agent's cursor: then
+// it moves to whichever edge has more room away from it, shrinking to fit.
+fn modal_rect(area: Rect, content_rows: usize, cursor: Option<Position>) -> Rect {
+    let width = ((area.width as u32 * 7 / 10) as u16).max(OVERLAY_MIN.0).min(area.width);
+    let floor = OVERLAY_MIN.1.min(area.height);
+    let (min, max) = (((area.height as u32 * 2 / 5) as u16).max(floor), (area.height as u32 * 85 / 100) as u16);
+    // Two rows of border
+    let mut height = (content_rows.min(u16::MAX as usize) as u16).saturating_add(2).clamp(min, max.max(min));
+    let x = area.x + (area.width - width) / 2;
+    let mut y = area.y + (area.height - height) / 2;
+    if let Some(cursor) = cursor
+        && (x..x + width).contains(&cursor.x)
+        && (y..y + height).contains(&cursor.y) {
+        let above = cursor.y - area.y;
+        let below = area.bottom() - cursor.y - 1;
+        // Squeezed into too few rows it's no use; covering the cursor is better
+        if above >= below && above >= floor {
+            height = height.min(above);
+            y = area.y;
+        } else if below > above && below >= floor {
+            height = height.min(below);
+            y = area.bottom() - height;
+        }
+    }
+    Rect::new(x, y, width, height)
+}
+
+fn normalize_path(path: &std::path::Path) -> PathKey {
+    PathKey::new(path)
 }
\ No newline at end of file
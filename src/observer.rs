@@ -0,0 +1,82 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{mpsc::{self, Sender}, Arc, Mutex},
+    thread,
+};
+use crate::config::Config;
+use crate::content::{self, FileContent};
+use crate::diff::{self, Hunk};
+use crate::fs::FileSystem;
+use crate::path_key::PathKey;
+use crate::AppEvent;
+
+// The file as read and, when it's text that changed, its diff against the baseline
+pub struct Read {
+    pub content: Option<FileContent>,
+    pub hunks: Vec<Hunk>,
+    // What `hunks` were cut from, for showing at another context width
+    pub full: Option<Hunk>,
+}
+
+// The slow half of recording a change; same code on and off the main thread
+pub fn read_and_diff(fs: &dyn FileSystem, path: &Path, old_content: &str, config: &Config) -> Read {
+    let content = content::read(fs, path, config);
+    let full = match &content {
+        // Diffed as LF, so a CRLF file's hunks are only the lines that really
+        // changed; an ending-only change is summarized by the caller instead
+        Some(FileContent::Text(text, _)) if text != old_content => {
+            let (old, new) = (diff::normalize_eol(old_content), diff::normalize_eol(text));
+            (old != new).then(|| diff::full_diff(&old, &new))
+        }
+        _ => None,
+    };
+    let hunks = full.as_ref().map_or_else(Vec::new, |full| diff::with_context(full, diff::DEFAULT_CONTEXT));
+    Read { content, hunks, full }
+}
+
+struct Job {
+    cache_key: PathKey,
+    path: PathBuf,
+    old_content: String,
+}
+
+// Sent back as AppEvent::Observed
+pub struct Observed {
+    pub cache_key: PathKey,
+    pub path: PathBuf,
+    // The baseline it was diffed against
+    pub old_content: String,
+    pub read: Read,
+}
+
+// A few threads reading and diffing files for the TUI, so a `git checkout`
+// touching thousands of files doesn't stall the screen. Callers keep at most
+// one job per path in flight; that's what keeps each file's events in order.
+pub struct ObservePool {
+    jobs: Sender<Job>,
+}
+
+impl ObservePool {
+    pub fn new(fs: Arc<dyn FileSystem + Sync>, config: Config, tx: Sender<AppEvent>) -> Self {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        let workers = thread::available_parallelism().map_or(2, |n| n.get()).clamp(2, 4);
+        for _ in 0..workers {
+            let (queue, fs, config, tx) = (queue.clone(), fs.clone(), config.clone(), tx.clone());
+            thread::spawn(move || loop {
+                // The lock is only held while waiting for the next job
+                let Ok(job) = queue.lock().unwrap().recv() else { break };
+                let read = read_and_diff(&*fs, &job.path, &job.old_content, &config);
+                let observed = Observed { cache_key: job.cache_key, path: job.path, old_content: job.old_content, read };
+                if tx.send(AppEvent::Observed(observed)).is_err() {
+                    break;
+                }
+            });
+        }
+        Self { jobs }
+    }
+
+    pub fn submit(&self, cache_key: PathKey, path: PathBuf, old_content: String) {
+        let _ = self.jobs.send(Job { cache_key, path, old_content });
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    borrow::Cow,
+    ffi::OsString,
+    fmt,
+    hash::{Hash, Hasher},
+    path::{Component, Path, PathBuf},
+};
+
+// Which file a path means, for the baseline cache, debouncing and our own
+// writes. The watcher, the startup scan and a revert can spell one file
+// differently (`\\?\C:\src\A.rs` vs `c:/src/a.rs`), so keys compare on a
+// folded form: components rejoined with `/` and, on Windows where NTFS
+// ignores case, lowercased. The path itself is kept as the OS gave it, and
+// that is what gets read and written back. Neither goes through a String:
+// on Linux a name can be any bytes, and two names that aren't UTF-8 would
+// otherwise both come out as the same run of U+FFFD.
+#[derive(Clone, Debug)]
+pub struct PathKey {
+    path: PathBuf,
+    folded: OsString,
+}
+
+impl PathKey {
+    // Canonical where the file exists (symlinks and `..` resolved); a deleted
+    // file keeps the path it was reported with
+    pub fn new(path: &Path) -> Self {
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        Self::exact(&path)
+    }
+
+    // Without touching the disk, for paths that are already canonical
+    pub fn exact(path: &Path) -> Self {
+        let path = match path.to_str().and_then(|text| text.strip_prefix(r"\\?\")) {
+            Some(rest) => PathBuf::from(rest),
+            None => path.to_path_buf(),
+        };
+        let folded = fold(&path);
+        Self { path, folded }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // For showing and logging; bytes that aren't UTF-8 come out as U+FFFD,
+    // so this is never used to find the file again
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        self.path.to_string_lossy()
+    }
+
+    pub fn starts_with(&self, dir: &PathKey) -> bool {
+        let (this, dir) = (self.folded.as_encoded_bytes(), dir.folded.as_encoded_bytes());
+        this.strip_prefix(dir).is_some_and(|rest| rest.is_empty() || rest.starts_with(b"/") || dir.ends_with(b"/"))
+    }
+}
+
+fn fold(path: &Path) -> OsString {
+    let mut folded = OsString::new();
+    let ends_with_slash = |folded: &OsString| folded.as_encoded_bytes().ends_with(b"/");
+    for component in path.components() {
+        let name = match component {
+            Component::Prefix(prefix) => {
+                folded.push(prefix.as_os_str());
+                continue;
+            }
+            Component::RootDir => {
+                folded.push("/");
+                continue;
+            }
+            Component::CurDir => continue,
+            Component::ParentDir => "..".as_ref(),
+            Component::Normal(name) => name,
+        };
+        if !folded.is_empty() && !ends_with_slash(&folded) {
+            folded.push("/");
+        }
+        folded.push(name);
+    }
+    // NTFS names are UTF-16, so lossy only touches unpaired surrogates there
+    if cfg!(windows) {
+        folded = folded.to_string_lossy().to_lowercase().into();
+    }
+    folded
+}
+
+impl PartialEq for PathKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.folded == other.folded
+    }
+}
+
+impl Eq for PathKey {}
+
+impl Hash for PathKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.folded.hash(state);
+    }
+}
+
+impl PartialOrd for PathKey {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PathKey {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.folded.cmp(&other.folded)
+    }
+}
+
+// A path typed in or handed over as text (ctl requests, a hook's path)
+impl From<&str> for PathKey {
+    fn from(path: &str) -> Self {
+        Self::exact(Path::new(path))
+    }
+}
+
+impl fmt::Display for PathKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.path.display().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn redundant_separators_and_dots_are_the_same_file() {
+        let key = PathKey::exact(Path::new("/src/app/lib.rs"));
+        assert_eq!(PathKey::exact(Path::new("/src//app/./lib.rs")), key);
+        assert_ne!(PathKey::exact(Path::new("/src/app/lib.rs.bak")), key);
+        // What's written back is the path as given, not the folded form
+        assert_eq!(PathKey::exact(Path::new("/src//app/./lib.rs")).path(), Path::new("/src//app/./lib.rs"));
+
+        let mut cache = HashMap::new();
+        cache.insert(key, "baseline");
+        assert_eq!(cache.get(&PathKey::exact(Path::new("/src/./app//lib.rs"))), Some(&"baseline"));
+    }
+
+    #[test]
+    fn starts_with_matches_whole_components() {
+        let dir = PathKey::exact(Path::new("/src/app"));
+        assert!(PathKey::exact(Path::new("/src/app/lib.rs")).starts_with(&dir));
+        assert!(PathKey::exact(Path::new("/src/app")).starts_with(&dir));
+        assert!(!PathKey::exact(Path::new("/src/apple/lib.rs")).starts_with(&dir));
+        assert!(PathKey::exact(Path::new("/etc")).starts_with(&PathKey::exact(Path::new("/"))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn names_that_are_not_utf8_stay_distinct() {
+        use std::os::unix::ffi::OsStrExt;
+        let name = |bytes: &[u8]| PathKey::exact(Path::new(std::ffi::OsStr::from_bytes(bytes)));
+        let (latin1, other) = (name(b"/src/caf\xe9.rs"), name(b"/src/caf\xff.rs"));
+        assert_ne!(latin1, other);
+        // Shown the same, but what's read and written is the real name
+        assert_eq!(latin1.to_string_lossy(), other.to_string_lossy());
+        assert_eq!(latin1.path().as_os_str().as_bytes(), b"/src/caf\xe9.rs");
+        assert_eq!(name(b"/src//./caf\xe9.rs"), latin1);
+        assert!(latin1.starts_with(&PathKey::exact(Path::new("/src"))));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_ignores_case_separators_and_the_verbatim_prefix() {
+        let key = PathKey::exact(Path::new(r"C:\Src\App\Lib.rs"));
+        assert_eq!(PathKey::exact(Path::new("c:/src/app/lib.RS")), key);
+        assert_eq!(PathKey::exact(Path::new(r"\\?\C:\Src\App\Lib.rs")), key);
+        assert_eq!(PathKey::exact(Path::new(r"C:\Src/App\\Lib.rs")), key);
+        assert_eq!(PathKey::exact(Path::new(r"\\?\C:\Src\App\Lib.rs")).path(), Path::new(r"C:\Src\App\Lib.rs"));
+        assert!(PathKey::exact(Path::new(r"c:\src\APP\x.rs")).starts_with(&PathKey::exact(Path::new(r"C:\Src\App"))));
+    }
+}
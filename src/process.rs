@@ -0,0 +1,108 @@
+use std::{
+    io::{self, Read},
+    path::Path,
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+// How often a running command is checked on
+const POLL: Duration = Duration::from_millis(50);
+
+// How a command started with `spawn` ended
+#[derive(Debug)]
+pub enum Exit {
+    Exited(ExitStatus),
+    Cancelled,
+    TimedOut,
+}
+
+// A shell command the way check_command, test_command and the on_accept hook
+// run: `sh -c` (cmd /C on Windows) in `cwd`, stdin closed, output piped. On
+// Unix it leads a process group of its own, so killing it takes whatever the
+// shell started too. `args` are the shell's positional parameters ($0, $1...).
+pub fn spawn(command: &str, args: &[&std::ffi::OsStr], cwd: &Path) -> io::Result<Child> {
+    let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let mut builder = Command::new(shell);
+    builder
+        .args([flag, command])
+        .args(args)
+        .current_dir(cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        builder.process_group(0);
+    }
+    builder.spawn()
+}
+
+// Wait for `child`, killing it (and its group) once `cancel` is set or
+// `timeout` passes
+pub fn wait(child: &mut Child, cancel: &AtomicBool, timeout: Option<Duration>) -> io::Result<Exit> {
+    let deadline = timeout.map(|t| Instant::now() + t);
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            kill(child);
+            return Ok(Exit::Cancelled);
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            kill(child);
+            return Ok(Exit::TimedOut);
+        }
+        match child.try_wait()? {
+            Some(status) => return Ok(Exit::Exited(status)),
+            None => thread::sleep(POLL),
+        }
+    }
+}
+
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    // SAFETY: signals the group `spawn` made, led by our own child
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+// Read a pipe to the end on its own thread, so a chatty command can't fill
+// it and stall while we wait
+pub fn drain(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        String::from_utf8_lossy(&bytes).into_owned()
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancelling_kills_what_the_shell_started() {
+        let dir = std::env::temp_dir();
+        let marker = dir.join(format!("aiui-process-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        // The subshell outlives sh itself unless the whole group goes
+        let command = format!("(sleep 1; touch '{}') & wait", marker.display());
+        let mut child = spawn(&command, &[], &dir).unwrap();
+        let cancel = AtomicBool::new(true);
+        assert!(matches!(wait(&mut child, &cancel, None).unwrap(), Exit::Cancelled));
+        thread::sleep(Duration::from_millis(1500));
+        assert!(!marker.exists());
+
+        let mut child = spawn("sleep 5", &[], &dir).unwrap();
+        let started = Instant::now();
+        let result = wait(&mut child, &AtomicBool::new(false), Some(Duration::from_millis(100))).unwrap();
+        assert!(matches!(result, Exit::TimedOut));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
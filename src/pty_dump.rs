@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+
+// Raw agent output kept for the --debug dump
+pub const KEEP_BYTES: usize = 64 * 1024;
+// Bytes per row of the hex view
+const HEX_ROW: usize = 16;
+
+// The last KEEP_BYTES the agent wrote, exactly as they came off the PTY,
+// for telling a vt100 bug from an agent that really sent that
+#[derive(Default)]
+pub struct PtyDump {
+    bytes: VecDeque<u8>,
+    // Dropped off the front so far; keeps hex offsets stable as it scrolls
+    dropped: usize,
+}
+
+impl PtyDump {
+    pub fn push(&mut self, data: &[u8]) {
+        self.bytes.extend(data);
+        let excess = self.bytes.len().saturating_sub(KEEP_BYTES);
+        self.bytes.drain(..excess);
+        self.dropped += excess;
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    // One line per line of output, escapes spelled out: `\e[1;31m`, `\r`,
+    // `\x07`. Text that isn't UTF-8 shows as `\xNN` per byte.
+    pub fn escaped(&self) -> Vec<String> {
+        let bytes: Vec<u8> = self.bytes.iter().copied().collect();
+        let mut lines = vec![String::new()];
+        for chunk in bytes.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                let line = lines.last_mut().expect("never empty");
+                match c {
+                    '\x1b' => line.push_str("\\e"),
+                    '\r' => line.push_str("\\r"),
+                    '\t' => line.push_str("\\t"),
+                    '\\' => line.push_str("\\\\"),
+                    '\n' => {
+                        line.push_str("\\n");
+                        lines.push(String::new());
+                    }
+                    c if c.is_control() => line.push_str(&format!("\\x{:02x}", c as u32)),
+                    c => line.push(c),
+                }
+            }
+            let line = lines.last_mut().expect("never empty");
+            for byte in chunk.invalid() {
+                line.push_str(&format!("\\x{:02x}", byte));
+            }
+        }
+        if lines.last().is_some_and(String::is_empty) {
+            lines.pop();
+        }
+        lines
+    }
+
+    // `xxd` style: offset, sixteen bytes in hex, then the printable ones
+    pub fn hex(&self) -> Vec<String> {
+        let bytes: Vec<u8> = self.bytes.iter().copied().collect();
+        bytes
+            .chunks(HEX_ROW)
+            .enumerate()
+            .map(|(row, chunk)| {
+                let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+                let text: String = chunk.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+                format!("{:08x}  {:<width$}  {}", self.dropped + row * HEX_ROW, hex.join(" "), text, width = HEX_ROW * 3 - 1)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_are_spelled_out_and_lines_split_after_newlines() {
+        let mut dump = PtyDump::default();
+        dump.push(b"\x1b[1;31mred\x1b[0m\r\n");
+        dump.push("wide \u{4e2d}\x07 ".as_bytes());
+        dump.push(b"\xff\\");
+        assert_eq!(dump.escaped(), ["\\e[1;31mred\\e[0m\\r\\n", "wide \u{4e2d}\\x07 \\xff\\\\"]);
+    }
+
+    #[test]
+    fn hex_rows_keep_their_offsets_once_the_front_is_dropped() {
+        let mut dump = PtyDump::default();
+        dump.push(&vec![b'a'; KEEP_BYTES]);
+        dump.push(b"\x1b[Hhi");
+        assert_eq!(dump.len(), KEEP_BYTES);
+        let hex = dump.hex();
+        assert_eq!(hex[0], format!("{:08x}  {}  {}", 5, ["61"; 16].join(" "), "a".repeat(16)));
+        let last = hex.last().unwrap();
+        assert!(last.starts_with(&format!("{:08x}  61 61", 5 + KEEP_BYTES - HEX_ROW)), "{}", last);
+        assert!(last.ends_with("1b 5b 48 68 69  aaaaaaaaaaa.[Hhi"), "{}", last);
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use crate::ignore::{IgnoreRules, IGNORE_FILE};
+
+// Directories we never report changes from, relative to a watch root
+const IGNORED_DIRS: [&str; 3] = [".git", "target", "node_modules"];
+// Any of these in a directory or above it makes it a checkout worth scanning
+const VCS_MARKERS: [&str; 4] = [".git", ".hg", ".svn", ".jj"];
+
+#[derive(Clone)]
+pub struct WatchRoot {
+    // Canonical absolute path
+    pub path: PathBuf,
+    // Prefix used in the sidebar when several roots are watched
+    pub name: String,
+    // From the root's .ai-tui-ignore, if it has one
+    pub ignore: IgnoreRules,
+}
+
+impl WatchRoot {
+    fn new(dir: &Path) -> Result<Self> {
+        let path = std::fs::canonicalize(dir)
+            .with_context(|| format!("cannot watch {}", dir.display()))?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string_lossy().into_owned());
+        let ignore = read_ignore(&path);
+        Ok(Self { path, name, ignore })
+    }
+
+    // Noise filter, applied only to the part of the path inside this root
+    pub fn is_ignored(&self, rel: &Path) -> bool {
+        if rel.components().any(|c| IGNORED_DIRS.iter().any(|d| c.as_os_str() == *d)) {
+            return true;
+        }
+        let file_name = rel.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        if file_name.starts_with('.') && file_name != ".gitignore" {
+            return true;
+        }
+        self.ignore.is_ignored(&rel.to_string_lossy().replace('\\', "/"))
+    }
+}
+
+pub struct Roots {
+    roots: Vec<WatchRoot>,
+}
+
+impl Roots {
+    pub fn new(dirs: &[PathBuf]) -> Result<Self> {
+        let mut roots: Vec<WatchRoot> = Vec::new();
+        for dir in dirs {
+            let root = WatchRoot::new(dir)?;
+            if !roots.iter().any(|r| r.path == root.path) {
+                roots.push(root);
+            }
+        }
+        if roots.is_empty() {
+            let cwd = std::env::current_dir().context("cannot read the current directory (was it deleted?); pass --dir")?;
+            roots.push(WatchRoot::new(&cwd)?);
+        }
+        Ok(Self { roots })
+    }
+
+    // The agent is spawned here
+    pub fn primary(&self) -> &WatchRoot {
+        &self.roots[0]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &WatchRoot> {
+        self.roots.iter()
+    }
+
+    pub fn to_vec(&self) -> Vec<WatchRoot> {
+        self.roots.clone()
+    }
+
+    // Read every root's .ai-tui-ignore again, for a re-scan
+    pub fn reload_ignores(&mut self) {
+        for root in &mut self.roots {
+            root.ignore = read_ignore(&root.path);
+        }
+    }
+
+    // Swap in new rules after the root's .ai-tui-ignore changed
+    pub fn set_ignore(&mut self, root_path: &Path, ignore: IgnoreRules) {
+        if let Some(root) = self.roots.iter_mut().find(|r| r.path == root_path) {
+            root.ignore = ignore;
+        }
+    }
+
+    // Root containing `path`, plus the path relative to that root
+    pub fn locate(&self, path: &Path) -> Option<(&WatchRoot, PathBuf)> {
+        let candidates = [Some(path.to_path_buf()), canonical(path)];
+        for candidate in candidates.iter().flatten() {
+            // Prefer the innermost root when roots are nested
+            let hit = self
+                .roots
+                .iter()
+                .filter_map(|root| candidate.strip_prefix(&root.path).ok().map(|rel| (root, rel.to_path_buf())))
+                .max_by_key(|(root, _)| root.path.components().count());
+            if hit.is_some() {
+                return hit;
+            }
+        }
+        None
+    }
+
+    // Sidebar label for a path inside `root`
+    pub fn display(&self, root: &WatchRoot, rel: &Path) -> String {
+        let rel = rel.to_string_lossy();
+        if self.roots.len() > 1 {
+            format!("{}/{}", root.name, rel)
+        } else {
+            rel.into_owned()
+        }
+    }
+}
+
+fn read_ignore(root: &Path) -> IgnoreRules {
+    std::fs::read_to_string(root.join(IGNORE_FILE))
+        .map(|text| IgnoreRules::parse(&text))
+        .unwrap_or_default()
+}
+
+// Why `dir` doesn't look like a project, if it doesn't. Scanning a home
+// directory or `/` by accident reads far more than anyone wanted.
+pub fn not_a_project(dir: &Path) -> Option<&'static str> {
+    judge_project(dir, dirs::home_dir().as_deref(), |d| VCS_MARKERS.iter().any(|m| d.join(m).exists()))
+}
+
+// not_a_project with the home directory and the checkout test passed in
+fn judge_project(dir: &Path, home: Option<&Path>, is_checkout: impl Fn(&Path) -> bool) -> Option<&'static str> {
+    if dir.parent().is_none() {
+        return Some("it's the filesystem root");
+    }
+    if home == Some(dir) {
+        return Some("it's your home directory");
+    }
+    if !dir.ancestors().any(is_checkout) {
+        return Some("it isn't inside a git (or hg, svn, jj) checkout");
+    }
+    None
+}
+
+// Canonical form of `path`, resolving through the parent when the file is gone
+fn canonical(path: &Path) -> Option<PathBuf> {
+    if let Ok(abs) = std::fs::canonicalize(path) {
+        return Some(abs);
+    }
+    let parent = std::fs::canonicalize(path.parent()?).ok()?;
+    Some(parent.join(path.file_name()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_checkouts_count_as_projects() {
+        let dir = std::env::temp_dir().join(format!("aiui-roots-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let nested = dir.join("src").join("app");
+        let home = dir.join("home");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir_all(&home).unwrap();
+        // Markers are only looked for inside the fixture, whatever the temp
+        // dir itself happens to sit in
+        let judge = |d: &Path| {
+            judge_project(d, Some(&home), |d| d.starts_with(&dir) && VCS_MARKERS.iter().any(|m| d.join(m).exists()))
+        };
+        assert_eq!(judge(&nested), Some("it isn't inside a git (or hg, svn, jj) checkout"));
+
+        // A subdirectory of a checkout is fine too
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        assert_eq!(judge(&nested), None);
+        assert_eq!(judge(&dir), None);
+        // Home and the root are refused even inside a checkout
+        assert_eq!(judge(&home), Some("it's your home directory"));
+        let root = dir.ancestors().last().unwrap();
+        assert_eq!(judge(root), Some("it's the filesystem root"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
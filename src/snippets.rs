@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+
+// Placeholders a snippet may use, filled from the sidebar selection or typed in
+pub const PLACEHOLDERS: [&str; 2] = ["{file}", "{selection}"];
+
+// A named prompt from ~/.config/ai-tui/snippets.toml, or a line from the
+// prompt history
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snippet {
+    pub name: String,
+    pub text: String,
+}
+
+// One `name = "template"` per snippet, listed by name:
+//
+//   review = "Review {file} for bugs and missing error handling"
+//   tests = """
+//   Write unit tests for this:
+//   {selection}
+//   """
+//
+// A missing file is no snippets; a broken one is an error worth showing.
+pub fn load() -> Result<Vec<Snippet>, String> {
+    let Some(path) = dirs::config_dir().map(|d| d.join("ai-tui").join("snippets.toml")) else {
+        return Ok(Vec::new());
+    };
+    let Ok(text) = std::fs::read_to_string(&path) else {
+        return Ok(Vec::new());
+    };
+    parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn parse(text: &str) -> Result<Vec<Snippet>, toml::de::Error> {
+    let table: BTreeMap<String, String> = toml::from_str(text)?;
+    Ok(table.into_iter().map(|(name, text)| Snippet { name, text }).collect())
+}
+
+// First placeholder still left in `text`
+pub fn next_placeholder(text: &str) -> Option<&'static str> {
+    PLACEHOLDERS.into_iter().find(|p| text.contains(p))
+}
+
+// Keep the last `cap` distinct lines, newest first
+pub fn remember(history: &mut std::collections::VecDeque<String>, line: &str, cap: usize) {
+    let line = line.trim();
+    if line.is_empty() || cap == 0 {
+        return;
+    }
+    history.retain(|l| l != line);
+    history.push_front(line.to_string());
+    history.truncate(cap);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snippets_parse_by_name_and_history_dedupes() {
+        let snippets = parse("tests = \"Test {selection}\"\nreview = \"Review {file}\"\n").unwrap();
+        assert_eq!(snippets[0], Snippet { name: "review".to_string(), text: "Review {file}".to_string() });
+        assert_eq!(next_placeholder(&snippets[1].text), Some("{selection}"));
+        assert!(parse("review = 3").is_err());
+
+        let mut history = std::collections::VecDeque::new();
+        for line in ["fix it", "  ", "add tests", "fix it", "docs"] {
+            remember(&mut history, line, 2);
+        }
+        assert_eq!(history, ["docs", "fix it"]);
+    }
+}
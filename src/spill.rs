@@ -0,0 +1,28 @@
+use std::{io, path::PathBuf};
+
+// Per-process temp directory holding diffs too large to keep in memory
+pub struct SpillStore {
+    dir: PathBuf,
+    next_id: u64,
+}
+
+impl SpillStore {
+    pub fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("aiui-{}", std::process::id()));
+        Self { dir, next_id: 0 }
+    }
+
+    pub fn write(&mut self, text: &str) -> io::Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir)?;
+        self.next_id += 1;
+        let path = self.dir.join(format!("{}.diff", self.next_id));
+        std::fs::write(&path, text)?;
+        Ok(path)
+    }
+}
+
+impl Drop for SpillStore {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,201 @@
+use std::path::Path;
+use ratatui::{
+    style::{Modifier, Style},
+    text::Span,
+};
+use crate::ui::theme::Theme;
+
+// Just enough of a language to make a new file's preview readable: where
+// comments start, what quotes a string, and the words worth bolding. No
+// state carries between lines, so block comments and multi-line strings
+// aren't followed.
+pub struct Syntax {
+    comment: &'static str,
+    quotes: &'static [char],
+    keywords: &'static [&'static str],
+}
+
+const RUST: Syntax = Syntax {
+    comment: "//",
+    quotes: &['"'],
+    keywords: &[
+        "as", "async", "await", "const", "crate", "dyn", "else", "enum", "false", "fn", "for", "if", "impl", "in", "let",
+        "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+        "trait", "true", "type", "unsafe", "use", "where", "while",
+    ],
+};
+
+const SCRIPT: Syntax = Syntax {
+    comment: "//",
+    quotes: &['"', '\'', '`'],
+    keywords: &[
+        "async", "await", "break", "case", "class", "const", "default", "else", "export", "extends", "false", "for",
+        "from", "function", "if", "implements", "import", "interface", "let", "new", "null", "return", "switch",
+        "this", "true", "type", "typeof", "undefined", "var", "while",
+    ],
+};
+
+const GO: Syntax = Syntax {
+    comment: "//",
+    quotes: &['"', '\'', '`'],
+    keywords: &[
+        "case", "chan", "const", "defer", "else", "false", "for", "func", "go", "if", "import", "interface", "map",
+        "nil", "package", "range", "return", "struct", "switch", "true", "type", "var",
+    ],
+};
+
+const C_LIKE: Syntax = Syntax {
+    comment: "//",
+    quotes: &['"', '\''],
+    keywords: &[
+        "class", "const", "else", "enum", "false", "for", "if", "import", "include", "new", "null", "private",
+        "public", "return", "static", "struct", "switch", "true", "void", "while",
+    ],
+};
+
+const PYTHON: Syntax = Syntax {
+    comment: "#",
+    quotes: &['"', '\''],
+    keywords: &[
+        "and", "as", "class", "def", "elif", "else", "except", "False", "for", "from", "if", "import", "in", "lambda",
+        "None", "not", "or", "pass", "raise", "return", "self", "True", "try", "while", "with", "yield",
+    ],
+};
+
+const SHELL: Syntax = Syntax {
+    comment: "#",
+    quotes: &['"', '\''],
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in", "local",
+        "return", "then", "while",
+    ],
+};
+
+const RUBY: Syntax = Syntax {
+    comment: "#",
+    quotes: &['"', '\''],
+    keywords: &[
+        "class", "def", "do", "else", "elsif", "end", "false", "if", "module", "nil", "return", "self", "true",
+        "unless", "while",
+    ],
+};
+
+const CONFIG: Syntax = Syntax {
+    comment: "#",
+    quotes: &['"', '\''],
+    keywords: &["false", "true"],
+};
+
+impl Syntax {
+    // By file extension; None for anything not listed, which stays plain
+    pub fn for_path(path: &Path) -> Option<&'static Syntax> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        Some(match ext.as_str() {
+            "rs" => &RUST,
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => &SCRIPT,
+            "go" => &GO,
+            "c" | "h" | "cc" | "cpp" | "hpp" | "java" | "kt" | "cs" | "swift" => &C_LIKE,
+            "py" => &PYTHON,
+            "sh" | "bash" | "zsh" => &SHELL,
+            "rb" => &RUBY,
+            "toml" | "yaml" | "yml" => &CONFIG,
+            _ => return None,
+        })
+    }
+
+    // `line` in `base`, with comments muted, strings in the info color and
+    // keywords bold
+    pub fn spans(&self, line: &str, base: Style, theme: &Theme) -> Vec<Span<'static>> {
+        let comment = Style::default().fg(theme.text_muted);
+        let string = Style::default().fg(theme.status_info);
+        let keyword = base.add_modifier(Modifier::BOLD);
+        let mut spans = Vec::new();
+        let mut plain = String::new();
+        let flush = |plain: &mut String, spans: &mut Vec<Span<'static>>| {
+            if !plain.is_empty() {
+                spans.push(Span::styled(std::mem::take(plain), base));
+            }
+        };
+        let mut rest = line;
+        while let Some(c) = rest.chars().next() {
+            // `#` only opens a comment at the start of a word ($#, ${#x} don't)
+            let before = &line[..line.len() - rest.len()];
+            let at_word = before.is_empty() || before.ends_with(char::is_whitespace);
+            if rest.starts_with(self.comment) && (self.comment != "#" || at_word) {
+                flush(&mut plain, &mut spans);
+                spans.push(Span::styled(rest.to_string(), comment));
+                break;
+            }
+            if self.quotes.contains(&c) {
+                let end = string_end(rest, c);
+                flush(&mut plain, &mut spans);
+                spans.push(Span::styled(rest[..end].to_string(), string));
+                rest = &rest[end..];
+                continue;
+            }
+            if c.is_alphabetic() || c == '_' {
+                let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+                let word = &rest[..end];
+                if self.keywords.contains(&word) {
+                    flush(&mut plain, &mut spans);
+                    spans.push(Span::styled(word.to_string(), keyword));
+                } else {
+                    plain.push_str(word);
+                }
+                rest = &rest[end..];
+                continue;
+            }
+            plain.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        flush(&mut plain, &mut spans);
+        spans
+    }
+}
+
+// Byte length of the string literal opening `s`, quotes included; the rest
+// of the line when it isn't closed
+fn string_end(s: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in s.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if c == quote => return i + c.len_utf8(),
+            _ => {}
+        }
+    }
+    s.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::theme::ThemeVariant;
+
+    #[test]
+    fn comments_strings_and_keywords_are_told_apart() {
+        let theme = Theme::new(ThemeVariant::Zinc);
+        let base = Style::default().fg(theme.diff_add);
+        let rust = Syntax::for_path(Path::new("src/Main.RS")).unwrap();
+        let spans = rust.spans(r#"let s = "a \" // b"; // done"#, base, &theme);
+        let parts: Vec<(&str, Style)> = spans.iter().map(|s| (s.content.as_ref(), s.style)).collect();
+        assert_eq!(parts, [
+            ("let", base.add_modifier(Modifier::BOLD)),
+            (" s = ", base),
+            (r#""a \" // b""#, Style::default().fg(theme.status_info)),
+            ("; ", base),
+            ("// done", Style::default().fg(theme.text_muted)),
+        ]);
+        // Keywords only as whole words
+        assert_eq!(rust.spans("letter", base, &theme).len(), 1);
+
+        let shell = Syntax::for_path(Path::new("run.sh")).unwrap();
+        let text = |spans: Vec<Span>| spans.iter().map(|s| s.content.to_string()).collect::<Vec<_>>();
+        assert_eq!(text(shell.spans("echo $# # count", base, &theme)), ["echo $# ", "# count"]);
+        // Unclosed strings run to the end of the line
+        assert_eq!(text(shell.spans("echo 'it", base, &theme)), ["echo ", "'it"]);
+        assert!(Syntax::for_path(Path::new("notes.txt")).is_none());
+        assert!(Syntax::for_path(Path::new("Makefile")).is_none());
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use crate::ansi;
+use crate::process::{self, Exit};
+use crate::types::TestRun;
+use crate::AppEvent;
+
+// Lines of output the Checks panel keeps
+pub const KEEP_LINES: usize = 20;
+// Accepts closer together than this share one run (accept-all, a quick y y y)
+pub const DEBOUNCE: Duration = Duration::from_millis(1500);
+
+// From the runner's threads; `run` tells a superseded run's leftovers apart
+#[derive(Debug)]
+pub enum TestEvent {
+    Line(u64, String),
+    // `code` is None when it was killed or never started
+    Done { run: u64, code: Option<i32>, cancelled: bool },
+}
+
+struct Running {
+    id: u64,
+    started: Instant,
+    cancel: Arc<AtomicBool>,
+    // Accepted changes this run follows
+    after: Vec<u64>,
+}
+
+// test_command: one run after each burst of accepts, output streamed
+// into the Checks panel
+pub struct TestRunner {
+    command: String,
+    cwd: PathBuf,
+    tx: Sender<AppEvent>,
+    next_run: u64,
+    // Start once this passes; every accept pushes it back
+    due: Option<Instant>,
+    // Accepted since the last run started
+    after: Vec<u64>,
+    running: Option<Running>,
+    // Tail of the current (or last) run's output
+    pub output: VecDeque<String>,
+    // The last run that finished
+    pub last: Option<TestRun>,
+}
+
+impl TestRunner {
+    pub fn new(command: String, cwd: PathBuf, tx: Sender<AppEvent>) -> Self {
+        Self { command, cwd, tx, next_run: 0, due: None, after: Vec::new(), running: None, output: VecDeque::new(), last: None }
+    }
+
+    pub fn accepted(&mut self, change_id: u64) {
+        self.after.push(change_id);
+        self.due = Some(Instant::now() + DEBOUNCE);
+    }
+
+    // How long the current run has been going
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.running.as_ref().map(|r| r.started.elapsed())
+    }
+
+    // Called every tick. A run still going when the next one is due is
+    // stale: it's killed and the new one answers for its changes too.
+    pub fn tick(&mut self) {
+        if self.due.is_none_or(|due| Instant::now() < due) {
+            return;
+        }
+        self.due = None;
+        if let Some(stale) = self.running.take() {
+            stale.cancel.store(true, Ordering::Relaxed);
+            self.after.splice(0..0, stale.after);
+        }
+        self.next_run += 1;
+        let running = Running {
+            id: self.next_run,
+            started: Instant::now(),
+            cancel: Arc::new(AtomicBool::new(false)),
+            after: std::mem::take(&mut self.after),
+        };
+        let (id, cancel, tx) = (running.id, running.cancel.clone(), self.tx.clone());
+        let (command, cwd) = (self.command.clone(), self.cwd.clone());
+        self.running = Some(running);
+        self.output.clear();
+        thread::spawn(move || execute(id, &command, &cwd, &cancel, &tx));
+    }
+
+    // Kill the run in flight; false if there's none
+    pub fn cancel(&mut self) -> bool {
+        self.due = None;
+        match &self.running {
+            Some(running) => {
+                running.cancel.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn line(&mut self, run: u64, line: String) {
+        if self.running.as_ref().is_some_and(|r| r.id == run) {
+            self.output.push_back(line);
+            while self.output.len() > KEEP_LINES {
+                self.output.pop_front();
+            }
+        }
+    }
+
+    // A run ended: its result and the changes it followed
+    pub fn finished(&mut self, run: u64, code: Option<i32>, cancelled: bool) -> Option<(TestRun, Vec<u64>)> {
+        if self.running.as_ref().is_none_or(|r| r.id != run) {
+            return None;
+        }
+        let running = self.running.take()?;
+        let result = TestRun { exit_code: code, duration_ms: running.started.elapsed().as_millis() as u64, cancelled };
+        self.last = Some(result.clone());
+        Some((result, running.after))
+    }
+}
+
+fn execute(run: u64, command: &str, cwd: &Path, cancel: &AtomicBool, tx: &Sender<AppEvent>) {
+    let done = |code, cancelled| {
+        let _ = tx.send(AppEvent::Test(TestEvent::Done { run, code, cancelled }));
+    };
+    let mut child = match process::spawn(command, &[], cwd) {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = tx.send(AppEvent::Test(TestEvent::Line(run, format!("cannot run {}: {}", command, e))));
+            return done(None, false);
+        }
+    };
+    // Each pipe streams its lines as they come
+    let stream = |pipe: Box<dyn Read + Send>| {
+        let tx = tx.clone();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(pipe);
+            let mut bytes = Vec::new();
+            while reader.read_until(b'\n', &mut bytes).is_ok_and(|n| n > 0) {
+                let line = ansi::strip(String::from_utf8_lossy(&bytes).trim_end());
+                let _ = tx.send(AppEvent::Test(TestEvent::Line(run, line)));
+                bytes.clear();
+            }
+        })
+    };
+    let readers: Vec<_> = [
+        child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>),
+        child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>),
+    ]
+    .into_iter()
+    .flatten()
+    .map(stream)
+    .collect();
+
+    let code = match process::wait(&mut child, cancel, None) {
+        Ok(Exit::Exited(status)) => status.code(),
+        Ok(_) => return done(None, true),
+        Err(_) => None,
+    };
+    // All output goes out before the result
+    for reader in readers {
+        let _ = reader.join();
+    }
+    done(code, false);
+}
@@ -0,0 +1,2014 @@
+// Drives AppState with synthetic file events against an in-memory file system
+use std::{path::{Path, PathBuf}, sync::mpsc, thread, time::{Duration, Instant}};
+use crate::config::Config;
+use crate::path_key::PathKey;
+use crate::export::ExportFormat;
+use crate::content::TextEncoding;
+#[cfg(unix)]
+use crate::control::{self, Request, Response};
+use crate::hooks::Hooks;
+use crate::check::{CheckStatus, Checker};
+use crate::churn::ChurnOrder;
+use crate::test_run::{TestRunner, DEBOUNCE};
+use crate::fs::MemFs;
+use crate::observer::ObservePool;
+use crate::roots::Roots;
+use crate::config::{ProtectedMode, RejectMode, SidebarSelection, TimeoutAction};
+use crate::toasts::Severity;
+use crate::types::{ChangeKind, ChangeStatus, Origin};
+use crate::ui::components::quick_open::QuickOpen;
+use crate::ui::components::snippet_picker::SnippetPicker;
+use crate::snippets::Snippet;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crate::{engine, modal_rect, normalize_path, AppEvent, AppState, Follow, RENAME_WINDOW};
+use crate::git;
+use ratatui::layout::{Position, Rect};
+
+struct Harness {
+    state: AppState,
+    fs: MemFs,
+    // Events normally arrive while the agent is printing; set to simulate user edits
+    agent_quiet: bool,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let fs = MemFs::default();
+        let roots = Roots::new(&[std::env::temp_dir()]).unwrap();
+        let mut state = AppState::with_fs(Config::default(), roots, Box::new(fs.clone()));
+        // Skip the startup scan; tests seed the cache themselves
+        state.indexing = None;
+        Self { state, fs, agent_quiet: false }
+    }
+
+    fn path(&self, rel: &str) -> PathBuf {
+        self.state.roots.primary().path.join("aiui-harness").join(rel)
+    }
+
+    // A file the app already knows about (on disk and in the cache)
+    fn known(&mut self, rel: &str, text: &str) -> PathBuf {
+        let path = self.path(rel);
+        self.fs.put(&path, text);
+        self.state.file_cache.insert(normalize_path(&path), text.to_string());
+        path
+    }
+
+    fn event(&mut self, path: &Path, kind: ChangeKind) {
+        if !self.agent_quiet {
+            self.state.last_pty_data = Some(Instant::now());
+        }
+        self.state.add_change(path.to_path_buf(), kind);
+    }
+
+    // Let held removes expire without waiting out the window
+    fn expire_held_removes(&mut self) {
+        for held in &mut self.state.held_removes {
+            held.at = Instant::now() - RENAME_WINDOW;
+        }
+        self.state.flush_held_removes();
+    }
+
+    // Send requests through a real control socket while this thread plays main loop
+    #[cfg(unix)]
+    fn control(&mut self, name: &str, requests: Vec<Request>) -> Vec<Response> {
+        let socket = std::env::temp_dir().join(format!("aiui-{}-{}.sock", std::process::id(), name));
+        let (tx, rx) = mpsc::channel::<AppEvent>();
+        control::serve(&socket, tx).unwrap();
+
+        let client_socket = socket.clone();
+        let client = thread::spawn(move || {
+            requests.iter().map(|r| control::request(&client_socket, r).unwrap()).collect::<Vec<_>>()
+        });
+        while !client.is_finished() {
+            if let Ok(event) = rx.recv_timeout(Duration::from_millis(10)) {
+                engine::handle(&mut self.state, event);
+            }
+        }
+        let _ = std::fs::remove_file(&socket);
+        client.join().unwrap()
+    }
+}
+
+#[test]
+fn create_is_queued_against_an_empty_baseline() {
+    let mut h = Harness::new();
+    let path = h.path("new.rs");
+    h.fs.put(&path, "fn main() {\r\n}");
+    h.event(&path, ChangeKind::Create);
+
+    assert_eq!(h.state.approval_queue.len(), 1);
+    let pending = &h.state.approval_queue[0];
+    assert_eq!(pending.kind, ChangeKind::Create);
+    assert!(pending.old_content.is_empty());
+    assert_eq!(pending.diff_text.as_str(), "New file (2 lines, 14 B)\n+fn main() {\n+}\n\\ No newline at end of file\n");
+    assert_eq!(h.state.file_changes[0].status, ChangeStatus::Pending);
+
+    // The baseline is what was read, not something rebuilt from the preview
+    h.state.accept_front();
+    assert_eq!(h.state.file_cache.get(&normalize_path(&path)).map(String::as_str), Some("fn main() {\r\n}"));
+}
+
+#[test]
+fn modify_is_queued_with_a_diff() {
+    let mut h = Harness::new();
+    let path = h.known("lib.rs", "a\nb\n");
+    h.fs.put(&path, "a\nc\n");
+    h.event(&path, ChangeKind::Modify);
+
+    assert_eq!(h.state.approval_queue.len(), 1);
+    let pending = &h.state.approval_queue[0];
+    assert_eq!(pending.kind, ChangeKind::Modify);
+    assert_eq!(pending.hunks.len(), 1);
+    assert!(pending.diff_text.as_str().contains("-b") && pending.diff_text.as_str().contains("+c"));
+}
+
+#[test]
+fn remove_is_queued_once_the_rename_window_passes() {
+    let mut h = Harness::new();
+    let path = h.known("gone.rs", "bye\n");
+    h.fs.delete(&path);
+    h.event(&path, ChangeKind::Remove);
+
+    // Held in case a matching create turns it into a rename
+    assert!(h.state.approval_queue.is_empty());
+    h.expire_held_removes();
+
+    assert_eq!(h.state.approval_queue.len(), 1);
+    assert_eq!(h.state.approval_queue[0].kind, ChangeKind::Remove);
+    assert_eq!(h.state.approval_queue[0].old_content, "bye\n");
+}
+
+#[test]
+fn identical_content_is_a_no_op() {
+    let mut h = Harness::new();
+    let path = h.known("same.rs", "unchanged\n");
+    h.event(&path, ChangeKind::Modify);
+
+    assert!(h.state.approval_queue.is_empty());
+    assert!(h.state.file_changes.is_empty());
+}
+
+#[test]
+fn repeated_events_are_debounced() {
+    let mut h = Harness::new();
+    let path = h.known("busy.rs", "1\n");
+    h.fs.put(&path, "2\n");
+    h.event(&path, ChangeKind::Modify);
+    h.fs.put(&path, "3\n");
+    h.event(&path, ChangeKind::Modify);
+
+    assert_eq!(h.state.approval_queue.len(), 1);
+    assert_eq!(h.state.file_changes.len(), 1);
+}
+
+#[test]
+fn approval_modal_sizes_to_the_diff_and_keeps_clear_of_the_cursor() {
+    let area = Rect::new(0, 0, 100, 40);
+    // A short diff gets the minimum, a long one stops short of the whole screen
+    assert_eq!(modal_rect(area, 3, None), Rect::new(15, 12, 70, 16));
+    assert_eq!(modal_rect(area, 500, None), Rect::new(15, 3, 70, 34));
+    // The agent's prompt at the bottom: the modal moves up and stops above it
+    assert_eq!(modal_rect(area, 500, Some(Position::new(20, 30))), Rect::new(15, 0, 70, 30));
+    // Near the top: it goes to the bottom edge instead
+    assert_eq!(modal_rect(area, 20, Some(Position::new(20, 12))), Rect::new(15, 18, 70, 22));
+    // A cursor it doesn't cover changes nothing
+    assert_eq!(modal_rect(area, 3, Some(Position::new(2, 20))), Rect::new(15, 12, 70, 16));
+}
+
+#[test]
+fn a_queued_change_can_be_decided_out_of_order() {
+    let mut h = Harness::new();
+    let paths: Vec<PathBuf> = ["a.rs", "b.rs", "c.rs"].iter().map(|name| {
+        let path = h.known(name, "old\n");
+        h.fs.put(&path, "new\n");
+        h.event(&path, ChangeKind::Modify);
+        path
+    }).collect();
+
+    // `3` then `y`: c.rs is accepted, a.rs and b.rs still wait in order
+    h.state.review_pending(2);
+    h.state.accept_front();
+    let key = |p: &PathBuf| normalize_path(p);
+    assert_eq!(h.state.file_changes.iter().find(|c| c.abs_path == key(&paths[2])).unwrap().status, ChangeStatus::Accepted);
+    let queued: Vec<PathKey> = h.state.approval_queue.iter().map(|p| p.path.clone()).collect();
+    assert_eq!(queued, [key(&paths[0]), key(&paths[1])]);
+    // Past the end does nothing
+    h.state.review_pending(5);
+    assert_eq!(h.state.approval_queue.len(), 2);
+}
+
+#[test]
+fn first_revert_is_explained_before_anything_is_written() {
+    let mut h = Harness::new();
+    let first = h.known("first.rs", "original\n");
+    let second = h.known("second.rs", "original\n");
+    for path in [&first, &second] {
+        h.fs.put(path, "agent edit\n");
+        h.event(path, ChangeKind::Modify);
+    }
+
+    h.state.request_reject();
+    assert!(h.state.revert_prompt);
+    assert_eq!(h.fs.get(&first).as_deref(), Some("agent edit\n"));
+    h.state.confirm_revert(false);
+    assert!(!h.state.revert_prompt);
+    assert_eq!(h.fs.get(&first).as_deref(), Some("original\n"));
+
+    // Once a session is enough
+    h.state.request_reject();
+    assert!(!h.state.revert_prompt);
+    assert_eq!(h.fs.get(&second).as_deref(), Some("original\n"));
+}
+
+#[test]
+fn rejecting_everything_on_quit_asks_before_the_first_revert() {
+    let mut h = Harness::new();
+    let paths = [h.known("one.rs", "original\n"), h.known("two.rs", "original\n")];
+    for path in &paths {
+        h.fs.put(path, "agent edit\n");
+        h.event(path, ChangeKind::Modify);
+    }
+    h.state.quit_prompt = true;
+    assert!(!h.state.request_reject_all());
+    assert!(h.state.revert_prompt && !h.state.quit_prompt);
+    assert!(paths.iter().all(|p| h.fs.get(p).as_deref() == Some("agent edit\n")));
+
+    // Confirming rejects them all, and it's time to quit
+    assert!(h.state.confirm_revert(false));
+    assert!(h.state.approval_queue.is_empty());
+    assert!(paths.iter().all(|p| h.fs.get(p).as_deref() == Some("original\n")));
+}
+
+#[test]
+fn reject_restores_and_swallows_its_own_write() {
+    let mut h = Harness::new();
+    let path = h.known("revert.rs", "original\n");
+    h.fs.put(&path, "agent edit\n");
+    h.event(&path, ChangeKind::Modify);
+    h.state.reject_front();
+
+    assert_eq!(h.fs.get(&path).as_deref(), Some("original\n"));
+    assert_eq!(h.state.file_changes[0].status, ChangeStatus::Rejected);
+
+    // The watcher then reports our own write, maybe more than once; it must
+    // not loop back into the queue
+    for _ in 0..2 {
+        h.state.debounce_map.clear();
+        h.event(&path, ChangeKind::Modify);
+    }
+    assert!(h.state.approval_queue.is_empty());
+    assert_eq!(h.state.file_changes.len(), 1);
+
+    // The agent writing again right after is a change, not an echo
+    h.state.debounce_map.clear();
+    h.fs.put(&path, "agent again\n");
+    h.event(&path, ChangeKind::Modify);
+    assert_eq!(h.state.approval_queue.len(), 1);
+    assert!(h.state.own_writes.is_empty());
+}
+
+#[test]
+fn differently_spelled_paths_are_one_file() {
+    let mut h = Harness::new();
+    let path = h.known("spelled.rs", "original\n");
+    // How a watcher or a revert might spell the same file
+    let messy = PathBuf::from(format!("{}//./spelled.rs", h.path("").display()));
+    h.fs.put(&path, "agent edit\n");
+    h.event(&messy, ChangeKind::Modify);
+    h.event(&path, ChangeKind::Modify);
+
+    // Diffed against the baseline and debounced as one file
+    assert_eq!(h.state.approval_queue.len(), 1);
+    assert_eq!(h.state.approval_queue[0].kind, ChangeKind::Modify);
+    assert_eq!(h.state.approval_queue[0].old_content, "original\n");
+
+    h.state.reject_front();
+    assert_eq!(h.fs.get(&path).as_deref(), Some("original\n"));
+    // Our own write comes back under the other spelling and is still ours
+    h.state.debounce_map.clear();
+    h.event(&messy, ChangeKind::Modify);
+    assert!(h.state.approval_queue.is_empty());
+    assert_eq!(h.state.file_changes.len(), 1);
+}
+
+#[test]
+fn a_revert_that_cannot_write_says_so() {
+    let mut h = Harness::new();
+    let path = h.known("locked.rs", "original\n");
+    h.fs.put(&path, "agent edit\n");
+    h.event(&path, ChangeKind::Modify);
+    h.fs.lock(&path);
+    h.state.reject_front();
+
+    assert_eq!(h.fs.get(&path).as_deref(), Some("agent edit\n"));
+    let toast = h.state.toasts.visible().next().unwrap();
+    assert_eq!(toast.severity, Severity::Error);
+    assert!(toast.text.starts_with("Revert failed: couldn't write"), "{}", toast.text);
+}
+
+#[test]
+fn a_partial_accept_that_cannot_write_says_so() {
+    let mut h = Harness::new();
+    let path = h.known("locked.rs", "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n");
+    h.fs.put(&path, "A\nb\nc\nd\ne\nf\ng\nh\ni\nJ\n");
+    h.event(&path, ChangeKind::Modify);
+    h.state.approval_queue[0].toggle_hunk(1);
+    h.fs.lock(&path);
+    h.state.accept_front();
+
+    assert_eq!(h.fs.get(&path).as_deref(), Some("A\nb\nc\nd\ne\nf\ng\nh\ni\nJ\n"));
+    let toast = h.state.toasts.visible().next().unwrap();
+    assert_eq!(toast.severity, Severity::Error);
+    assert!(toast.text.starts_with("Accept failed: couldn't write"), "{}", toast.text);
+    // Still waiting, hunk choice and all, with the old baseline and no stats
+    assert!(h.state.approval_queue[0].is_partial());
+    assert_eq!(h.state.file_changes[0].status, ChangeStatus::Pending);
+    assert_eq!(h.state.file_cache.get(&normalize_path(&path)).map(String::as_str), Some("a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n"));
+    assert_eq!(h.state.stats.accepted, 0);
+}
+
+#[test]
+fn accepting_everything_leaves_what_cannot_be_written_queued() {
+    let mut h = Harness::new();
+    let locked = h.known("locked.rs", "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n");
+    h.fs.put(&locked, "A\nb\nc\nd\ne\nf\ng\nh\ni\nJ\n");
+    h.event(&locked, ChangeKind::Modify);
+    h.state.approval_queue[0].toggle_hunk(1);
+    h.fs.lock(&locked);
+    let other = h.known("other.rs", "x\n");
+    h.fs.put(&other, "y\n");
+    h.event(&other, ChangeKind::Modify);
+
+    // Gives up on the locked file instead of retrying it forever
+    assert!(!h.state.resolve_all(true));
+    assert_eq!(h.state.approval_queue.len(), 1);
+    assert_eq!(h.state.approval_queue[0].path, normalize_path(&locked));
+    assert!(h.state.modal_active);
+    assert_eq!(h.state.stats.accepted, 1);
+
+    // A client is told why, not that the file changed again
+    let id = h.state.approval_queue[0].change_id;
+    let response = control::handle(&mut h.state, Request::Approve { path: None, id: Some(id) });
+    let error = response.error.unwrap();
+    assert!(error.starts_with("accept failed: couldn't write"), "{}", error);
+}
+
+#[test]
+fn own_write_guard_expires_without_an_echo() {
+    let mut h = Harness::new();
+    let path = h.known("quiet.rs", "original\n");
+    h.fs.put(&path, "agent\n");
+    h.event(&path, ChangeKind::Modify);
+    h.state.reject_front();
+    let key = normalize_path(&path);
+
+    // The revert's event never arrives. Once the window is over, even a write
+    // that happens to match ours is the agent's, with or without a tick in between.
+    h.state.own_writes.get_mut(&key).unwrap().until = Instant::now();
+    h.state.debounce_map.clear();
+    // Something the event would be diffed against
+    h.state.file_cache.insert(key.clone(), "agent\n".to_string());
+    h.event(&path, ChangeKind::Modify);
+    assert_eq!(h.state.approval_queue.len(), 1);
+    assert!(h.state.own_writes.is_empty());
+}
+
+#[test]
+fn accept_updates_the_baseline() {
+    let mut h = Harness::new();
+    let path = h.known("accept.rs", "old\n");
+    h.fs.put(&path, "new\n");
+    h.event(&path, ChangeKind::Modify);
+    h.state.accept_front();
+
+    assert!(h.state.approval_queue.is_empty());
+    assert_eq!(h.state.file_cache.get(&normalize_path(&path)).map(String::as_str), Some("new\n"));
+    assert_eq!(h.state.file_changes[0].status, ChangeStatus::Accepted);
+}
+
+#[test]
+fn remove_then_create_with_same_content_is_a_rename() {
+    let mut h = Harness::new();
+    let from = h.known("before.rs", "moved\n");
+    let to = h.path("after.rs");
+    h.fs.delete(&from);
+    h.fs.put(&to, "moved\n");
+    h.event(&from, ChangeKind::Remove);
+    h.event(&to, ChangeKind::Create);
+    h.expire_held_removes();
+
+    assert!(h.state.approval_queue.is_empty());
+    assert_eq!(h.state.file_changes.len(), 1);
+    assert_eq!(h.state.file_changes[0].kind, ChangeKind::Rename);
+    assert!(h.state.file_cache.contains_key(&normalize_path(&to)));
+    assert!(!h.state.file_cache.contains_key(&normalize_path(&from)));
+}
+
+#[test]
+fn a_temp_file_renamed_over_a_known_file_is_an_edit_of_it() {
+    let mut h = Harness::new();
+    let target = h.known("saved.rs", "old\n");
+    let tmp = h.path("saved.rs.swp4");
+    h.fs.put(&tmp, "new\n");
+    h.event(&tmp, ChangeKind::Create);
+    assert_eq!(h.state.approval_queue.len(), 1);
+
+    h.fs.delete(&tmp);
+    h.fs.put(&target, "new\n");
+    h.state.rename_event(tmp.clone(), target.clone());
+
+    assert_eq!(h.state.approval_queue.len(), 1);
+    let pending = &h.state.approval_queue[0];
+    assert_eq!((pending.path.path(), &pending.kind), (target.as_path(), &ChangeKind::Modify));
+    assert_eq!((pending.old_content.as_str(), pending.new_content.as_str()), ("old\n", "new\n"));
+    assert!(h.state.file_changes.iter().all(|c| c.abs_path == normalize_path(&target)));
+    // Rejecting puts the file back
+    h.state.reject_front();
+    assert_eq!(h.fs.get(&target).as_deref(), Some("old\n"));
+}
+
+#[test]
+#[cfg(unix)]
+fn control_socket_lists_and_approves_pending_changes() {
+    let mut h = Harness::new();
+    h.state.config.agent_env.insert("GITHUB_TOKEN".to_string(), "ghp_secret".to_string());
+    let path = h.known("ctl_accept.rs", "old\n");
+    h.fs.put(&path, "new\n");
+    h.event(&path, ChangeKind::Modify);
+    let key = normalize_path(&path);
+
+    let responses = h.control("approve", vec![
+        Request::ListPending,
+        Request::Approve { path: Some(key.to_string()), id: None },
+        Request::Status,
+    ]);
+
+    assert!(responses.iter().all(|r| r.ok));
+    assert_eq!(responses[0].data[0]["path"], key.to_string_lossy().as_ref());
+    assert_eq!(responses[0].data[0]["id"], h.state.file_changes[0].id);
+    assert_eq!(responses[0].data[0]["insertions"], 1);
+    assert_eq!(responses[1].data["decision"], "accepted");
+    assert_eq!(responses[2].data["pending"], 0);
+    assert_eq!(responses[2].data["accepted"], 1);
+    // Secrets the agent was given don't leak through status
+    assert_eq!(responses[2].data["agent_env"], serde_json::json!({ "AI_TUI": "1", "GITHUB_TOKEN": "********" }));
+    assert_eq!(h.state.file_cache.get(&key).map(String::as_str), Some("new\n"));
+}
+
+#[test]
+#[cfg(unix)]
+fn control_socket_rejects_out_of_order() {
+    let mut h = Harness::new();
+    let first = h.known("ctl_first.rs", "a\n");
+    let second = h.known("ctl_second.rs", "b\n");
+    h.fs.put(&first, "a2\n");
+    h.fs.put(&second, "b2\n");
+    h.event(&first, ChangeKind::Modify);
+    h.event(&second, ChangeKind::Modify);
+
+    // A client can't see the first-revert explanation, so it can't be the one to consent
+    let reject = || vec![Request::Reject { path: Some(normalize_path(&second).to_string()), id: None }];
+    let responses = h.control("reject-unconfirmed", reject());
+    assert!(responses[0].error.as_deref().unwrap().contains("revert_acknowledged"));
+    assert_eq!(h.fs.get(&second).as_deref(), Some("b2\n"));
+    h.state.revert_confirmed = true;
+    let responses = h.control("reject", reject());
+
+    assert!(responses[0].ok);
+    assert_eq!(h.fs.get(&second).as_deref(), Some("b\n"));
+    assert_eq!(h.state.approval_queue.len(), 1);
+    assert_eq!(h.state.approval_queue[0].path, normalize_path(&first));
+
+    // The id list-pending gave picks the same entry however the path is spelled
+    let id = h.state.approval_queue[0].change_id;
+    let responses = h.control("approve-id", vec![Request::Approve { path: None, id: Some(id) }, Request::Reject { path: None, id: Some(id) }]);
+    assert_eq!(responses[0].data["id"], id);
+    assert_eq!(h.state.file_changes.iter().find(|c| c.id == id).unwrap().status, ChangeStatus::Accepted);
+    assert_eq!(responses[1].error.as_deref(), Some(format!("no pending change for id {}", id).as_str()));
+}
+
+#[test]
+#[cfg(unix)]
+fn control_socket_reports_errors() {
+    let mut h = Harness::new();
+    let responses = h.control("errors", vec![Request::Approve { path: Some("nope.rs".to_string()), id: None }]);
+
+    assert!(!responses[0].ok);
+    assert!(responses[0].error.as_deref().unwrap().contains("nope.rs"));
+}
+
+#[test]
+fn line_counts_cover_creates_modifies_and_deletes() {
+    let mut h = Harness::new();
+    let created = h.path("counted_new.rs");
+    h.fs.put(&created, "a\nb\nc\n");
+    h.event(&created, ChangeKind::Create);
+
+    let modified = h.known("counted_mod.rs", "a\nb\nc\n");
+    h.fs.put(&modified, "a\nB\nc\nd\n");
+    h.event(&modified, ChangeKind::Modify);
+
+    let removed = h.known("counted_gone.rs", "1\n2\n3\n4\n5\n");
+    h.fs.delete(&removed);
+    h.event(&removed, ChangeKind::Remove);
+    h.expire_held_removes();
+
+    let counts: Vec<(usize, usize)> = h.state.approval_queue.iter().map(|p| (p.insertions, p.deletions)).collect();
+    assert_eq!(counts, [(3, 0), (2, 1), (0, 5)]);
+
+    // Sidebar entries carry the same numbers (newest first); the session adds them up
+    let logged: Vec<(usize, usize)> = h.state.file_changes.iter().map(|c| (c.insertions, c.deletions)).collect();
+    assert_eq!(logged, [(0, 5), (2, 1), (3, 0)]);
+    assert_eq!((h.state.stats.insertions, h.state.stats.deletions), (5, 6));
+}
+
+#[test]
+fn partial_accept_counts_only_kept_hunks() {
+    let mut h = Harness::new();
+    let body: String = (0..20).map(|i| format!("line {}\n", i)).collect();
+    let path = h.known("counted_partial.rs", &body);
+    h.fs.put(&path, &body.replace("line 1\n", "one\n").replace("line 18\n", "eighteen\nextra\n"));
+    h.event(&path, ChangeKind::Modify);
+
+    let pending = &mut h.state.approval_queue[0];
+    assert_eq!(pending.hunks.len(), 2);
+    assert_eq!(pending.kept_line_counts(), (3, 2));
+    pending.toggle_hunk(1);
+    assert_eq!(pending.kept_line_counts(), (1, 1));
+}
+
+#[test]
+fn accept_hook_runs_and_reports_its_exit_status() {
+    for (command, expected) in [("true {path}", "Hook ok"), ("exit 3", "Hook failed (exit 3)")] {
+        let mut h = Harness::new();
+        let (tx, rx) = mpsc::channel::<AppEvent>();
+        h.state.hooks = Some(Hooks::new(command.to_string(), std::env::temp_dir(), None, tx));
+        let path = h.known("hooked.rs", "old\n");
+        h.fs.put(&path, "new\n");
+        h.event(&path, ChangeKind::Modify);
+        h.state.accept_front();
+
+        // The hook's own writes are swallowed while it runs
+        let key = normalize_path(&path);
+        assert!(h.state.own_writes.contains_key(&key));
+
+        // Stand-in for a formatter rewriting the file - or the agent writing
+        // it meanwhile: there's no telling them apart
+        h.fs.put(&path, "formatted\n");
+        let event = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        engine::handle(&mut h.state, event);
+
+        assert!(h.state.own_writes.is_empty());
+        let (message, _) = h.state.status_message.as_ref().unwrap();
+        assert!(message.starts_with(expected), "{}", message);
+        // So it's asked about, against the content that was accepted
+        assert_eq!(h.state.file_cache.get(&key).map(String::as_str), Some("new\n"));
+        assert_eq!(h.state.approval_queue.len(), 1);
+        assert_eq!(h.state.approval_queue[0].new_content, "formatted\n");
+    }
+}
+
+#[test]
+fn a_hook_left_untouched_asks_nothing_and_a_hung_one_is_killed() {
+    let mut h = Harness::new();
+    let (tx, rx) = mpsc::channel::<AppEvent>();
+    h.state.hooks = Some(Hooks::new("sleep 30".to_string(), std::env::temp_dir(), Some(Duration::from_millis(100)), tx));
+    let path = h.known("hung.rs", "old\n");
+    h.fs.put(&path, "new\n");
+    h.event(&path, ChangeKind::Modify);
+    h.state.accept_front();
+
+    let started = Instant::now();
+    engine::handle(&mut h.state, rx.recv_timeout(Duration::from_secs(10)).unwrap());
+    assert!(started.elapsed() < Duration::from_secs(10));
+    let (message, _) = h.state.status_message.as_ref().unwrap();
+    assert!(message.starts_with("Hook failed: aiui-harness/hung.rs - killed after 100ms"), "{}", message);
+    assert!(h.state.approval_queue.is_empty());
+    assert!(h.state.own_writes.is_empty());
+}
+
+#[test]
+fn observer_pool_keeps_each_files_events_in_order() {
+    let mut h = Harness::new();
+    let (tx, rx) = mpsc::channel::<AppEvent>();
+    h.state.observer = Some(ObservePool::new(std::sync::Arc::new(h.fs.clone()), Config::default(), tx));
+    let path = h.path("new.rs");
+    h.fs.put(&path, "one\n");
+    h.event(&path, ChangeKind::Create);
+    // Being read off-thread; the modify behind it has to wait
+    h.event(&path, ChangeKind::Modify);
+    assert!(h.state.approval_queue.is_empty());
+    assert_eq!(h.state.file_changes.len(), 0);
+
+    h.fs.put(&path, "one\ntwo\n");
+    engine::handle(&mut h.state, rx.recv_timeout(Duration::from_secs(10)).unwrap());
+    // The create was queued first and the modify merged into it
+    assert_eq!(h.state.approval_queue.len(), 1);
+    assert_eq!(h.state.approval_queue[0].kind, ChangeKind::Create);
+    assert_eq!(h.state.approval_queue[0].new_content, "one\ntwo\n");
+    assert!(h.state.observing.is_empty());
+}
+
+#[test]
+fn a_rename_waits_for_a_read_still_in_flight() {
+    let mut h = Harness::new();
+    let (tx, rx) = mpsc::channel::<AppEvent>();
+    h.state.observer = Some(ObservePool::new(std::sync::Arc::new(h.fs.clone()), Config::default(), tx));
+    let from = h.path("draft.rs");
+    let to = h.path("final.rs");
+    h.fs.put(&from, "text\n");
+    h.event(&from, ChangeKind::Create);
+    h.fs.delete(&from);
+    h.fs.put(&to, "text\n");
+    h.state.rename_event(from.clone(), to.clone());
+    // Nothing was decided ahead of the read
+    assert!(!h.state.file_cache.contains_key(&normalize_path(&to)));
+
+    while !h.state.observing.is_empty() {
+        engine::handle(&mut h.state, rx.recv_timeout(Duration::from_secs(10)).unwrap());
+    }
+    let queued: Vec<(&Path, &ChangeKind)> = h.state.approval_queue.iter().map(|p| (p.path.path(), &p.kind)).collect();
+    assert_eq!(queued, [(to.as_path(), &ChangeKind::Create)]);
+}
+
+#[test]
+fn check_command_runs_one_at_a_time_and_stops_when_decided() {
+    let mut h = Harness::new();
+    let (tx, rx) = mpsc::channel::<AppEvent>();
+    let failing = "echo 'src/a.rs:1:1: error: boom' >&2; exit 101";
+    h.state.checker = Some(Checker::new(failing.to_string(), std::env::temp_dir(), tx.clone()));
+    h.state.config.check_paths = vec!["**/*.rs".to_string()];
+    for name in ["a.rs", "b.rs", "notes.txt"] {
+        let path = h.known(name, "old\n");
+        h.fs.put(&path, "new\n");
+        h.event(&path, ChangeKind::Modify);
+    }
+    let ids: Vec<u64> = h.state.approval_queue.iter().map(|p| p.change_id).collect();
+    // notes.txt isn't covered by check_paths
+    assert_eq!(h.state.checks.len(), 2);
+
+    // a.rs started a run; b.rs waits for the next one rather than starting its own
+    engine::handle(&mut h.state, rx.recv_timeout(Duration::from_secs(10)).unwrap());
+    let failed = CheckStatus::Failed(vec!["src/a.rs:1:1: error: boom".to_string()]);
+    assert_eq!(h.state.checks.get(&ids[0]), Some(&failed));
+    assert_eq!(h.state.checks.get(&ids[1]), Some(&CheckStatus::Pending));
+    engine::handle(&mut h.state, rx.recv_timeout(Duration::from_secs(10)).unwrap());
+    assert_eq!(h.state.checks.get(&ids[1]), Some(&failed));
+
+    // A failed check doesn't stand in the way of accepting
+    h.state.accept_front();
+    assert_eq!(h.state.file_changes.iter().find(|c| c.id == ids[0]).unwrap().status, ChangeStatus::Accepted);
+
+    // Deciding the change a slow check is for kills it
+    h.state.checker = Some(Checker::new("sleep 30".to_string(), std::env::temp_dir(), tx));
+    let slow = h.known("slow.rs", "old\n");
+    h.fs.put(&slow, "new\n");
+    h.event(&slow, ChangeKind::Modify);
+    while h.state.approval_queue.len() > 1 {
+        h.state.accept_front();
+    }
+    let started = Instant::now();
+    h.state.reject_front();
+    engine::handle(&mut h.state, rx.recv_timeout(Duration::from_secs(10)).unwrap());
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert!(h.state.checks.is_empty());
+}
+
+#[test]
+fn accepts_in_a_burst_share_one_test_run() {
+    let mut h = Harness::new();
+    let (tx, rx) = mpsc::channel::<AppEvent>();
+    h.state.tests = Some(TestRunner::new("echo running; echo 'test a ... FAILED' >&2; exit 101".to_string(), std::env::temp_dir(), tx));
+    for name in ["a.rs", "b.rs"] {
+        let path = h.known(name, "old\n");
+        h.fs.put(&path, "new\n");
+        h.event(&path, ChangeKind::Modify);
+    }
+    h.state.accept_front();
+    h.state.accept_front();
+
+    // Nothing runs until the accepts settle
+    engine::handle(&mut h.state, AppEvent::Tick);
+    assert!(h.state.tests.as_ref().unwrap().elapsed().is_none());
+    thread::sleep(DEBOUNCE);
+    engine::handle(&mut h.state, AppEvent::Tick);
+    assert!(h.state.tests.as_ref().unwrap().elapsed().is_some());
+
+    while h.state.tests.as_ref().unwrap().elapsed().is_some() {
+        engine::handle(&mut h.state, rx.recv_timeout(Duration::from_secs(10)).unwrap());
+    }
+    let tests = h.state.tests.as_ref().unwrap();
+    let last = tests.last.clone().unwrap();
+    assert_eq!((last.exit_code, last.cancelled), (Some(101), false));
+    let mut output: Vec<&str> = tests.output.iter().map(String::as_str).collect();
+    output.sort();
+    assert_eq!(output, ["running", "test a ... FAILED"]);
+    // Both changes record the one run that followed them
+    assert!(h.state.file_changes.iter().all(|c| c.tests.as_ref() == Some(&last)));
+}
+
+#[test]
+fn protected_paths_are_reverted_on_the_spot() {
+    let mut h = Harness::new();
+    h.state.config.protected = vec![".env".to_string(), "aiui-harness/secrets/**".to_string()];
+
+    let env = h.known(".env", "KEY=1\n");
+    h.fs.put(&env, "KEY=stolen\n");
+    h.event(&env, ChangeKind::Modify);
+
+    let created = h.path("secrets/new.pem");
+    h.fs.put(&created, "-----BEGIN-----\n");
+    h.event(&created, ChangeKind::Create);
+
+    let removed = h.known("secrets/old.pem", "keep me\n");
+    h.fs.delete(&removed);
+    h.event(&removed, ChangeKind::Remove);
+    h.expire_held_removes();
+
+    assert!(h.state.approval_queue.is_empty());
+    assert_eq!(h.fs.get(&env).as_deref(), Some("KEY=1\n"));
+    assert_eq!(h.fs.get(&created), None);
+    assert_eq!(h.fs.get(&removed).as_deref(), Some("keep me\n"));
+    assert!(h.state.file_changes.iter().all(|c| c.status == ChangeStatus::Blocked));
+    assert_eq!(h.state.file_changes.len(), 3);
+}
+
+#[test]
+fn changes_without_a_baseline_are_never_truncated() {
+    let mut h = Harness::new();
+    h.state.config.protected = vec![".env".to_string()];
+    // Mid-scan, neither file is cached yet
+    h.state.indexing = Some(0);
+
+    let edited = h.path("edited.txt");
+    h.fs.put(&edited, "indexed later\nand edited\n");
+    h.event(&edited, ChangeKind::Modify);
+    assert!(h.state.approval_queue[0].baseline_missing);
+    h.state.reject_front();
+    assert_eq!(h.fs.get(&edited).as_deref(), Some("indexed later\nand edited\n"));
+    assert_eq!(h.state.file_cache.get(&normalize_path(&edited)).map(String::as_str), Some("indexed later\nand edited\n"));
+
+    let env = h.path(".env");
+    h.fs.put(&env, "KEY=2\n");
+    h.event(&env, ChangeKind::Modify);
+    assert_eq!(h.fs.get(&env).as_deref(), Some("KEY=2\n"));
+    // Blocking can't undo it, so it waits for a decision instead
+    let queued = &h.state.approval_queue[0];
+    assert!(queued.protected && queued.baseline_missing);
+    assert_eq!(h.state.file_changes[0].status, ChangeStatus::Pending);
+}
+
+#[test]
+fn a_change_without_a_baseline_keeps_that_through_a_requeue() {
+    let mut h = Harness::new();
+    h.state.indexing = Some(0);
+    let edited = h.path("edited.txt");
+    h.fs.put(&edited, "indexed later\n");
+    h.event(&edited, ChangeKind::Modify);
+
+    // Changed again before the decision: the reject requeues it against ""
+    h.fs.put(&edited, "indexed later\nand edited\n");
+    h.state.reject_front();
+    let queued = &h.state.approval_queue[0];
+    assert!(queued.refreshed && queued.baseline_missing);
+    h.state.reject_front();
+    assert_eq!(h.fs.get(&edited).as_deref(), Some("indexed later\nand edited\n"));
+    assert!(h.state.approval_queue.is_empty());
+}
+
+#[test]
+fn a_change_without_a_baseline_is_accepted_whole() {
+    let mut h = Harness::new();
+    h.state.indexing = Some(0);
+    let edited = h.path("edited.txt");
+    h.fs.put(&edited, "indexed later\n");
+    h.event(&edited, ChangeKind::Modify);
+
+    let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+    h.state.handle_modal_key(key(' '));
+    assert!(!h.state.approval_queue[0].is_partial());
+    h.state.handle_modal_key(key('y'));
+    assert_eq!(h.fs.get(&edited).as_deref(), Some("indexed later\n"));
+    assert_eq!(h.state.file_changes[0].status, ChangeStatus::Accepted);
+}
+
+#[test]
+fn quarantined_paths_wait_for_approval_even_when_auto_approved() {
+    let mut h = Harness::new();
+    h.state.config.protected = vec![".env".to_string()];
+    h.state.config.protected_mode = ProtectedMode::Quarantine;
+    h.state.config.auto_approve = vec!["**".to_string()];
+
+    let env = h.known(".env", "KEY=1\n");
+    h.fs.put(&env, "KEY=2\n");
+    h.event(&env, ChangeKind::Modify);
+
+    assert_eq!(h.state.approval_queue.len(), 1);
+    assert!(h.state.approval_queue[0].protected);
+    assert_eq!(h.state.file_changes[0].status, ChangeStatus::Pending);
+    assert_eq!(h.fs.get(&env).as_deref(), Some("KEY=2\n"));
+}
+
+#[test]
+fn dismiss_leaves_the_file_and_stops_asking() {
+    let mut h = Harness::new();
+    h.state.config.reject_mode = RejectMode::Dismiss;
+    let path = h.known("dismissed.rs", "mine\n");
+    h.fs.put(&path, "agent's\n");
+    h.event(&path, ChangeKind::Modify);
+    h.state.reject_front();
+
+    assert_eq!(h.fs.get(&path).as_deref(), Some("agent's\n"));
+    assert_eq!(h.state.file_changes[0].status, ChangeStatus::Dismissed);
+    assert_eq!(h.state.stats.rejected, 1);
+
+    // Another event for the same content is a no-op
+    h.state.debounce_map.clear();
+    h.event(&path, ChangeKind::Modify);
+    assert!(h.state.approval_queue.is_empty());
+    assert_eq!(h.state.file_changes.len(), 1);
+
+    // A quarantined protected file is put back all the same
+    h.state.config.protected = vec![".env".to_string()];
+    h.state.config.protected_mode = ProtectedMode::Quarantine;
+    h.state.config.revert_acknowledged = true;
+    let env = h.known(".env", "KEY=1\n");
+    h.fs.put(&env, "KEY=2\n");
+    h.event(&env, ChangeKind::Modify);
+    assert!(h.state.approval_queue[0].protected);
+    h.state.reject_front();
+    assert_eq!(h.fs.get(&env).as_deref(), Some("KEY=1\n"));
+    assert_eq!(h.state.file_changes[0].status, ChangeStatus::Rejected);
+}
+
+#[test]
+fn edits_while_the_agent_is_quiet_are_the_users() {
+    let mut h = Harness::new();
+    h.state.config.strict_origin = false;
+    h.agent_quiet = true;
+    let path = h.known("mine.rs", "old\n");
+    h.fs.put(&path, "new\n");
+    h.event(&path, ChangeKind::Modify);
+
+    assert!(h.state.approval_queue.is_empty());
+    assert_eq!(h.state.file_changes[0].origin, Origin::User);
+    assert_eq!(h.state.file_cache[&normalize_path(&path)], "new\n");
+    assert_eq!(h.state.stats.insertions, 0);
+
+    // Strict mode asks about everything
+    h.state.config.strict_origin = true;
+    h.fs.put(&path, "newer\n");
+    h.state.debounce_map.clear();
+    h.event(&path, ChangeKind::Modify);
+    assert_eq!(h.state.approval_queue.len(), 1);
+    assert_eq!(h.state.file_changes[0].origin, Origin::Agent);
+}
+
+#[test]
+fn quick_open_jumps_to_the_newest_change_of_a_file() {
+    let mut h = Harness::new();
+    for (name, text) in [("src/main.rs", "a\n"), ("README.md", "b\n"), ("src/diff.rs", "c\n")] {
+        let path = h.known(name, "");
+        h.fs.put(&path, text);
+        h.event(&path, ChangeKind::Modify);
+        h.state.accept_front();
+    }
+    // Filtered out, but quick-open still finds it
+    h.state.sidebar_filter.query = "README".to_string();
+
+    h.state.quick_open = Some(QuickOpen::default());
+    for c in "mainrs".chars() {
+        h.state.handle_quick_open_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+    }
+    h.state.handle_quick_open_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+
+    assert!(h.state.quick_open.is_none());
+    assert!(h.state.show_diff_view);
+    assert!(!h.state.sidebar_filter.is_active());
+    assert!(h.state.selected_change().unwrap().path.ends_with("src/main.rs"));
+}
+
+#[test]
+fn follow_opens_new_changes_until_the_user_navigates() {
+    let mut h = Harness::new();
+    h.state.follow = Follow::On;
+    let first = h.known("first.rs", "a\n");
+    h.fs.put(&first, "b\n");
+    h.event(&first, ChangeKind::Modify);
+
+    assert!(h.state.show_diff_view);
+    assert!(h.state.modal_active, "the approval still comes first");
+    assert_eq!(h.state.diff_cursor.current, Some(0));
+    assert!(h.state.selected_change().unwrap().path.ends_with("first.rs"));
+    h.state.accept_front();
+
+    // Moving the selection by hand pauses it
+    h.state.select_next();
+    assert!(h.state.follow == Follow::Paused);
+    h.state.show_diff_view = false;
+    let second = h.known("second.rs", "a\n");
+    h.fs.put(&second, "b\n");
+    h.event(&second, ChangeKind::Modify);
+    assert!(!h.state.show_diff_view);
+}
+
+#[test]
+fn latin1_and_utf16_files_are_diffed_as_text_and_reverted_byte_for_byte() {
+    let mut h = Harness::new();
+    let path = h.path("legacy.txt");
+    let original = b"caf\xE9\n".to_vec();
+    h.fs.put_bytes(&path, &original);
+    let latin1 = TextEncoding { encoding: encoding_rs::WINDOWS_1252, bom: false };
+    h.state.set_baseline(normalize_path(&path), "café\n".to_string(), latin1);
+
+    h.fs.put_bytes(&path, b"caf\xE9 cr\xE8me\n");
+    h.event(&path, ChangeKind::Modify);
+    let pending = h.state.approval_queue.front().unwrap();
+    assert!(pending.diff_text.as_str().contains("+café crème"));
+    h.state.reject_front();
+    assert_eq!(h.fs.get_bytes(&path), Some(original));
+
+    // UTF-16 with a BOM used to look binary (NULs everywhere)
+    let wide = h.path("wide.txt");
+    h.fs.put_bytes(&wide, b"\xFF\xFEh\x00i\x00\n\x00");
+    h.event(&wide, ChangeKind::Create);
+    let pending = h.state.approval_queue.front().unwrap();
+    assert!(!pending.binary);
+    assert!(pending.diff_text.as_str().contains("+hi"));
+    h.state.accept_front();
+    assert_eq!(h.state.file_cache[&normalize_path(&wide)], "hi\n");
+}
+
+// Permissions need a real file system; MemFs has none
+#[cfg(unix)]
+fn on_disk(name: &str) -> (AppState, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("aiui-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let roots = Roots::new(std::slice::from_ref(&dir)).unwrap();
+    let mut state = AppState::with_fs(Config::default(), roots, Box::new(crate::fs::RealFs));
+    state.indexing = None;
+    state.config.strict_origin = true;
+    (state, dir)
+}
+
+#[cfg(unix)]
+#[test]
+fn reverts_bring_back_the_executable_bit() {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    let (mut state, dir) = on_disk("modes");
+    let script = dir.join("build.sh");
+    std::fs::write(&script, "#!/bin/sh\nmake\n").unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    state.set_baseline(normalize_path(&script), "#!/bin/sh\nmake\n".to_string(), TextEncoding::default());
+
+    // Editors and agents often write a temp file and rename it over, losing the mode
+    let temp = dir.join("build.sh.tmp");
+    std::fs::write(&temp, "#!/bin/sh\nrm -rf /\n").unwrap();
+    std::fs::set_permissions(&temp, std::fs::Permissions::from_mode(0o644)).unwrap();
+    std::fs::rename(&temp, &script).unwrap();
+    state.add_change(script.clone(), ChangeKind::Modify);
+    state.reject_front();
+    assert_eq!(std::fs::read_to_string(&script).unwrap(), "#!/bin/sh\nmake\n");
+    assert_eq!(mode(&script), 0o755);
+    // Written through a temp file that's gone again
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    // What the watcher would report for our own write
+    state.debounce_map.clear();
+    state.add_change(script.clone(), ChangeKind::Modify);
+
+    // A deleted script comes back executable
+    std::fs::remove_file(&script).unwrap();
+    state.add_change(script.clone(), ChangeKind::Remove);
+    for held in &mut state.held_removes {
+        held.at = Instant::now() - RENAME_WINDOW;
+    }
+    state.flush_held_removes();
+    state.reject_front();
+    assert_eq!(mode(&script), 0o755);
+
+    // Rejecting a read-only creation still removes it
+    let created = dir.join("locked.sh");
+    std::fs::write(&created, "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(&created, std::fs::Permissions::from_mode(0o555)).unwrap();
+    state.add_change(created.clone(), ChangeKind::Create);
+    state.reject_front();
+    assert!(!created.exists());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn dropped_watch_events_rescan_the_cached_files() {
+    let mut h = Harness::new();
+    let edited = h.known("edited.rs", "old\n");
+    let deleted = h.known("deleted.rs", "bye\n");
+    let waiting = h.known("waiting.rs", "a\n");
+    h.fs.put(&waiting, "b\n");
+    h.event(&waiting, ChangeKind::Modify);
+    // Changes the watcher never got to report
+    h.fs.put(&edited, "new\n");
+    h.fs.delete(&deleted);
+    h.fs.put(&waiting, "c\n");
+
+    // Stand-in for the OS watcher
+    let (tx, rx) = mpsc::channel();
+    let overflow = notify::Event::new(notify::EventKind::Other).set_flag(notify::event::Flag::Rescan);
+    engine::forward(Ok(overflow), &tx);
+    engine::handle(&mut h.state, rx.try_recv().unwrap());
+
+    assert_eq!(h.state.file_cache[&normalize_path(&edited)], "new\n");
+    assert!(!h.state.file_cache.contains_key(&normalize_path(&deleted)));
+    // A pending approval keeps the baseline its diff was made against
+    assert_eq!(h.state.file_cache[&normalize_path(&waiting)], "a\n");
+    assert_eq!(h.state.approval_queue.len(), 1);
+    let problem = h.state.toasts.latest_problem(Duration::from_secs(30)).unwrap();
+    assert!(problem.contains("re-read 2 files, 2 changed"), "{}", problem);
+
+    engine::forward(Err(notify::Error::generic("too many watches")), &tx);
+    engine::handle(&mut h.state, rx.try_recv().unwrap());
+    let problem = h.state.toasts.latest_problem(Duration::from_secs(30)).unwrap();
+    assert!(problem.contains("too many watches"), "{}", problem);
+}
+
+#[test]
+fn saves_to_a_waiting_file_update_its_entry_in_place() {
+    let mut h = Harness::new();
+    let path = h.known("burst.rs", "a\n");
+    for (i, text) in ["a\nb\n", "a\nb\nc\n", "x\nb\nc\n"].into_iter().enumerate() {
+        h.fs.put(&path, text);
+        // Spread out past the debounce window
+        h.state.debounce_map.clear();
+        h.event(&path, ChangeKind::Modify);
+        assert_eq!(h.state.approval_queue.len(), 1, "save {}", i);
+    }
+
+    let pending = &h.state.approval_queue[0];
+    assert_eq!(pending.old_content, "a\n");
+    assert_eq!(pending.new_content, "x\nb\nc\n");
+    assert_eq!(h.state.file_changes.len(), 1);
+    let entry = &h.state.file_changes[0];
+    assert_eq!((entry.insertions, entry.deletions), (3, 1));
+    assert_eq!((h.state.stats.insertions, h.state.stats.deletions), (3, 1));
+
+    // Stats that no longer count the old diff don't underflow
+    h.state.stats = Default::default();
+    h.fs.put(&path, "a\nb\n");
+    h.state.debounce_map.clear();
+    h.event(&path, ChangeKind::Modify);
+    assert_eq!((h.state.stats.insertions, h.state.stats.deletions), (0, 0));
+
+    // Saved back to the original: nothing left to approve
+    h.fs.put(&path, "a\n");
+    h.event(&path, ChangeKind::Modify);
+    assert!(h.state.approval_queue.is_empty());
+    assert!(!h.state.modal_active);
+    assert_eq!(h.state.file_changes[0].status, ChangeStatus::Info);
+}
+
+#[test]
+fn sticky_selection_stays_on_the_entry_being_read() {
+    let mut h = Harness::new();
+    h.agent_quiet = true;
+    h.state.config.sidebar_selection = SidebarSelection::Sticky;
+    for name in ["one.rs", "two.rs"] {
+        let path = h.known(name, "a\n");
+        h.fs.put(&path, "b\n");
+        h.event(&path, ChangeKind::Modify);
+    }
+    h.state.select_row(Some(1));
+    assert_eq!(h.state.selected_change().unwrap().path, "aiui-harness/one.rs");
+
+    let path = h.known("three.rs", "a\n");
+    h.fs.put(&path, "b\n");
+    h.event(&path, ChangeKind::Modify);
+    assert_eq!(h.state.selected_row(), Some(2));
+    assert_eq!(h.state.selected_change().unwrap().path, "aiui-harness/one.rs");
+
+    // The default jumps to the newest
+    h.state.config.sidebar_selection = SidebarSelection::Newest;
+    let path = h.known("four.rs", "a\n");
+    h.fs.put(&path, "b\n");
+    h.event(&path, ChangeKind::Modify);
+    assert_eq!(h.state.selected_row(), Some(0));
+
+    // ...unless that entry's diff is open: it isn't swapped out mid-read
+    h.state.select_row(Some(2));
+    h.state.show_diff_view = true;
+    let reading = h.state.selected.unwrap();
+    let path = h.known("five.rs", "a\n");
+    h.fs.put(&path, "b\n");
+    h.event(&path, ChangeKind::Modify);
+    assert_eq!((h.state.selected, h.state.selected_row()), (Some(reading), Some(3)));
+}
+
+#[test]
+fn directories_are_logged_and_a_rejected_removal_restores_their_files() {
+    let mut h = Harness::new();
+    let fresh = h.path("fresh");
+    h.fs.put(&fresh.join("x.rs"), "x\n");
+    h.event(&fresh, ChangeKind::Create);
+    assert!(h.state.file_changes[0].dir);
+    assert!(h.state.approval_queue.is_empty());
+
+    let a = h.known("module/a.rs", "a\n");
+    let b = h.known("module/sub/b.rs", "b1\nb2\n");
+    let module = h.path("module");
+    h.fs.delete_dir(&module);
+    // Children first, like inotify reports an `rm -r`
+    for path in [a.clone(), b.clone(), module.join("sub"), module.clone()] {
+        h.event(&path, ChangeKind::Remove);
+    }
+    h.expire_held_removes();
+
+    // One approval for the directory, listing what it held
+    assert_eq!(h.state.approval_queue.len(), 1);
+    let entry = &h.state.file_changes[0];
+    assert!(entry.dir);
+    assert_eq!(entry.deletions, 3);
+    let listing = entry.diff.as_ref().unwrap().as_str();
+    assert!(listing.contains("-a.rs (1 lines)") && listing.contains("b.rs (2 lines)"), "{}", listing);
+
+    h.state.reject_front();
+    assert_eq!(h.fs.get(&a).as_deref(), Some("a\n"));
+    assert_eq!(h.fs.get(&b).as_deref(), Some("b1\nb2\n"));
+    // The recreated directory is our own doing
+    let logged = h.state.file_changes.len();
+    h.state.debounce_map.clear();
+    h.event(&module, ChangeKind::Create);
+    assert_eq!(h.state.file_changes.len(), logged);
+}
+
+#[test]
+fn a_removed_empty_file_is_a_file_not_a_directory() {
+    let mut h = Harness::new();
+    // Cached the way the startup scan does it
+    let path = h.path("empty.txt");
+    h.fs.put(&path, "");
+    let file = crate::indexer::IndexedFile { key: normalize_path(&path), text: String::new(), encoding: TextEncoding::default(), permissions: None };
+    h.state.apply_index_event(crate::indexer::IndexEvent::Batch(vec![file]));
+    h.fs.delete(&path);
+    h.event(&path, ChangeKind::Remove);
+    h.expire_held_removes();
+
+    assert_eq!(h.state.approval_queue.len(), 1);
+    let pending = &h.state.approval_queue[0];
+    assert!(pending.dir_files.is_empty());
+    assert_eq!(pending.diff_text.as_str(), "File Deleted: empty.txt");
+    assert!(!h.state.file_changes[0].dir);
+
+    h.state.reject_front();
+    assert_eq!(h.fs.get(&path).as_deref(), Some(""));
+}
+
+#[test]
+fn an_idle_screen_stops_animating() {
+    let mut h = Harness::new();
+    assert!(!h.state.animating());
+    // The spinner runs while the agent prints, and a held remove is resolved on a tick
+    let path = h.known("gone.rs", "bye\n");
+    h.fs.delete(&path);
+    h.event(&path, ChangeKind::Remove);
+    assert!(h.state.animating());
+    h.expire_held_removes();
+    h.state.last_pty_data = Some(Instant::now() - Duration::from_secs(2));
+    assert!(!h.state.animating());
+
+    h.state.notify(Severity::Info, "hello".to_string());
+    assert!(h.state.animating());
+}
+
+#[test]
+fn ignore_file_filters_and_reloads() {
+    let mut h = Harness::new();
+    let ignore_file = h.state.roots.primary().path.join(".ai-tui-ignore");
+    h.fs.put(&ignore_file, "*.lock\naiui-harness/gen/\n");
+    h.event(&ignore_file, ChangeKind::Create);
+    // The ignore file itself isn't a change
+    assert!(h.state.file_changes.is_empty());
+
+    for rel in ["Cargo.lock", "gen/api.pb.go"] {
+        let path = h.path(rel);
+        h.fs.put(&path, "generated\n");
+        h.event(&path, ChangeKind::Create);
+    }
+    assert!(h.state.file_changes.is_empty());
+
+    // Dropping a rule lets its files through again
+    h.fs.put(&ignore_file, "aiui-harness/gen/\n");
+    h.event(&ignore_file, ChangeKind::Modify);
+    let lock = h.path("Cargo.lock");
+    h.fs.put(&lock, "regenerated\n");
+    h.event(&lock, ChangeKind::Modify);
+    assert_eq!(h.state.approval_queue.len(), 1);
+}
+
+#[test]
+fn unanswered_approvals_time_out() {
+    let mut h = Harness::new();
+    h.state.config.approval_timeout_secs = 60;
+    let path = h.known("slow.rs", "old\n");
+    h.fs.put(&path, "new\n");
+    h.event(&path, ChangeKind::Modify);
+    assert_eq!(h.state.approval_queue.len(), 1);
+    // An unattended reject can't be the first revert
+    assert!(h.state.approval_time_left().is_none());
+    h.state.config.revert_acknowledged = true;
+
+    // The first tick starts the clock
+    engine::handle(&mut h.state, AppEvent::Tick);
+    assert_eq!(h.state.approval_queue.len(), 1);
+    assert!(h.state.approval_time_left().unwrap() > Duration::from_secs(59));
+
+    let id = h.state.approval_queue[0].change_id;
+    h.state.front_since = Some((id, Instant::now() - Duration::from_secs(60)));
+    engine::handle(&mut h.state, AppEvent::Tick);
+    assert!(h.state.approval_queue.is_empty());
+    assert_eq!(h.fs.get(&path).as_deref(), Some("old\n"));
+    assert_eq!(h.state.file_changes[0].status, ChangeStatus::Rejected);
+    assert!(h.state.file_changes[0].note.as_deref().unwrap().starts_with("auto-decided: auto-reject"));
+}
+
+#[test]
+fn protected_and_unindexed_approvals_never_time_out() {
+    let mut h = Harness::new();
+    h.state.config.approval_timeout_secs = 60;
+    h.state.config.approval_timeout_action = TimeoutAction::Approve;
+    h.state.config.protected = vec![".env".to_string()];
+    h.state.config.protected_mode = ProtectedMode::Quarantine;
+    let env = h.known(".env", "KEY=1\n");
+    h.fs.put(&env, "KEY=2\n");
+    h.event(&env, ChangeKind::Modify);
+    h.state.indexing = Some(0);
+    let unindexed = h.path("unindexed.rs");
+    h.fs.put(&unindexed, "new\n");
+    h.event(&unindexed, ChangeKind::Modify);
+    assert_eq!(h.state.approval_queue.len(), 2);
+
+    for _ in 0..2 {
+        assert!(h.state.approval_time_left().is_none());
+        let id = h.state.approval_queue[0].change_id;
+        h.state.front_since = Some((id, Instant::now() - Duration::from_secs(60)));
+        engine::handle(&mut h.state, AppEvent::Tick);
+        assert_eq!(h.state.approval_queue[0].change_id, id);
+        h.state.review_pending(1);
+    }
+    assert!(h.state.file_changes.iter().all(|c| c.status == ChangeStatus::Pending));
+}
+
+#[test]
+fn typed_prompts_and_snippets_reach_the_picker() {
+    let mut h = Harness::new();
+    let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+    for c in "fix teh bug".chars() {
+        h.state.note_typed(&key(KeyCode::Char(c)));
+    }
+    for _ in 0..6 {
+        h.state.note_typed(&key(KeyCode::Backspace));
+    }
+    for c in "he bug".chars() {
+        h.state.note_typed(&key(KeyCode::Char(c)));
+    }
+    h.state.note_typed(&key(KeyCode::Enter));
+    // Recalled with an arrow: what's on the line is anyone's guess
+    h.state.note_typed(&key(KeyCode::Char('x')));
+    h.state.note_typed(&key(KeyCode::Up));
+    h.state.note_typed(&key(KeyCode::Enter));
+    assert_eq!(h.state.prompt_history, ["fix the bug"]);
+
+    let review = Snippet { name: "review".to_string(), text: "Review {file}".to_string() };
+    let history = h.state.prompt_history.iter().cloned();
+    h.state.snippet_picker = Some(SnippetPicker::new(vec![review.clone()], history, None));
+    for c in "rev".chars() {
+        assert_eq!(h.state.handle_snippet_key(key(KeyCode::Char(c))), None);
+    }
+    // Nothing selected in the sidebar, so {file} is asked for
+    assert_eq!(h.state.handle_snippet_key(key(KeyCode::Enter)), None);
+    for c in "a.rs".chars() {
+        h.state.handle_snippet_key(key(KeyCode::Char(c)));
+    }
+    assert_eq!(h.state.handle_snippet_key(key(KeyCode::Enter)).as_deref(), Some("Review a.rs"));
+    assert!(h.state.snippet_picker.is_none());
+
+    // With a change selected it fills itself in
+    let path = h.known("lib.rs", "old\n");
+    h.fs.put(&path, "new\n");
+    h.event(&path, ChangeKind::Modify);
+    h.state.select_row(Some(0));
+    h.state.snippet_picker = Some(SnippetPicker::new(vec![review], Vec::new(), None));
+    let sent = h.state.handle_snippet_key(key(KeyCode::Enter)).unwrap();
+    assert!(sent.starts_with("Review ") && sent.ends_with("lib.rs"), "{}", sent);
+}
+
+#[test]
+fn dismissing_an_entry_leaves_baselines_and_approvals_alone() {
+    let mut h = Harness::new();
+    let mut paths = Vec::new();
+    for name in ["one.rs", "two.rs", "three.rs"] {
+        let path = h.known(name, "old\n");
+        h.fs.put(&path, "new\n");
+        h.event(&path, ChangeKind::Modify);
+        paths.push(path);
+    }
+    // Newest first: three, two, one
+    h.state.select_row(Some(1));
+    h.state.handle_sidebar_key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::NONE));
+    let left: Vec<&str> = h.state.file_changes.iter().map(|c| c.path.as_str()).collect();
+    assert!(left.len() == 2 && left[0].ends_with("three.rs") && left[1].ends_with("one.rs"), "{:?}", left);
+    assert!(h.state.selected_change().unwrap().path.ends_with("one.rs"));
+    assert_eq!(h.state.approval_queue.len(), 3);
+    assert!(h.state.file_cache.contains_key(&normalize_path(&paths[1])));
+
+    // The last row falls back to the one above it
+    h.state.handle_sidebar_key(KeyEvent::new(KeyCode::Delete, KeyModifiers::NONE));
+    assert!(h.state.selected_change().unwrap().path.ends_with("three.rs"));
+    h.state.handle_sidebar_key(KeyEvent::new(KeyCode::Delete, KeyModifiers::NONE));
+    assert!(h.state.file_changes.is_empty() && h.state.selected_row().is_none());
+}
+
+#[test]
+fn marked_entries_can_be_requeued_and_deleted_together() {
+    let mut h = Harness::new();
+    let key = |h: &mut Harness, c: char| h.state.handle_sidebar_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+    let edited = h.known("edited.rs", "one\ntwo\nthree\n");
+    h.fs.put(&edited, "one\n2\nthree\n");
+    h.event(&edited, ChangeKind::Modify);
+    h.state.accept_front();
+    let created = h.path("created.rs");
+    h.fs.put(&created, "fresh\n");
+    h.event(&created, ChangeKind::Create);
+    h.state.accept_front();
+
+    // Newest first: created, edited
+    for row in [0, 1] {
+        h.state.select_row(Some(row));
+        key(&mut h, ' ');
+    }
+    // A new entry pushes the rows down; the marks stay on their entries
+    let other = h.known("other.rs", "a\n");
+    h.fs.put(&other, "b\n");
+    h.event(&other, ChangeKind::Modify);
+    h.state.accept_front();
+    let marked: Vec<&str> = h.state.marked_changes().iter().map(|c| c.path.as_str()).collect();
+    assert!(marked.len() == 2 && marked[0].ends_with("edited.rs") && marked[1].ends_with("created.rs"), "{:?}", marked);
+
+    key(&mut h, 'a');
+    key(&mut h, 'r');
+    assert!(h.state.marked.is_empty());
+    assert_eq!(h.state.approval_queue.len(), 2);
+    assert_eq!(h.state.approval_queue[0].old_content, "one\ntwo\nthree\n");
+    assert_eq!(h.state.approval_queue[1].kind, ChangeKind::Create);
+    assert_eq!(h.state.file_changes.iter().filter(|c| c.status == ChangeStatus::Pending).count(), 2);
+    // Turning it down this time puts the old content back
+    h.state.reject_front();
+    assert_eq!(h.fs.get(&edited).as_deref(), Some("one\ntwo\nthree\n"));
+
+    h.state.select_row(Some(0));
+    key(&mut h, ' ');
+    key(&mut h, 'a');
+    key(&mut h, 'd');
+    assert_eq!(h.state.file_changes.len(), 2);
+    assert!(h.state.file_changes.iter().all(|c| !c.path.ends_with("other.rs")));
+}
+
+#[test]
+fn marks_go_with_their_entries_when_the_log_is_cleared_or_trimmed() {
+    let mut h = Harness::new();
+    let key = |h: &mut Harness, c: char| h.state.handle_sidebar_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+    let create = |h: &mut Harness, name: &str| {
+        let path = h.path(name);
+        h.fs.put(&path, "x\n");
+        h.event(&path, ChangeKind::Create);
+        h.state.accept_front();
+    };
+    h.state.history_cap = 1;
+    create(&mut h, "old.rs");
+    h.state.select_row(Some(0));
+    key(&mut h, ' ');
+    // Evicting old.rs takes its mark with it
+    create(&mut h, "new.rs");
+    assert_eq!(h.state.file_changes.len(), 1);
+    assert!(h.state.marked.is_empty());
+
+    h.state.select_row(Some(0));
+    key(&mut h, ' ');
+    h.state.clear_log();
+    assert!(h.state.marked.is_empty());
+    key(&mut h, 'a');
+    assert!(!h.state.mark_menu);
+}
+
+#[test]
+fn clearing_the_log_deletes_its_spilled_diffs() {
+    let mut h = Harness::new();
+    h.state.config.spill_diff_bytes = 10;
+    let path = h.known("long.rs", "a\n");
+    h.fs.put(&path, "a long enough line to spill\n");
+    h.event(&path, ChangeKind::Modify);
+    h.state.accept_front();
+    let spill = h.state.file_changes[0].spill.clone().unwrap();
+    h.state.select_row(Some(0));
+    h.state.prepare_selected_diff();
+    assert_eq!(h.state.loaded_spill.as_ref(), Some(&spill));
+
+    h.state.clear_log();
+    assert!(!spill.exists());
+    assert!(h.state.loaded_spill.is_none());
+}
+
+#[test]
+fn selected_change_copies_as_markdown() {
+    let mut h = Harness::new();
+    let path = h.known("share.rs", "a\n");
+    h.fs.put(&path, "a\nb\n");
+    h.event(&path, ChangeKind::Modify);
+    h.state.select_row(Some(0));
+    h.state.handle_sidebar_key(KeyEvent::new(KeyCode::Char('m'), KeyModifiers::NONE));
+
+    let text = h.state.clipboard.take().unwrap();
+    assert!(text.starts_with("### ") && text.contains("share.rs (Modify)"), "{}", text);
+    assert!(text.contains("+1 −0 lines\n\n```diff\n@@"), "{}", text);
+    assert!(text.ends_with("+b\n```\n"), "{}", text);
+}
+
+#[test]
+fn churn_counts_outlive_the_log_entries() {
+    let mut h = Harness::new();
+    let busy = h.known("busy.rs", "0\n");
+    for n in 1..=3 {
+        h.fs.put(&busy, &format!("{}\n", n));
+        h.event(&busy, ChangeKind::Modify);
+        h.state.accept_front();
+        h.state.debounce_map.clear();
+    }
+    let calm = h.known("calm.rs", "a\n");
+    h.fs.put(&calm, "b\nc\nd\n");
+    h.event(&calm, ChangeKind::Modify);
+
+    // Dismissing the entries doesn't reset the count
+    while !h.state.file_changes.is_empty() {
+        h.state.select_row(Some(0));
+        h.state.dismiss_selected();
+    }
+    let ranked = h.state.churn.ranked(ChurnOrder::Count);
+    assert_eq!(ranked.iter().map(|f| (f.path.as_str(), f.changes)).collect::<Vec<_>>(), [("aiui-harness/busy.rs", 3), ("aiui-harness/calm.rs", 1)]);
+    assert_eq!((ranked[0].insertions, ranked[0].deletions), (3, 3));
+    assert_eq!(h.state.churn.ranked(ChurnOrder::Recent)[0].path, "aiui-harness/calm.rs");
+}
+
+#[test]
+fn json_export_leads_with_session_stats() {
+    let mut h = Harness::new();
+    let edited = h.known("stat.rs", "a\n");
+    h.fs.put(&edited, "b\n");
+    h.event(&edited, ChangeKind::Modify);
+    let created = h.path("stat_new.rs");
+    h.fs.put(&created, "x\ny\n");
+    h.event(&created, ChangeKind::Create);
+    h.state.approval_queue[0].queued_at -= Duration::from_secs(4);
+    h.state.accept_front();
+    h.state.reject_front();
+    h.state.output.record(b"thinking\ndone\n", Instant::now());
+
+    let path = h.path("session-report.json");
+    h.state.export(&path, ExportFormat::Json).unwrap();
+    let report: serde_json::Value = serde_json::from_str(&h.fs.get(&path).unwrap()).unwrap();
+    // Written into the project, and not mistaken for a change there
+    let logged = h.state.file_changes.len();
+    h.event(&path, ChangeKind::Create);
+    assert_eq!(h.state.file_changes.len(), logged);
+    // The log used to be the whole report; the version says it no longer is
+    assert_eq!(report["version"], 2);
+    let stats = &report["stats"];
+    assert_eq!(stats["changes"], serde_json::json!({ "create": 1, "modify": 1 }));
+    assert_eq!((stats["accepted"].as_u64(), stats["rejected"].as_u64()), (Some(1), Some(1)));
+    // One waited four seconds, the other hardly at all
+    let average = stats["average_decision_secs"].as_f64().unwrap();
+    assert!((2.0..3.0).contains(&average), "{}", average);
+    assert_eq!((stats["output_bytes"].as_u64(), stats["output_lines"].as_u64()), (Some(14), Some(2)));
+    assert_eq!(report["changes"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn only_changes_that_are_shown_ask_git_for_a_fresh_status() {
+    let mut h = Harness::new();
+    let t0 = Instant::now();
+    assert!(h.state.git_status.start(t0));
+    h.state.git_status.finished(Some(false));
+    let soon = t0 + Duration::from_millis(1500);
+
+    // Build output under target/ churns constantly; it's not worth a `git status`
+    let built = h.path("target/debug/out.o");
+    h.fs.put(&built, "obj");
+    h.event(&built, ChangeKind::Create);
+    assert!(!h.state.git_status.start(soon));
+
+    let edited = h.known("dirty.rs", "a\n");
+    h.fs.put(&edited, "b\n");
+    h.event(&edited, ChangeKind::Modify);
+    assert!(h.state.git_status.start(soon));
+}
+
+#[test]
+fn patch_exports_add_new_files_and_follow_renames() {
+    let mut h = Harness::new();
+    let edited = h.known("p/edited.rs", "a\nb\n");
+    h.fs.put(&edited, "a\nB\n");
+    h.event(&edited, ChangeKind::Modify);
+    let created = h.path("p/created.rs");
+    h.fs.put(&created, "x\ny");
+    h.event(&created, ChangeKind::Create);
+    let from = h.known("p/old.rs", "1\n2\n");
+    let to = h.path("p/new.rs");
+    h.fs.delete(&from);
+    h.fs.put(&to, "1\n2\n3\n");
+    h.state.record_rename(from.clone(), to.clone());
+    let removed = h.known("p/removed.rs", "gone\n");
+    h.fs.delete(&removed);
+    h.event(&removed, ChangeKind::Remove);
+    h.expire_held_removes();
+
+    let changes: Vec<_> = h.state.file_changes.iter().rev().collect();
+    let (patch, skipped) = crate::export::patch(&changes, &h.state.roots.primary().path);
+    assert_eq!(skipped, 1, "the removal has no line diff");
+    let p = "aiui-harness/p";
+    let expected = [
+        format!("diff --git a/{p}/edited.rs b/{p}/edited.rs\n--- a/{p}/edited.rs\n+++ b/{p}/edited.rs\n@@ -1,2 +1,2 @@\n a\n-b\n+B\n"),
+        format!("diff --git a/{p}/created.rs b/{p}/created.rs\nnew file mode 100644\n--- /dev/null\n+++ b/{p}/created.rs\n@@ -0,0 +1,2 @@\n+x\n+y\n\\ No newline at end of file\n"),
+        format!("diff --git a/{p}/old.rs b/{p}/new.rs\nrename from {p}/old.rs\nrename to {p}/new.rs\n--- a/{p}/old.rs\n+++ b/{p}/new.rs\n@@ -1,2 +1,3 @@\n 1\n 2\n+3\n"),
+    ];
+    assert_eq!(patch, expected.concat());
+    // Anything outside the root it's applied in is left out
+    assert_eq!(crate::export::patch(&changes, &h.path("p/elsewhere")).1, changes.len());
+}
+
+#[test]
+fn line_ending_flips_are_not_changes_when_normalized() {
+    let mut h = Harness::new();
+    h.state.config.normalize_line_endings = true;
+    let path = h.known("eol.rs", "one\r\ntwo\r\nthree\r\n");
+    h.fs.put(&path, "one\ntwo\nthree\n");
+    h.event(&path, ChangeKind::Modify);
+    assert!(h.state.approval_queue.is_empty() && h.state.file_changes.is_empty());
+
+    // A real edit shows only its own line, and a reject puts the original bytes back
+    h.state.debounce_map.clear();
+    h.fs.put(&path, "one\nTWO\nthree\n");
+    h.event(&path, ChangeKind::Modify);
+    assert_eq!((h.state.approval_queue[0].insertions, h.state.approval_queue[0].deletions), (1, 1));
+    h.state.reject_front();
+    assert_eq!(h.fs.get(&path).as_deref(), Some("one\r\ntwo\r\nthree\r\n"));
+
+    // Off, it's a change, but one that says what it is
+    h.state.config.normalize_line_endings = false;
+    h.state.debounce_map.clear();
+    h.fs.put(&path, "one\ntwo\nthree\n");
+    h.event(&path, ChangeKind::Modify);
+    assert_eq!(h.state.approval_queue[0].insertions, 0);
+    assert_eq!(h.state.approval_queue[0].diff_text.as_str(), "Line ending changes only: CRLF → LF (3 lines)\n");
+}
+
+#[test]
+fn line_endings_survive_diffs_and_reverts() {
+    let mut h = Harness::new();
+    let lf = h.known("lf.rs", "a\nb\nc\n");
+    let crlf = h.known("crlf.rs", "a\r\nb\r\nc\r\n");
+    let mixed = h.known("mixed.rs", "a\r\nb\nc\r\nd");
+
+    // Content edits show just the edited line whatever the endings
+    h.fs.put(&lf, "a\nB\nc\n");
+    h.fs.put(&crlf, "a\r\nB\r\nc\r\n");
+    h.fs.put(&mixed, "a\r\nB\nc\r\nd");
+    for path in [&lf, &crlf, &mixed] {
+        h.event(path, ChangeKind::Modify);
+    }
+    assert_eq!(h.state.approval_queue.len(), 3);
+    for pending in &h.state.approval_queue {
+        assert_eq!((pending.insertions, pending.deletions), (1, 1));
+        assert!(pending.diff_text.as_str().starts_with("@@ -1,"));
+        assert!(pending.diff_text.as_str().contains("\n a\n-b\n+B\n c\n"), "{}", pending.diff_text.as_str());
+    }
+    // and a reject writes back exactly what was there
+    (0..3).for_each(|_| h.state.reject_front());
+    assert_eq!(h.fs.get(&lf).as_deref(), Some("a\nb\nc\n"));
+    assert_eq!(h.fs.get(&crlf).as_deref(), Some("a\r\nb\r\nc\r\n"));
+    assert_eq!(h.fs.get(&mixed).as_deref(), Some("a\r\nb\nc\r\nd"));
+
+    // Endings alone: marked as such, noted in the sidebar, and undone byte for byte
+    h.state.debounce_map.clear();
+    h.fs.put(&mixed, "a\nb\nc\nd");
+    h.event(&mixed, ChangeKind::Modify);
+    assert_eq!(h.state.approval_queue[0].diff_text.as_str(), "Line ending changes only: mixed → LF (2 lines)\n");
+    assert_eq!(h.state.file_changes[0].note.as_deref(), Some("Line ending changes only: mixed → LF (2 lines)"));
+    h.state.reject_front();
+    assert_eq!(h.fs.get(&mixed).as_deref(), Some("a\r\nb\nc\r\nd"));
+
+    // Or taken as noise when that's configured
+    h.state.config.auto_approve_line_endings = true;
+    h.state.debounce_map.clear();
+    h.fs.put(&crlf, "a\nb\nc\n");
+    h.event(&crlf, ChangeKind::Modify);
+    assert!(h.state.approval_queue.is_empty());
+    assert_eq!(h.state.file_changes[0].status, ChangeStatus::AutoApproved);
+    assert_eq!(h.state.file_cache[&normalize_path(&crlf)], "a\nb\nc\n");
+    // which doesn't stretch to real edits
+    h.state.debounce_map.clear();
+    h.fs.put(&crlf, "a\nB\nc\n");
+    h.event(&crlf, ChangeKind::Modify);
+    assert_eq!(h.state.approval_queue.len(), 1);
+}
+
+#[test]
+fn edits_that_also_flip_line_endings_say_so_and_can_be_requeued() {
+    let mut h = Harness::new();
+    let edited = h.known("edited.rs", "a\r\nb\r\nc\r\n");
+    let flipped = h.known("flipped.rs", "a\r\nb\r\n");
+
+    // The diff is just the edited line, so the note is what tells of the rest
+    h.fs.put(&edited, "a\nB\nc\n");
+    h.event(&edited, ChangeKind::Modify);
+    assert_eq!(h.state.approval_queue[0].diff_text.as_str().matches('\n').count(), 5);
+    assert_eq!(h.state.file_changes[0].note.as_deref(), Some("also changes line endings CRLF → LF"));
+    h.fs.put(&flipped, "a\nb\n");
+    h.event(&flipped, ChangeKind::Modify);
+    h.state.accept_front();
+    h.state.accept_front();
+
+    // Both go back up against what they were, endings included
+    h.state.marked = h.state.file_changes.iter().map(|c| c.id).collect();
+    assert_eq!(h.state.requeue_marked(), (2, 0));
+    (0..2).for_each(|_| h.state.reject_front());
+    assert_eq!(h.fs.get(&edited).as_deref(), Some("a\r\nb\r\nc\r\n"));
+    assert_eq!(h.fs.get(&flipped).as_deref(), Some("a\r\nb\r\n"));
+}
+
+#[test]
+fn changes_that_dropped_escape_sequences_are_not_requeued() {
+    let mut h = Harness::new();
+    let snapshot = h.known("colored.snap", "ok\n\x1b[31mfailed\x1b[0m\n");
+    let plain = h.known("plain.rs", "a\nb\n");
+    h.fs.put(&snapshot, "ok\n");
+    h.event(&snapshot, ChangeKind::Modify);
+    h.fs.put(&plain, "a\n");
+    h.event(&plain, ChangeKind::Modify);
+    h.state.accept_front();
+    h.state.accept_front();
+
+    // The diff only has "failed" for the removed line; rebuilding from it
+    // would make a reject write the snapshot back without its colors
+    h.state.marked = h.state.file_changes.iter().map(|c| c.id).collect();
+    assert_eq!(h.state.requeue_marked(), (1, 1));
+    assert_eq!(h.state.approval_queue[0].path, normalize_path(&plain));
+    assert_eq!(h.state.file_cache[&normalize_path(&snapshot)], "ok\n");
+}
+
+#[test]
+fn diff_context_recuts_the_selected_change_without_reading_disk() {
+    let mut h = Harness::new();
+    let old: String = (1..=20).map(|n| format!("line {}\n", n)).collect();
+    let path = h.known("wide.rs", &old);
+    h.fs.put(&path, &old.replace("line 2\n", "LINE 2\n").replace("line 18\n", "LINE 18\n"));
+    h.event(&path, ChangeKind::Modify);
+    h.state.accept_front();
+    assert_eq!(h.state.file_changes[0].hunks.len(), 2);
+
+    // Disk has moved on, and the view still widens the change as it was: one
+    // hunk over the whole file
+    h.fs.put(&path, "rewritten\n");
+    h.state.select_row(Some(0));
+    h.state.diff_context = crate::diff::step_context(50, true);
+    h.state.prepare_selected_diff();
+    let shown = h.state.shown_diff().unwrap();
+    assert_eq!((shown.hunks.len(), shown.context), (1, usize::MAX));
+    assert_eq!(shown.diff.unwrap().line_count(), 1 + 20 + 2);
+    assert_eq!(crate::diff::line_counts(shown.hunks), (2, 2));
+    // Only the view: the entry, and so exports and copies, keep what was recorded
+    let change = &h.state.file_changes[0];
+    assert_eq!((change.hunks.len(), change.context), (2, crate::diff::DEFAULT_CONTEXT));
+    assert!(crate::export::markdown_change(change, false).contains("@@ -1,5 +1,5 @@"));
+
+    // Down to the changed lines alone
+    h.state.diff_context = 0;
+    h.state.prepare_selected_diff();
+    let shown = h.state.shown_diff().unwrap();
+    assert_eq!(shown.hunks.len(), 2);
+    assert!(shown.hunks.iter().all(|hunk| hunk.lines.len() == 2));
+    let step = crate::diff::step_context;
+    assert_eq!((step(0, false), step(4, true), step(4, false)), (0, 5, 3));
+
+    // A whole-file diff past the spill limit isn't kept just for re-cutting
+    h.state.config.spill_diff_bytes = 100;
+    let big: String = (1..=50).map(|n| format!("line {}\n", n)).collect();
+    let path = h.known("big.rs", &big);
+    h.fs.put(&path, &big.replace("line 25\n", "LINE 25\n"));
+    h.event(&path, ChangeKind::Modify);
+    let change = h.state.file_changes.iter().find(|c| c.path.ends_with("big.rs")).unwrap();
+    assert!(change.spill.is_none() && change.full.is_none());
+}
+
+#[test]
+fn full_diff_from_the_modal_leaves_a_banner_and_the_queue_alone() {
+    let mut h = Harness::new();
+    let first = h.known("first.rs", "a\n");
+    let second = h.known("second.rs", "b\n");
+    h.fs.put(&first, "a\nA\n");
+    h.fs.put(&second, "b\nB\n");
+    h.event(&first, ChangeKind::Modify);
+    h.event(&second, ChangeKind::Modify);
+    h.state.approval_queue[0].toggle_hunk(0);
+
+    let screen = |state: &mut AppState| {
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(120, 30)).unwrap();
+        terminal.draw(|frame| crate::draw(frame, state, None)).unwrap();
+        let buffer = terminal.backend().buffer().clone();
+        buffer.content.chunks(120).map(|row| row.iter().map(|c| c.symbol()).collect::<String>()).collect::<Vec<_>>()
+    };
+    assert!(screen(&mut h.state).iter().any(|row| row.contains("[d/Ctrl+K] Full diff  [o] Edit")));
+
+    // `d`: the modal shrinks to the row above the status bar
+    assert_eq!(h.state.handle_modal_key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::NONE)), None);
+    let rows = screen(&mut h.state);
+    assert!(!rows.iter().any(|row| row.contains("Approve change")));
+    assert!(rows[28].starts_with("  2 pending: "), "{}", rows[28]);
+    assert!(rows[28].contains("first.rs +1 −0  —  [Ctrl+K] back to the approval"), "{}", rows[28]);
+
+    // Nothing was decided or reordered, and the hunk toggle is still there
+    let queued: Vec<_> = h.state.approval_queue.iter().map(|p| p.path.clone()).collect();
+    assert_eq!(queued, [normalize_path(&first), normalize_path(&second)]);
+    assert_eq!(h.state.approval_queue[0].hunk_decisions, [false]);
+    assert_eq!(h.state.file_changes.iter().filter(|c| c.status == ChangeStatus::Pending).count(), 2);
+
+    // Out of the diff view, the modal is back and the banner row gone
+    h.state.show_diff_view = false;
+    h.state.modal_active = true;
+    let rows = screen(&mut h.state);
+    assert!(rows.iter().any(|row| row.contains("Approve change")));
+    assert!(!rows.iter().any(|row| row.contains("back to the approval")));
+}
+
+#[test]
+fn modal_keys_open_the_file_only_when_its_on_disk_and_follow_toggle_diff() {
+    let press = |h: &mut Harness, code, modifiers| h.state.handle_modal_key(KeyEvent::new(code, modifiers));
+
+    // `o` on an edit: the file, at its first changed line
+    let mut h = Harness::new();
+    let edited = h.known("open.rs", "a\nb\n");
+    h.fs.put(&edited, "a\nB\n");
+    h.event(&edited, ChangeKind::Modify);
+    assert_eq!(press(&mut h, KeyCode::Char('o'), KeyModifiers::NONE), Some((normalize_path(&edited).path().to_path_buf(), Some(2))));
+
+    // Nothing to open for a removal or a binary file
+    let mut h = Harness::new();
+    let removed = h.known("gone.rs", "a\n");
+    h.fs.delete(&removed);
+    h.event(&removed, ChangeKind::Remove);
+    h.expire_held_removes();
+    assert_eq!(press(&mut h, KeyCode::Char('o'), KeyModifiers::NONE), None);
+    assert_eq!(h.state.status_message.as_ref().map(|(m, _)| m.as_str()), Some("Nothing on disk to open"));
+    let mut h = Harness::new();
+    h.state.config.binary_approval = crate::config::BinaryApproval::Metadata;
+    let blob = h.path("blob.bin");
+    h.fs.put_bytes(&blob, b"\0\x01\x02");
+    h.event(&blob, ChangeKind::Create);
+    assert!(h.state.approval_queue[0].binary);
+    assert_eq!(press(&mut h, KeyCode::Char('o'), KeyModifiers::NONE), None);
+    assert!(h.state.modal_active);
+
+    // With toggle_diff moved, the footer and the cut preview name the new key
+    let mut h = Harness::new();
+    h.state.config.keys = toml::from_str("toggle_diff = \"alt+d\"").unwrap();
+    h.state.config.new_file_preview_lines = 3;
+    let long = h.path("long.rs");
+    h.fs.put(&long, "1\n2\n3\n4\n5\n6\n");
+    h.event(&long, ChangeKind::Create);
+    let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(120, 30)).unwrap();
+    terminal.draw(|frame| crate::draw(frame, &mut h.state, None)).unwrap();
+    let buffer = terminal.backend().buffer().clone();
+    let rows: Vec<String> = buffer.content.chunks(120).map(|row| row.iter().map(|c| c.symbol()).collect()).collect();
+    assert!(rows.iter().any(|row| row.contains("[d/Alt+D] Full diff")));
+    assert!(rows.iter().any(|row| row.contains("more lines - d/Alt+D opens the full diff")));
+    // Either key leaves the modal for the diff view
+    press(&mut h, KeyCode::Char('d'), KeyModifiers::ALT);
+    assert!(h.state.show_diff_view && !h.state.modal_active);
+}
+
+#[test]
+fn commit_takes_approved_files_once_and_never_ones_still_waiting() {
+    let mut h = Harness::new();
+    h.state.config.commit_message = "Apply {count} (+{insertions} -{deletions})\n{files}".to_string();
+    let accepted = h.known("accepted.rs", "a\n");
+    let twice = h.known("twice.rs", "t\n");
+    let rejected = h.known("rejected.rs", "r\n");
+    let waiting = h.known("waiting.rs", "w\n");
+    assert!(h.state.begin_commit().is_none());
+    assert_eq!(h.state.status_message.as_ref().unwrap().0, "Nothing approved to commit");
+
+    let change = |h: &mut Harness, path: &PathBuf, text: &str| {
+        h.state.debounce_map.clear();
+        h.fs.put(path, text);
+        h.event(path, ChangeKind::Modify);
+    };
+    change(&mut h, &accepted, "a\nA\n");
+    h.state.accept_front();
+    change(&mut h, &twice, "t\nT\n");
+    h.state.accept_front();
+    change(&mut h, &twice, "T\n");
+    h.state.accept_front();
+    change(&mut h, &rejected, "R\n");
+    h.state.reject_front();
+    // Still being read, or held as a possible rename
+    let observed = h.known("observed.rs", "o\n");
+    change(&mut h, &observed, "o\nO\n");
+    h.state.accept_front();
+    let held = h.known("held.rs", "h\n");
+    change(&mut h, &held, "h\nH\n");
+    h.state.accept_front();
+    h.fs.delete(&held);
+    h.event(&held, ChangeKind::Remove);
+    // Accepted earlier, but what's on disk now hasn't been looked at
+    change(&mut h, &waiting, "w\nW\n");
+    h.state.accept_front();
+    change(&mut h, &waiting, "w\nW\nWW\n");
+
+    // The accepted content goes in, whatever has reached the disk since
+    h.fs.put(&accepted, "a\nA\nunseen\n");
+    let recording = crate::Recording {
+        path: observed.clone(),
+        cache_key: normalize_path(&observed),
+        display_path: "aiui-harness/observed.rs".to_string(),
+        kind: ChangeKind::Modify,
+        baseline_missing: false,
+        change_id: 0,
+        protected: false,
+        origin: Origin::Agent,
+        dir: false,
+    };
+    h.state.observing.insert(normalize_path(&observed), (recording, Default::default()));
+    let (root, files, message) = h.state.begin_commit().unwrap();
+    assert_eq!(root, h.state.roots.primary().path);
+    let relative = |p: &Path| p.strip_prefix(&root).unwrap().to_path_buf();
+    let paths: Vec<PathBuf> = files.iter().map(|f| f.path.clone()).collect();
+    assert_eq!(paths, [relative(&accepted), relative(&twice)]);
+    assert_eq!(files[0].content.as_deref(), Some(&b"a\nA\n"[..]));
+    assert_eq!(message, "Apply 2 (+2 -1)\n- aiui-harness/accepted.rs\n- aiui-harness/twice.rs");
+    // One at a time
+    assert!(h.state.begin_commit().is_none());
+
+    // A failure leaves them to commit again
+    h.state.commit_finished(Err(anyhow::anyhow!("Author identity unknown")));
+    assert_eq!(h.state.status_message.as_ref().unwrap().0, "Commit failed: Author identity unknown");
+    assert_eq!(h.state.begin_commit().unwrap().1.len(), 2);
+    let commit = |hash: &str, skipped: &[&str]| git::Commit {
+        hash: Some(hash.to_string()),
+        files: 2 - skipped.len(),
+        skipped: skipped.iter().map(PathBuf::from).collect(),
+    };
+    h.state.commit_finished(Ok(commit("abc1234", &[])));
+    assert_eq!(h.state.status_message.as_ref().unwrap().0, "Committed 2 files as abc1234");
+
+    // Once it's decided, the waiting file's turn comes; a file git left
+    // out isn't offered again either
+    h.state.accept_front();
+    h.state.observing.clear();
+    h.expire_held_removes();
+    h.state.accept_front();
+    let (_, files, _) = h.state.begin_commit().unwrap();
+    let paths: Vec<PathBuf> = files.iter().map(|f| f.path.clone()).collect();
+    assert_eq!(paths, [relative(&observed), relative(&held), relative(&waiting)]);
+    assert_eq!(files[1].content, None);
+    h.state.commit_finished(Ok(commit("def5678", &["aiui-harness/held.rs"])));
+    assert!(h.state.status_message.as_ref().unwrap().0.contains("left out aiui-harness/held.rs"));
+    assert!(h.state.begin_commit().is_none());
+}
+
+#[test]
+fn tiny_terminals_get_a_message_and_small_ones_a_compact_layout() {
+    let mut h = Harness::new();
+    let path = h.known("small.rs", "one\n");
+    h.fs.put(&path, "one\ntwo\n");
+    h.event(&path, ChangeKind::Modify);
+    h.state.modal_active = true;
+    h.state.show_stats = true;
+    h.state.focus = crate::Focus::Sidebar;
+
+    let screen = |state: &mut AppState, width: u16, height: u16| {
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(width, height)).unwrap();
+        terminal.draw(|frame| crate::draw(frame, state, None)).unwrap();
+        let buffer = terminal.backend().buffer().clone();
+        buffer.content.chunks(width.max(1) as usize).map(|row| row.iter().map(|c| c.symbol()).collect::<String>()).collect::<Vec<_>>().join("\n")
+    };
+
+    // Nothing drawn but the message, at any size below the minimum
+    for (width, height) in [(0, 0), (1, 1), (80, 10), (59, 40), (200, 14)] {
+        let text = screen(&mut h.state, width, height);
+        assert!(!text.contains("Approve change"), "{}x{}", width, height);
+        if width >= 40 {
+            assert!(text.contains("terminal too small (need at least 60x15)"), "{}x{}:\n{}", width, height, text);
+        }
+    }
+
+    // In between: no sidebar, focus back on the pane, and the modal still fits
+    for (width, height) in [(60, 15), (79, 40), (120, 19)] {
+        h.state.focus = crate::Focus::Sidebar;
+        h.state.set_fit(crate::Fit::of(width, height));
+        let text = screen(&mut h.state, width, height);
+        assert!(!text.contains("Active Monitoring"), "{}x{}", width, height);
+        assert!(text.contains("Approve change"), "{}x{}:\n{}", width, height, text);
+        assert!(h.state.focus == crate::Focus::Terminal);
+    }
+    assert!(screen(&mut h.state, 120, 40).contains("Active Monitoring"));
+
+    // Overlays never spill past the frame, and keep a usable size when there's room
+    let area = Rect::new(0, 0, 60, 15);
+    assert_eq!(crate::centered_rect(30, 30, area), Rect::new(10, 3, 40, 8));
+    assert_eq!(crate::centered_rect(90, 80, Rect::new(0, 0, 20, 4)), Rect::new(0, 0, 20, 4));
+    // A cursor that would squeeze the modal to a sliver is covered instead
+    assert_eq!(modal_rect(area, 3, Some(Position::new(30, 7))), Rect::new(9, 3, 42, 8));
+}
+
+#[cfg(unix)]
+#[test]
+fn files_whose_names_are_not_utf8_are_kept_apart() {
+    use std::os::unix::ffi::OsStrExt;
+    let mut h = Harness::new();
+    let dir = h.path("");
+    let named = |bytes: &[u8]| dir.join(std::ffi::OsStr::from_bytes(bytes));
+    let (latin1, other) = (named(b"caf\xe9.txt"), named(b"caf\xff.txt"));
+    for path in [&latin1, &other] {
+        h.fs.put(path, "old\n");
+        h.state.file_cache.insert(normalize_path(path), "old\n".to_string());
+    }
+    h.fs.put(&latin1, "new\n");
+    h.event(&latin1, ChangeKind::Modify);
+    h.fs.put(&other, "newer\n");
+    h.event(&other, ChangeKind::Modify);
+
+    // Two approvals, both shown with U+FFFD, each for its own file
+    assert_eq!(h.state.approval_queue.len(), 2);
+    assert!(h.state.file_changes.iter().all(|c| c.path.ends_with("caf\u{fffd}.txt")));
+    h.state.reject_front();
+    assert_eq!(h.fs.get(&latin1).as_deref(), Some("old\n"));
+    assert_eq!(h.fs.get(&other).as_deref(), Some("newer\n"));
+    assert_eq!(h.state.approval_queue[0].path.path(), other);
+}
+
+#[test]
+fn rescan_rebuilds_the_cache_but_keeps_pending_baselines() {
+    let mut h = Harness::new();
+    let stale = h.known("stale.rs", "main branch\n");
+    let pending = h.known("pending.rs", "old\n");
+    h.fs.put(&pending, "new\n");
+    h.event(&pending, ChangeKind::Modify);
+    assert!(!h.state.debounce_map.is_empty());
+
+    assert!(h.state.begin_rescan());
+    assert!(h.state.debounce_map.is_empty());
+    // Until the scan is done, diffs and reverts use the baselines we had
+    assert_eq!(h.state.file_cache.get(&normalize_path(&stale)).map(String::as_str), Some("main branch\n"));
+    assert_eq!(h.state.file_cache.get(&normalize_path(&pending)).map(String::as_str), Some("old\n"));
+    // One at a time
+    assert!(!h.state.begin_rescan());
+
+    // Edited mid-scan: a diff against what we had, and a reject restores it
+    let edited = h.known("edited.rs", "kept\n");
+    h.fs.put(&edited, "agent\n");
+    h.event(&edited, ChangeKind::Modify);
+    assert!(!h.state.approval_queue[1].baseline_missing);
+    assert_eq!(h.state.approval_queue[1].old_content, "kept\n");
+    h.state.review_pending(1);
+    h.state.reject_front();
+    assert_eq!(h.fs.get(&edited).as_deref(), Some("kept\n"));
+
+    // What the scan finds is the new baseline, except under the pending approval
+    let file = |path: &Path, text: &str| crate::indexer::IndexedFile {
+        key: normalize_path(path),
+        text: text.to_string(),
+        encoding: TextEncoding::default(),
+        permissions: None,
+    };
+    // The scan read the edited file before the revert
+    let batch = vec![file(&stale, "feature branch\n"), file(&pending, "new\n"), file(&edited, "agent\n")];
+    h.state.apply_index_event(crate::indexer::IndexEvent::Batch(batch));
+    h.state.apply_index_event(crate::indexer::IndexEvent::Done { truncated: false });
+    assert_eq!(h.state.file_cache.get(&normalize_path(&stale)).map(String::as_str), Some("feature branch\n"));
+    assert_eq!(h.state.file_cache.get(&normalize_path(&pending)).map(String::as_str), Some("old\n"));
+    assert_eq!(h.state.file_cache.get(&normalize_path(&edited)).map(String::as_str), Some("kept\n"));
+    assert!(h.state.indexing.is_none() && h.state.rescan.is_none());
+    assert!(h.state.toasts.log().any(|t| t.text.contains("Re-scan done")));
+}
+
+#[test]
+fn prompt_files_get_fresh_private_names() {
+    let first = crate::create_prompt_file().unwrap();
+    let second = crate::create_prompt_file().unwrap();
+    assert_ne!(first, second);
+    assert_eq!(std::fs::read_to_string(&first).unwrap(), "");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&first).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+    let _ = std::fs::remove_file(&first);
+    let _ = std::fs::remove_file(&second);
+}
@@ -0,0 +1,129 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+// Seconds of history the sparkline draws from
+pub const SAMPLES: usize = 60;
+// Weight of the newest second in the smoothed rate
+const SMOOTHING: f64 = 0.3;
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sample {
+    pub bytes: u64,
+    pub lines: u64,
+}
+
+// How much the agent writes: session totals, a smoothed per-second rate, and
+// the last SAMPLES seconds one by one for the sparkline
+pub struct Throughput {
+    pub total: Sample,
+    // Finished seconds, oldest first
+    samples: VecDeque<Sample>,
+    current: Sample,
+    second_started: Instant,
+    bytes_rate: f64,
+    lines_rate: f64,
+}
+
+impl Default for Throughput {
+    fn default() -> Self {
+        Self::new(Instant::now())
+    }
+}
+
+impl Throughput {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            total: Sample::default(),
+            samples: VecDeque::with_capacity(SAMPLES),
+            current: Sample::default(),
+            second_started: now,
+            bytes_rate: 0.0,
+            lines_rate: 0.0,
+        }
+    }
+
+    pub fn record(&mut self, data: &[u8], now: Instant) {
+        self.tick(now);
+        let lines = data.iter().filter(|&&b| b == b'\n').count() as u64;
+        self.current.bytes += data.len() as u64;
+        self.current.lines += lines;
+        self.total.bytes += data.len() as u64;
+        self.total.lines += lines;
+    }
+
+    // Close off every whole second up to `now`; quiet ones count as zero
+    pub fn tick(&mut self, now: Instant) {
+        while now.duration_since(self.second_started) >= Duration::from_secs(1) {
+            let done = std::mem::take(&mut self.current);
+            self.bytes_rate += SMOOTHING * (done.bytes as f64 - self.bytes_rate);
+            self.lines_rate += SMOOTHING * (done.lines as f64 - self.lines_rate);
+            if self.samples.len() == SAMPLES {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(done);
+            self.second_started += Duration::from_secs(1);
+            // After a long quiet spell there's no point stepping through every second
+            if now.duration_since(self.second_started) > Duration::from_secs(SAMPLES as u64) {
+                self.samples.clear();
+                self.bytes_rate = 0.0;
+                self.lines_rate = 0.0;
+                self.second_started = now;
+            }
+        }
+    }
+
+    // Smoothed (bytes, lines) per second
+    pub fn rate(&self) -> (f64, f64) {
+        (self.bytes_rate, self.lines_rate)
+    }
+
+    // The last `width` seconds of bytes as block characters, newest at the right
+    pub fn sparkline(&self, width: usize) -> String {
+        let skip = self.samples.len().saturating_sub(width);
+        let shown: Vec<u64> = self.samples.iter().skip(skip).map(|s| s.bytes).collect();
+        let max = shown.iter().copied().max().unwrap_or(0);
+        shown
+            .iter()
+            .map(|&bytes| match bytes {
+                0 => ' ',
+                // Anything at all gets at least the lowest bar
+                b => BARS[(b * (BARS.len() as u64 - 1) / max) as usize],
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seconds_roll_into_samples_and_the_rate_smooths() {
+        let start = Instant::now();
+        let mut output = Throughput::new(start);
+        output.record(b"one\ntwo\n", start);
+        output.record(&[b'x'; 92], start + Duration::from_millis(500));
+        // Two quiet seconds go by
+        output.tick(start + Duration::from_millis(3100));
+        assert_eq!(output.total, Sample { bytes: 100, lines: 2 });
+        assert_eq!(output.samples, [Sample { bytes: 100, lines: 2 }, Sample::default(), Sample::default()]);
+        let (bytes, lines) = output.rate();
+        assert!((bytes - 100.0 * 0.3 * 0.7 * 0.7).abs() < 1e-9, "{}", bytes);
+        assert!(lines > 0.0 && lines < 2.0);
+    }
+
+    #[test]
+    fn sparkline_scales_to_the_busiest_second() {
+        let start = Instant::now();
+        let mut output = Throughput::new(start);
+        for (n, bytes) in [800, 0, 100, 400].into_iter().enumerate() {
+            output.record(&vec![b'x'; bytes], start + Duration::from_secs(n as u64));
+        }
+        output.tick(start + Duration::from_secs(4));
+        assert_eq!(output.sparkline(10), "█ ▁▄");
+        assert_eq!(output.sparkline(2), "▂█");
+    }
+}
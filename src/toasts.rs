@@ -0,0 +1,135 @@
+use chrono::{DateTime, Local};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+// How long each toast stays up once it's on screen
+pub const SHOWN_FOR: Duration = Duration::from_secs(3);
+// Toasts stacked above the status bar at once; the rest wait their turn
+pub const MAX_VISIBLE: usize = 3;
+// Entries the notification log keeps
+pub const KEEP: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub severity: Severity,
+    pub text: String,
+    pub time: DateTime<Local>,
+    at: Instant,
+}
+
+// Transient messages for things that happened off to the side (a revert that
+// couldn't write, the watcher falling over, an export), plus a log of them
+#[derive(Default)]
+pub struct Toasts {
+    log: VecDeque<Toast>,
+    // Ever pushed, so readers of the log can tell what's new to them
+    pushed: usize,
+    waiting: VecDeque<Toast>,
+    showing: Vec<(Toast, Instant)>,
+}
+
+impl Toasts {
+    pub fn push(&mut self, severity: Severity, text: String) {
+        let toast = Toast { severity, text, time: Local::now(), at: Instant::now() };
+        self.log.push_back(toast.clone());
+        while self.log.len() > KEEP {
+            self.log.pop_front();
+        }
+        self.pushed += 1;
+        // A repeat of one already up or queued would only stretch the queue
+        if !self.showing.iter().map(|(t, _)| t).chain(&self.waiting).any(|t| t.text == toast.text) {
+            self.waiting.push_back(toast);
+        }
+        self.tick(Instant::now());
+    }
+
+    // Retire toasts whose time is up and bring on the next ones
+    pub fn tick(&mut self, now: Instant) {
+        self.showing.retain(|(_, since)| now.duration_since(*since) < SHOWN_FOR);
+        while self.showing.len() < MAX_VISIBLE && let Some(toast) = self.waiting.pop_front() {
+            self.showing.push((toast, now));
+        }
+    }
+
+    // Oldest first, the way they're stacked
+    pub fn visible(&self) -> impl DoubleEndedIterator<Item = &Toast> {
+        self.showing.iter().map(|(toast, _)| toast)
+    }
+
+    // Newest last
+    pub fn log(&self) -> impl DoubleEndedIterator<Item = &Toast> {
+        self.log.iter()
+    }
+
+    // Entries pushed after the first `seen`; advances `seen` past them
+    pub fn since(&self, seen: &mut usize) -> Vec<&Toast> {
+        let skip = self.log.len().saturating_sub(self.pushed - *seen);
+        *seen = self.pushed;
+        self.log.iter().skip(skip).collect()
+    }
+
+    // The latest warning or error, if it's recent
+    pub fn latest_problem(&self, within: Duration) -> Option<&str> {
+        self.log
+            .iter()
+            .rev()
+            .find(|t| t.severity >= Severity::Warning)
+            .filter(|t| t.at.elapsed() < within)
+            .map(|t| t.text.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts<'a>(toasts: impl Iterator<Item = &'a Toast>) -> Vec<&'a str> {
+        toasts.map(|t| t.text.as_str()).collect()
+    }
+
+    #[test]
+    fn toasts_stack_three_deep_and_the_rest_wait() {
+        let mut toasts = Toasts::default();
+        for n in 1..=5 {
+            toasts.push(Severity::Info, format!("toast {}", n));
+        }
+        // Already up or queued: not shown twice
+        toasts.push(Severity::Info, "toast 2".to_string());
+        assert_eq!(texts(toasts.visible()), ["toast 1", "toast 2", "toast 3"]);
+
+        toasts.tick(Instant::now() + SHOWN_FOR);
+        assert_eq!(texts(toasts.visible()), ["toast 4", "toast 5"]);
+        toasts.tick(Instant::now() + SHOWN_FOR * 2);
+        assert_eq!(toasts.visible().count(), 0);
+        // The log has every one, repeat included
+        assert_eq!(toasts.log().count(), 6);
+    }
+
+    #[test]
+    fn log_keeps_the_last_fifty_and_reports_whats_new() {
+        let mut toasts = Toasts::default();
+        let mut seen = 0;
+        toasts.push(Severity::Warning, "watcher hiccup".to_string());
+        assert_eq!(texts(toasts.since(&mut seen).into_iter()), ["watcher hiccup"]);
+        assert!(toasts.since(&mut seen).is_empty());
+        assert_eq!(toasts.latest_problem(Duration::from_secs(30)), Some("watcher hiccup"));
+
+        for n in 0..60 {
+            toasts.push(Severity::Info, format!("info {}", n));
+        }
+        assert_eq!(toasts.log().count(), KEEP);
+        assert_eq!(toasts.log().next().map(|t| t.text.as_str()), Some("info 10"));
+        // More than the log holds came in: only what's left is reported
+        assert_eq!(toasts.since(&mut seen).len(), KEEP);
+    }
+}
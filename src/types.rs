@@ -1,16 +1,227 @@
 use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::{collections::{HashMap, HashSet}, path::PathBuf, time::Duration};
+use crate::diff::{DiffText, Hunk, LineEnding};
+use crate::path_key::PathKey;
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ChangeKind {
     Create,
     Modify,
     Remove,
+    Rename,
+}
+
+impl ChangeKind {
+    // Sidebar letter, plus the word for it in ASCII-only mode
+    pub fn letter(&self) -> &'static str {
+        match self {
+            Self::Create => "A",
+            Self::Modify => "M",
+            Self::Remove => "D",
+            Self::Rename => "R",
+        }
+    }
+
+    pub fn word(&self) -> &'static str {
+        match self {
+            Self::Create => "added",
+            Self::Modify => "modified",
+            Self::Remove => "deleted",
+            Self::Rename => "renamed",
+        }
+    }
+}
+
+// How a change was finally resolved
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Accepted,
+    Rejected,
+    // Recorded without going through approval (binary, unreadable, ...)
+    Logged,
+}
+
+// Who made a change, guessed from the agent's terminal activity
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Origin {
+    #[default]
+    Agent,
+    // The agent's terminal was quiet at the time, so most likely the user's own editor
+    User,
+}
+
+impl Origin {
+    // Sidebar marker; the agent's changes are the norm and go unmarked
+    pub fn glyph(&self) -> &'static str {
+        match self {
+            Self::Agent => "",
+            Self::User => "✎ ",
+        }
+    }
+
+    pub fn ascii_glyph(&self) -> &'static str {
+        match self {
+            Self::Agent => "",
+            Self::User => "(user) ",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Agent => "Agent",
+            Self::User => "External edit (the agent's terminal was quiet)",
+        }
+    }
+}
+
+// Where a sidebar entry stands in the approval flow
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeStatus {
+    Pending,
+    Accepted,
+    Rejected,
+    AutoApproved,
+    // Touched a protected path and was reverted on the spot
+    Blocked,
+    // Rejected with reject_mode = "dismiss": left on disk as the agent wrote it
+    Dismissed,
+    // Logged only; nothing to decide (binary, unreadable, ...)
+    Info,
+}
+
+impl ChangeStatus {
+    pub fn glyph(&self) -> &'static str {
+        match self {
+            Self::Pending => "?",
+            Self::Accepted => "✓",
+            Self::Rejected => "✗",
+            Self::AutoApproved => "⚡",
+            Self::Blocked => "⊘",
+            Self::Dismissed => "–",
+            Self::Info => "·",
+        }
+    }
+
+    // glyph() for terminals that can't show it, spelled out where color did the work
+    pub fn ascii_glyph(&self) -> &'static str {
+        match self {
+            Self::Pending => "?",
+            Self::Accepted => "ok",
+            Self::Rejected => "x",
+            Self::AutoApproved => "auto",
+            Self::Blocked => "BLOCKED",
+            Self::Dismissed => "-",
+            Self::Info => ".",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Pending => "Pending review",
+            Self::Accepted => "Accepted",
+            Self::Rejected => "Rejected (reverted)",
+            Self::AutoApproved => "Auto-approved",
+            Self::Blocked => "BLOCKED (protected path, reverted)",
+            Self::Dismissed => "Dismissed (left on disk)",
+            Self::Info => "Logged (no approval needed)",
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct FileChange {
+    // Links the entry to its PendingChange
+    pub id: u64,
     pub path: String,
+    // Normalized absolute path, for copying/revealing and finding the file again
+    pub abs_path: PathKey,
+    // A rename's source, which a commit records as deleted
+    pub from: Option<PathKey>,
     pub kind: ChangeKind,
     pub timestamp: DateTime<Local>,
-    pub diff: Option<String>, 
+    pub diff: Option<DiffText>,
+    pub hunks: Vec<Hunk>,
+    // The whole-file diff `hunks` and `diff` were cut from, `context` lines
+    // around each change. None when there's no line diff to re-cut (binary,
+    // a new file's listing, spilled) or it's past spill_diff_bytes.
+    pub full: Option<Hunk>,
+    pub context: usize,
+    // Line counts from diff generation; kept when the diff is spilled
+    pub insertions: usize,
+    pub deletions: usize,
+    // Shown next to the path when there's no diff (binary, oversized)
+    pub note: Option<String>,
+    // Line endings the change switched the file between, (from, to)
+    pub line_endings: Option<(LineEnding, LineEnding)>,
+    // The content before had escape sequences, which the stored diff drops
+    // (see `ansi::strip`), so the file can't be rebuilt from the diff
+    pub escapes_dropped: bool,
+    // Large diffs live here instead of in `diff`/`hunks` until viewed
+    pub spill: Option<PathBuf>,
+    pub status: ChangeStatus,
+    pub origin: Origin,
+    // Pinned entries sit above the rest and survive the history cap
+    pub pinned: bool,
+    // A directory was created or removed; there's no content of its own
+    pub dir: bool,
+    // The test_command run that followed accepting this change
+    pub tests: Option<TestRun>,
+}
+
+// One test_command run
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TestRun {
+    // None when it was killed or never started
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    pub cancelled: bool,
+}
+
+impl TestRun {
+    pub fn passed(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    // "passed (3.2s)", "failed: exit 101 (12.0s)", "cancelled (1.4s)"
+    pub fn summary(&self) -> String {
+        let secs = self.duration_ms as f64 / 1000.0;
+        match self.exit_code {
+            _ if self.cancelled => format!("cancelled ({:.1}s)", secs),
+            Some(0) => format!("passed ({:.1}s)", secs),
+            Some(code) => format!("failed: exit {} ({:.1}s)", code, secs),
+            None => format!("failed: no exit code ({:.1}s)", secs),
+        }
+    }
+}
+
+// Running totals for the whole session (reset with Ctrl+L)
+#[derive(Default)]
+pub struct SessionStats {
+    pub files: HashSet<PathKey>,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub accepted: usize,
+    pub rejected: usize,
+    // The agent's changes by kind
+    pub changes: HashMap<ChangeKind, usize>,
+    // Waits between a change being queued and the user deciding it, summed;
+    // auto-approved and blocked ones never wait, so they aren't counted
+    pub decision_wait: Duration,
+    pub decisions: usize,
+}
+
+impl SessionStats {
+    pub fn decided(&mut self, waited: Duration) {
+        self.decision_wait += waited;
+        self.decisions += 1;
+    }
+
+    pub fn average_decision(&self) -> Option<Duration> {
+        (self.decisions > 0).then(|| self.decision_wait / self.decisions as u32)
+    }
 }
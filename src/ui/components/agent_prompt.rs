@@ -0,0 +1,75 @@
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+use crate::ui::theme::Theme;
+
+// Restart/swap flow for the agent process
+pub enum AgentPrompt {
+    // Restart the current command?
+    Confirm,
+    // Typing a different command line to swap in
+    Edit(String),
+    // Spawning failed; any key dismisses
+    Error(String),
+}
+
+pub fn render(frame: &mut Frame, area: Rect, prompt: &AgentPrompt, command: &str, theme: &Theme) {
+    let (title, border, lines) = match prompt {
+        AgentPrompt::Confirm => (
+            " Restart agent ",
+            theme.status_warning,
+            vec![
+                Line::from(vec![
+                    Span::styled("Kill and restart ", Style::default().fg(theme.text_main)),
+                    Span::styled(command, Style::default().fg(theme.text_main).add_modifier(Modifier::BOLD)),
+                    Span::styled("?", Style::default().fg(theme.text_main)),
+                ]),
+                Line::from(Span::styled(
+                    "The change log, cache and pending approvals are kept.",
+                    Style::default().fg(theme.text_muted),
+                )),
+                Line::from(""),
+                Line::from(Span::styled(
+                    "[y] Restart  [e] Swap command  [Esc] Cancel",
+                    Style::default().fg(theme.text_muted),
+                )),
+            ],
+        ),
+        AgentPrompt::Edit(input) => (
+            " Swap agent ",
+            theme.border_focus,
+            vec![
+                Line::from(Span::styled("Command to run instead:", Style::default().fg(theme.text_muted))),
+                Line::from(Span::styled(format!("> {}_", input), Style::default().fg(theme.text_main))),
+                Line::from(""),
+                Line::from(Span::styled("[Enter] Spawn  [Esc] Cancel", Style::default().fg(theme.text_muted))),
+            ],
+        ),
+        AgentPrompt::Error(message) => (
+            " Agent failed to start ",
+            theme.status_error,
+            vec![
+                Line::from(Span::styled(message.as_str(), Style::default().fg(theme.status_error))),
+                Line::from(""),
+                Line::from(Span::styled(
+                    "Press any key; Ctrl+Shift+R to try again",
+                    Style::default().fg(theme.text_muted),
+                )),
+            ],
+        ),
+    };
+
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .border_style(Style::default().fg(border))
+        .style(Style::default().bg(theme.bg_primary));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block).wrap(Wrap { trim: false }), area);
+}
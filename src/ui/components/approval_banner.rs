@@ -0,0 +1,73 @@
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+use crate::config::RejectMode;
+use crate::ui::theme::Theme;
+
+// Head of the approval queue, as the inline banner shows it
+pub struct BannerHead<'a> {
+    pub path: &'a str,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub protected: bool,
+}
+
+// What the banner is standing in for, which decides the keys it offers
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BannerMode<'a> {
+    // approval_ui = "inline", keys going to the agent until `review` (Ctrl+A)
+    Unarmed { review: &'a str },
+    Armed,
+    // The approval modal, minimized while `d` has the diff view up; `back` is
+    // the key that closes it again
+    Minimized { back: &'a str },
+}
+
+// approval_ui = "inline": one row above the status bar instead of a modal.
+// y/n/d only reach it while armed (review_banner); otherwise keys go to the agent.
+pub fn render(
+    frame: &mut Frame,
+    area: Rect,
+    pending: usize,
+    head: Option<&BannerHead>,
+    mode: BannerMode,
+    reject_mode: RejectMode,
+    theme: &Theme,
+) {
+    let Some(head) = head else {
+        let idle = Paragraph::new(Span::styled("  No pending changes", Style::default().fg(theme.text_muted)))
+            .style(Style::default().bg(theme.bg_primary));
+        frame.render_widget(idle, area);
+        return;
+    };
+
+    let (bg, fg) = if mode == BannerMode::Armed {
+        (theme.status_warning, theme.bg_primary)
+    } else {
+        (theme.bg_secondary, theme.status_warning)
+    };
+    let base = Style::default().fg(fg).bg(bg);
+
+    let mut spans = vec![
+        Span::styled(format!("  {} pending: ", pending), base.add_modifier(Modifier::BOLD)),
+        Span::styled(head.path.to_string(), base),
+        Span::styled(format!(" +{} −{}", head.insertions, head.deletions), base),
+    ];
+    if head.protected {
+        spans.push(Span::styled("  PROTECTED", base.fg(theme.status_error).add_modifier(Modifier::BOLD)));
+    }
+    let keys = match (mode, reject_mode) {
+        (BannerMode::Armed, RejectMode::Revert) => "  —  [y]accept [n]reject [d]iff [Esc] disarm".to_string(),
+        (BannerMode::Armed, RejectMode::Dismiss) => "  —  [y]accept [n]dismiss [d]iff [Esc] disarm".to_string(),
+        (BannerMode::Unarmed { review: "" }, _) => String::new(),
+        (BannerMode::Unarmed { review }, _) => format!("  —  [{}] review", review),
+        (BannerMode::Minimized { back }, _) => format!("  —  [{}] back to the approval", back),
+    };
+    spans.push(Span::styled(keys, base));
+
+    frame.render_widget(Paragraph::new(Line::from(spans)).style(base), area);
+}
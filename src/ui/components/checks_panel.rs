@@ -0,0 +1,41 @@
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+use crate::test_run::{TestRunner, KEEP_LINES};
+use crate::ui::theme::Theme;
+
+// Rows the panel wants: its border plus the output tail, or just a summary
+pub fn height(collapsed: bool) -> u16 {
+    if collapsed { 3 } else { KEEP_LINES as u16 + 3 }
+}
+
+// test_command output under the sidebar; `k` folds it to the summary line
+pub fn render(frame: &mut Frame, area: Rect, tests: &TestRunner, collapsed: bool, theme: &Theme) {
+    let (summary, color) = match (tests.elapsed(), &tests.last) {
+        (Some(elapsed), _) => (format!("running {}s  [x] Cancel", elapsed.as_secs()), theme.status_info),
+        (None, Some(last)) if last.passed() => (last.summary(), theme.status_success),
+        (None, Some(last)) if last.cancelled => (last.summary(), theme.text_muted),
+        (None, Some(last)) => (last.summary(), theme.status_error),
+        (None, None) => ("waiting for an accepted change".to_string(), theme.text_muted),
+    };
+    let fold = if collapsed { theme.symbol("▸", ">") } else { theme.symbol("▾", "v") };
+    let block = Block::default()
+        .title(format!(" {} Checks (k) ", fold))
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .border_style(Style::default().fg(theme.border_dim))
+        .style(Style::default().bg(theme.bg_primary));
+
+    let mut lines = vec![Line::from(Span::styled(summary, Style::default().fg(color).add_modifier(Modifier::BOLD)))];
+    if !collapsed {
+        // Newest output at the bottom, like the terminal it came from
+        let rows = (area.height as usize).saturating_sub(3);
+        let skip = tests.output.len().saturating_sub(rows);
+        lines.extend(tests.output.iter().skip(skip).map(|l| Line::from(Span::styled(l.as_str(), Style::default().fg(theme.text_main)))));
+    }
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
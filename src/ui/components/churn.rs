@@ -0,0 +1,55 @@
+use chrono::Local;
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use crate::churn::{ChurnOrder, FileChurn};
+use crate::config::TimeFormat;
+use crate::ui::components::sidebar::format_timestamp;
+use crate::ui::theme::Theme;
+
+// Files the agent keeps coming back to; `s` flips the order
+pub fn render(frame: &mut Frame, area: Rect, files: &[&FileChurn], order: ChurnOrder, key: &str, theme: &Theme) {
+    let sorted = match order {
+        ChurnOrder::Count => "most edited",
+        ChurnOrder::Recent => "most recent",
+    };
+    let block = Block::default()
+        .title(format!(" Most churned files, {} ", sorted))
+        .title_bottom(format!(" [s] Sort by {}  [Esc/{}] Close ", if order == ChurnOrder::Count { "recency" } else { "edits" }, key))
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .border_style(Style::default().fg(theme.border_focus))
+        .style(Style::default().bg(theme.bg_primary));
+
+    let now = Local::now();
+    let rows = area.height.saturating_sub(2) as usize;
+    let mut lines: Vec<Line> = files
+        .iter()
+        .take(rows)
+        .map(|file| {
+            // Three or more edits is worth a second look
+            let count_style = if file.changes >= 3 {
+                Style::default().fg(theme.status_warning).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(theme.text_main)
+            };
+            Line::from(vec![
+                Span::styled(format!(" {:>4}x ", file.changes), count_style),
+                Span::styled(format!("{:>6}", format!("+{}", file.insertions)), Style::default().fg(theme.diff_add)),
+                Span::styled(format!(" {:<6}", format!("-{}", file.deletions)), Style::default().fg(theme.diff_remove)),
+                Span::styled(format!("{:>4} ago  ", format_timestamp(file.last, now, TimeFormat::Relative)), Style::default().fg(theme.text_muted)),
+                Span::styled(file.path.as_str(), Style::default().fg(theme.text_main)),
+            ])
+        })
+        .collect();
+    if lines.is_empty() {
+        lines.push(Line::from(Span::styled(" No agent edits yet", Style::default().fg(theme.text_muted))));
+    }
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
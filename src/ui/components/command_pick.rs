@@ -0,0 +1,135 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use crate::command_pick::Candidate;
+use crate::ui::theme::Theme;
+
+// Pick-command mode: numbered hints over commands the agent printed
+pub struct CommandPick {
+    pub candidates: Vec<Candidate>,
+    // Picked and waiting for Enter, as it will be sent
+    chosen: Option<String>,
+    editing: bool,
+}
+
+pub enum PickOutcome {
+    Open,
+    Close,
+    Send(String),
+}
+
+impl CommandPick {
+    pub fn new(candidates: Vec<Candidate>) -> Self {
+        Self { candidates, chosen: None, editing: false }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> PickOutcome {
+        let Some(text) = self.chosen.as_mut() else {
+            return match key.code {
+                KeyCode::Char(c @ '1'..='9') => {
+                    let index = c as usize - '1' as usize;
+                    if let Some(candidate) = self.candidates.get(index) {
+                        self.chosen = Some(candidate.text.clone());
+                    }
+                    PickOutcome::Open
+                }
+                KeyCode::Esc | KeyCode::Char('q') => PickOutcome::Close,
+                _ => PickOutcome::Open,
+            };
+        };
+        match key.code {
+            KeyCode::Enter => return PickOutcome::Send(text.clone()),
+            // Shift makes capitals; Ctrl and Alt chords aren't text
+            KeyCode::Char(_) if self.editing && key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {}
+            KeyCode::Char(c) if self.editing => text.push(c),
+            KeyCode::Backspace if self.editing => {
+                text.pop();
+            }
+            KeyCode::Esc if self.editing => self.editing = false,
+            KeyCode::Char('e') => self.editing = true,
+            // Back to the hints
+            KeyCode::Esc => self.chosen = None,
+            _ => {}
+        }
+        PickOutcome::Open
+    }
+}
+
+// Badges on the rows the commands are on, and a box along the bottom of the
+// pane listing them all (scrolled-off ones too), or the one picked
+pub fn render(frame: &mut Frame, pane: Rect, pick: &CommandPick, theme: &Theme) {
+    let badge = Style::default().fg(theme.bg_primary).bg(theme.status_warning).add_modifier(Modifier::BOLD);
+    if pick.chosen.is_none() {
+        for (n, candidate) in pick.candidates.iter().enumerate() {
+            if let Some(row) = candidate.row.filter(|&row| row < pane.height) {
+                frame.buffer_mut().set_string(pane.x, pane.y + row, format!("{}", n + 1), badge);
+            }
+        }
+    }
+
+    let (lines, bottom): (Vec<Line>, &str) = match &pick.chosen {
+        None => (
+            pick.candidates
+                .iter()
+                .enumerate()
+                .map(|(n, candidate)| {
+                    let mut spans = vec![
+                        Span::styled(format!("{}", n + 1), badge),
+                        Span::styled(format!(" {}", candidate.text), Style::default().fg(theme.text_main)),
+                    ];
+                    if candidate.row.is_none() {
+                        spans.push(Span::styled("  (scrolled off)", Style::default().fg(theme.text_muted)));
+                    }
+                    Line::from(spans)
+                })
+                .collect(),
+            " [1-9] Pick  [Esc] Close ",
+        ),
+        Some(text) => (
+            vec![
+                Line::from(Span::styled("This goes to the agent, followed by Enter:", Style::default().fg(theme.text_muted))),
+                Line::from(vec![
+                    Span::styled(text.as_str(), Style::default().fg(theme.text_main).add_modifier(Modifier::BOLD)),
+                    Span::styled(if pick.editing { "▏" } else { "" }, Style::default().fg(theme.border_focus)),
+                ]),
+            ],
+            if pick.editing { " [Enter] Send  [Esc] Stop editing " } else { " [Enter] Send  [e] Edit  [Esc] Back " },
+        ),
+    };
+
+    let height = (lines.len() as u16 + 2).min(pane.height);
+    let area = Rect::new(pane.x, pane.y + pane.height - height, pane.width, height);
+    let block = Block::default()
+        .title(" Pick a command ")
+        .title_bottom(bottom)
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .border_style(Style::default().fg(theme.border_focus))
+        .style(Style::default().bg(theme.bg_primary));
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn editing_types_text_but_not_chords() {
+        let mut pick = CommandPick::new(vec![Candidate { text: "make".to_string(), row: None }]);
+        let press = |pick: &mut CommandPick, code, modifiers| pick.handle_key(KeyEvent::new(code, modifiers));
+        press(&mut pick, KeyCode::Char('1'), KeyModifiers::NONE);
+        press(&mut pick, KeyCode::Char('e'), KeyModifiers::NONE);
+        press(&mut pick, KeyCode::Char(' '), KeyModifiers::NONE);
+        press(&mut pick, KeyCode::Char('X'), KeyModifiers::SHIFT);
+        press(&mut pick, KeyCode::Char('w'), KeyModifiers::CONTROL);
+        press(&mut pick, KeyCode::Char('b'), KeyModifiers::ALT);
+        let sent = press(&mut pick, KeyCode::Enter, KeyModifiers::NONE);
+        assert!(matches!(sent, PickOutcome::Send(text) if text == "make X"));
+    }
+}
@@ -2,46 +2,216 @@ use ratatui::{
     layout::Rect,
     style::{Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Paragraph, Wrap},
     Frame,
 };
+use crate::diff::{self, DiffText, Hunk, HunkCursor};
 use crate::types::FileChange;
 use crate::ui::theme::Theme;
 
-pub fn render(frame: &mut Frame, area: Rect, change: Option<&FileChange>, theme: &Theme) {
+// Continuation rows of a wrapped line start past the +/- gutter
+const WRAP_INDENT: &str = "  ";
+
+// A change as the view shows it: its own diff, or one re-cut to the
+// view's context
+pub struct Shown<'a> {
+    pub change: &'a FileChange,
+    pub diff: Option<&'a DiffText>,
+    pub hunks: &'a [Hunk],
+    pub context: usize,
+}
+
+impl<'a> Shown<'a> {
+    // The change as it was recorded
+    pub fn of(change: &'a FileChange) -> Self {
+        Self { change, diff: change.diff.as_ref(), hunks: &change.hunks, context: change.context }
+    }
+}
+
+// `wrap` (toggled with `w`) folds long lines instead of clipping them. Off by
+// default so code stays aligned. `whitespace` (`s`) shows tabs and trailing
+// blanks in changed lines. The title says how much context `+`/`-` have left
+// around the changes.
+pub fn render(frame: &mut Frame, area: Rect, shown: Option<Shown>, cursor: &HunkCursor, wrap: bool, whitespace: bool, theme: &Theme) {
+    let hunk_count = shown.as_ref().map_or(0, |s| s.hunks.len());
+    let title = if let Some(shown) = shown.as_ref().filter(|_| hunk_count > 0) {
+        // Without the whole-file diff (spilled ones) +/- can't change anything
+        let context = if shown.change.full.is_some() { format!(", {} (+/-)", diff::context_label(shown.context)) } else { String::new() };
+        format!(" Diff View - {} (Tab/Shift+Tab){} ", cursor.label(hunk_count), context)
+    } else {
+        " Diff View ".to_string()
+    };
+
     let block = Block::default()
-        .title(" Diff View ")
+        .title(title)
         .borders(Borders::ALL)
+        .border_set(theme.border_set())
         .border_style(Style::default().fg(theme.status_info)) // Highlight border to show it's active
         .style(Style::default().bg(theme.bg_primary));
 
+    let lines = body(shown, cursor, area.height.saturating_sub(2) as usize, whitespace, theme);
+    let p = if wrap {
+        // Wrapped by hand for the continuation indent; rows past the pane are clipped
+        Paragraph::new(wrap_lines(lines, area.width.saturating_sub(2) as usize)).wrap(Wrap { trim: false })
+    } else {
+        Paragraph::new(lines)
+    };
+    frame.render_widget(p.block(block), area);
+}
+
+// The rows `height` has room for. Only the visible window is styled; a
+// 50k-line diff costs the same as a short one. A selected hunk scrolls the
+// header off and sits at the top.
+fn body<'a>(shown: Option<Shown<'a>>, cursor: &HunkCursor, height: usize, whitespace: bool, theme: &Theme) -> Vec<Line<'a>> {
+    let start = match &shown {
+        Some(shown) => cursor.scroll(&diff::hunk_offsets(shown.hunks)),
+        None => 0,
+    };
+
     let mut lines = vec![];
 
-    if let Some(change) = change {
-        lines.push(Line::from(vec![
-            Span::styled(format!("File: {}", change.path), Style::default().add_modifier(Modifier::BOLD).fg(theme.text_main))
-        ]));
-        lines.push(Line::from(""));
-
-        if let Some(diff_text) = &change.diff {
-            for line_str in diff_text.lines() {
-                if line_str.starts_with('+') {
-                    lines.push(Line::from(Span::styled(line_str, Style::default().fg(theme.status_success))));
-                } else if line_str.starts_with('-') {
-                    lines.push(Line::from(Span::styled(line_str, Style::default().fg(theme.status_error))));
-                } else if line_str.starts_with('@') {
-                     lines.push(Line::from(Span::styled(line_str, Style::default().fg(theme.status_info))));
-                } else {
-                    lines.push(Line::from(Span::styled(line_str, Style::default().fg(theme.text_muted))));
-                }
-            }
+    if let Some(shown) = shown {
+        let change = shown.change;
+        if cursor.current.is_none() && cursor.offset == 0 {
+            lines.push(Line::from(vec![
+                Span::styled(
+                    if change.dir { format!("Directory: {}/", change.path) } else { format!("File: {}", change.path) },
+                    Style::default().add_modifier(Modifier::BOLD).fg(theme.text_main),
+                )
+            ]));
+            let status_color = theme.status_color(change.status);
+            let (glyph, origin) = if theme.ascii_only {
+                (change.status.ascii_glyph(), change.origin.ascii_glyph())
+            } else {
+                (change.status.glyph(), change.origin.glyph())
+            };
+            lines.push(Line::from(vec![
+                Span::styled("Status: ", Style::default().fg(theme.text_muted)),
+                Span::styled(format!("{} {}", glyph, change.status.label()), Style::default().fg(status_color)),
+            ]));
+            lines.push(Line::from(vec![
+                Span::styled("Time: ", Style::default().fg(theme.text_muted)),
+                Span::styled(change.timestamp.format("%Y-%m-%d %H:%M:%S %:z").to_string(), Style::default().fg(theme.text_main)),
+            ]));
+            lines.push(Line::from(vec![
+                Span::styled("Source: ", Style::default().fg(theme.text_muted)),
+                Span::styled(format!("{}{} (probable)", origin, change.origin.label()), Style::default().fg(theme.text_main)),
+            ]));
+            lines.push(Line::from(""));
+        }
+
+        if let Some(diff_text) = shown.diff {
+            let room = height.saturating_sub(lines.len());
+            lines.extend(diff::diff_lines_to_spans(diff_text, start..start + room, theme, whitespace));
         } else {
             lines.push(Line::from(Span::styled("No diff details available.", Style::default().fg(theme.text_muted))));
         }
     } else {
         lines.push(Line::from(Span::styled("Select a file to see changes.", Style::default().fg(theme.text_muted))));
     }
+    lines
+}
 
-    let p = Paragraph::new(lines).block(block);
-    frame.render_widget(p, area);
+// Split lines wider than `width` into rows
+fn wrap_lines(lines: Vec<Line<'_>>, width: usize) -> Vec<Line<'_>> {
+    let mut rows = Vec::with_capacity(lines.len());
+    for line in lines {
+        if line.width() <= width || width <= WRAP_INDENT.len() {
+            rows.push(line);
+            continue;
+        }
+
+        let line_style = line.style;
+        let mut row: Vec<Span<'static>> = Vec::new();
+        let mut text = String::new();
+        let mut text_style = Style::default();
+        let mut used = 0;
+        for grapheme in line.styled_graphemes(Style::default()) {
+            let w = Span::raw(grapheme.symbol).width();
+            if used + w > width {
+                row.push(Span::styled(std::mem::take(&mut text), text_style));
+                rows.push(Line::from(std::mem::take(&mut row)).style(line_style));
+                row.push(Span::raw(WRAP_INDENT));
+                used = WRAP_INDENT.len();
+            } else if grapheme.style != text_style && !text.is_empty() {
+                row.push(Span::styled(std::mem::take(&mut text), text_style));
+            }
+            text_style = grapheme.style;
+            text.push_str(grapheme.symbol);
+            used += w;
+        }
+        row.push(Span::styled(text, text_style));
+        rows.push(Line::from(row).style(line_style));
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+    use ratatui::{backend::TestBackend, Terminal};
+    use crate::diff::DiffText;
+    use crate::path_key::PathKey;
+    use crate::types::{ChangeKind, ChangeStatus, Origin};
+    use crate::ui::theme::ThemeVariant;
+
+    fn change_with_lines(n: usize) -> FileChange {
+        let text: String = (0..n).map(|i| format!("+line {}\n", i)).collect();
+        FileChange {
+            id: 1,
+            path: "Cargo.lock".to_string(),
+            abs_path: PathKey::from("/tmp/Cargo.lock"),
+            from: None,
+            kind: ChangeKind::Modify,
+            timestamp: Local::now(),
+            diff: Some(DiffText::new(text)),
+            hunks: Vec::new(),
+            full: None,
+            context: 3,
+            insertions: n,
+            deletions: 0,
+            note: None,
+            line_endings: None,
+            escapes_dropped: false,
+            spill: None,
+            status: ChangeStatus::Pending,
+            origin: Origin::Agent,
+            pinned: false,
+            dir: false,
+            tests: None,
+        }
+    }
+
+    #[test]
+    fn only_the_visible_window_is_styled() {
+        let theme = Theme::new(ThemeVariant::Zinc);
+        let change = change_with_lines(50_000);
+        let text = |lines: Vec<Line>| lines.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+
+        // The header and as much of the diff as fits, however long it is
+        let top = text(body(Some(Shown::of(&change)), &HunkCursor::default(), 38, false, &theme));
+        assert_eq!(top.len(), 38);
+        assert_eq!((top[0].as_str(), top[5].as_str(), top[37].as_str()), ("File: Cargo.lock", "+line 0", "+line 32"));
+
+        // Deep in, the header is gone and the window starts at the offset
+        let cursor = HunkCursor { offset: 49_990, ..HunkCursor::default() };
+        let deep = text(body(Some(Shown::of(&change)), &cursor, 38, false, &theme));
+        assert_eq!(deep.len(), 10);
+        assert_eq!((deep[0].as_str(), deep[9].as_str()), ("+line 49990", "+line 49999"));
+
+        // And the frame drawn from it
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        terminal.draw(|frame| render(frame, frame.area(), Some(Shown::of(&change)), &cursor, true, false, &theme)).unwrap();
+        let row: String = terminal.backend().buffer().content[120..240].iter().map(|c| c.symbol()).collect();
+        assert!(row.starts_with("│+line 49990"), "{}", row);
+    }
+
+    #[test]
+    fn long_lines_wrap_with_an_indent() {
+        let lines = vec![Line::from("+abcdefgh"), Line::from("ok")];
+        let rows = wrap_lines(lines, 4);
+        let text: Vec<String> = rows.iter().map(|r| r.to_string()).collect();
+        assert_eq!(text, ["+abc", "  de", "  fg", "  h", "ok"]);
+    }
 }
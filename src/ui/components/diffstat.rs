@@ -0,0 +1,208 @@
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use std::collections::HashMap;
+use crate::path_key::PathKey;
+use crate::types::{ChangeStatus, FileChange, Origin};
+use crate::ui::theme::Theme;
+
+// One file's churn over the session, all of its log entries combined
+#[derive(Debug, PartialEq, Eq)]
+pub struct FileStat {
+    pub path: String,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl FileStat {
+    fn churn(&self) -> usize {
+        self.insertions + self.deletions
+    }
+}
+
+// Sum what was kept of the log per file, most churn first. Repeated edits
+// combine, and so do edits from before and after a rename. Rejected changes,
+// and the user's own edits as in the session totals, are left out.
+pub fn aggregate<'a>(changes: impl IntoIterator<Item = &'a FileChange>) -> Vec<FileStat> {
+    let mut by_path: HashMap<&PathKey, FileStat> = HashMap::new();
+    // Old names, and the file they became
+    let mut renamed: HashMap<&PathKey, &PathKey> = HashMap::new();
+    let kept = |c: &&FileChange| {
+        c.origin == Origin::Agent && matches!(c.status, ChangeStatus::Accepted | ChangeStatus::AutoApproved)
+    };
+    // Newest first, so the name shown is the file's latest and a rename is
+    // seen before the edits made under the old name
+    for change in changes.into_iter().filter(kept) {
+        let key = renamed.get(&change.abs_path).copied().unwrap_or(&change.abs_path);
+        if let Some(from) = &change.from {
+            renamed.insert(from, key);
+        }
+        let stat = by_path.entry(key).or_insert_with(|| FileStat {
+            path: change.path.clone(),
+            insertions: 0,
+            deletions: 0,
+        });
+        stat.insertions += change.insertions;
+        stat.deletions += change.deletions;
+    }
+    let mut stats: Vec<FileStat> = by_path.into_values().collect();
+    stats.sort_by(|a, b| b.churn().cmp(&a.churn()).then_with(|| a.path.cmp(&b.path)));
+    stats
+}
+
+// How many `+` and `-` to draw in `width` columns when the busiest file has
+// `max` changed lines. Like git, anything non-zero gets at least one column.
+pub fn bar(insertions: usize, deletions: usize, max: usize, width: usize) -> (usize, usize) {
+    let scale = |n: usize| match n {
+        0 => 0,
+        n if max <= width => n,
+        n => (n * width / max).max(1),
+    };
+    let (mut plus, mut minus) = (scale(insertions), scale(deletions));
+    // Rounding up both sides can overshoot by a column
+    while plus + minus > width && (plus > 1 || minus > 1) {
+        if plus >= minus { plus -= 1 } else { minus -= 1 }
+    }
+    (plus, minus)
+}
+
+pub fn render(frame: &mut Frame, area: Rect, stats: &[FileStat], theme: &Theme) {
+    let block = Block::default()
+        .title(" Diffstat (Alt+S) ")
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .border_style(Style::default().fg(theme.border_focus))
+        .style(Style::default().bg(theme.bg_primary));
+
+    let inner_width = area.width.saturating_sub(2) as usize;
+    let rows = (area.height as usize).saturating_sub(4); // borders, blank line, summary
+    let max = stats.iter().map(FileStat::churn).max().unwrap_or(0);
+    let count_width = max.to_string().len();
+    // Paths get what's left after " | 1234 " and a bar of at least 10
+    let path_width = stats.iter().map(|s| s.path.chars().count()).max().unwrap_or(0)
+        .min(inner_width.saturating_sub(count_width + 14).max(10));
+    let bar_width = inner_width.saturating_sub(path_width + count_width + 5);
+
+    let mut lines: Vec<Line> = stats.iter().take(rows).map(|stat| {
+        let (plus, minus) = bar(stat.insertions, stat.deletions, max, bar_width);
+        Line::from(vec![
+            Span::styled(format!(" {:<w$}", truncate_left(&stat.path, path_width), w = path_width), Style::default().fg(theme.text_main)),
+            Span::styled(format!(" | {:>w$} ", stat.churn(), w = count_width), Style::default().fg(theme.text_muted)),
+            Span::styled("+".repeat(plus), Style::default().fg(theme.diff_add)),
+            Span::styled("-".repeat(minus), Style::default().fg(theme.diff_remove)),
+        ])
+    }).collect();
+    if stats.len() > rows {
+        lines.push(Line::from(Span::styled(
+            format!(" {} {} more files", theme.symbol("…", "..."), stats.len() - rows),
+            Style::default().fg(theme.text_muted).add_modifier(Modifier::ITALIC),
+        )));
+    }
+    if stats.is_empty() {
+        lines.push(Line::from(Span::styled(" No changes yet", Style::default().fg(theme.text_muted))));
+    }
+
+    let insertions: usize = stats.iter().map(|s| s.insertions).sum();
+    let deletions: usize = stats.iter().map(|s| s.deletions).sum();
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        format!(
+            " {} file{} changed, {} insertion{}(+), {} deletion{}(-)",
+            stats.len(), plural(stats.len()), insertions, plural(insertions), deletions, plural(deletions)
+        ),
+        Style::default().fg(theme.text_main).add_modifier(Modifier::BOLD),
+    )));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 { "" } else { "s" }
+}
+
+// Keep the end of the path (the file name) when it doesn't fit
+fn truncate_left(path: &str, width: usize) -> String {
+    let len = path.chars().count();
+    if len <= width {
+        return path.to_string();
+    }
+    let tail: String = path.chars().skip(len + 1 - width).collect();
+    format!("…{}", tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+    use crate::types::{ChangeKind, ChangeStatus};
+
+    fn change(path: &str, insertions: usize, deletions: usize) -> FileChange {
+        FileChange {
+            id: 0,
+            path: path.to_string(),
+            abs_path: PathKey::from(format!("/repo/{}", path).as_str()),
+            from: None,
+            kind: ChangeKind::Modify,
+            timestamp: Local::now(),
+            diff: None,
+            hunks: Vec::new(),
+            full: None,
+            context: 3,
+            insertions,
+            deletions,
+            note: None,
+            line_endings: None,
+            escapes_dropped: false,
+            spill: None,
+            status: ChangeStatus::Accepted,
+            origin: Origin::Agent,
+            pinned: false,
+            dir: false,
+            tests: None,
+        }
+    }
+
+    #[test]
+    fn repeated_edits_combine_and_sort_by_churn() {
+        let mut mine = change("notes.md", 50, 0);
+        mine.origin = Origin::User;
+        let log = [change("a.rs", 2, 1), change("b.rs", 10, 0), change("a.rs", 5, 5), mine];
+        let stats = aggregate(&log);
+        assert_eq!(stats, vec![
+            FileStat { path: "a.rs".to_string(), insertions: 7, deletions: 6 },
+            FileStat { path: "b.rs".to_string(), insertions: 10, deletions: 0 },
+        ]);
+    }
+
+    #[test]
+    fn renames_carry_earlier_edits_and_rejected_changes_are_left_out() {
+        let mut moved = change("new.rs", 1, 0);
+        moved.from = Some(PathKey::from("/repo/old.rs"));
+        let mut rejected = change("old.rs", 40, 0);
+        rejected.status = ChangeStatus::Rejected;
+        let mut pending = change("c.rs", 9, 9);
+        pending.status = ChangeStatus::Pending;
+        // Newest first, as the log keeps it
+        let log = [change("new.rs", 2, 0), moved, rejected, change("old.rs", 3, 1), pending];
+        assert_eq!(aggregate(&log), vec![FileStat { path: "new.rs".to_string(), insertions: 6, deletions: 1 }]);
+    }
+
+    #[test]
+    fn bars_scale_to_the_busiest_file() {
+        // Fits: one column per line
+        assert_eq!(bar(3, 2, 5, 40), (3, 2));
+        // Scaled down, proportions kept
+        assert_eq!(bar(300, 100, 400, 40), (30, 10));
+        // Small counts next to a huge file still show up
+        assert_eq!(bar(1, 1, 10_000, 40), (1, 1));
+        assert_eq!(bar(0, 0, 10, 40), (0, 0));
+        // Never wider than the space given
+        let (plus, minus) = bar(999, 1, 1000, 10);
+        assert!(plus + minus <= 10 && minus == 1);
+    }
+}
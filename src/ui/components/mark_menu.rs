@@ -0,0 +1,35 @@
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use crate::ui::theme::Theme;
+
+// `a` in the sidebar with entries marked: what to do with all of them
+pub fn render(frame: &mut Frame, area: Rect, marked: usize, theme: &Theme) {
+    let block = Block::default()
+        .title(format!(" {} marked ", marked))
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .border_style(Style::default().fg(theme.border_focus))
+        .style(Style::default().bg(theme.bg_primary));
+
+    let item = |key: &str, label: &str| {
+        Line::from(vec![
+            Span::styled(format!(" [{}] ", key), Style::default().fg(theme.status_info).add_modifier(Modifier::BOLD)),
+            Span::styled(label.to_string(), Style::default().fg(theme.text_main)),
+        ])
+    };
+    let lines = vec![
+        item("e", "Export as marked.patch"),
+        item("c", "Copy paths"),
+        item("r", "Re-queue for approval"),
+        item("d", "Delete from log"),
+        Line::from(Span::styled(" Any other key closes", Style::default().fg(theme.text_muted))),
+    ];
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
@@ -1,3 +1,20 @@
 pub mod sidebar;
 pub mod status_bar;
 pub mod diff_view;
+pub mod terminal;
+pub mod stats;
+pub mod diffstat;
+pub mod agent_prompt;
+pub mod quit_prompt;
+pub mod approval_banner;
+pub mod tail_panel;
+pub mod quick_open;
+pub mod snippet_picker;
+pub mod checks_panel;
+pub mod revert_prompt;
+pub mod toasts;
+pub mod pty_dump;
+pub mod mark_menu;
+pub mod churn;
+pub mod command_pick;
+pub mod too_small;
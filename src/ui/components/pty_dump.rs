@@ -0,0 +1,80 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::Rect,
+    style::Style,
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use crate::pty_dump::PtyDump;
+use crate::ui::theme::Theme;
+
+// --debug overlay over the raw PTY bytes
+#[derive(Default)]
+pub struct DumpView {
+    hex: bool,
+    // Rows up from the newest output; 0 follows it
+    scroll: usize,
+    // Rows that fit, from the last frame, for paging
+    rows: usize,
+}
+
+impl DumpView {
+    // False when the key closes the view
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let page = self.rows.max(1);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return false,
+            KeyCode::Char('h') => {
+                self.hex = !self.hex;
+                self.scroll = 0;
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.scroll += 1,
+            KeyCode::Down | KeyCode::Char('j') => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::PageUp => self.scroll += page,
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(page),
+            KeyCode::Home => self.scroll = usize::MAX,
+            KeyCode::End => self.scroll = 0,
+            _ => {}
+        }
+        true
+    }
+}
+
+pub fn render(frame: &mut Frame, area: Rect, dump: &PtyDump, view: &mut DumpView, key: &str, theme: &Theme) {
+    // Full-screen agents go a long way between newlines; fold to the pane
+    let width = area.width.saturating_sub(2).max(1) as usize;
+    let lines: Vec<String> = if view.hex { dump.hex() } else { dump.escaped() }
+        .iter()
+        .flat_map(|line| {
+            let chars: Vec<char> = line.chars().collect();
+            chars.chunks(width).map(|c| c.iter().collect::<String>()).collect::<Vec<_>>()
+        })
+        .collect();
+    view.rows = area.height.saturating_sub(2) as usize;
+    view.scroll = view.scroll.min(lines.len().saturating_sub(view.rows));
+    let end = lines.len() - view.scroll;
+    let start = end.saturating_sub(view.rows);
+
+    let following = if view.scroll == 0 { "following" } else { "scrolled" };
+    let block = Block::default()
+        .title(format!(
+            " Raw PTY output: last {} bytes, {} ({}) ",
+            dump.len(),
+            if view.hex { "hex" } else { "escaped" },
+            following
+        ))
+        .title_bottom(format!(" [h] Hex/escaped  [Up/Down/PgUp/PgDn/Home/End] Scroll  [Esc/{}] Close ", key))
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .border_style(Style::default().fg(theme.border_focus))
+        .style(Style::default().bg(theme.bg_primary));
+
+    let body: Vec<Line> = lines[start..end]
+        .iter()
+        .map(|line| Line::from(Span::styled(line.as_str(), Style::default().fg(theme.text_main))))
+        .collect();
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(body).block(block), area);
+}
@@ -0,0 +1,80 @@
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use crate::fuzzy;
+use crate::types::FileChange;
+use crate::ui::theme::Theme;
+
+// Ctrl+O: jump to a file's latest change by typing part of its name
+#[derive(Default)]
+pub struct QuickOpen {
+    pub query: String,
+    // Row in `matches`
+    pub selected: usize,
+}
+
+impl QuickOpen {
+    // Log indices of the newest entry per path that matches the query, best
+    // first; ties go to the more recent change
+    pub fn matches<'a>(&self, changes: impl IntoIterator<Item = &'a FileChange>) -> Vec<usize> {
+        let mut seen = std::collections::HashSet::new();
+        let mut scored: Vec<(i64, usize)> = changes
+            .into_iter()
+            .enumerate()
+            .filter(|(_, c)| seen.insert(c.path.as_str()))
+            .filter_map(|(i, c)| fuzzy::score(&self.query, &c.path).map(|s| (s, i)))
+            .collect();
+        scored.sort_by_key(|&(score, i)| (std::cmp::Reverse(score), i));
+        scored.into_iter().map(|(_, i)| i).collect()
+    }
+
+    pub fn move_selection(&mut self, down: bool, total: usize) {
+        self.selected = if down {
+            (self.selected + 1).min(total.saturating_sub(1))
+        } else {
+            self.selected.saturating_sub(1)
+        };
+    }
+}
+
+pub fn render(frame: &mut Frame, area: Rect, prompt: &QuickOpen, matches: &[&FileChange], theme: &Theme) {
+    let block = Block::default()
+        .title(" Open change ")
+        .title_bottom(theme.symbol(" [Enter] Open diff  [↑/↓] Select  [Esc] Cancel ", " [Enter] Open diff  [Up/Down] Select  [Esc] Cancel "))
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .border_style(Style::default().fg(theme.border_focus))
+        .style(Style::default().bg(theme.bg_primary));
+
+    let mut lines = vec![
+        Line::from(Span::styled(format!("> {}_", prompt.query), Style::default().fg(theme.text_main))),
+        Line::from(""),
+    ];
+    let rows = (area.height as usize).saturating_sub(4);
+    if matches.is_empty() {
+        lines.push(Line::from(Span::styled("No matching files", Style::default().fg(theme.text_muted))));
+    }
+    // Keep the selection on screen
+    let skip = (prompt.selected + 1).saturating_sub(rows);
+    lines.extend(matches.iter().enumerate().skip(skip).take(rows).map(|(row, change)| {
+        let style = if row == prompt.selected {
+            Style::default().fg(theme.text_main).bg(theme.bg_secondary).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(theme.text_main)
+        };
+        Line::from(vec![
+            Span::styled(
+                format!("{} ", if theme.ascii_only { change.status.ascii_glyph() } else { change.status.glyph() }),
+                Style::default().fg(theme.status_color(change.status)),
+            ),
+            Span::styled(change.path.as_str(), style),
+        ])
+    }));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
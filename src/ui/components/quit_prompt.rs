@@ -0,0 +1,40 @@
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use crate::config::RejectMode;
+use crate::ui::theme::Theme;
+
+pub fn render(frame: &mut Frame, area: Rect, pending: usize, reject_mode: RejectMode, theme: &Theme) {
+    let block = Block::default()
+        .title(" Quit ")
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .border_style(Style::default().fg(theme.status_warning))
+        .style(Style::default().bg(theme.bg_primary));
+
+    let noun = if pending == 1 { "change is" } else { "changes are" };
+    let lines = vec![
+        Line::from(Span::styled(
+            format!("{} {} still awaiting approval.", pending, noun),
+            Style::default().fg(theme.text_main).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(Span::styled("[a] Accept all and quit", Style::default().fg(theme.status_success))),
+        Line::from(Span::styled(
+            match reject_mode {
+                RejectMode::Revert => "[r] Reject (revert) all and quit",
+                RejectMode::Dismiss => "[r] Dismiss all and quit",
+            },
+            Style::default().fg(theme.status_error),
+        )),
+        Line::from(Span::styled("[Ctrl+Q] Quit, leaving them unresolved", Style::default().fg(theme.text_muted))),
+        Line::from(Span::styled("[Esc] Cancel", Style::default().fg(theme.text_muted))),
+    ];
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
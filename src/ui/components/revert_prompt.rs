@@ -0,0 +1,42 @@
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+use crate::ui::theme::Theme;
+
+// Shown before the first reject that writes to disk; `effect` says what this
+// one will do ("delete src/new.rs")
+pub fn render(frame: &mut Frame, area: Rect, effect: &str, theme: &Theme) {
+    let block = Block::default()
+        .title(" Reject reverts files ")
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .border_style(Style::default().fg(theme.status_warning))
+        .style(Style::default().bg(theme.bg_primary));
+
+    let text = |s: &str| Line::from(Span::styled(s.to_string(), Style::default().fg(theme.text_main)));
+    let muted = |s: &str| Line::from(Span::styled(s.to_string(), Style::default().fg(theme.text_muted)));
+    let bullet = theme.symbol("•", "-");
+    let lines = vec![
+        text("Rejecting a change undoes it on disk:"),
+        text(&format!(" {} an edited file is overwritten with its content from before the change", bullet)),
+        text(&format!(" {} a new file is deleted", bullet)),
+        text(&format!(" {} a deleted file or directory is recreated from its cached copy", bullet)),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("This reject will ", Style::default().fg(theme.text_main)),
+            Span::styled(effect.to_string(), Style::default().fg(theme.status_warning).add_modifier(Modifier::BOLD)),
+            Span::styled(".", Style::default().fg(theme.text_main)),
+        ]),
+        Line::from(""),
+        muted("If the file changed again after the diff was shown, nothing is written and the change comes back updated. reject_mode = \"dismiss\" never touches disk."),
+        Line::from(""),
+        muted("[y] Revert  [a] Revert, don't ask again  [Esc] Cancel"),
+    ];
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block).wrap(Wrap { trim: false }), area);
+}
@@ -1,60 +1,357 @@
 use ratatui::{
-    layout::Rect,
+    layout::{Margin, Rect},
     style::{Modifier, Style},
-    widgets::{Block, Borders, List, ListItem, ListState},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Scrollbar, ScrollbarOrientation, ScrollbarState},
     Frame,
 };
-use chrono::Local;
-use crate::types::{ChangeKind, FileChange};
+use chrono::{DateTime, Local, SecondsFormat};
+use std::collections::HashSet;
+use crate::config::TimeFormat;
+use crate::glob::glob_match;
+use crate::types::{ChangeKind, ChangeStatus, FileChange, Origin};
 use crate::ui::theme::Theme;
 
+// Which files the sidebar shows, by name rather than by change kind
+#[derive(Clone, Default, PartialEq, Eq)]
+pub enum TypeFilter {
+    #[default]
+    All,
+    // Extensions from config, without the leading dot
+    Extensions(Vec<String>),
+    Glob(String),
+}
+
+impl TypeFilter {
+    pub fn matches(&self, path: &str) -> bool {
+        match self {
+            Self::All => true,
+            Self::Extensions(exts) => path
+                .rsplit_once('.')
+                .is_some_and(|(_, ext)| exts.iter().any(|e| e.eq_ignore_ascii_case(ext))),
+            // An empty glob is still being typed; don't blank the list meanwhile
+            Self::Glob(pattern) => pattern.is_empty() || glob_match(pattern, path),
+        }
+    }
+
+    fn label(&self) -> Option<String> {
+        match self {
+            Self::All => None,
+            Self::Extensions(exts) => Some(format!("[.{}]", exts.join(",."))),
+            Self::Glob(pattern) => Some(format!("[{}]", pattern)),
+        }
+    }
+}
+
+// View-only filter over the change log; the backing deque is never touched
+pub struct SidebarFilter {
+    pub query: String,
+    pub editing: bool,
+    pub show_create: bool,
+    pub show_modify: bool,
+    pub show_remove: bool,
+    pub file_type: TypeFilter,
+    // Typing a custom glob for `file_type`
+    pub editing_glob: bool,
+    // Entry (by id) to select again once the filter is cleared
+    pub saved_selection: Option<u64>,
+}
+
+impl Default for SidebarFilter {
+    fn default() -> Self {
+        Self {
+            query: String::new(),
+            editing: false,
+            show_create: true,
+            show_modify: true,
+            show_remove: true,
+            file_type: TypeFilter::All,
+            editing_glob: false,
+            saved_selection: None,
+        }
+    }
+}
+
+impl SidebarFilter {
+    pub fn is_active(&self) -> bool {
+        !(self.query.is_empty()
+            && self.show_create
+            && self.show_modify
+            && self.show_remove
+            && self.file_type == TypeFilter::All)
+    }
+
+    pub fn matches(&self, change: &FileChange) -> bool {
+        let kind_visible = match change.kind {
+            ChangeKind::Create => self.show_create,
+            // Renames are filtered together with modifications
+            ChangeKind::Modify | ChangeKind::Rename => self.show_modify,
+            ChangeKind::Remove => self.show_remove,
+        };
+        kind_visible
+            && self.file_type.matches(&change.path)
+            && change.path.to_lowercase().contains(&self.query.to_lowercase())
+    }
+
+    // all -> configured extensions (if any) -> custom glob -> all
+    pub fn cycle_type(&mut self, extensions: &[String]) {
+        self.file_type = match self.file_type {
+            TypeFilter::All if !extensions.is_empty() => TypeFilter::Extensions(extensions.to_vec()),
+            TypeFilter::All | TypeFilter::Extensions(_) => TypeFilter::Glob(String::new()),
+            TypeFilter::Glob(_) => TypeFilter::All,
+        };
+        self.editing_glob = matches!(self.file_type, TypeFilter::Glob(_));
+    }
+
+    pub fn toggle_kind(&mut self, kind: ChangeKind) {
+        let flag = match kind {
+            ChangeKind::Create => &mut self.show_create,
+            ChangeKind::Modify | ChangeKind::Rename => &mut self.show_modify,
+            ChangeKind::Remove => &mut self.show_remove,
+        };
+        *flag = !*flag;
+    }
+
+    // Log indices of the entries that pass the filter, in display order:
+    // pinned entries first, each section newest first
+    pub fn visible_indices<'a>(&'a self, changes: impl IntoIterator<Item = &'a FileChange>) -> Vec<usize> {
+        let (pinned, rest): (Vec<_>, Vec<_>) = changes
+            .into_iter()
+            .enumerate()
+            .filter(|(_, c)| self.matches(c))
+            .partition(|(_, c)| c.pinned);
+        pinned.into_iter().chain(rest).map(|(i, _)| i).collect()
+    }
+
+    fn title(&self) -> String {
+        let mut title = " Active Monitoring ".to_string();
+        let chip = |on: bool, label: &str| if on { format!("[{}]", label) } else { String::new() };
+        if self.is_active() {
+            title.push_str(&chip(self.show_create, "A"));
+            title.push_str(&chip(self.show_modify, "M"));
+            title.push_str(&chip(self.show_remove, "D"));
+            title.push(' ');
+        }
+        if let Some(label) = self.file_type.label() {
+            title.push_str(&label);
+            title.push_str(if self.editing_glob { "_ " } else { " " });
+        }
+        if self.editing {
+            title.push_str(&format!("/{}_ ", self.query));
+        } else if !self.query.is_empty() {
+            title.push_str(&format!("/{} ", self.query));
+        }
+        title
+    }
+}
+
+// Display settings that aren't part of the filter
+pub struct SidebarView<'a> {
+    pub focused: bool,
+    pub time_format: TimeFormat,
+    // FileChange ids marked with Space
+    pub marked: &'a HashSet<u64>,
+}
+
 pub fn render(
     frame: &mut Frame,
     area: Rect,
-    changes: &[FileChange],
+    changes: &[&FileChange],
     state: &mut ListState,
+    filter: &SidebarFilter,
+    view: &SidebarView,
     theme: &Theme,
 ) {
+    let border = if view.focused { theme.border_focus } else { theme.border_dim };
+    let mut title = filter.title();
+    // Focus is only a border color otherwise
+    if view.focused && theme.ascii_only {
+        title.insert_str(0, " [focused]");
+    }
+    let rows = area.height.saturating_sub(2) as usize; // inside the borders
+    if changes.len() > rows {
+        title.push_str(&format!("(showing {} of {}) ", rows, format_count(changes.len())));
+    }
+    if !view.marked.is_empty() {
+        title.push_str(&format!("({} marked, [a] Act [x] Clear) ", view.marked.len()));
+    }
     let block = Block::default()
-        .title(" Active Monitoring ")
+        .title(title)
         .borders(Borders::ALL)
+        .border_set(theme.border_set())
         .style(Style::default().fg(theme.border_dim))
-        .border_style(Style::default().fg(theme.border_dim)); // Subtle border
+        .border_style(Style::default().fg(border)); // Subtle border unless focused
     
     let now = Local::now();
+    let pinned_count = changes.iter().filter(|c| c.pinned).count();
     
-    let styled_items: Vec<ListItem> = changes.iter().map(|change| {
+    let styled_items: Vec<ListItem> = changes.iter().enumerate().map(|(row, change)| {
          let color = match change.kind {
             ChangeKind::Create => theme.status_success,
             ChangeKind::Modify => theme.status_warning,
             ChangeKind::Remove => theme.status_error,
+            ChangeKind::Rename => theme.status_info,
         };
         
-        let time_diff = now.signed_duration_since(change.timestamp);
-        let time_str = if time_diff.num_seconds() < 60 {
-            format!("{}s", time_diff.num_seconds())
+        let time_str = format_timestamp(change.timestamp, now, view.time_format);
+        
+        // A, M, D, R; with the word too when color can't tell them apart
+        let symbol = if theme.ascii_only {
+            format!("{} {}", change.kind.letter(), change.kind.word())
         } else {
-            change.timestamp.format("%H:%M").to_string()
+            change.kind.letter().to_string()
         };
-        
-        let symbol = match change.kind {
-            ChangeKind::Create => "A", // Added
-            ChangeKind::Modify => "M", // Modified
-            ChangeKind::Remove => "D", // Deleted
+        let (glyph, origin) = if theme.ascii_only {
+            (change.status.ascii_glyph(), change.origin.ascii_glyph())
+        } else {
+            (change.status.glyph(), change.origin.glyph())
         };
 
-        ListItem::new(format!("{:>3} {} {}", time_str, symbol, change.path))
-            .style(Style::default().fg(color))
+        // Review debt stands out: pending entries are bold and blink
+        let mut status_style = Style::default().fg(theme.status_color(change.status));
+        let mut entry_style = Style::default().fg(color);
+        if change.status == ChangeStatus::Pending {
+            status_style = status_style.add_modifier(Modifier::BOLD | Modifier::SLOW_BLINK);
+            entry_style = entry_style.add_modifier(Modifier::BOLD);
+        }
+        if change.status == ChangeStatus::Blocked {
+            entry_style = Style::default().fg(theme.status_error).add_modifier(Modifier::BOLD);
+        }
+        // The user's own edits are background noise next to the agent's
+        if change.origin == Origin::User {
+            entry_style = Style::default().fg(theme.text_muted).add_modifier(Modifier::DIM);
+        }
+
+        // The last pinned row is underlined to close off the pinned section
+        if change.pinned && row + 1 == pinned_count && pinned_count < changes.len() {
+            entry_style = entry_style.add_modifier(Modifier::UNDERLINED);
+        }
+        let pin = if change.pinned { theme.symbol("⚑ ", "[pin] ") } else { "" };
+        // Checkboxes only once something is marked
+        let mark = match (view.marked.is_empty(), view.marked.contains(&change.id)) {
+            (true, _) => "",
+            (false, true) => theme.symbol("☑ ", "[x] "),
+            (false, false) => theme.symbol("☐ ", "[ ] "),
+        };
+        if view.marked.contains(&change.id) {
+            entry_style = entry_style.add_modifier(Modifier::REVERSED);
+        }
+        let slash = if change.dir { "/" } else { "" };
+
+        let mut line = Line::from(vec![
+            Span::styled(mark, Style::default().fg(theme.text_main)),
+            Span::styled(format!("{} ", glyph), status_style),
+            Span::styled(pin, Style::default().fg(theme.status_info)),
+            Span::styled(origin, Style::default().fg(theme.text_muted)),
+            Span::styled(format!("{:>3} {} {}{}", time_str, symbol, change.path, slash), entry_style),
+        ]);
+        if change.insertions > 0 {
+            line.push_span(Span::styled(format!(" +{}", change.insertions), Style::default().fg(theme.diff_add)));
+        }
+        if change.deletions > 0 {
+            line.push_span(Span::styled(format!(" {}{}", theme.symbol("−", "-"), change.deletions), Style::default().fg(theme.diff_remove)));
+        }
+        if let Some(note) = &change.note {
+            line.push_span(Span::styled(format!(" - {}", note), Style::default().fg(theme.text_muted)));
+        }
+
+        ListItem::new(line)
     }).collect();
 
+    // Without colors the selection is reversed instead of shaded
+    let highlight = if theme.ascii_only { Modifier::BOLD | Modifier::REVERSED } else { Modifier::BOLD };
     let list = List::new(styled_items)
         .block(block)
         .highlight_style(
             Style::default()
                 .bg(theme.bg_secondary)
-                .add_modifier(Modifier::BOLD)
+                .add_modifier(highlight)
         )
-        .highlight_symbol("▎"); // A nice solid bar instead of ">"
+        .highlight_symbol(theme.symbol("▎", ">")); // A nice solid bar instead of ">"
 
+    // The list scrolls just enough to keep the selection in view
     frame.render_stateful_widget(list, area, state);
+
+    // Position within a long history, drawn over the right border
+    if changes.len() > rows {
+        let mut scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+            .begin_symbol(None)
+            .end_symbol(None)
+            .thumb_style(Style::default().fg(border))
+            .track_style(Style::default().fg(theme.border_dim));
+        if theme.ascii_only {
+            scrollbar = scrollbar.thumb_symbol("#").track_symbol(Some("|"));
+        }
+        let mut scroll_state = ScrollbarState::new(changes.len() - rows)
+            .position(state.offset())
+            .viewport_content_length(rows);
+        frame.render_stateful_widget(scrollbar, area.inner(Margin { vertical: 1, horizontal: 0 }), &mut scroll_state);
+    }
+}
+
+// Relative labels use the largest whole unit: 59s, 1m, 59m, 1h, 23h, 1d.
+// Absolute ones add the weekday, then the date, once the change isn't from today.
+pub fn format_timestamp(ts: DateTime<Local>, now: DateTime<Local>, format: TimeFormat) -> String {
+    match format {
+        TimeFormat::Relative => {
+            let secs = now.signed_duration_since(ts).num_seconds().max(0);
+            match secs {
+                0..60 => format!("{}s", secs),
+                60..3600 => format!("{}m", secs / 60),
+                3600..86400 => format!("{}h", secs / 3600),
+                _ => format!("{}d", secs / 86400),
+            }
+        }
+        TimeFormat::Absolute => {
+            let days = now.date_naive().signed_duration_since(ts.date_naive()).num_days();
+            let format = match days {
+                ..=0 => "%H:%M",
+                1..7 => "%a %H:%M",
+                _ => "%m-%d",
+            };
+            ts.format(format).to_string()
+        }
+        TimeFormat::Iso => ts.to_rfc3339_opts(SecondsFormat::Secs, false),
+    }
+}
+
+// 1243 -> "1,243"
+fn format_count(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, ch) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn absolute_timestamps_gain_a_date_once_they_are_not_from_today() {
+        let now = Local.with_ymd_and_hms(2024, 5, 10, 9, 0, 0).unwrap(); // a Friday
+        let at = |d, h, m| Local.with_ymd_and_hms(2024, 5, d, h, m, 0).unwrap();
+        assert_eq!(format_timestamp(at(10, 8, 5), now, TimeFormat::Absolute), "08:05");
+        assert_eq!(format_timestamp(at(9, 23, 59), now, TimeFormat::Absolute), "Thu 23:59");
+        assert_eq!(format_timestamp(at(4, 12, 0), now, TimeFormat::Absolute), "Sat 12:00");
+        assert_eq!(format_timestamp(at(3, 12, 0), now, TimeFormat::Absolute), "05-03");
+        assert_eq!(format_timestamp(at(10, 8, 59), now, TimeFormat::Relative), "1m");
+    }
+
+    #[test]
+    fn relative_timestamps_use_the_largest_whole_unit() {
+        let now = Local.with_ymd_and_hms(2024, 5, 10, 9, 0, 0).unwrap();
+        let ago = |secs| format_timestamp(now - chrono::Duration::seconds(secs), now, TimeFormat::Relative);
+        assert_eq!([ago(0), ago(59), ago(60), ago(3599), ago(3600), ago(86399), ago(86400)], ["0s", "59s", "1m", "59m", "1h", "23h", "1d"]);
+        // A clock that stepped back doesn't make it negative
+        assert_eq!(ago(-30), "0s");
+        let iso = format_timestamp(now, now, TimeFormat::Iso);
+        assert!(iso.starts_with("2024-05-10T09:00:00"), "{}", iso);
+    }
 }
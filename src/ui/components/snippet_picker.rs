@@ -0,0 +1,122 @@
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use crate::fuzzy;
+use crate::snippets::Snippet;
+use crate::ui::theme::Theme;
+
+// Ctrl+Y: snippets from snippets.toml, then recently typed prompts
+#[derive(Default)]
+pub struct SnippetPicker {
+    pub entries: Vec<Snippet>,
+    // How many of `entries` are snippets; the rest is history
+    pub snippet_count: usize,
+    pub query: String,
+    // Row in `matches`
+    pub selected: usize,
+    // A chosen snippet waiting on a placeholder nothing could fill
+    pub filling: Option<Filling>,
+    // snippets.toml didn't parse
+    pub error: Option<String>,
+}
+
+pub struct Filling {
+    pub text: String,
+    pub placeholder: &'static str,
+    pub input: String,
+}
+
+impl SnippetPicker {
+    pub fn new(snippets: Vec<Snippet>, history: impl IntoIterator<Item = String>, error: Option<String>) -> Self {
+        let snippet_count = snippets.len();
+        let mut entries = snippets;
+        entries.extend(history.into_iter().map(|text| Snippet { name: String::new(), text }));
+        Self { entries, snippet_count, error, ..Self::default() }
+    }
+
+    // Indices into `entries`, best match first; snippets match on name and text
+    pub fn matches(&self) -> Vec<usize> {
+        let mut scored: Vec<(i64, usize)> = self.entries.iter().enumerate()
+            .filter_map(|(i, e)| {
+                let haystack = if e.name.is_empty() { e.text.clone() } else { format!("{} {}", e.name, e.text) };
+                fuzzy::score(&self.query, &haystack).map(|s| (s, i))
+            })
+            .collect();
+        // An empty query keeps the file's order: snippets, then newest history
+        if !self.query.is_empty() {
+            scored.sort_by_key(|&(score, i)| (std::cmp::Reverse(score), i));
+        }
+        scored.into_iter().map(|(_, i)| i).collect()
+    }
+
+    pub fn move_selection(&mut self, down: bool, total: usize) {
+        self.selected = if down {
+            (self.selected + 1).min(total.saturating_sub(1))
+        } else {
+            self.selected.saturating_sub(1)
+        };
+    }
+}
+
+pub fn render(frame: &mut Frame, area: Rect, picker: &SnippetPicker, theme: &Theme) {
+    let block = Block::default()
+        .title(" Snippets & history ")
+        .title_bottom(" [Enter] Send  [Esc] Cancel ")
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .border_style(Style::default().fg(theme.border_focus))
+        .style(Style::default().bg(theme.bg_primary));
+
+    let mut lines = Vec::new();
+    if let Some(filling) = &picker.filling {
+        lines.push(Line::from(Span::styled(
+            format!("Nothing selected for {} - type it:", filling.placeholder),
+            Style::default().fg(theme.text_muted),
+        )));
+        lines.push(Line::from(Span::styled(format!("> {}_", filling.input), Style::default().fg(theme.text_main))));
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+        return;
+    }
+
+    lines.push(Line::from(Span::styled(format!("> {}_", picker.query), Style::default().fg(theme.text_main))));
+    if let Some(error) = &picker.error {
+        lines.push(Line::from(Span::styled(error.as_str(), Style::default().fg(theme.status_error))));
+    }
+    lines.push(Line::from(""));
+    let matches = picker.matches();
+    if matches.is_empty() {
+        let hint = if picker.entries.is_empty() {
+            "No snippets yet - add `name = \"prompt\"` lines to ~/.config/ai-tui/snippets.toml"
+        } else {
+            "Nothing matches"
+        };
+        lines.push(Line::from(Span::styled(hint, Style::default().fg(theme.text_muted))));
+    }
+    let rows = (area.height as usize).saturating_sub(lines.len() + 2);
+    // Keep the selection on screen
+    let skip = (picker.selected + 1).saturating_sub(rows);
+    lines.extend(matches.iter().enumerate().skip(skip).take(rows).map(|(row, &i)| {
+        let entry = &picker.entries[i];
+        let style = if row == picker.selected {
+            Style::default().fg(theme.text_main).bg(theme.bg_secondary).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(theme.text_main)
+        };
+        let (tag, tag_style) = if i < picker.snippet_count {
+            (format!("{} ", entry.name), Style::default().fg(theme.status_info))
+        } else {
+            ("history ".to_string(), Style::default().fg(theme.text_muted))
+        };
+        // Multi-line templates show their first line
+        let first = entry.text.lines().next().unwrap_or_default();
+        Line::from(vec![Span::styled(tag, tag_style), Span::styled(first.to_string(), style)])
+    }));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
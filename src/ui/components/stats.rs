@@ -0,0 +1,62 @@
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use std::time::Duration;
+use crate::throughput::Throughput;
+use crate::types::{ChangeKind, SessionStats};
+use crate::ui::theme::Theme;
+
+// `quiet` is how long since the agent last wrote anything
+pub fn render(frame: &mut Frame, area: Rect, stats: &SessionStats, output: &Throughput, quiet: Option<Duration>, theme: &Theme) {
+    let block = Block::default()
+        .title(" Session Stats (Ctrl+S) ")
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .border_style(Style::default().fg(theme.border_focus))
+        .style(Style::default().bg(theme.bg_primary));
+
+    let row = |label: &str, value: String, color| {
+        Line::from(vec![
+            Span::styled(format!("{:<18}", label), Style::default().fg(theme.text_muted)),
+            Span::styled(value, Style::default().fg(color).add_modifier(Modifier::BOLD)),
+        ])
+    };
+
+    let kinds: Vec<String> = [ChangeKind::Create, ChangeKind::Modify, ChangeKind::Remove, ChangeKind::Rename]
+        .iter()
+        .map(|kind| format!("{} {:?}", stats.changes.get(kind).copied().unwrap_or(0), kind).to_lowercase())
+        .collect();
+    let (bytes_rate, lines_rate) = output.rate();
+    let width = area.width.saturating_sub(2) as usize;
+
+    let lines = vec![
+        row("Files touched", stats.files.len().to_string(), theme.text_main),
+        row("Changes", kinds.join(", "), theme.text_main),
+        row("Lines added", format!("+{}", stats.insertions), theme.status_success),
+        row("Lines removed", format!("-{}", stats.deletions), theme.status_error),
+        Line::from(""),
+        row("Accepted", stats.accepted.to_string(), theme.status_success),
+        row("Rejected", stats.rejected.to_string(), theme.status_error),
+        row("Time to decide", stats.average_decision().map_or("-".to_string(), |d| format!("{:.1}s on average", d.as_secs_f64())), theme.text_main),
+        Line::from(""),
+        row("Agent output", format!("{:.0} B/s, {:.1} lines/s", bytes_rate, lines_rate), theme.text_main),
+        row("Session total", format!("{} in {} lines", bytes(output.total.bytes), output.total.lines), theme.text_main),
+        row("Last output", quiet.map_or("never".to_string(), |d| format!("{}s ago", d.as_secs())), theme.text_main),
+        Line::from(Span::styled(output.sparkline(width), Style::default().fg(theme.status_info))),
+    ];
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn bytes(n: u64) -> String {
+    match n {
+        0..1024 => format!("{} B", n),
+        1024..1_048_576 => format!("{:.1} KB", n as f64 / 1024.0),
+        _ => format!("{:.1} MB", n as f64 / 1_048_576.0),
+    }
+}
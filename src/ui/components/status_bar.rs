@@ -1,29 +1,274 @@
 use ratatui::{
     layout::Rect,
-    style::Style,
+    style::{Modifier, Style},
+    text::{Line, Span},
     widgets::Paragraph,
     Frame,
 };
+use std::time::Duration;
+use crate::config::{StatusBar, StatusSegment};
 use crate::types::ChangeKind;
-use crate::types::FileChange;
+use crate::types::{FileChange, TestRun};
 use crate::ui::theme::Theme;
 
-pub fn render(frame: &mut Frame, area: Rect, changes: &[FileChange], theme: &Theme) {
-    let total = changes.len();
-    let created = changes.iter().filter(|c| c.kind == ChangeKind::Create).count();
-    let modified = changes.iter().filter(|c| c.kind == ChangeKind::Modify).count();
-    let removed = changes.iter().filter(|c| c.kind == ChangeKind::Remove).count();
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const ASCII_SPINNER: [&str; 4] = ["|", "/", "-", "\\"];
 
-    // Shadcn style: Clean, minimal status bar. No garish background.
-    // Maybe just text with some colored dots.
+// App-level state the status bar reports on
+pub struct StatusInfo<'a> {
+    // Counts reflect the sidebar filter rather than the whole log
+    pub filtered: bool,
+    pub working: bool,
+    pub spinner_frame: usize,
+    // Files cached so far while the startup scan is still running
+    pub indexing: Option<usize>,
+    // That scan is an Alt+R re-scan
+    pub rescanning: bool,
+    // The scan hit max_index_bytes, so some files have no baseline
+    pub index_truncated: bool,
+    // Terminal title set by the agent (OSC 0/2), empty if none
+    pub title: &'a str,
+    // Command line of the running agent
+    pub agent: &'a str,
+    // Git branch, empty outside a repository
+    pub branch: &'a str,
+    // The working tree has uncommitted changes
+    pub dirty: bool,
+    // Follow mode, empty when off
+    pub follow: &'a str,
+    // Ctrl+C escalation under way, empty otherwise
+    pub interrupt: &'a str,
+    // Watcher errors and dropped events; leads the bar while recent
+    pub watch_problem: Option<&'a str>,
+    // Short-lived feedback such as "Exported to ..."
+    pub message: Option<&'a str>,
+    // Session line totals
+    pub insertions: usize,
+    pub deletions: usize,
+    // test_command: how long it's been running, else how it last went
+    pub tests: Option<(Option<Duration>, Option<&'a TestRun>)>,
+    // Approvals waiting
+    pub pending: usize,
+    // Keybinding reminders, from the keymap
+    pub hints: &'a str,
+}
+
+const SEPARATOR: &str = "  |  ";
+
+// One segment's text, ready to place
+struct Piece {
+    priority: u8,
+    spans: Vec<Span<'static>>,
+}
 
-    let status_text = format!(
-        "  AI Terminal  |  Theme: {} (Ctrl+T)  |  Total: {}  |  +{}  ~{}  -{}  |  Ctrl+H: Sidebar  Ctrl+K: Diff  Ctrl+L: Clear",
-        theme.variant.name(), total, created, modified, removed
-    );
+impl Piece {
+    fn width(&self) -> usize {
+        self.spans.iter().map(Span::width).sum()
+    }
+}
 
-    let p = Paragraph::new(status_text)
+pub fn render(frame: &mut Frame, area: Rect, changes: &[&FileChange], info: &StatusInfo, layout: &StatusBar, theme: &Theme) {
+    let pieces = |side: &[StatusSegment]| side.iter().filter_map(|&s| segment(s, changes, info, theme)).collect();
+    let line = arrange(pieces(&layout.left), pieces(&layout.right), area.width as usize);
+    let p = Paragraph::new(line)
         .style(Style::default().fg(theme.text_main).bg(theme.border_dim)); // Subtle bar at bottom
-    
+
     frame.render_widget(p, area);
 }
+
+// Small terminals: only the most important segment that has something to
+// say, whichever side it's configured on
+pub fn render_compact(frame: &mut Frame, area: Rect, changes: &[&FileChange], info: &StatusInfo, layout: &StatusBar, theme: &Theme) {
+    let best = layout.left.iter().chain(&layout.right)
+        .filter_map(|&s| segment(s, changes, info, theme))
+        .min_by_key(|p| p.priority);
+    let line = arrange(best.into_iter().collect(), Vec::new(), area.width as usize);
+    frame.render_widget(Paragraph::new(line).style(Style::default().fg(theme.text_main).bg(theme.border_dim)), area);
+}
+
+// Drop the least important pieces until both sides fit in `width`, then pad
+// between them so the right side sits against the edge. A last piece that
+// still doesn't fit is clipped.
+fn arrange(mut left: Vec<Piece>, mut right: Vec<Piece>, width: usize) -> Line<'static> {
+    let side = |pieces: &[Piece]| pieces.iter().map(Piece::width).sum::<usize>() + SEPARATOR.len() * pieces.len().saturating_sub(1);
+    // One column of margin at each end, and a separator's worth between the sides
+    let needed = |left: &[Piece], right: &[Piece]| {
+        side(left) + side(right) + 2 + if left.is_empty() || right.is_empty() { 0 } else { SEPARATOR.len() }
+    };
+    while needed(&left, &right) > width && left.len() + right.len() > 1 {
+        let worst = |pieces: &[Piece]| pieces.iter().enumerate().max_by_key(|(_, p)| p.priority).map(|(i, p)| (p.priority, i));
+        match (worst(&left), worst(&right)) {
+            (Some((l, i)), Some((r, _))) if l >= r => drop(left.remove(i)),
+            (_, Some((_, i))) => drop(right.remove(i)),
+            (Some((_, i)), None) => drop(left.remove(i)),
+            (None, None) => break,
+        }
+    }
+
+    let join = |pieces: Vec<Piece>| {
+        let mut spans = Vec::new();
+        for (i, piece) in pieces.into_iter().enumerate() {
+            if i > 0 {
+                spans.push(Span::raw(SEPARATOR));
+            }
+            spans.extend(piece.spans);
+        }
+        spans
+    };
+    let gap = width.saturating_sub(needed(&left, &right)) + if left.is_empty() || right.is_empty() { 0 } else { SEPARATOR.len() };
+    let mut spans = vec![Span::raw(" ")];
+    spans.extend(join(left));
+    spans.push(Span::raw(" ".repeat(gap)));
+    spans.extend(join(right));
+    Line::from(spans)
+}
+
+fn segment(segment: StatusSegment, changes: &[&FileChange], info: &StatusInfo, theme: &Theme) -> Option<Piece> {
+    let spinner: &[&str] = if theme.ascii_only { &ASCII_SPINNER } else { &SPINNER };
+    let plain = |text: String| vec![Span::raw(text)];
+    let spans = match segment {
+        StatusSegment::Warning => vec![Span::styled(
+            format!(" {} {} ", theme.symbol("⚠", "!"), info.watch_problem?),
+            Style::default().fg(theme.bg_primary).bg(theme.status_warning).add_modifier(Modifier::BOLD),
+        )],
+        StatusSegment::Pending if info.pending == 0 => return None,
+        StatusSegment::Pending => vec![Span::styled(
+            format!("{} pending", info.pending),
+            Style::default().fg(theme.status_warning).add_modifier(Modifier::BOLD),
+        )],
+        StatusSegment::Activity => {
+            let mut activity = if info.working {
+                format!("{} AI working", spinner[info.spinner_frame % spinner.len()])
+            } else {
+                "idle".to_string()
+            };
+            if let Some(files) = info.indexing {
+                let scan = if info.rescanning { "Re-scanning" } else { "Indexing" };
+                activity.push_str(&format!("{}{}{} {} files", SEPARATOR, scan, theme.symbol("…", "..."), files));
+            } else if info.index_truncated {
+                activity.push_str(&format!("{}Index truncated", SEPARATOR));
+            }
+            if !info.follow.is_empty() {
+                activity.push_str(&format!("{}{}", SEPARATOR, info.follow));
+            }
+            if !info.interrupt.is_empty() {
+                activity.push_str(&format!("{}{}", SEPARATOR, info.interrupt));
+            }
+            plain(activity)
+        }
+        StatusSegment::Tests => match info.tests? {
+            (Some(elapsed), _) => vec![Span::styled(
+                format!("{} tests {}s", spinner[info.spinner_frame % spinner.len()], elapsed.as_secs()),
+                Style::default().fg(theme.status_info),
+            )],
+            (None, Some(last)) => {
+                let color = if last.passed() {
+                    theme.status_success
+                } else if last.cancelled {
+                    theme.text_muted
+                } else {
+                    theme.status_error
+                };
+                vec![Span::styled(format!("{} tests {}", theme.symbol("●", "*"), last.summary()), Style::default().fg(color).add_modifier(Modifier::BOLD))]
+            }
+            (None, None) => return None,
+        },
+        StatusSegment::Message => plain(info.message?.to_string()),
+        StatusSegment::Counts => {
+            let created = changes.iter().filter(|c| c.kind == ChangeKind::Create).count();
+            let modified = changes.iter().filter(|c| matches!(c.kind, ChangeKind::Modify | ChangeKind::Rename)).count();
+            let removed = changes.iter().filter(|c| c.kind == ChangeKind::Remove).count();
+            let label = if info.filtered { "Shown" } else { "Total" };
+            plain(format!("{}: {}  +{}  ~{}  -{}", label, changes.len(), created, modified, removed))
+        }
+        StatusSegment::Lines => plain(format!("Lines +{} {}{}", info.insertions, theme.symbol("−", "-"), info.deletions)),
+        StatusSegment::Branch if info.branch.is_empty() => return None,
+        StatusSegment::Branch => {
+            let mut spans = plain(format!("Branch: {}", info.branch));
+            if info.dirty {
+                spans.push(Span::styled(theme.symbol(" ●", " (dirty)"), Style::default().fg(theme.status_warning)));
+            }
+            spans
+        }
+        StatusSegment::Agent => plain(format!("Agent: {}", info.agent)),
+        StatusSegment::Title if info.title.is_empty() => return None,
+        StatusSegment::Title => plain(info.title.to_string()),
+        StatusSegment::Theme => plain(format!("Theme: {}", theme.variant.name())),
+        StatusSegment::Hints if info.hints.is_empty() => return None,
+        StatusSegment::Hints => plain(info.hints.to_string()),
+    };
+    Some(Piece { priority: segment.priority(), spans })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{backend::TestBackend, Terminal};
+    use crate::ui::theme::ThemeVariant;
+
+    fn info(pending: usize) -> StatusInfo<'static> {
+        StatusInfo {
+            filtered: false,
+            working: false,
+            spinner_frame: 0,
+            indexing: None,
+            rescanning: false,
+            index_truncated: false,
+            title: "",
+            agent: "claude",
+            branch: "main",
+            dirty: false,
+            follow: "",
+            interrupt: "",
+            watch_problem: None,
+            message: None,
+            insertions: 12,
+            deletions: 3,
+            tests: None,
+            pending,
+            hints: "Ctrl+H: Sidebar  Ctrl+K: Diff  Ctrl+S: Stats",
+        }
+    }
+
+    fn row(width: u16, info: &StatusInfo, layout: &StatusBar) -> String {
+        let theme = Theme::new(ThemeVariant::Zinc);
+        let mut terminal = Terminal::new(TestBackend::new(width, 1)).unwrap();
+        terminal.draw(|frame| render(frame, frame.area(), &[], info, layout, &theme)).unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..width).map(|x| buffer.cell((x, 0)).unwrap().symbol()).collect()
+    }
+
+    #[test]
+    fn wide_bar_shows_everything_with_hints_on_the_right() {
+        let text = row(200, &info(2), &StatusBar::default());
+        assert!(text.starts_with(" idle  |  2 pending  |  Agent: claude  |  Branch: main  |  Theme: Zinc  |  Total"), "{}", text);
+        assert!(text.ends_with("Ctrl+S: Stats "), "{}", text);
+    }
+
+    #[test]
+    fn narrow_bar_drops_hints_before_the_pending_count() {
+        let text = row(46, &info(2), &StatusBar::default());
+        assert_eq!(text.trim_end(), " idle  |  2 pending  |  Total: 0  +0  ~0  -0");
+        let text = row(20, &info(2), &StatusBar::default());
+        assert_eq!(text.trim_end(), " idle  |  2 pending");
+        // Down to the one piece that matters most, clipped
+        let text = row(6, &info(2), &StatusBar::default());
+        assert_eq!(text, " 2 pen");
+    }
+
+    #[test]
+    fn segments_follow_the_configured_order() {
+        let layout = StatusBar { left: vec![StatusSegment::Lines, StatusSegment::Pending], right: vec![StatusSegment::Agent] };
+        assert_eq!(row(50, &info(1), &layout), format!(" Lines +12 −3  |  1 pending{}Agent: claude ", " ".repeat(9)));
+        // Nothing pending: that segment just isn't there
+        assert_eq!(row(50, &info(0), &layout), format!(" Lines +12 −3{}Agent: claude ", " ".repeat(23)));
+
+        // Branch, with a dot for a dirty tree, and gone outside a repository
+        let layout = StatusBar { left: vec![StatusSegment::Branch, StatusSegment::Activity], right: Vec::new() };
+        assert_eq!(row(30, &info(0), &layout).trim_end(), " Branch: main  |  idle");
+        let dirty = StatusInfo { dirty: true, ..info(0) };
+        assert_eq!(row(30, &dirty, &layout).trim_end(), " Branch: main ●  |  idle");
+        let outside = StatusInfo { branch: "", ..info(0) };
+        assert_eq!(row(30, &outside, &layout).trim_end(), " idle");
+    }
+}
@@ -0,0 +1,48 @@
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame,
+};
+use crate::ui::theme::Theme;
+
+// --tail: stands in for the terminal pane when there's no agent to embed
+pub fn render(frame: &mut Frame, area: Rect, roots: &[String], theme: &Theme) {
+    let block = Block::default()
+        .title(" Watching ")
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .border_style(Style::default().fg(theme.border_dim))
+        .style(Style::default().bg(theme.bg_primary));
+
+    let key = |k: &'static str, what: &'static str| {
+        Line::from(vec![
+            Span::styled(format!("  {:<12}", k), Style::default().fg(theme.status_info)),
+            Span::styled(what, Style::default().fg(theme.text_main)),
+        ])
+    };
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            "Tail mode: no agent runs here. Start it in its own terminal;",
+            Style::default().fg(theme.text_main).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(Span::styled(
+            "its changes under these directories show up in the sidebar:",
+            Style::default().fg(theme.text_main).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+    ];
+    lines.extend(roots.iter().map(|root| Line::from(Span::styled(format!("  {}", root), Style::default().fg(theme.text_muted)))));
+    lines.extend([
+        Line::from(""),
+        key("y / n", "accept / reject the change in the approval prompt"),
+        key("Ctrl+K", "full diff of the selected change"),
+        key("Ctrl+F", "focus the sidebar or this pane"),
+        key("Ctrl+E", "export the change log"),
+        key("Ctrl+Q", "quit"),
+    ]);
+
+    frame.render_widget(Paragraph::new(lines).block(block).wrap(Wrap { trim: false }), area);
+}
@@ -0,0 +1,145 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+};
+use std::path::{Path, MAIN_SEPARATOR};
+
+// Copy the child's screen into the terminal pane. No wrapping here: the child
+// owns its screen and wraps (or not) at the size we gave the PTY.
+//
+// Wide glyphs (CJK, most emoji) take two vt100 cells, the second an empty
+// continuation. Ratatui hides the column after a wide symbol by itself, so
+// continuations are skipped rather than painted over, and a wide glyph that
+// would straddle the pane's right edge is drawn as a blank instead of
+// spilling into the sidebar.
+pub fn render(buffer: &mut Buffer, area: Rect, screen: &vt100::Screen) {
+    let (rows, cols) = screen.size();
+    for row in 0..rows.min(area.height) {
+        for col in 0..cols.min(area.width) {
+            let Some(cell) = screen.cell(row, col) else { continue };
+            if cell.is_wide_continuation() {
+                continue;
+            }
+            let contents = cell.contents();
+            let fits = !cell.is_wide() || col + 1 < area.width;
+            let symbol = if contents.is_empty() || !fits { " " } else { contents.as_str() };
+            buffer.set_string(area.x + col, area.y + row, symbol, cell_style(cell));
+        }
+    }
+}
+
+// Every attribute vt100 keeps per cell. 0.15 parses only bold, italic,
+// underline and inverse; dim (SGR 2) first shows up in 0.16, which needs a
+// newer unicode-width than ratatui 0.29 pins. Blink and strikethrough aren't
+// parsed by either, so those stay plain.
+pub fn cell_style(cell: &vt100::Cell) -> Style {
+    let mut style = Style::default().fg(convert_color(cell.fgcolor())).bg(convert_color(cell.bgcolor()));
+    for (on, modifier) in [
+        (cell.bold(), Modifier::BOLD),
+        (cell.italic(), Modifier::ITALIC),
+        (cell.underline(), Modifier::UNDERLINED),
+        (cell.inverse(), Modifier::REVERSED),
+    ] {
+        if on {
+            style = style.add_modifier(modifier);
+        }
+    }
+    style
+}
+
+// What the pane title's placeholders stand for
+pub struct PaneTitle<'a> {
+    pub cmd: &'a str,
+    pub cwd: &'a str,
+    // Empty outside a git repository
+    pub branch: &'a str,
+    pub pending: usize,
+}
+
+// Fill in {cmd}, {cwd}, {branch} and {pending}; anything else is kept as typed
+pub fn expand_title(template: &str, values: &PaneTitle) -> String {
+    template
+        .replace("{cmd}", values.cmd)
+        .replace("{cwd}", values.cwd)
+        .replace("{branch}", values.branch)
+        .replace("{pending}", &values.pending.to_string())
+}
+
+// `dir` for {cwd}, with `home` shortened to ~. Compared a component at a time,
+// so /home/al isn't taken for part of /home/alice.
+pub fn tilde(dir: &Path, home: Option<&Path>) -> String {
+    match home.and_then(|home| dir.strip_prefix(home).ok()) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~{}{}", MAIN_SEPARATOR, rest.display()),
+        None => dir.display().to_string(),
+    }
+}
+
+fn convert_color(c: vt100::Color) -> Color {
+    match c {
+        vt100::Color::Default => Color::Reset,
+        vt100::Color::Idx(i) => Color::Indexed(i),
+        vt100::Color::Rgb(r, g, b) => Color::Rgb(r, g, b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols(buffer: &Buffer, y: u16) -> Vec<String> {
+        (0..buffer.area.width).map(|x| buffer[(x, y)].symbol().to_string()).collect()
+    }
+
+    #[test]
+    fn wide_glyphs_take_two_columns() {
+        let mut parser = vt100::Parser::new(2, 10, 0);
+        parser.process("a中b🙂c".as_bytes());
+        let area = Rect::new(0, 0, 10, 2);
+        let mut buffer = Buffer::empty(area);
+        render(&mut buffer, area, parser.screen());
+
+        // The column after each wide glyph stays ratatui's hidden filler
+        assert_eq!(symbols(&buffer, 0)[..7], ["a", "中", " ", "b", "🙂", " ", "c"]);
+        // Drawing it sends each glyph once and nothing for the hidden columns
+        assert_eq!(Buffer::empty(area).diff(&buffer).len(), 5);
+    }
+
+    #[test]
+    fn sgr_attributes_become_modifiers() {
+        let mut parser = vt100::Parser::new(1, 10, 0);
+        parser.process(b"\x1b[1;3ma\x1b[0;4;7mb\x1b[0;38;5;9mc");
+        let style = |col| cell_style(parser.screen().cell(0, col).unwrap());
+        assert_eq!(style(0).add_modifier, Modifier::BOLD | Modifier::ITALIC);
+        assert_eq!(style(1).add_modifier, Modifier::UNDERLINED | Modifier::REVERSED);
+        assert_eq!(style(2), Style::default().fg(Color::Indexed(9)).bg(Color::Reset));
+    }
+
+    #[test]
+    fn pane_title_placeholders() {
+        let values = PaneTitle { cmd: "claude", cwd: "~/src/app", branch: "main", pending: 2 };
+        assert_eq!(expand_title(" {cmd} · {cwd} ({branch}) · {pending} {other} ", &values), " claude · ~/src/app (main) · 2 {other} ");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn home_is_shortened_only_on_a_component_boundary() {
+        let home = Some(Path::new("/home/al"));
+        assert_eq!(tilde(Path::new("/home/al/src/app"), home), "~/src/app");
+        assert_eq!(tilde(Path::new("/home/al"), home), "~");
+        assert_eq!(tilde(Path::new("/home/alice/app"), home), "/home/alice/app");
+        assert_eq!(tilde(Path::new("/srv/app"), None), "/srv/app");
+    }
+
+    #[test]
+    fn wide_glyph_at_the_edge_does_not_spill() {
+        let mut parser = vt100::Parser::new(1, 4, 0);
+        parser.process("a中".as_bytes());
+        // Pane is two columns wide; the sidebar starts right after
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 4, 1));
+        buffer.set_string(2, 0, "|", Style::default());
+        render(&mut buffer, Rect::new(0, 0, 2, 1), parser.screen());
+        assert_eq!(symbols(&buffer, 0), ["a", " ", "|", " "]);
+    }
+}
@@ -0,0 +1,177 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use crate::toasts::{Severity, Toast};
+use crate::ui::theme::Theme;
+
+fn color(severity: Severity, theme: &Theme) -> Color {
+    match severity {
+        Severity::Info => theme.status_info,
+        Severity::Success => theme.status_success,
+        Severity::Warning => theme.status_warning,
+        Severity::Error => theme.status_error,
+    }
+}
+
+// Rows a toast can take before the rest of its text is cut
+const MAX_TOAST_ROWS: usize = 4;
+
+// The notification log's scroll position, while it's open
+#[derive(Default)]
+pub struct LogView {
+    // Rows down from the newest entry
+    scroll: usize,
+    // Rows that fit, from the last frame, for paging
+    rows: usize,
+}
+
+impl LogView {
+    // False when the key closes the view
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let page = self.rows.max(1);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll += 1,
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(page),
+            KeyCode::PageDown => self.scroll += page,
+            KeyCode::Home => self.scroll = 0,
+            KeyCode::End => self.scroll = usize::MAX,
+            _ => {}
+        }
+        true
+    }
+}
+
+// `text` in rows of at most `width` characters, broken between words where
+// it can be
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut rows: Vec<String> = Vec::new();
+    let mut row = String::new();
+    for word in text.split(' ') {
+        let mut word: Vec<char> = word.chars().collect();
+        let used = row.chars().count();
+        if used > 0 && used + 1 + word.len() <= width {
+            row.push(' ');
+        } else if used > 0 {
+            rows.push(std::mem::take(&mut row));
+        }
+        // Longer than a whole row: hard breaks
+        while word.len() > width {
+            let rest = word.split_off(width);
+            rows.push(word.into_iter().collect());
+            word = rest;
+        }
+        row.extend(word);
+    }
+    rows.push(row);
+    rows
+}
+
+// Right-aligned boxes stacked up from just above `bottom` (the status bar's
+// row), the newest lowest. Long messages wrap onto a few rows.
+pub fn render<'a>(frame: &mut Frame, area: Rect, bottom: u16, toasts: impl DoubleEndedIterator<Item = &'a Toast>, theme: &Theme) {
+    let max_width = (area.width / 2).clamp(20.min(area.width), 60);
+    let mut y = bottom;
+    for toast in toasts.rev() {
+        let mut rows = wrap(&toast.text, max_width.saturating_sub(4) as usize);
+        if rows.len() > MAX_TOAST_ROWS {
+            rows.truncate(MAX_TOAST_ROWS);
+            if let Some(last) = rows.last_mut() {
+                last.pop();
+                last.push_str(theme.symbol("…", "~"));
+            }
+        }
+        let height = rows.len() as u16 + 2;
+        if y < area.y + height {
+            break;
+        }
+        y -= height;
+        let longest = rows.iter().map(|r| r.chars().count()).max().unwrap_or(0) as u16;
+        let width = (longest + 4).min(max_width);
+        let rect = Rect::new(area.x + area.width.saturating_sub(width + 1), y, width, height);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_set(theme.border_set())
+            .border_style(Style::default().fg(color(toast.severity, theme)))
+            .style(Style::default().bg(theme.bg_primary));
+        let lines: Vec<Line> = rows.into_iter().map(|row| Line::from(Span::styled(row, Style::default().fg(theme.text_main)))).collect();
+        frame.render_widget(Clear, rect);
+        frame.render_widget(Paragraph::new(lines).block(block), rect);
+    }
+}
+
+// Everything notified this session, newest first, wrapped under the time and
+// severity columns
+pub fn render_log<'a>(frame: &mut Frame, area: Rect, log: impl DoubleEndedIterator<Item = &'a Toast>, view: &mut LogView, key: &str, theme: &Theme) {
+    const PREFIX: usize = 17; // "12:34:56 warning "
+    let width = (area.width.saturating_sub(2) as usize).saturating_sub(PREFIX);
+    let mut lines: Vec<Line> = Vec::new();
+    for toast in log.rev() {
+        for (i, row) in wrap(&toast.text, width).into_iter().enumerate() {
+            let mut spans = if i == 0 {
+                vec![
+                    Span::styled(toast.time.format("%H:%M:%S ").to_string(), Style::default().fg(theme.text_muted)),
+                    Span::styled(
+                        format!("{:<8}", format!("{:?}", toast.severity).to_lowercase()),
+                        Style::default().fg(color(toast.severity, theme)).add_modifier(Modifier::BOLD),
+                    ),
+                ]
+            } else {
+                vec![Span::raw(" ".repeat(PREFIX))]
+            };
+            spans.push(Span::styled(row, Style::default().fg(theme.text_main)));
+            lines.push(Line::from(spans));
+        }
+    }
+    if lines.is_empty() {
+        lines.push(Line::from(Span::styled("Nothing yet", Style::default().fg(theme.text_muted))));
+    }
+    view.rows = area.height.saturating_sub(2) as usize;
+    view.scroll = view.scroll.min(lines.len().saturating_sub(view.rows));
+
+    let block = Block::default()
+        .title(format!(" Notifications ({}) ", key))
+        .title_bottom(format!(" [Up/Down/PgUp/PgDn/Home/End] Scroll  [Esc/{}] Close ", key))
+        .borders(Borders::ALL)
+        .border_set(theme.border_set())
+        .border_style(Style::default().fg(theme.border_focus))
+        .style(Style::default().bg(theme.bg_primary));
+    let shown: Vec<Line> = lines.into_iter().skip(view.scroll).take(view.rows).collect();
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(shown).block(block), area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_messages_wrap_between_words() {
+        assert_eq!(wrap("Exported the session report to /tmp/report.md", 20), ["Exported the session", "report to", "/tmp/report.md"]);
+        // A word longer than the row is broken up
+        assert_eq!(wrap("at /a/very/long/path/indeed", 10), ["at", "/a/very/lo", "ng/path/in", "deed"]);
+        assert_eq!(wrap("", 10), [""]);
+    }
+
+    #[test]
+    fn the_log_scrolls_within_what_it_holds() {
+        let mut view = LogView { scroll: 0, rows: 5 };
+        let key = |code| KeyEvent::new(code, crossterm::event::KeyModifiers::NONE);
+        view.handle_key(key(KeyCode::PageDown));
+        assert_eq!(view.scroll, 5);
+        view.handle_key(key(KeyCode::Up));
+        assert_eq!(view.scroll, 4);
+        view.handle_key(key(KeyCode::Home));
+        assert_eq!(view.scroll, 0);
+        view.handle_key(key(KeyCode::Up));
+        assert_eq!(view.scroll, 0);
+        assert!(!view.handle_key(key(KeyCode::Esc)));
+    }
+}
@@ -0,0 +1,26 @@
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Paragraph, Wrap},
+    Frame,
+};
+use crate::ui::theme::Theme;
+
+// In place of everything else while the terminal is below the minimum size.
+// Only Ctrl+Q reaches anything from here: it quits, or with changes waiting
+// opens the quit prompt for when the terminal is big enough again.
+pub fn render(frame: &mut Frame, area: Rect, (min_width, min_height): (u16, u16), theme: &Theme) {
+    let lines = vec![
+        Line::from(Span::styled(
+            format!("terminal too small (need at least {}x{})", min_width, min_height),
+            Style::default().fg(theme.status_warning).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(Span::styled(format!("now {}x{}", area.width, area.height), Style::default().fg(theme.text_muted))),
+    ];
+    // Vertically centered when there's room for that
+    let top = area.height.saturating_sub(lines.len() as u16) / 2;
+    let text_area = Rect { y: area.y + top, height: area.height - top, ..area };
+    frame.render_widget(Paragraph::new("").style(Style::default().bg(theme.bg_primary)), area);
+    frame.render_widget(Paragraph::new(lines).alignment(ratatui::layout::Alignment::Center).wrap(Wrap { trim: true }), text_area);
+}
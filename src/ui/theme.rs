@@ -1,4 +1,6 @@
-use ratatui::style::Color;
+use ratatui::{style::Color, symbols::border};
+use crate::config::DiffColors;
+use crate::types::ChangeStatus;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ThemeVariant {
@@ -6,6 +8,9 @@ pub enum ThemeVariant {
     Nord,
     Cyberpunk,
     SolarizedDark,
+    // No colors of our own and ASCII only, for monochrome terminals, screen
+    // readers and NO_COLOR
+    HighContrast,
 }
 
 impl ThemeVariant {
@@ -14,7 +19,8 @@ impl ThemeVariant {
             Self::Zinc => Self::Nord,
             Self::Nord => Self::Cyberpunk,
             Self::Cyberpunk => Self::SolarizedDark,
-            Self::SolarizedDark => Self::Zinc,
+            Self::SolarizedDark => Self::HighContrast,
+            Self::HighContrast => Self::Zinc,
         }
     }
 
@@ -24,6 +30,31 @@ impl ThemeVariant {
             Self::Nord => "Nord",
             Self::Cyberpunk => "Cyberpunk",
             Self::SolarizedDark => "Solarized Dark",
+            Self::HighContrast => "High Contrast",
+        }
+    }
+}
+
+// How many colors the real terminal can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+impl ColorDepth {
+    // Same heuristics most terminal apps use: COLORTERM, then TERM
+    pub fn detect() -> Self {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default().to_lowercase();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return Self::TrueColor;
+        }
+        let term = std::env::var("TERM").unwrap_or_default();
+        if term.contains("256color") {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
         }
     }
 }
@@ -40,6 +71,15 @@ pub struct Theme {
     pub status_warning: Color,
     pub status_error: Color,
     pub status_info: Color,
+    // Diff lines get their own colors so the status accents can be loud
+    // without every added line shouting too
+    pub diff_add: Color,
+    pub diff_remove: Color,
+    pub diff_context: Color,
+    pub diff_header: Color,
+    // Everything color says is also spelled out, and no box drawing or other
+    // non-ASCII symbols are drawn
+    pub ascii_only: bool,
 }
 
 impl Theme {
@@ -62,6 +102,11 @@ impl Theme {
                 status_warning: Color::Rgb(234, 179, 8), // Yellow 500
                 status_error: Color::Rgb(239, 68, 68), // Red 500
                 status_info: Color::Rgb(59, 130, 246), // Blue 500
+                diff_add: Color::Rgb(74, 222, 128), // Green 400
+                diff_remove: Color::Rgb(248, 113, 113), // Red 400
+                diff_context: Color::Rgb(161, 161, 170), // Zinc 400
+                diff_header: Color::Rgb(96, 165, 250), // Blue 400
+                ascii_only: false,
             },
             ThemeVariant::Nord => Self {
                 variant,
@@ -75,6 +120,11 @@ impl Theme {
                 status_warning: Color::Rgb(235, 203, 139), // nord13
                 status_error: Color::Rgb(191, 97, 106),    // nord11
                 status_info: Color::Rgb(94, 129, 172),     // nord10
+                diff_add: Color::Rgb(163, 190, 140),   // nord14
+                diff_remove: Color::Rgb(191, 97, 106), // nord11
+                diff_context: Color::Rgb(216, 222, 233), // nord4
+                diff_header: Color::Rgb(129, 161, 193), // nord9
+                ascii_only: false,
             },
             ThemeVariant::Cyberpunk => Self {
                 variant,
@@ -88,6 +138,12 @@ impl Theme {
                 status_warning: Color::Rgb(255, 150, 0),
                 status_error: Color::Rgb(255, 0, 50),
                 status_info: Color::Rgb(0, 200, 255),
+                // Softer than the neon accents; long diffs are read line by line
+                diff_add: Color::Rgb(110, 210, 140),
+                diff_remove: Color::Rgb(230, 100, 120),
+                diff_context: Color::Rgb(150, 150, 175),
+                diff_header: Color::Rgb(90, 170, 220),
+                ascii_only: false,
             },
             ThemeVariant::SolarizedDark => Self {
                 variant,
@@ -101,11 +157,215 @@ impl Theme {
                 status_warning: Color::Rgb(181, 137, 0),  // yellow
                 status_error: Color::Rgb(220, 50, 47),    // red
                 status_info: Color::Rgb(38, 139, 210),    // blue
+                diff_add: Color::Rgb(133, 153, 0),   // green
+                diff_remove: Color::Rgb(220, 50, 47), // red
+                diff_context: Color::Rgb(131, 148, 150), // base0
+                diff_header: Color::Rgb(38, 139, 210), // blue
+                ascii_only: false,
+            },
+            // The terminal's own foreground and background throughout
+            ThemeVariant::HighContrast => Self {
+                variant,
+                bg_primary: Color::Reset,
+                bg_secondary: Color::Reset,
+                text_main: Color::Reset,
+                text_muted: Color::Reset,
+                border_focus: Color::Reset,
+                border_dim: Color::Reset,
+                status_success: Color::Reset,
+                status_warning: Color::Reset,
+                status_error: Color::Reset,
+                status_info: Color::Reset,
+                diff_add: Color::Reset,
+                diff_remove: Color::Reset,
+                diff_context: Color::Reset,
+                diff_header: Color::Reset,
+                ascii_only: true,
             },
         }
     }
-    
-    pub fn default() -> Self {
+
+    // `fancy` normally, `plain` in ASCII-only mode
+    pub fn symbol<'a>(&self, fancy: &'a str, plain: &'a str) -> &'a str {
+        if self.ascii_only { plain } else { fancy }
+    }
+
+    // Box drawing, or +-| in ASCII-only mode
+    pub fn border_set(&self) -> border::Set {
+        if self.ascii_only {
+            border::Set {
+                top_left: "+",
+                top_right: "+",
+                bottom_left: "+",
+                bottom_right: "+",
+                vertical_left: "|",
+                vertical_right: "|",
+                horizontal_top: "-",
+                horizontal_bottom: "-",
+            }
+        } else {
+            border::PLAIN
+        }
+    }
+
+    // The user's [diff_colors] in place of the variant's, with the status
+    // colors for any it leaves out. High Contrast keeps to the terminal's own
+    // colors regardless.
+    pub fn with_diff_colors(mut self, colors: Option<&DiffColors>) -> Self {
+        let Some(colors) = colors.filter(|_| !self.ascii_only) else {
+            return self;
+        };
+        self.diff_add = colors.add.unwrap_or(self.status_success);
+        self.diff_remove = colors.remove.unwrap_or(self.status_error);
+        self.diff_context = colors.context.unwrap_or(self.text_muted);
+        self.diff_header = colors.header.unwrap_or(self.status_info);
+        self
+    }
+
+    pub fn status_color(&self, status: ChangeStatus) -> Color {
+        match status {
+            ChangeStatus::Pending => self.status_warning,
+            ChangeStatus::Accepted => self.status_success,
+            ChangeStatus::Rejected | ChangeStatus::Blocked => self.status_error,
+            ChangeStatus::AutoApproved => self.status_info,
+            ChangeStatus::Info | ChangeStatus::Dismissed => self.text_muted,
+        }
+    }
+}
+
+impl Theme {
+    // Remap our own colors for terminals without true color. The child's
+    // vt100 colors are forwarded as-is and never go through here.
+    pub fn degrade(self, depth: ColorDepth) -> Self {
+        let map = |color| degrade_color(color, depth);
+        Self {
+            variant: self.variant,
+            bg_primary: map(self.bg_primary),
+            bg_secondary: map(self.bg_secondary),
+            text_main: map(self.text_main),
+            text_muted: map(self.text_muted),
+            border_focus: map(self.border_focus),
+            border_dim: map(self.border_dim),
+            status_success: map(self.status_success),
+            status_warning: map(self.status_warning),
+            status_error: map(self.status_error),
+            status_info: map(self.status_info),
+            diff_add: map(self.diff_add),
+            diff_remove: map(self.diff_remove),
+            diff_context: map(self.diff_context),
+            diff_header: map(self.diff_header),
+            ascii_only: self.ascii_only,
+        }
+    }
+}
+
+// Only RGB colors need converting; named and indexed ones already fit
+pub fn degrade_color(color: Color, depth: ColorDepth) -> Color {
+    match (color, depth) {
+        (Color::Rgb(r, g, b), ColorDepth::Ansi256) => Color::Indexed(rgb_to_256(r, g, b)),
+        (Color::Rgb(r, g, b), ColorDepth::Ansi16) => rgb_to_16(r, g, b),
+        _ => color,
+    }
+}
+
+// xterm 6x6x6 cube channel levels
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+// Nearest of the cube (16-231) or the grayscale ramp (232-255)
+pub fn rgb_to_256(r: u8, g: u8, b: u8) -> u8 {
+    let nearest_level = |v: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - v as i32).abs())
+            .unwrap_or(0)
+    };
+    let (ri, gi, bi) = (nearest_level(r), nearest_level(g), nearest_level(b));
+    let cube_rgb = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+    let cube_index = 16 + 36 * ri + 6 * gi + bi;
+
+    let avg = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_step = (avg.saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + 10 * gray_step;
+    let gray_index = 232 + gray_step;
+
+    if distance((r, g, b), (gray, gray, gray)) < distance((r, g, b), cube_rgb) {
+        gray_index
+    } else {
+        cube_index as u8
+    }
+}
+
+// Last resort: the basic ANSI palette (xterm defaults)
+pub fn rgb_to_16(r: u8, g: u8, b: u8) -> Color {
+    const PALETTE: [(Color, (u8, u8, u8)); 16] = [
+        (Color::Black, (0, 0, 0)),
+        (Color::Red, (205, 0, 0)),
+        (Color::Green, (0, 205, 0)),
+        (Color::Yellow, (205, 205, 0)),
+        (Color::Blue, (0, 0, 238)),
+        (Color::Magenta, (205, 0, 205)),
+        (Color::Cyan, (0, 205, 205)),
+        (Color::Gray, (229, 229, 229)),
+        (Color::DarkGray, (127, 127, 127)),
+        (Color::LightRed, (255, 0, 0)),
+        (Color::LightGreen, (0, 255, 0)),
+        (Color::LightYellow, (255, 255, 0)),
+        (Color::LightBlue, (92, 92, 255)),
+        (Color::LightMagenta, (255, 0, 255)),
+        (Color::LightCyan, (0, 255, 255)),
+        (Color::White, (255, 255, 255)),
+    ];
+    PALETTE
+        .iter()
+        .min_by_key(|(_, rgb)| distance((r, g, b), *rgb))
+        .map_or(Color::Reset, |(color, _)| *color)
+}
+
+impl Default for Theme {
+    fn default() -> Self {
         Self::new(ThemeVariant::Zinc)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn diff_colors_from_config_override_the_theme() {
+        let config: Config = toml::from_str("[diff_colors]\nadd = \"#a3be8c\"\nheader = \"108\"\n").unwrap();
+        let theme = Theme::new(ThemeVariant::Cyberpunk).with_diff_colors(config.diff_colors.as_ref());
+        assert_eq!(theme.diff_add, Color::Rgb(163, 190, 140));
+        assert_eq!(theme.diff_header, Color::Indexed(108));
+        // Left out: the theme's status colors
+        assert_eq!(theme.diff_remove, theme.status_error);
+        assert_eq!(theme.diff_context, theme.text_muted);
+        // No table at all: the theme's diff colors
+        let plain = Theme::new(ThemeVariant::Cyberpunk).with_diff_colors(None);
+        assert_eq!(plain.diff_remove, Theme::new(ThemeVariant::Cyberpunk).diff_remove);
+        assert_eq!(Theme::new(ThemeVariant::HighContrast).with_diff_colors(config.diff_colors.as_ref()).diff_add, Color::Reset);
+        assert!(toml::from_str::<Config>("[diff_colors]\nadd = \"greenish\"\n").is_err());
+    }
+
+    #[test]
+    fn true_colors_fall_back_to_the_nearest_palette_entry() {
+        // Cube corners and a color right on the cube
+        assert_eq!(rgb_to_256(0, 0, 0), 16);
+        assert_eq!(rgb_to_256(255, 255, 255), 231);
+        assert_eq!(rgb_to_256(255, 0, 0), 196);
+        assert_eq!(rgb_to_256(95, 135, 175), 67);
+        // Grays land on the ramp, which is finer than the cube's diagonal
+        assert_eq!(rgb_to_256(128, 128, 128), 244);
+        assert_eq!(rgb_to_256(130, 128, 126), 244);
+
+        assert_eq!(rgb_to_16(200, 0, 0), Color::Red);
+        assert_eq!(rgb_to_16(250, 10, 10), Color::LightRed);
+        assert_eq!(rgb_to_16(130, 130, 130), Color::DarkGray);
+        assert_eq!(rgb_to_16(10, 10, 10), Color::Black);
+    }
+}